@@ -0,0 +1,43 @@
+use button_beam_core::headless::{Options, Server};
+use tracing_subscriber::EnvFilter;
+
+// Runs the server without the desktop app; see `button_beam_core::headless`.
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let server = match Server::bind(options).await {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    // The only place to read the token from without a window
+    match server.local_addr() {
+        Ok(addr) => println!(
+            "Listening on {}; connect phones with token {}",
+            addr,
+            server.token()
+        ),
+        Err(e) => eprintln!("{}", e),
+    }
+
+    let shutdown = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    if let Err(e) = server.run(shutdown).await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
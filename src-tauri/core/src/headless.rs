@@ -0,0 +1,771 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_tungstenite::tungstenite::handshake::server::{
+    ErrorResponse, Request, Response as HandshakeResponse,
+};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, warn};
+
+use crate::keyboard::{self, is_text_string, TypingSpeed};
+use crate::protocol::{
+    cycle_position_message, cycle_positions_message, shortcut_message, shortcut_page,
+    ClientMessage, DeviceRole, DeviceStatus, Response, CAP_CYCLE_POSITIONS, CAP_PRESS_KINDS,
+    CAP_SHORTCUT_DIFFS, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use crate::shortcuts::{PressKind, Shortcut, ShortcutChange, ShortcutStore, Step, Timing};
+use crate::storage::{read_json, write_json};
+
+// The server without the desktop app, for a machine nobody sits at, e.g. a
+// mini-PC under the TV that is only driven from the phone. It needs no
+// window system beyond what key simulation does, speaks the same protocol
+// and uses the same data folder, so the two must not run at the same time:
+//
+//   button-beam-headless --data-dir <path> [--port <port>] [--admin <device id>]...
+//
+// Nobody can compare pairing PINs without a window, so holding the token is
+// enough to connect, as a trigger-only device. Only devices named with
+// `--admin` may edit shortcuts. Steps other than key combos and text need
+// the desktop app and fail here.
+
+pub const USAGE: &str =
+    "Usage: button-beam-headless --data-dir <path> [--port <port>] [--admin <device id>]...";
+
+/// Optional features this server offers, announced in the `hello` reply.
+const CAPABILITIES: &[&str] = &[
+    "responses",
+    CAP_SHORTCUT_DIFFS,
+    CAP_PRESS_KINDS,
+    CAP_CYCLE_POSITIONS,
+];
+
+const BIND_ADDRESS: &str = "0.0.0.0";
+
+/// Options given on the command line.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The desktop app's data folder, or any other.
+    pub data_dir: PathBuf,
+    /// Port to listen on; the one in the settings when unset, or a free one
+    /// that is then kept in the settings.
+    pub port: Option<u16>,
+    /// Devices that may edit shortcuts.
+    pub admins: HashSet<String>,
+}
+
+impl Options {
+    /// Reads the options from the process arguments, without the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut data_dir = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "--data-dir" => data_dir = Some(PathBuf::from(value()?)),
+                "--port" => {
+                    let port = value()?;
+                    options.port = Some(
+                        port.parse()
+                            .map_err(|_| format!("Invalid port \"{}\"", port))?,
+                    );
+                }
+                "--admin" => {
+                    options.admins.insert(value()?);
+                }
+                _ => return Err(format!("Unknown argument \"{}\"\n{}", arg, USAGE)),
+            }
+        }
+        options.data_dir = data_dir.ok_or_else(|| USAGE.to_string())?;
+        Ok(options)
+    }
+}
+
+/// The token file, shared with the desktop app.
+#[derive(Serialize, Deserialize)]
+struct AuthData {
+    token: String,
+}
+
+/// The desktop's token, or a new one on first start.
+fn load_token(path: &Path) -> Result<String, String> {
+    if let Some(data) = read_json::<AuthData>(path)? {
+        return Ok(data.token);
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    write_json(
+        path,
+        &AuthData {
+            token: token.clone(),
+        },
+    )?;
+    Ok(token)
+}
+
+/// Keeps `port` in the desktop's settings, leaving everything else as it is.
+fn remember_port(path: &Path, port: u16) -> Result<(), String> {
+    let mut settings = read_json::<Value>(path)?.unwrap_or_else(|| json!({}));
+    settings["port"] = json!(port);
+    write_json(path, &settings)?;
+    Ok(())
+}
+
+/// What every connection shares.
+struct Shared {
+    store: Arc<ShortcutStore>,
+    token: String,
+    admins: HashSet<String>,
+}
+
+impl Shared {
+    /// Compares in constant time so the token can't be guessed byte by byte.
+    fn verify(&self, candidate: &str) -> bool {
+        self.token.len() == candidate.len()
+            && self
+                .token
+                .bytes()
+                .zip(candidate.bytes())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    /// Loads the data folder and starts listening.
+    pub async fn bind(options: Options) -> Result<Self, String> {
+        let dir = &options.data_dir;
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Cannot create data directory {}: {}", dir.display(), e))?;
+        let token = load_token(&dir.join("auth.json"))?;
+
+        let settings_file = dir.join("settings.json");
+        let settings = read_json::<Value>(&settings_file)?.unwrap_or_default();
+        let saved_port = settings["port"]
+            .as_u64()
+            .and_then(|port| port.try_into().ok());
+        let bind_address = settings["bind_address"].as_str().unwrap_or(BIND_ADDRESS);
+        let port = options.port.or(saved_port).unwrap_or(0);
+        let listener = TcpListener::bind((bind_address, port))
+            .await
+            .map_err(|e| format!("Failed to listen on {}:{}: {}", bind_address, port, e))?;
+        if options.port.is_none() && saved_port.is_none() {
+            // Phones remember the address, so the port picked now has to stay
+            let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+            remember_port(&settings_file, port)?;
+        }
+
+        let (broadcaster, _) = broadcast::channel(64);
+        let store = Arc::new(ShortcutStore::new(dir.join("shortcuts.json"), broadcaster));
+        Ok(Self {
+            listener,
+            shared: Arc::new(Shared {
+                store,
+                token,
+                admins: options.admins,
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.listener.local_addr().map_err(|e| e.to_string())
+    }
+
+    pub fn token(&self) -> &str {
+        &self.shared.token
+    }
+
+    /// Serves devices until `shutdown` completes, then writes any pending
+    /// change to the shortcuts.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Result<(), String> {
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, addr)) => {
+                        info!("Connection from {}", addr);
+                        tokio::spawn(serve(stream, Arc::clone(&self.shared)));
+                    }
+                    Err(e) => error!("Failed to accept a connection: {}", e),
+                },
+                () = &mut shutdown => break,
+            }
+        }
+        self.shared.store.flush()?;
+        Ok(())
+    }
+}
+
+/// One device's connection.
+#[derive(Default)]
+struct Connection {
+    authenticated: bool,
+    /// Whether the client takes diffs rather than the whole list.
+    diffs: bool,
+    /// Whether the client wants to know where cycles are.
+    cycle_positions: bool,
+    /// Set by `device_info`.
+    device: Option<(String, DeviceRole)>,
+}
+
+/// The token from `?token=...` in the connection URL.
+fn query_token(query: Option<&str>) -> Option<&str> {
+    query?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+}
+
+async fn serve(stream: TcpStream, shared: Arc<Shared>) {
+    let mut connection = Connection::default();
+    // Clients may authenticate up front with `?token=...` in the URL
+    // The error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let check_url = |request: &Request, response: HandshakeResponse| -> Result<_, ErrorResponse> {
+        connection.authenticated =
+            query_token(request.uri().query()).is_some_and(|token| shared.verify(token));
+        Ok(response)
+    };
+    let socket = match tokio_tungstenite::accept_hdr_async(stream, check_url).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("WebSocket handshake failed: {}", e);
+            return;
+        }
+    };
+    let (mut sink, mut incoming) = socket.split();
+    let mut changes = shared.store.broadcaster.subscribe();
+    let mut cycle_moves = shared.store.subscribe_cycle_moves();
+
+    loop {
+        let outgoing = tokio::select! {
+            frame = incoming.next() => match frame {
+                Some(Ok(Message::Text(text))) => {
+                    let (outgoing, keep_open) = connection.handle(&text, &shared);
+                    if !keep_open {
+                        for message in outgoing {
+                            sink.send(Message::text(message.to_string())).await.ok();
+                        }
+                        sink.close().await.ok();
+                        break;
+                    }
+                    outgoing
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => {
+                    warn!("Connection error: {}", e);
+                    break;
+                }
+            },
+            change = changes.recv() => {
+                if connection.device.is_none() {
+                    continue;
+                }
+                let change = match change {
+                    Ok(change) => change,
+                    // Missed changes: send the whole list instead
+                    Err(RecvError::Lagged(_)) => ShortcutChange::Reset,
+                    Err(RecvError::Closed) => break,
+                };
+                let shortcuts = shared.store.get_shortcuts();
+                match shortcut_message(&change, &shortcuts, connection.diffs) {
+                    Ok(message) => vec![message],
+                    Err(e) => {
+                        error!("Error serializing shortcuts: {}", e);
+                        continue;
+                    }
+                }
+            }
+            moved = cycle_moves.recv() => {
+                if connection.device.is_none() || !connection.cycle_positions {
+                    continue;
+                }
+                match moved {
+                    Ok(moved) => vec![cycle_position_message(&moved)],
+                    // Missed moves: send where every cycle is
+                    Err(RecvError::Lagged(_)) => {
+                        vec![cycle_positions_message(&shared.store.cycle_positions())]
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+        for message in outgoing {
+            if let Err(e) = sink.send(Message::text(message.to_string())).await {
+                warn!("Failed to send: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+impl Connection {
+    /// Handles one text frame. Returns the messages to send back and whether
+    /// the connection stays open.
+    fn handle(&mut self, text: &str, shared: &Shared) -> (Vec<Value>, bool) {
+        let data: Value = match serde_json::from_str(text) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Received undecodable message: {}", e);
+                return (Vec::new(), true);
+            }
+        };
+        let request_id = data.get("id").cloned();
+        let message = serde_json::from_value::<ClientMessage>(data);
+
+        let mut outgoing = Vec::new();
+        let mut keep_open = true;
+        let result = match message {
+            // Refused whether or not the client is authenticated
+            Ok(ClientMessage::Hello {
+                protocol_version, ..
+            }) if protocol_version < MIN_PROTOCOL_VERSION => {
+                keep_open = false;
+                Err(format!(
+                    "Client protocol version {} is too old; this server requires version {} or newer. Please update the mobile app.",
+                    protocol_version, MIN_PROTOCOL_VERSION
+                ))
+            }
+            _ if !self.authenticate(&message, shared) => {
+                keep_open = false;
+                Err("Authentication required: missing or invalid token".to_string())
+            }
+            Ok(message) => self.dispatch(message, shared, &mut outgoing),
+            Err(e) => Err(format!("Invalid message: {}", e)),
+        };
+
+        match request_id {
+            // The reply goes before whatever the message brought on
+            Some(id) => outgoing.insert(0, json!(Response::from_result(id, result))),
+            None if !keep_open => {
+                if let Err(e) = result {
+                    outgoing.insert(0, json!({ "type": "error", "error": e }));
+                }
+            }
+            None => {
+                if let Err(e) = result {
+                    warn!("{}", e);
+                }
+            }
+        }
+        (outgoing, keep_open)
+    }
+
+    /// Returns whether the connection may proceed, authenticating it if this
+    /// message carries a valid token.
+    fn authenticate(
+        &mut self,
+        message: &Result<ClientMessage, serde_json::Error>,
+        shared: &Shared,
+    ) -> bool {
+        if self.authenticated {
+            return true;
+        }
+        let token = match message {
+            Ok(ClientMessage::Auth { token }) => Some(token),
+            Ok(ClientMessage::Hello { token, .. }) => token.as_ref(),
+            // Answered with an error, so the client pairs again
+            Ok(ClientMessage::Resume { .. }) => return true,
+            _ => None,
+        };
+        self.authenticated = token.is_some_and(|token| shared.verify(token));
+        self.authenticated
+    }
+
+    /// Checks that the device identified itself and its role permits the
+    /// message.
+    fn authorize(&self, message: &ClientMessage) -> Result<(), String> {
+        let Some(required) = message.required_role() else {
+            return Ok(());
+        };
+        let (_, role) = self
+            .device
+            .as_ref()
+            .ok_or("Send device_info before other messages")?;
+        if required == DeviceRole::Admin && *role != DeviceRole::Admin {
+            return Err(
+                "This device may only trigger shortcuts; start the server with --admin <device id> to let it edit them"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn dispatch(
+        &mut self,
+        message: ClientMessage,
+        shared: &Shared,
+        outgoing: &mut Vec<Value>,
+    ) -> Result<Option<Value>, String> {
+        self.authorize(&message)?;
+        let store = &shared.store;
+        match message {
+            ClientMessage::Hello { capabilities, .. } => {
+                self.diffs = capabilities.iter().any(|c| c == CAP_SHORTCUT_DIFFS);
+                self.cycle_positions = capabilities.iter().any(|c| c == CAP_CYCLE_POSITIONS);
+                Ok(Some(json!({
+                    "protocol_version": PROTOCOL_VERSION,
+                    "min_protocol_version": MIN_PROTOCOL_VERSION,
+                    "capabilities": CAPABILITIES,
+                })))
+            }
+            ClientMessage::Auth { .. } | ClientMessage::DeviceStatus(_) => Ok(None),
+            ClientMessage::DeviceInfo {
+                device_name,
+                device_id,
+                status,
+                ..
+            } => Ok(Some(self.identify(
+                device_name,
+                device_id,
+                status,
+                shared,
+                outgoing,
+            )?)),
+            ClientMessage::Resume { .. } => {
+                Err("Session expired or unknown; send device_info to pair again".to_string())
+            }
+            ClientMessage::ExecuteShortcut {
+                shortcut_id,
+                interval_ms,
+                press_kind,
+            } => {
+                let shortcut = find(store, shortcut_id)?;
+                trigger(store, &shortcut, interval_ms, press_kind);
+                Ok(None)
+            }
+            ClientMessage::VoiceCommand { text } => {
+                let shortcut = store.find_by_spoken_name(&text)?;
+                trigger(store, &shortcut, None, PressKind::Tap);
+                Ok(Some(json!({
+                    "shortcut_id": shortcut.id,
+                    "name": shortcut.name,
+                })))
+            }
+            ClientMessage::GetShortcuts {
+                group,
+                tag,
+                page,
+                page_size,
+            } => shortcut_page(store.get_shortcuts(), group, tag, page, page_size).map(Some),
+            ClientMessage::AddShortcut { mut shortcut } => {
+                check_editable(&mut shortcut)?;
+                {
+                    let mut shortcuts = store.shortcuts.write();
+                    // IDs are timestamps, like the desktop app's
+                    shortcut.id = shortcuts
+                        .iter()
+                        .map(|s| s.id + 1)
+                        .max()
+                        .unwrap_or(0)
+                        .max(now_millis());
+                    shortcuts.push(shortcut.clone());
+                }
+                store.save();
+                store.broadcast_change(ShortcutChange::Added(shortcut.id));
+                serde_json::to_value(shortcut)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+            ClientMessage::UpdateShortcut { mut shortcut } => {
+                check_editable(&mut shortcut)?;
+                {
+                    let mut shortcuts = store.shortcuts.write();
+                    let existing = shortcuts
+                        .iter_mut()
+                        .find(|s| s.id == shortcut.id)
+                        .ok_or_else(|| format!("Shortcut with ID {} not found.", shortcut.id))?;
+                    *existing = shortcut.clone();
+                }
+                store.save();
+                store.broadcast_change(ShortcutChange::Updated(shortcut.id));
+                serde_json::to_value(shortcut)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+            ClientMessage::DeleteShortcut { shortcut_id } => {
+                store.shortcuts.write().retain(|s| s.id != shortcut_id);
+                store.save();
+                store.broadcast_change(ShortcutChange::Deleted(shortcut_id));
+                Ok(None)
+            }
+            ClientMessage::DialRotate { .. }
+            | ClientMessage::SliderSet { .. }
+            | ClientMessage::GetSettings
+            | ClientMessage::UploadAsset { .. }
+            | ClientMessage::ListAssets
+            | ClientMessage::GetSystemLevels
+            | ClientMessage::GetNowPlaying => {
+                Err("Not available on the headless server; use the desktop app".to_string())
+            }
+        }
+    }
+
+    /// Accepts the device at its role and queues the shortcut list for it.
+    fn identify(
+        &mut self,
+        name: String,
+        device_id: Option<String>,
+        status: DeviceStatus,
+        shared: &Shared,
+        outgoing: &mut Vec<Value>,
+    ) -> Result<Value, String> {
+        let id = device_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let role = if shared.admins.contains(&id) {
+            DeviceRole::Admin
+        } else {
+            DeviceRole::default()
+        };
+        info!("Device connected: {} ({:?})", name, role);
+        self.device = Some((id.clone(), role));
+
+        let shortcuts = shared.store.get_shortcuts();
+        outgoing.push(
+            shortcut_message(&ShortcutChange::Reset, &shortcuts, self.diffs)
+                .map_err(|e| format!("Error serializing shortcuts: {}", e))?,
+        );
+        if self.cycle_positions {
+            outgoing.push(cycle_positions_message(&shared.store.cycle_positions()));
+        }
+        let mut device = json!({
+            "id": id,
+            "name": name,
+            "connected": true,
+            "approved": true,
+        });
+        if let (Value::Object(device), Value::Object(status)) = (&mut device, json!(status)) {
+            device.extend(status);
+        }
+        Ok(device)
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn find(store: &ShortcutStore, id: u64) -> Result<Shortcut, String> {
+    store
+        .get_shortcuts()
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("Shortcut with ID {} not found.", id))
+}
+
+/// Only key combos and text can be stored here: other steps are checked and
+/// prepared by the desktop app, e.g. secrets are moved to the keychain.
+fn check_editable(shortcut: &mut Shortcut) -> Result<(), String> {
+    for sequence in shortcut.sequences() {
+        check_keys_only(sequence)?;
+    }
+    shortcut.normalize_keys();
+    Ok(())
+}
+
+fn check_keys_only(sequence: &[Step]) -> Result<(), String> {
+    match sequence.iter().find_map(|step| match step {
+        Step::Action(step) => Some(&step.kind),
+        Step::Keys(_) => None,
+    }) {
+        Some(kind) => Err(format!(
+            "\"{}\" steps need the desktop app; the headless server only presses keys and types text",
+            kind
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Runs a press of `shortcut` in the background, logging how it went.
+fn trigger(store: &ShortcutStore, shortcut: &Shortcut, interval_ms: Option<u64>, kind: PressKind) {
+    let sequence = store.next_sequence(shortcut, kind);
+    let timing = shortcut.timing().or_default_interval(interval_ms);
+    let id = shortcut.id;
+    info!("Executing shortcut with ID {}", id);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = run_sequence(&sequence, timing) {
+            error!("Shortcut {} failed: {}", id, e);
+        }
+    });
+}
+
+/// Presses the key combos and types the text of a sequence.
+pub fn run_sequence(sequence: &[Step], timing: Timing) -> Result<(), String> {
+    // Checked up front, so a sequence doesn't stop halfway
+    check_keys_only(sequence)?;
+    let speed = TypingSpeed {
+        chars_per_second: timing.chars_per_second,
+        ..TypingSpeed::default()
+    };
+    for step in sequence {
+        let Step::Keys(keys) = step else { continue };
+        if is_text_string(keys) {
+            if timing.game_mode {
+                keyboard::simulate_text_typing_game_mode(keys, speed)?;
+            } else {
+                keyboard::simulate_text_typing(keys, speed)?;
+            }
+        } else if timing.game_mode {
+            keyboard::simulate_shortcut_game_mode(vec![keys.clone()], timing.interval_ms)?;
+        } else {
+            keyboard::simulate_shortcut(vec![keys.clone()], timing.interval_ms)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_the_command_line() {
+        let options = Options::parse(args(&[
+            "--data-dir",
+            "data",
+            "--admin",
+            "a",
+            "--admin",
+            "b",
+        ]))
+        .unwrap();
+        assert_eq!(options.data_dir, PathBuf::from("data"));
+        assert_eq!(options.admins.len(), 2);
+        assert!(Options::parse(args(&["--admin", "a"])).is_err());
+        assert!(Options::parse(args(&["--data-dir", "data", "--port", "x"])).is_err());
+        assert!(Options::parse(args(&["--data-dir"])).is_err());
+    }
+
+    #[test]
+    fn reads_the_token_from_the_url() {
+        assert_eq!(query_token(Some("device_id=x&token=abc")), Some("abc"));
+        assert_eq!(query_token(Some("device_id=x")), None);
+        assert_eq!(query_token(None), None);
+    }
+
+    #[test]
+    fn only_keys_and_text_run() {
+        let sequence: Vec<Step> =
+            serde_json::from_value(json!([{ "type": "launch_app", "path": "x" }])).unwrap();
+        assert!(run_sequence(&sequence, Timing::default())
+            .unwrap_err()
+            .contains("launch_app"));
+    }
+
+    async fn start(admins: &[&str]) -> (SocketAddr, String) {
+        let data_dir = std::env::temp_dir().join(format!(
+            "button-beam-headless-{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        let options = Options {
+            data_dir,
+            port: Some(0),
+            admins: admins.iter().map(|id| id.to_string()).collect(),
+        };
+        let server = Server::bind(options).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let token = server.token().to_string();
+        tokio::spawn(server.run(std::future::pending()));
+        (addr, token)
+    }
+
+    async fn request(client: &mut Client, message: Value) -> Value {
+        client
+            .send(Message::text(message.to_string()))
+            .await
+            .unwrap();
+        loop {
+            let Some(Ok(Message::Text(text))) = client.next().await else {
+                panic!("Connection closed while waiting for a response");
+            };
+            let reply: Value = serde_json::from_str(&text).unwrap();
+            if reply["type"] == "response" && reply["id"] == message["id"] {
+                return reply;
+            }
+        }
+    }
+
+    async fn connect(addr: SocketAddr, token: &str, device_id: &str) -> Client {
+        let (mut client, _) = connect_async(format!("ws://{}/?token={}", addr, token))
+            .await
+            .unwrap();
+        let identified = request(
+            &mut client,
+            json!({ "type": "device_info", "id": 1, "device_name": "Phone", "device_id": device_id }),
+        )
+        .await;
+        assert_eq!(identified["payload"]["approved"], true);
+        client
+    }
+
+    #[tokio::test]
+    async fn wrong_token_is_refused() {
+        let (addr, _) = start(&[]).await;
+        let (mut client, _) = connect_async(format!("ws://{}/", addr)).await.unwrap();
+        let hello = json!({ "type": "hello", "id": 1, "protocol_version": PROTOCOL_VERSION, "token": "wrong" });
+        assert_eq!(request(&mut client, hello).await["ok"], false);
+    }
+
+    #[tokio::test]
+    async fn outdated_clients_are_refused_before_authenticating() {
+        let (addr, _) = start(&[]).await;
+        let (mut client, _) = connect_async(format!("ws://{}/", addr)).await.unwrap();
+        let hello = json!({ "type": "hello", "protocol_version": MIN_PROTOCOL_VERSION - 1 });
+        client.send(Message::text(hello.to_string())).await.unwrap();
+        let Some(Ok(Message::Text(text))) = client.next().await else {
+            panic!("Connection closed without an error");
+        };
+        let error: Value = serde_json::from_str(&text).unwrap();
+        assert!(error["error"].as_str().unwrap().contains("too old"));
+    }
+
+    #[tokio::test]
+    async fn only_admins_edit_shortcuts() {
+        let (addr, token) = start(&["admin"]).await;
+        let add = json!({
+            "type": "add_shortcut",
+            "id": 2,
+            "shortcut": { "id": 0, "name": "Save", "sequence": ["control+s"] },
+        });
+
+        let mut phone = connect(addr, &token, "phone").await;
+        assert_eq!(request(&mut phone, add.clone()).await["ok"], false);
+
+        let mut admin = connect(addr, &token, "admin").await;
+        let added = request(&mut admin, add).await;
+        assert_eq!(added["payload"]["sequence"][0], "Ctrl+s");
+
+        let unsupported = json!({
+            "type": "add_shortcut",
+            "id": 3,
+            "shortcut": { "id": 0, "name": "Open", "sequence": [{ "type": "open_url", "url": "x" }] },
+        });
+        assert_eq!(request(&mut admin, unsupported).await["ok"], false);
+
+        let list = request(&mut phone, json!({ "type": "get_shortcuts", "id": 4 })).await;
+        assert_eq!(list["payload"]["total"], 1);
+    }
+}
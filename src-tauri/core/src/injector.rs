@@ -0,0 +1,183 @@
+use enigo::{Direction, Enigo, Key, Keyboard};
+use std::ops::{Deref, DerefMut};
+use tracing::error;
+
+// Where simulated key presses go. The key simulation only talks to an
+// `InputInjector`, so enigo, which sends to the OS, can be swapped for the
+// `MockInjector`, which records what would have been sent: tests check the
+// order of presses and releases with it, and dry runs show what a shortcut
+// does without touching the keyboard.
+
+/// One event handed to an injector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    Key(Key, Direction),
+    /// A scancode, see `scancodes`.
+    Raw(u16, Direction),
+    Text(String),
+}
+
+pub trait InputInjector {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), String>;
+    fn raw(&mut self, code: u16, direction: Direction) -> Result<(), String>;
+    fn text(&mut self, text: &str) -> Result<(), String>;
+}
+
+impl InputInjector for Enigo {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), String> {
+        Keyboard::key(self, key, direction).map_err(|e| e.to_string())
+    }
+
+    fn raw(&mut self, code: u16, direction: Direction) -> Result<(), String> {
+        Keyboard::raw(self, code, direction).map_err(|e| e.to_string())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        Keyboard::text(self, text).map_err(|e| e.to_string())
+    }
+}
+
+/// A key held down by [`HeldKeys`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Held {
+    Key(Key),
+    Raw(u16),
+}
+
+/// Holds keys down through an injector and releases them, last pressed
+/// first, when dropped. A combo that fails halfway or a step that panics
+/// therefore can't leave Ctrl or Shift stuck down. Other events go through
+/// to the injector as usual.
+pub struct HeldKeys<'a, I: InputInjector> {
+    injector: &'a mut I,
+    held: Vec<Held>,
+}
+
+impl<'a, I: InputInjector> HeldKeys<'a, I> {
+    pub fn new(injector: &'a mut I) -> Self {
+        Self {
+            injector,
+            held: Vec::new(),
+        }
+    }
+
+    /// Presses `key` and keeps it down until released.
+    pub fn press(&mut self, key: Key) -> Result<(), String> {
+        self.injector.key(key, Direction::Press)?;
+        self.held.push(Held::Key(key));
+        Ok(())
+    }
+
+    /// Presses the scancode `code` and keeps it down until released.
+    pub fn press_raw(&mut self, code: u16) -> Result<(), String> {
+        self.injector.raw(code, Direction::Press)?;
+        self.held.push(Held::Raw(code));
+        Ok(())
+    }
+
+    pub fn is_held(&self, key: Key) -> bool {
+        self.held.contains(&Held::Key(key))
+    }
+
+    /// Releases every held key, last pressed first. Failures are logged so
+    /// the remaining keys are still released.
+    pub fn release_all(&mut self) {
+        while let Some(held) = self.held.pop() {
+            let released = match held {
+                Held::Key(key) => self.injector.key(key, Direction::Release),
+                Held::Raw(code) => self.injector.raw(code, Direction::Release),
+            };
+            if let Err(e) = released {
+                error!("Error releasing {:?}: {}", held, e);
+            }
+        }
+    }
+}
+
+impl<I: InputInjector> Deref for HeldKeys<'_, I> {
+    type Target = I;
+
+    fn deref(&self) -> &I {
+        self.injector
+    }
+}
+
+impl<I: InputInjector> DerefMut for HeldKeys<'_, I> {
+    fn deref_mut(&mut self) -> &mut I {
+        self.injector
+    }
+}
+
+impl<I: InputInjector> Drop for HeldKeys<'_, I> {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+/// Records events instead of sending them.
+#[derive(Clone, Debug, Default)]
+pub struct MockInjector {
+    pub events: Vec<InputEvent>,
+    /// A key whose press fails, to check that held keys are still released.
+    pub failing_key: Option<Key>,
+}
+
+impl MockInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn failing_on(key: Key) -> Self {
+        Self {
+            failing_key: Some(key),
+            ..Self::default()
+        }
+    }
+}
+
+impl InputInjector for MockInjector {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), String> {
+        if self.failing_key == Some(key) && direction != Direction::Release {
+            return Err(format!("{:?} can't be pressed", key));
+        }
+        self.events.push(InputEvent::Key(key, direction));
+        Ok(())
+    }
+
+    fn raw(&mut self, code: u16, direction: Direction) -> Result<(), String> {
+        self.events.push(InputEvent::Raw(code, direction));
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        self.events.push(InputEvent::Text(text.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[test]
+    fn held_keys_are_released_when_a_step_panics() {
+        let mut injector = MockInjector::new();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut held = HeldKeys::new(&mut injector);
+            held.press(Key::Control).unwrap();
+            held.press_raw(0x2A).unwrap();
+            panic!("the step failed halfway");
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            injector.events,
+            [
+                InputEvent::Key(Key::Control, Direction::Press),
+                InputEvent::Raw(0x2A, Direction::Press),
+                InputEvent::Raw(0x2A, Direction::Release),
+                InputEvent::Key(Key::Control, Direction::Release),
+            ]
+        );
+    }
+}
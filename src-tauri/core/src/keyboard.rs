@@ -0,0 +1,735 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+use tracing::{debug, error};
+
+use crate::injector::{HeldKeys, InputInjector};
+
+#[cfg(target_os = "linux")]
+use crate::wayland;
+
+/// How key presses reach other apps.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputBackend {
+    Enigo,
+    /// On Wayland, through the uinput device.
+    Ydotool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct InputBackendStatus {
+    pub backend: InputBackend,
+    /// Whether the session is a Wayland one, where enigo only reaches
+    /// XWayland windows.
+    pub wayland: bool,
+    /// What the user still has to do for keys to reach every window.
+    pub setup_steps: Vec<String>,
+}
+
+/// The typing speeds, in characters per second, a shortcut or the settings
+/// may ask for.
+pub const CHARS_PER_SECOND: RangeInclusive<f64> = 1.0..=1000.0;
+
+/// Refuses a typing speed outside [`CHARS_PER_SECOND`] before it is saved.
+pub fn validate_chars_per_second(chars_per_second: Option<f64>) -> Result<(), String> {
+    match chars_per_second {
+        Some(cps) if !CHARS_PER_SECOND.contains(&cps) => Err(format!(
+            "Typing speed must be between {} and {} characters per second",
+            CHARS_PER_SECOND.start(),
+            CHARS_PER_SECOND.end()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// How fast text is typed. Electron apps and remote desktop sessions drop
+/// characters that arrive faster than they can handle, so text can be slowed
+/// down per character and typed in chunks with a pause after each. Unset
+/// fields type as fast as the backend allows.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct TypingSpeed {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chars_per_second: Option<f64>,
+    /// Characters typed before pausing for `chunk_delay_ms`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_delay_ms: Option<u64>,
+}
+
+impl TypingSpeed {
+    /// This speed, with the fields it leaves unset taken from `fallback`.
+    pub fn or(self, fallback: TypingSpeed) -> TypingSpeed {
+        TypingSpeed {
+            chars_per_second: self.chars_per_second.or(fallback.chars_per_second),
+            chunk_size: self.chunk_size.or(fallback.chunk_size),
+            chunk_delay_ms: self.chunk_delay_ms.or(fallback.chunk_delay_ms),
+        }
+    }
+
+    /// Refuses a speed that would type absurdly slow or fast.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_chars_per_second(self.chars_per_second)
+    }
+
+    /// The delay between two characters. Speeds saved before they were
+    /// validated may be too slow for a `Duration`; those type without delay.
+    pub fn char_delay(&self) -> Option<Duration> {
+        self.chars_per_second
+            .filter(|cps| *cps > 0.0)
+            .and_then(|cps| Duration::try_from_secs_f64(1.0 / cps).ok())
+    }
+
+    /// The chunk size and the pause after each chunk, when both are set.
+    pub fn chunking(&self) -> Option<(usize, Duration)> {
+        let size = self.chunk_size.filter(|size| *size > 0)?;
+        let delay = self.chunk_delay_ms?;
+        Some((size, Duration::from_millis(delay)))
+    }
+
+    /// How long to wait after typing the `typed`th character.
+    pub fn pause_after(&self, typed: usize) -> Option<Duration> {
+        let chunk_delay = self
+            .chunking()
+            .filter(|(size, _)| typed.is_multiple_of(*size))
+            .map(|(_, delay)| delay);
+        match (self.char_delay(), chunk_delay) {
+            (None, None) => None,
+            (char_delay, chunk_delay) => {
+                Some(char_delay.unwrap_or_default() + chunk_delay.unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Reports the backend key presses currently go through.
+pub fn input_backend_status() -> InputBackendStatus {
+    #[cfg(target_os = "linux")]
+    if wayland::is_wayland_session() {
+        let status = wayland::ydotool_status();
+        return InputBackendStatus {
+            backend: if status.ready() {
+                InputBackend::Ydotool
+            } else {
+                InputBackend::Enigo
+            },
+            wayland: true,
+            setup_steps: status.setup_steps(),
+        };
+    }
+    InputBackendStatus {
+        backend: InputBackend::Enigo,
+        wayland: false,
+        setup_steps: Vec::new(),
+    }
+}
+
+/// ydotool's status when this is a Wayland session and it is set up.
+/// Otherwise keys go through enigo, which on Wayland still reaches XWayland
+/// windows.
+#[cfg(target_os = "linux")]
+fn ydotool_backend() -> Option<wayland::YdotoolStatus> {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+
+    if !wayland::is_wayland_session() {
+        return None;
+    }
+    let status = wayland::ydotool_status();
+    if status.ready() {
+        return Some(status);
+    }
+    WARNED.call_once(|| {
+        tracing::warn!(
+            "Keys only reach XWayland windows until ydotool is set up: {}",
+            status.setup_steps().join("; ")
+        )
+    });
+    None
+}
+
+/// The key a modifier name holds down. `Primary` is Cmd on macOS and Ctrl
+/// elsewhere, so one shortcut works on every OS. The left and right
+/// variants are for apps that bind them apart, e.g. AltGr combos.
+fn modifier_key(name: &str) -> Option<enigo::Key> {
+    use enigo::Key;
+
+    let key = match name {
+        "Ctrl" | "Control" => Key::Control,
+        "Alt" => Key::Alt,
+        "Shift" => Key::Shift,
+        "Cmd" | "Command" | "Meta" => Key::Meta,
+        "Primary" if cfg!(target_os = "macos") => Key::Meta,
+        "Primary" => Key::Control,
+        "LShift" => Key::LShift,
+        "RShift" => Key::RShift,
+        "LCtrl" => Key::LControl,
+        "RCtrl" => Key::RControl,
+        "LAlt" => sided::LEFT_ALT,
+        "RAlt" | "AltGr" => sided::RIGHT_ALT,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Keypad keys, which apps may bind apart from their main-row twins:
+/// `Numpad0` to `Numpad9` and `NumpadEnter`.
+fn numpad_key(name: &str) -> Option<enigo::Key> {
+    let rest = name.strip_prefix("Numpad")?;
+    if rest == "Enter" {
+        return Some(sided::NUMPAD_ENTER);
+    }
+    let digit = rest.parse::<usize>().ok().filter(|_| rest.len() == 1)?;
+    Some(sided::NUMPAD_DIGITS[digit])
+}
+
+/// Keys enigo only names on some OSes, by their virtual key code (Windows),
+/// key code (macOS) or keysym (X11).
+#[cfg(target_os = "windows")]
+mod sided {
+    use enigo::Key;
+
+    pub const LEFT_ALT: Key = Key::LMenu;
+    pub const RIGHT_ALT: Key = Key::RMenu;
+    // Windows only tells the keypad's Enter apart by a flag enigo doesn't set
+    pub const NUMPAD_ENTER: Key = Key::Return;
+    pub const NUMPAD_DIGITS: [Key; 10] = [
+        Key::Numpad0,
+        Key::Numpad1,
+        Key::Numpad2,
+        Key::Numpad3,
+        Key::Numpad4,
+        Key::Numpad5,
+        Key::Numpad6,
+        Key::Numpad7,
+        Key::Numpad8,
+        Key::Numpad9,
+    ];
+}
+
+#[cfg(target_os = "macos")]
+mod sided {
+    use enigo::Key;
+
+    pub const LEFT_ALT: Key = Key::Option;
+    pub const RIGHT_ALT: Key = Key::ROption;
+    pub const NUMPAD_ENTER: Key = Key::Other(76);
+    pub const NUMPAD_DIGITS: [Key; 10] = [
+        Key::Other(82),
+        Key::Other(83),
+        Key::Other(84),
+        Key::Other(85),
+        Key::Other(86),
+        Key::Other(87),
+        Key::Other(88),
+        Key::Other(89),
+        Key::Other(91),
+        Key::Other(92),
+    ];
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod sided {
+    use enigo::Key;
+
+    pub const LEFT_ALT: Key = Key::Alt;
+    /// ISO_Level3_Shift, what AltGr sends on layouts that have it.
+    pub const RIGHT_ALT: Key = Key::Other(0xfe03);
+    pub const NUMPAD_ENTER: Key = Key::Other(0xff8d);
+    pub const NUMPAD_DIGITS: [Key; 10] = [
+        Key::Other(0xffb0),
+        Key::Other(0xffb1),
+        Key::Other(0xffb2),
+        Key::Other(0xffb3),
+        Key::Other(0xffb4),
+        Key::Other(0xffb5),
+        Key::Other(0xffb6),
+        Key::Other(0xffb7),
+        Key::Other(0xffb8),
+        Key::Other(0xffb9),
+    ];
+}
+
+/// Simulates a keyboard shortcut based on the provided keys.
+///
+/// # Arguments
+///
+/// * `shortcut_keys` - A string representing the keyboard shortcut keys (e.g., "Ctrl+S").
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+pub fn simulate_shortcut(sequence: Vec<String>, interval_ms: Option<u64>) -> Result<(), String> {
+    // println!("Simulating shortcut sequence: {:?}", sequence);
+
+    use enigo::{Enigo, Settings};
+
+    let interval = std::time::Duration::from_millis(interval_ms.unwrap_or(100)); // Default interval is 100ms
+
+    #[cfg(target_os = "linux")]
+    if let Some(status) = ydotool_backend() {
+        return wayland::simulate_shortcut(&status, sequence, interval);
+    }
+
+    // Create Enigo instance (keeping the initialization as it was)
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+
+    for shortcut_keys in sequence {
+        press_combo(&mut enigo, &shortcut_keys)?;
+
+        // Wait for the specified interval before the next shortcut
+        std::thread::sleep(interval);
+    }
+
+    Ok(())
+}
+
+/// Presses one combo such as "Ctrl+Shift+T" through `injector`: modifiers
+/// down, then each key, then the modifiers up in reverse order. Keys that
+/// fail are logged and skipped; the modifiers are held by a [`HeldKeys`]
+/// guard, so they are released even if pressing a key panics.
+pub fn press_combo(injector: &mut impl InputInjector, shortcut_keys: &str) -> Result<(), String> {
+    use enigo::{Direction, Key};
+
+    debug!("Simulating shortcut: {}", shortcut_keys);
+
+    // Keep track of pressed modifiers
+    let mut held = HeldKeys::new(injector);
+
+    // Split the shortcut keys and trim whitespace
+    let keys: Vec<&str> = shortcut_keys.split('+').map(|k| k.trim()).collect();
+
+    // Press down modifier keys first
+    for key in &keys {
+        let Some(modifier) = modifier_key(key) else {
+            continue;
+        };
+        if let Err(e) = held.press(modifier) {
+            error!("Error pressing key {}: {}", key, e);
+        }
+    }
+
+    // Press the main key(s)
+    let mut result = Ok(());
+    for key in &keys {
+        if modifier_key(key).is_none() {
+            let key_str = key.trim();
+            let pressed = match key_str {
+                "Enter" => held.key(Key::Return, Direction::Click),
+                "Tab" => held.key(Key::Tab, Direction::Click),
+                "Backspace" => held.key(Key::Backspace, Direction::Click),
+                "Space" => held.key(Key::Space, Direction::Click),
+                "Left" => held.key(Key::LeftArrow, Direction::Click),
+                "Right" => held.key(Key::RightArrow, Direction::Click),
+                "Up" => held.key(Key::UpArrow, Direction::Click),
+                "Down" => held.key(Key::DownArrow, Direction::Click),
+                // Add other special keys as needed
+                _ if key_str.starts_with("Numpad") => match numpad_key(key_str) {
+                    Some(numpad) => held.key(numpad, Direction::Click),
+                    None => {
+                        error!("Unknown key {}", key_str);
+                        continue;
+                    }
+                },
+                _ => {
+                    // Handle character keys
+                    let Some(character) = key_str.chars().next() else {
+                        continue;
+                    };
+                    let mut need_shift = false;
+                    let mut char_to_use = character;
+
+                    // Check if character is uppercase or requires Shift
+                    if character.is_uppercase() || is_special_character(character) {
+                        need_shift = true;
+                        char_to_use = character.to_ascii_lowercase();
+                    }
+
+                    // Press Shift if needed and not already pressed
+                    let shift_held = [Key::Shift, Key::LShift, Key::RShift]
+                        .iter()
+                        .any(|shift| held.is_held(*shift));
+                    if need_shift && !shift_held {
+                        if let Err(e) = held.press(Key::Shift) {
+                            result = Err(format!("Error pressing Shift key: {}", e));
+                            break;
+                        }
+                    }
+
+                    held.key(Key::Unicode(char_to_use), Direction::Click)
+                }
+            };
+
+            if let Err(e) = pressed {
+                error!("Error pressing key {}: {}", key_str, e);
+            }
+        }
+    }
+
+    // Release modifier keys in reverse order
+    held.release_all();
+
+    result
+}
+
+// Helper function to check if a character is a special character that requires Shift
+fn is_special_character(c: char) -> bool {
+    matches!(
+        c,
+        '!' | '@'
+            | '#'
+            | '$'
+            | '%'
+            | '^'
+            | '&'
+            | '*'
+            | '('
+            | ')'
+            | '_'
+            | '+'
+            | '{'
+            | '}'
+            | '|'
+            | ':'
+            | '"'
+            | '<'
+            | '>'
+            | '?'
+    )
+}
+
+pub fn is_text_string(input: &str) -> bool {
+    // If the string does not contain any modifier keys or '+', treat it as text
+    !input.contains('+')
+        && !input.to_lowercase().contains("ctrl")
+        && !input.to_lowercase().contains("control")
+        && !input.to_lowercase().contains("shift")
+        && !input.to_lowercase().contains("alt")
+        && !input.to_lowercase().contains("cmd")
+        && !input.to_lowercase().contains("command")
+        && !input.to_lowercase().contains("meta")
+        && !is_named_key(input.trim())
+}
+
+/// Whether `token`, one `+`-separated part of a combo, is a key named by a
+/// whole word such as `Primary` or `Numpad7`, so text merely starting with
+/// one (`primary@example.com`) is still typed.
+fn is_named_key(token: &str) -> bool {
+    let lower = token.to_lowercase();
+    if lower == "primary" {
+        return true;
+    }
+    match lower.strip_prefix("numpad") {
+        Some("enter") => true,
+        Some(rest) => numpad_key(&format!("Numpad{}", rest)).is_some(),
+        None => false,
+    }
+}
+
+/// Marks where the caret goes once a snippet is typed.
+pub const CURSOR_MARKER: &str = "{cursor}";
+
+/// Takes the `{cursor}` marker out of a snippet. Returns the text to type and
+/// how many times to press Left afterwards to put the caret where the marker
+/// was. Line breaks come back as `\n`, which takes one press, as it does in
+/// text fields.
+pub fn split_at_cursor(snippet: &str) -> Result<(String, usize), String> {
+    let snippet = snippet.replace("\r\n", "\n");
+    let Some((before, after)) = snippet.split_once(CURSOR_MARKER) else {
+        return Ok((snippet, 0));
+    };
+    if after.contains(CURSOR_MARKER) {
+        return Err(format!("A snippet can have only one {}", CURSOR_MARKER));
+    }
+    Ok((format!("{}{}", before, after), after.chars().count()))
+}
+
+/// Spells the modifiers of a key combo the way they are stored, so that
+/// "control+shift+s" and "Ctrl+Shift+s" are the same step and the
+/// cross-platform spellings "CmdOrCtrl" and "Mod" become `Primary`. Text to
+/// type is returned unchanged.
+pub fn normalize_keys(keys: &str) -> String {
+    if is_text_string(keys) {
+        return keys.to_string();
+    }
+    keys.split('+')
+        .map(|key| {
+            let key = key.trim();
+            match key.to_lowercase().as_str() {
+                "ctrl" | "control" => "Ctrl",
+                "alt" | "option" => "Alt",
+                "shift" => "Shift",
+                "cmd" | "command" | "meta" | "super" | "win" => "Cmd",
+                "primary" | "cmdorctrl" | "commandorcontrol" | "mod" => "Primary",
+                "lshift" => "LShift",
+                "rshift" => "RShift",
+                "lctrl" | "lcontrol" => "LCtrl",
+                "rctrl" | "rcontrol" => "RCtrl",
+                "lalt" => "LAlt",
+                "ralt" | "altgr" => "RAlt",
+                "numpadenter" => "NumpadEnter",
+                lower if lower.starts_with("numpad") => {
+                    return format!("Numpad{}", &key["numpad".len()..]);
+                }
+                _ => key,
+            }
+            .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+pub fn simulate_text_typing(text: &str, speed: TypingSpeed) -> Result<(), String> {
+    use enigo::{Enigo, Settings};
+
+    #[cfg(target_os = "linux")]
+    if let Some(status) = ydotool_backend() {
+        return wayland::simulate_text_typing(&status, text, speed);
+    }
+
+    // Create Enigo instance (keeping the initialization as it was)
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    type_text(&mut enigo, text, speed)
+}
+
+/// Types `text` through `injector` one character at a time, at `speed`.
+pub fn type_text(
+    injector: &mut impl InputInjector,
+    text: &str,
+    speed: TypingSpeed,
+) -> Result<(), String> {
+    // Type each character in the text
+    for (i, c) in text.chars().enumerate() {
+        injector
+            .text(&c.to_string())
+            .map_err(|e| format!("Error typing character '{}': {}", c, e))?;
+        if let Some(pause) = speed.pause_after(i + 1) {
+            std::thread::sleep(pause);
+        }
+    }
+
+    Ok(())
+}
+
+/// Game mode fallback on systems without scancode injection: logs once, and
+/// keys go through the normal path.
+#[cfg(not(target_os = "windows"))]
+fn warn_no_game_mode() {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| tracing::warn!("Game mode is only available on Windows"));
+}
+
+/// Like [`simulate_shortcut`], but presses keys by scancode so games that
+/// read raw input see them. Only Windows has this; elsewhere it is the same
+/// as [`simulate_shortcut`].
+pub fn simulate_shortcut_game_mode(
+    sequence: Vec<String>,
+    interval_ms: Option<u64>,
+) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let interval = std::time::Duration::from_millis(interval_ms.unwrap_or(100));
+        crate::scancodes::simulate_shortcut(sequence, interval)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        warn_no_game_mode();
+        simulate_shortcut(sequence, interval_ms)
+    }
+}
+
+/// Like [`simulate_text_typing`], but presses the keys that produce the text
+/// by scancode instead of sending Unicode characters. Only Windows has this;
+/// elsewhere it is the same as [`simulate_text_typing`].
+pub fn simulate_text_typing_game_mode(text: &str, speed: TypingSpeed) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        crate::scancodes::simulate_text_typing(text, speed)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        warn_no_game_mode();
+        simulate_text_typing(text, speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::injector::{InputEvent, MockInjector};
+
+    #[test]
+    fn tells_text_from_key_combos() {
+        assert!(is_text_string("hello world"));
+        assert!(!is_text_string("Ctrl+S"));
+        assert!(!is_text_string("Cmd"));
+        assert!(!is_text_string("Primary"));
+    }
+
+    #[test]
+    fn normalizes_modifier_spellings() {
+        assert_eq!(normalize_keys("control + shift+s"), "Ctrl+Shift+s");
+        assert_eq!(normalize_keys("CmdOrCtrl+C"), "Primary+C");
+        assert_eq!(normalize_keys("Option+Win+Tab"), "Alt+Cmd+Tab");
+        assert_eq!(normalize_keys("just text"), "just text");
+        assert_eq!(normalize_keys("altgr+e"), "RAlt+e");
+        assert_eq!(normalize_keys("Ctrl+numpad5"), "Ctrl+Numpad5");
+    }
+
+    #[test]
+    fn cursor_marker_becomes_left_presses() {
+        assert_eq!(
+            split_at_cursor("Dear {cursor},\r\nBest").unwrap(),
+            ("Dear ,\nBest".to_string(), 6)
+        );
+        assert_eq!(
+            split_at_cursor("<b>{cursor}</b>").unwrap(),
+            ("<b></b>".to_string(), 4)
+        );
+        assert_eq!(
+            split_at_cursor("no marker").unwrap(),
+            ("no marker".to_string(), 0)
+        );
+        assert!(split_at_cursor("{cursor} and {cursor}").is_err());
+    }
+
+    #[test]
+    fn tells_numpad_keys_apart() {
+        assert!(numpad_key("Numpad0").is_some());
+        assert!(numpad_key("NumpadEnter").is_some());
+        assert!(numpad_key("Numpad10").is_none());
+        assert!(numpad_key("5").is_none());
+        assert!(!is_text_string("Numpad7"));
+        assert!(!is_text_string("numpadenter"));
+        assert!(is_text_string("primary@example.com"));
+        assert!(is_text_string("numpad keys"));
+    }
+
+    #[test]
+    fn combos_release_modifiers_in_reverse() {
+        use enigo::{Direction, Key};
+
+        let mut injector = MockInjector::new();
+        press_combo(&mut injector, "Ctrl+Alt+t").unwrap();
+        assert_eq!(
+            injector.events,
+            [
+                InputEvent::Key(Key::Control, Direction::Press),
+                InputEvent::Key(Key::Alt, Direction::Press),
+                InputEvent::Key(Key::Unicode('t'), Direction::Click),
+                InputEvent::Key(Key::Alt, Direction::Release),
+                InputEvent::Key(Key::Control, Direction::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn shifted_characters_hold_shift_once() {
+        use enigo::{Direction, Key};
+
+        let mut injector = MockInjector::new();
+        press_combo(&mut injector, "Ctrl+A").unwrap();
+        assert_eq!(
+            injector.events,
+            [
+                InputEvent::Key(Key::Control, Direction::Press),
+                InputEvent::Key(Key::Shift, Direction::Press),
+                InputEvent::Key(Key::Unicode('a'), Direction::Click),
+                InputEvent::Key(Key::Shift, Direction::Release),
+                InputEvent::Key(Key::Control, Direction::Release),
+            ]
+        );
+
+        let mut injector = MockInjector::new();
+        press_combo(&mut injector, "Shift+A").unwrap();
+        let shift_presses = injector
+            .events
+            .iter()
+            .filter(|e| **e == InputEvent::Key(Key::Shift, Direction::Press))
+            .count();
+        assert_eq!(shift_presses, 1);
+    }
+
+    #[test]
+    fn failed_keys_still_release_modifiers() {
+        use enigo::{Direction, Key};
+
+        let mut injector = MockInjector::failing_on(Key::Return);
+        press_combo(&mut injector, "Ctrl+Enter").unwrap();
+        assert_eq!(
+            injector.events,
+            [
+                InputEvent::Key(Key::Control, Direction::Press),
+                InputEvent::Key(Key::Control, Direction::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_is_typed_per_character() {
+        let mut injector = MockInjector::new();
+        type_text(&mut injector, "hé", TypingSpeed::default()).unwrap();
+        assert_eq!(
+            injector.events,
+            [
+                InputEvent::Text("h".to_string()),
+                InputEvent::Text("é".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunks_pause_after_their_last_character() {
+        let speed = TypingSpeed {
+            chars_per_second: Some(100.0),
+            chunk_size: Some(3),
+            chunk_delay_ms: Some(200),
+        };
+        assert_eq!(speed.pause_after(1), Some(Duration::from_millis(10)));
+        assert_eq!(speed.pause_after(3), Some(Duration::from_millis(210)));
+        // A chunk size without a delay doesn't pause
+        let speed = TypingSpeed {
+            chunk_size: Some(3),
+            ..TypingSpeed::default()
+        };
+        assert_eq!(speed.pause_after(3), None);
+    }
+
+    #[test]
+    fn speeds_out_of_range_are_refused() {
+        for cps in [f64::NAN, f64::INFINITY, 1e-300, 0.5, 1000.5] {
+            let speed = TypingSpeed {
+                chars_per_second: Some(cps),
+                ..TypingSpeed::default()
+            };
+            assert!(speed.validate().is_err(), "{} was accepted", cps);
+        }
+        assert!(TypingSpeed::default().validate().is_ok());
+        // Stored before validation, they still mustn't panic while typing
+        let crawling = TypingSpeed {
+            chars_per_second: Some(f64::MIN_POSITIVE / 4.0),
+            ..TypingSpeed::default()
+        };
+        assert_eq!(crawling.char_delay(), None);
+    }
+
+    #[test]
+    fn step_speeds_fall_back_field_by_field() {
+        let step = TypingSpeed {
+            chars_per_second: Some(20.0),
+            ..TypingSpeed::default()
+        };
+        let global = TypingSpeed {
+            chars_per_second: Some(50.0),
+            chunk_size: Some(10),
+            chunk_delay_ms: Some(100),
+        };
+        assert_eq!(
+            step.or(global),
+            TypingSpeed {
+                chars_per_second: Some(20.0),
+                ..global
+            }
+        );
+    }
+}
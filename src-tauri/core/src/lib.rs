@@ -0,0 +1,18 @@
+//! The parts of Button Beam that don't depend on Tauri: the shortcut store,
+//! the messages exchanged with devices, key and mouse simulation and finding
+//! images on screen. The desktop app wraps these; with the `headless`
+//! feature they also make up `button-beam-headless`, a server that runs
+//! without a window.
+
+#[cfg(feature = "headless")]
+pub mod headless;
+pub mod injector;
+pub mod keyboard;
+pub mod mouse;
+pub mod protocol;
+pub mod scancodes;
+pub mod shortcuts;
+pub mod storage;
+pub mod template;
+#[cfg(target_os = "linux")]
+pub mod wayland;
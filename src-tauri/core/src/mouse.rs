@@ -0,0 +1,62 @@
+use enigo::{Axis, Button, Coordinate, Direction, Enigo, Mouse, Settings};
+use serde::Deserialize;
+
+// Moves and clicks the mouse through enigo, in desktop coordinates: the
+// pixels of the whole virtual screen, with the primary monitor's top-left
+// corner at 0,0. Picking the point on a particular monitor is up to the app.
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Right,
+    Middle,
+}
+
+impl From<MouseButton> for Button {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => Button::Left,
+            MouseButton::Right => Button::Right,
+            MouseButton::Middle => Button::Middle,
+        }
+    }
+}
+
+/// Moves the pointer to `x`, `y` in desktop coordinates.
+pub fn move_to(x: i32, y: i32) -> Result<(), String> {
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    enigo
+        .move_mouse(x, y, Coordinate::Abs)
+        .map_err(|e| format!("Error moving the mouse: {}", e))
+}
+
+/// Turns the scroll wheel by `lines`; positive scrolls down, or right when
+/// `horizontal`.
+pub fn scroll(lines: i32, horizontal: bool) -> Result<(), String> {
+    let axis = if horizontal {
+        Axis::Horizontal
+    } else {
+        Axis::Vertical
+    };
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    enigo
+        .scroll(lines, axis)
+        .map_err(|e| format!("Error scrolling: {}", e))
+}
+
+/// Moves the pointer to `x`, `y` in desktop coordinates and clicks `button`
+/// `count` times there.
+pub fn click_at(x: i32, y: i32, button: MouseButton, count: u32) -> Result<(), String> {
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    enigo
+        .move_mouse(x, y, Coordinate::Abs)
+        .map_err(|e| format!("Error moving the mouse: {}", e))?;
+    for _ in 0..count {
+        enigo
+            .button(button.into(), Direction::Click)
+            .map_err(|e| format!("Error clicking the mouse: {}", e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::shortcuts::{CycleMove, PressKind, Shortcut, ShortcutChange};
+
+// Messages exchanged with the phone app over WS, as JSON text frames or,
+// after negotiating `msgpack`, MessagePack binary frames.
+
+/// Protocol version spoken by this server.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// Oldest client protocol version still accepted. Clients that never send
+/// `hello` are treated as version 1.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// What a paired device may do over WS.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceRole {
+    /// May fire shortcuts and list them, nothing else.
+    #[default]
+    TriggerOnly,
+    /// May also edit shortcuts and read settings.
+    Admin,
+}
+
+/// Optional features offered by this server, announced in the `hello` reply.
+pub const SERVER_CAPABILITIES: &[&str] = &[
+    "responses",
+    CAP_SHORTCUT_DIFFS,
+    CAP_MSGPACK,
+    CAP_EXECUTION_RESULTS,
+    CAP_LAYOUTS,
+    CAP_LATENCY,
+    CAP_HOTKEY_CONFLICTS,
+    CAP_PRESS_KINDS,
+    CAP_CONTROLS,
+    CAP_SHORTCUT_STATES,
+    CAP_ASSETS,
+    CAP_CYCLE_POSITIONS,
+    CAP_NOW_PLAYING,
+];
+/// Clients announcing this get `shortcut_added`/`shortcut_updated`/
+/// `shortcut_deleted`/`sync` messages instead of the bare shortcut list.
+pub const CAP_SHORTCUT_DIFFS: &str = "shortcut_diffs";
+/// Clients announcing this switch to MessagePack binary frames right after
+/// the `hello` reply, which is still sent as JSON.
+pub const CAP_MSGPACK: &str = "msgpack";
+/// Clients announcing this get an `execution_result` message once a shortcut
+/// they triggered has finished running, listing any `files` it wrote. It
+/// carries the `id` of the `execute_shortcut` message as `request_id`.
+pub const CAP_EXECUTION_RESULTS: &str = "execution_results";
+/// Clients announcing this get a `layout` message with the button grid the
+/// desktop assigned to them, after the shortcut list and whenever it changes.
+pub const CAP_LAYOUTS: &str = "layouts";
+/// Clients announcing this along with `execution_results` get a `latency`
+/// object in each `execution_result`, with the milliseconds the desktop spent
+/// before running the sequence (`queue_ms`), running it (`execution_ms`) and
+/// in total (`total_ms`).
+pub const CAP_LATENCY: &str = "latency";
+/// Clients announcing this get a `hotkey_conflicts` message listing the
+/// global hotkeys another app took, with the shortcuts bound to them, after
+/// the shortcut list when there are any and whenever the list changes.
+pub const CAP_HOTKEY_CONFLICTS: &str = "hotkey_conflicts";
+/// Announced by the server: `execute_shortcut` takes a `press_kind` of `tap`,
+/// `long_press` or `double_tap`, and shortcuts may carry a
+/// `long_press_sequence` and a `double_tap_sequence`. Older servers ignore
+/// the field and always run the tap sequence.
+pub const CAP_PRESS_KINDS: &str = "press_kinds";
+/// Announced by the server: shortcuts with a `control` can be shown as dials
+/// and sliders, driven by `dial_rotate` and `slider_set`.
+pub const CAP_CONTROLS: &str = "controls";
+/// Clients announcing this get a `shortcut_states` message with the on/off
+/// state of every toggle shortcut after the shortcut list, and a
+/// `shortcut_state_changed` message with its `shortcut_id` and `active`
+/// whenever one changes.
+pub const CAP_SHORTCUT_STATES: &str = "shortcut_states";
+/// Announced by the server: admin devices can send the reference images of
+/// `wait_for_image` and `click_image` steps with `upload_asset` and list them
+/// with `list_assets`.
+pub const CAP_ASSETS: &str = "assets";
+/// Clients announcing this get a `cycle_positions` message with where the
+/// cycle of every cycling shortcut is after the shortcut list, and a
+/// `cycle_position` message with its `shortcut_id` and `position` whenever a
+/// tap moves one on. Position 0 is the shortcut's `sequence`, 1 the first of
+/// its `cycle`, and so on.
+pub const CAP_CYCLE_POSITIONS: &str = "cycle_positions";
+/// Clients announcing this get a `now_playing` message with the `track`
+/// whenever the active media player's track or playback state changes. The
+/// player is only watched while such a client is paired.
+pub const CAP_NOW_PLAYING: &str = "now_playing";
+
+/// Messages a client may send. Each one may carry an `id`; when it does, the
+/// server answers with a [`Response`] carrying the same `id`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        /// Lets a client authenticate in the same message as the handshake.
+        token: Option<String>,
+    },
+    /// Must be the first message unless the token was given in the WS URL.
+    Auth {
+        token: String,
+    },
+    DeviceInfo {
+        device_name: String,
+        /// Stable identifier of the phone, used to remember pairing approval.
+        device_id: Option<String>,
+        /// Issued with `pairing_approved` to a device paired without the
+        /// token; stands in for the token on later connections.
+        device_secret: Option<String>,
+        #[serde(flatten)]
+        status: DeviceStatus,
+    },
+    /// Updates battery level and the like after `device_info`.
+    DeviceStatus(DeviceStatus),
+    ExecuteShortcut {
+        shortcut_id: u64,
+        interval_ms: Option<u64>,
+        /// Picks the shortcut's sequence; a tap when unset.
+        #[serde(default)]
+        press_kind: PressKind,
+    },
+    /// Turns a dial shortcut by `delta` detents, negative for
+    /// counterclockwise.
+    DialRotate {
+        shortcut_id: u64,
+        delta: i32,
+    },
+    /// Moves a slider shortcut to `value` percent of its range.
+    SliderSet {
+        shortcut_id: u64,
+        value: f64,
+    },
+    /// Reclaims a previous connection's device and pairing state. Accepted in
+    /// place of the auth token.
+    Resume {
+        session_token: String,
+    },
+    /// Remote editing; admin devices only.
+    AddShortcut {
+        shortcut: Shortcut,
+    },
+    UpdateShortcut {
+        shortcut: Shortcut,
+    },
+    DeleteShortcut {
+        shortcut_id: u64,
+    },
+    /// Re-requests the shortcut list, optionally filtered and paginated. The
+    /// result comes back as the response payload, so an `id` is required.
+    GetShortcuts {
+        group: Option<String>,
+        tag: Option<String>,
+        /// Zero-based page index; all matches are returned when unset.
+        page: Option<usize>,
+        #[serde(default = "default_page_size")]
+        page_size: usize,
+    },
+    /// Reads the desktop settings; admin devices only.
+    GetSettings,
+    /// Stores a PNG, base64-encoded in `data`, under `name` for image steps,
+    /// replacing any image of that name; admin devices only.
+    UploadAsset {
+        name: String,
+        data: String,
+    },
+    /// Lists the names of the stored images; admin devices only.
+    ListAssets,
+    /// Reads the current output volume and display brightness. Devices are
+    /// also sent `system_levels` whenever a step changes them.
+    GetSystemLevels,
+    /// Reads the active media player's track, or null when nothing is
+    /// playing. Devices announcing `now_playing` are also sent it whenever it
+    /// changes.
+    GetNowPlaying,
+    /// Runs the shortcut named in a phrase the phone recognized, e.g. "start
+    /// standup" for a shortcut called "Standup". The response carries the
+    /// matched `shortcut_id`.
+    VoiceCommand {
+        text: String,
+    },
+}
+
+impl ClientMessage {
+    /// Role a paired device needs to send this message, or `None` for the
+    /// handshake messages that are allowed before pairing.
+    pub fn required_role(&self) -> Option<DeviceRole> {
+        match self {
+            ClientMessage::Hello { .. }
+            | ClientMessage::Auth { .. }
+            | ClientMessage::DeviceInfo { .. }
+            | ClientMessage::DeviceStatus(_)
+            | ClientMessage::Resume { .. } => None,
+            ClientMessage::ExecuteShortcut { .. }
+            | ClientMessage::DialRotate { .. }
+            | ClientMessage::SliderSet { .. }
+            | ClientMessage::GetShortcuts { .. }
+            | ClientMessage::GetSystemLevels
+            | ClientMessage::GetNowPlaying
+            | ClientMessage::VoiceCommand { .. } => Some(DeviceRole::TriggerOnly),
+            ClientMessage::AddShortcut { .. }
+            | ClientMessage::UpdateShortcut { .. }
+            | ClientMessage::DeleteShortcut { .. }
+            | ClientMessage::GetSettings
+            | ClientMessage::UploadAsset { .. }
+            | ClientMessage::ListAssets => Some(DeviceRole::Admin),
+        }
+    }
+}
+
+fn default_page_size() -> usize {
+    50
+}
+
+/// Reply to a client message, matched to it by `id`.
+#[derive(Debug, Serialize)]
+pub struct Response {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: Value,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+}
+
+impl Response {
+    pub fn from_result(id: Value, result: Result<Option<Value>, String>) -> Self {
+        let (ok, error, payload) = match result {
+            Ok(payload) => (true, None, payload),
+            Err(error) => (false, Some(error), None),
+        };
+        Self {
+            kind: "response",
+            id,
+            ok,
+            error,
+            payload,
+        }
+    }
+}
+
+/// Payload of the `get_shortcuts` response: the shortcuts in `group` and with
+/// `tag`, where given, and how many there are. With `page` set, only that
+/// page of `page_size` shortcuts is included.
+pub fn shortcut_page(
+    shortcuts: Vec<Shortcut>,
+    group: Option<String>,
+    tag: Option<String>,
+    page: Option<usize>,
+    page_size: usize,
+) -> Result<Value, String> {
+    let matching: Vec<Shortcut> = shortcuts
+        .into_iter()
+        .filter(|s| group.is_none() || s.group == group)
+        .filter(|s| tag.as_ref().is_none_or(|tag| s.tags.contains(tag)))
+        .collect();
+    let total = matching.len();
+
+    let shortcuts: Vec<Shortcut> = match page {
+        Some(page) => {
+            if page_size == 0 {
+                return Err("page_size must be at least 1".to_string());
+            }
+            matching
+                .into_iter()
+                .skip(page.saturating_mul(page_size))
+                .take(page_size)
+                .collect()
+        }
+        None => matching,
+    };
+
+    Ok(json!({
+        "shortcuts": shortcuts,
+        "total": total,
+    }))
+}
+
+/// Builds the message that brings a client's shortcut list up to date, from
+/// the list as it is now. A shortcut that was added or updated but is gone
+/// again by now is reported as deleted.
+pub fn shortcut_message(
+    change: &ShortcutChange,
+    shortcuts: &[Shortcut],
+    diffs: bool,
+) -> Result<Value, serde_json::Error> {
+    if !diffs {
+        // Legacy clients always get the full list as a bare array
+        return serde_json::to_value(shortcuts);
+    }
+
+    let find = |id: &u64| shortcuts.iter().find(|s| s.id == *id);
+    Ok(match change {
+        ShortcutChange::Added(id) => match find(id) {
+            Some(shortcut) => json!({ "type": "shortcut_added", "shortcut": shortcut }),
+            None => json!({ "type": "shortcut_deleted", "id": id }),
+        },
+        ShortcutChange::Updated(id) => match find(id) {
+            Some(shortcut) => json!({ "type": "shortcut_updated", "shortcut": shortcut }),
+            None => json!({ "type": "shortcut_deleted", "id": id }),
+        },
+        ShortcutChange::Deleted(id) => json!({ "type": "shortcut_deleted", "id": id }),
+        ShortcutChange::Reset => json!({ "type": "sync", "shortcuts": shortcuts }),
+    })
+}
+
+/// The `cycle_positions` message, see [`CAP_CYCLE_POSITIONS`].
+pub fn cycle_positions_message(positions: &BTreeMap<u64, usize>) -> Value {
+    let positions: Vec<Value> = positions
+        .iter()
+        .map(|(id, position)| json!({ "shortcut_id": id, "position": position }))
+        .collect();
+    json!({ "type": "cycle_positions", "positions": positions })
+}
+
+/// The `cycle_position` message, see [`CAP_CYCLE_POSITIONS`].
+pub fn cycle_position_message(moved: &CycleMove) -> Value {
+    json!({
+        "type": "cycle_position",
+        "shortcut_id": moved.shortcut_id,
+        "position": moved.position,
+    })
+}
+
+/// Optional details a phone reports about itself, shown on the desktop.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceStatus {
+    /// Battery charge in percent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery_level: Option<u8>,
+    /// E.g. `ios` or `android`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    /// Version of the mobile app, to warn about outdated clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+}
+
+impl DeviceStatus {
+    /// Takes every field the update carries, keeping the others.
+    pub fn merge(&mut self, update: DeviceStatus) {
+        if update.battery_level.is_some() {
+            self.battery_level = update.battery_level;
+        }
+        if update.platform.is_some() {
+            self.platform = update.platform;
+        }
+        if update.app_version.is_some() {
+            self.app_version = update.app_version;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tagged_messages() {
+        let message: ClientMessage =
+            serde_json::from_value(json!({ "type": "execute_shortcut", "shortcut_id": 7 }))
+                .unwrap();
+        assert!(matches!(
+            message,
+            ClientMessage::ExecuteShortcut {
+                shortcut_id: 7,
+                interval_ms: None,
+                press_kind: PressKind::Tap,
+            }
+        ));
+        assert_eq!(message.required_role(), Some(DeviceRole::TriggerOnly));
+    }
+
+    #[test]
+    fn execute_shortcut_takes_a_press_kind() {
+        let message: ClientMessage = serde_json::from_value(json!({
+            "type": "execute_shortcut",
+            "shortcut_id": 7,
+            "press_kind": "long_press",
+        }))
+        .unwrap();
+        assert!(matches!(
+            message,
+            ClientMessage::ExecuteShortcut {
+                press_kind: PressKind::LongPress,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn controls_carry_their_value() {
+        let message: ClientMessage =
+            serde_json::from_value(json!({ "type": "dial_rotate", "shortcut_id": 7, "delta": -3 }))
+                .unwrap();
+        assert!(matches!(
+            message,
+            ClientMessage::DialRotate {
+                shortcut_id: 7,
+                delta: -3
+            }
+        ));
+        assert_eq!(message.required_role(), Some(DeviceRole::TriggerOnly));
+        let message: ClientMessage = serde_json::from_value(
+            json!({ "type": "slider_set", "shortcut_id": 7, "value": 40.5 }),
+        )
+        .unwrap();
+        assert!(matches!(message, ClientMessage::SliderSet { value, .. } if value == 40.5));
+    }
+
+    #[test]
+    fn editing_needs_admin() {
+        let message: ClientMessage =
+            serde_json::from_value(json!({ "type": "delete_shortcut", "shortcut_id": 7 })).unwrap();
+        assert_eq!(message.required_role(), Some(DeviceRole::Admin));
+        let message: ClientMessage = serde_json::from_value(
+            json!({ "type": "upload_asset", "name": "play", "data": "iVBORw0KGgo=" }),
+        )
+        .unwrap();
+        assert_eq!(message.required_role(), Some(DeviceRole::Admin));
+    }
+
+    #[test]
+    fn shortcuts_come_in_pages() {
+        let shortcuts: Vec<Shortcut> = (1..=3)
+            .map(|id| {
+                serde_json::from_value(json!({ "id": id, "name": "Page", "sequence": [] })).unwrap()
+            })
+            .collect();
+        let page = shortcut_page(shortcuts.clone(), None, None, Some(1), 2).unwrap();
+        assert_eq!(page["total"], 3);
+        assert_eq!(page["shortcuts"][0]["id"], 3);
+        let page = shortcut_page(shortcuts.clone(), Some("none".into()), None, None, 2).unwrap();
+        assert_eq!(page["total"], 0);
+        assert!(shortcut_page(shortcuts, None, None, Some(0), 0).is_err());
+    }
+
+    #[test]
+    fn legacy_clients_get_the_whole_list() {
+        let message = shortcut_message(&ShortcutChange::Deleted(3), &[], false).unwrap();
+        assert_eq!(message, json!([]));
+        let message = shortcut_message(&ShortcutChange::Deleted(3), &[], true).unwrap();
+        assert_eq!(message, json!({ "type": "shortcut_deleted", "id": 3 }));
+    }
+
+    #[test]
+    fn changes_to_shortcuts_gone_since_are_deletions() {
+        let message = shortcut_message(&ShortcutChange::Updated(3), &[], true).unwrap();
+        assert_eq!(message, json!({ "type": "shortcut_deleted", "id": 3 }));
+    }
+}
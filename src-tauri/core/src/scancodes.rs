@@ -0,0 +1,254 @@
+// Game mode: key presses sent as hardware scancodes. Games read the keyboard
+// through DirectInput or raw input, which only see a key's scancode, so they
+// ignore the virtual-key and Unicode events enigo sends by default. Text is
+// typed by pressing the keys that produce it, with Shift where needed,
+// rather than through per-character Unicode events.
+//
+// Codes are PC set 1 scancodes and assume a US layout, like the rest of the
+// key simulation. For the keys up to F12 they equal the Linux kernel's key
+// codes, which the Wayland backend uses as well.
+
+use enigo::Direction;
+
+use crate::injector::{HeldKeys, InputInjector};
+use crate::keyboard::TypingSpeed;
+
+/// Marks codes that need the extended flag, e.g. the right-hand Ctrl and Alt
+/// and the arrow keys. enigo sets the flag for any code above 0x7F.
+const EXTENDED: u16 = 0xE000;
+
+const LCTRL: u16 = 0x1D;
+const LSHIFT: u16 = 0x2A;
+const RSHIFT: u16 = 0x36;
+const LALT: u16 = 0x38;
+const ENTER: u16 = 0x1C;
+const TAB: u16 = 0x0F;
+
+fn modifier_code(name: &str) -> Option<u16> {
+    match name {
+        "Ctrl" | "Control" | "LCtrl" | "Primary" => Some(LCTRL),
+        "RCtrl" => Some(LCTRL | EXTENDED),
+        "Shift" | "LShift" => Some(LSHIFT),
+        "RShift" => Some(RSHIFT),
+        "Alt" | "LAlt" => Some(LALT),
+        "RAlt" | "AltGr" => Some(LALT | EXTENDED),
+        "Cmd" | "Command" | "Meta" => Some(0x5B | EXTENDED),
+        _ => None,
+    }
+}
+
+fn named_key_code(name: &str) -> Option<u16> {
+    let code = match name {
+        "Enter" => ENTER,
+        "Tab" => TAB,
+        "Backspace" => 0x0E,
+        "Space" => 0x39,
+        "Esc" | "Escape" => 0x01,
+        "Delete" => 0x53 | EXTENDED,
+        "Insert" => 0x52 | EXTENDED,
+        "Home" => 0x47 | EXTENDED,
+        "End" => 0x4F | EXTENDED,
+        "PageUp" => 0x49 | EXTENDED,
+        "PageDown" => 0x51 | EXTENDED,
+        "Up" => 0x48 | EXTENDED,
+        "Down" => 0x50 | EXTENDED,
+        "Left" => 0x4B | EXTENDED,
+        "Right" => 0x4D | EXTENDED,
+        "NumpadEnter" => ENTER | EXTENDED,
+        _ if name.starts_with("Numpad") => {
+            const KEYPAD: [u16; 10] = [0x52, 0x4F, 0x50, 0x51, 0x4B, 0x4C, 0x4D, 0x47, 0x48, 0x49];
+            let digit = name["Numpad".len()..].parse::<usize>().ok()?;
+            return KEYPAD.get(digit).copied();
+        }
+        _ => {
+            let number: u16 = name.strip_prefix('F')?.parse().ok()?;
+            return match number {
+                1..=10 => Some(0x3A + number),
+                11 | 12 => Some(0x4C + number),
+                _ => None,
+            };
+        }
+    };
+    Some(code)
+}
+
+/// The key that types `c` on a US layout, and whether it needs Shift.
+pub(crate) fn char_key(c: char) -> Option<(u16, bool)> {
+    const ROWS: [(&str, &str, u16); 4] = [
+        ("1234567890-=", "!@#$%^&*()_+", 0x02),
+        ("qwertyuiop[]", "QWERTYUIOP{}", 0x10),
+        ("asdfghjkl;'`", "ASDFGHJKL:\"~", 0x1E),
+        ("zxcvbnm,./", "ZXCVBNM<>?", 0x2C),
+    ];
+    match c {
+        ' ' => return Some((0x39, false)),
+        '\\' => return Some((0x2B, false)),
+        '|' => return Some((0x2B, true)),
+        _ => {}
+    }
+    ROWS.iter().find_map(|(plain, shifted, first)| {
+        let position = |row: &str| row.chars().position(|k| k == c).map(|i| first + i as u16);
+        position(plain)
+            .map(|code| (code, false))
+            .or_else(|| position(shifted).map(|code| (code, true)))
+    })
+}
+
+/// The scancodes of one combo such as "Ctrl+Shift+T": the modifiers to hold,
+/// Shift included when a key's character needs it, and the keys to click
+/// while they are held.
+fn combo_codes(combo: &str) -> Result<(Vec<u16>, Vec<u16>), String> {
+    let keys: Vec<&str> = combo.split('+').map(|k| k.trim()).collect();
+    let mut modifiers: Vec<u16> = keys.iter().filter_map(|key| modifier_code(key)).collect();
+    let mut clicks = Vec::new();
+    for key in keys.iter().filter(|key| modifier_code(key).is_none()) {
+        let (code, shift) = match named_key_code(key) {
+            Some(code) => (code, false),
+            None => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    // A letter names its key, `Ctrl+S` doesn't mean Shift
+                    (Some(c), None) => char_key(c.to_ascii_lowercase())
+                        .ok_or_else(|| format!("\"{}\" has no key to press in game mode", key))?,
+                    (None, _) => continue,
+                    (Some(_), Some(_)) => return Err(format!("Unknown key \"{}\"", key)),
+                }
+            }
+        };
+        if shift && !modifiers.contains(&LSHIFT) && !modifiers.contains(&RSHIFT) {
+            modifiers.push(LSHIFT);
+        }
+        clicks.push(code);
+    }
+    Ok((modifiers, clicks))
+}
+
+/// The scancode that types `c`, and whether it needs Shift. Line breaks and
+/// tabs press Enter and Tab.
+fn text_key(c: char) -> Result<(u16, bool), String> {
+    match c {
+        '\n' => Ok((ENTER, false)),
+        '\t' => Ok((TAB, false)),
+        _ => char_key(c).ok_or_else(|| format!("'{}' can't be typed in game mode", c)),
+    }
+}
+
+/// Presses one combo by scancode through `injector`, releasing the held
+/// modifiers even when a key fails or panics.
+pub fn press_combo(injector: &mut impl InputInjector, combo: &str) -> Result<(), String> {
+    let (modifiers, clicks) = combo_codes(combo)?;
+    let mut held = HeldKeys::new(injector);
+    modifiers
+        .into_iter()
+        .try_for_each(|code| held.press_raw(code))
+        .and_then(|()| {
+            clicks
+                .into_iter()
+                .try_for_each(|code| held.raw(code, Direction::Click))
+        })
+        .map_err(|e| format!("Error pressing {}: {}", combo, e))
+}
+
+/// Types `text` by pressing the keys that produce it, at `speed`. Fails
+/// before pressing anything when a character has no key on a US layout.
+pub fn type_text(
+    injector: &mut impl InputInjector,
+    text: &str,
+    speed: TypingSpeed,
+) -> Result<(), String> {
+    let keys = text.chars().map(text_key).collect::<Result<Vec<_>, _>>()?;
+
+    let mut held = HeldKeys::new(injector);
+    for (i, (code, shift)) in keys.into_iter().enumerate() {
+        if shift {
+            held.press_raw(LSHIFT)?;
+        }
+        let result = held.raw(code, Direction::Click);
+        held.release_all();
+        result.map_err(|e| format!("Error typing scancode {:#x}: {}", code, e))?;
+        if let Some(pause) = speed.pause_after(i + 1) {
+            std::thread::sleep(pause);
+        }
+    }
+    Ok(())
+}
+
+/// Presses each combo in `sequence` by scancode, waiting `interval` after
+/// each one.
+#[cfg(target_os = "windows")]
+pub fn simulate_shortcut(
+    sequence: Vec<String>,
+    interval: std::time::Duration,
+) -> Result<(), String> {
+    use enigo::{Enigo, Settings};
+
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    for combo in sequence {
+        tracing::debug!("Simulating shortcut by scancode: {}", combo);
+        press_combo(&mut enigo, &combo)?;
+        std::thread::sleep(interval);
+    }
+    Ok(())
+}
+
+/// Types `text` by scancode, see [`type_text`].
+#[cfg(target_os = "windows")]
+pub fn simulate_text_typing(text: &str, speed: TypingSpeed) -> Result<(), String> {
+    use enigo::{Enigo, Settings};
+
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    type_text(&mut enigo, text, speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::injector::{InputEvent, MockInjector};
+
+    #[test]
+    fn combos_hold_modifiers_and_click_keys() {
+        assert_eq!(combo_codes("Ctrl+S").unwrap(), (vec![LCTRL], vec![0x1F]));
+        // Shifted characters bring their own Shift
+        assert_eq!(
+            combo_codes("Ctrl+?").unwrap(),
+            (vec![LCTRL, LSHIFT], vec![0x35])
+        );
+        assert_eq!(
+            combo_codes("RAlt+Up").unwrap(),
+            (vec![LALT | EXTENDED], vec![0x48 | EXTENDED])
+        );
+        assert_eq!(combo_codes("Alt+F4").unwrap(), (vec![LALT], vec![0x3E]));
+        assert_eq!(combo_codes("F12").unwrap(), (vec![], vec![0x58]));
+        assert!(combo_codes("Ctrl+Whatever").is_err());
+    }
+
+    #[test]
+    fn characters_map_to_us_keys() {
+        assert_eq!(char_key('a'), Some((0x1E, false)));
+        assert_eq!(char_key('A'), Some((0x1E, true)));
+        assert_eq!(char_key('0'), Some((0x0B, false)));
+        assert_eq!(char_key('"'), Some((0x28, true)));
+        assert_eq!(char_key('é'), None);
+        assert_eq!(text_key('\n'), Ok((ENTER, false)));
+        assert!(text_key('é').is_err());
+    }
+
+    #[test]
+    fn text_holds_shift_per_character() {
+        let mut injector = MockInjector::new();
+        type_text(&mut injector, "aB", TypingSpeed::default()).unwrap();
+        assert_eq!(
+            injector.events,
+            [
+                InputEvent::Raw(0x1E, Direction::Click),
+                InputEvent::Raw(LSHIFT, Direction::Press),
+                InputEvent::Raw(0x30, Direction::Click),
+                InputEvent::Raw(LSHIFT, Direction::Release),
+            ]
+        );
+        // Nothing is pressed when part of the text can't be typed
+        let mut injector = MockInjector::new();
+        assert!(type_text(&mut injector, "ab€", TypingSpeed::default()).is_err());
+        assert!(injector.events.is_empty());
+    }
+}
@@ -0,0 +1,851 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tracing::error;
+
+use crate::keyboard::normalize_keys;
+use crate::storage::{read_json_or_default, write_json, Error};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Shortcut {
+    pub id: u64,
+    pub name: String,
+    pub sequence: Vec<Step>,
+    /// Run instead of `sequence` when the button is held down on the phone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long_press_sequence: Option<Vec<Step>>,
+    /// Run instead of `sequence` when the button is tapped twice in a row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub double_tap_sequence: Option<Vec<Step>>,
+    /// Makes taps cycle: each tap runs the next of `sequence` and these,
+    /// starting over after the last, e.g. to step through OBS scenes. Where
+    /// the cycle is, is kept by the store, see [`ShortcutStore::next_sequence`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycle: Vec<Vec<Step>>,
+    /// Delay after each key combo; falls back to the client's value, then the
+    /// `default_interval_ms` setting, then 100ms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_ms: Option<u64>,
+    /// Typing speed for text steps; unset types as fast as possible.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chars_per_second: Option<f64>,
+    /// Optional grouping, so clients can fetch part of a large collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Sends keys as hardware scancodes, for games that ignore the usual
+    /// simulated events. Text is limited to what a US layout can type.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub game_mode: bool,
+    /// Makes the button a dial or slider on the phone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control: Option<Control>,
+    /// Makes the button a toggle that lights up on the phone while on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_source: Option<StateSource>,
+}
+
+impl Shortcut {
+    pub fn timing(&self) -> Timing {
+        Timing {
+            interval_ms: self.interval_ms,
+            chars_per_second: self.chars_per_second,
+            game_mode: self.game_mode,
+        }
+    }
+
+    /// The sequence to run for `kind` of press, a tap at `cycle_position` of
+    /// the cycle. Presses the shortcut has no sequence of its own for run the
+    /// tap one, so phones may send any kind.
+    pub fn sequence_for(&self, kind: PressKind, cycle_position: usize) -> &[Step] {
+        let own = match kind {
+            // 0 is `sequence`, as is a position past a cycle that got shorter
+            PressKind::Tap => cycle_position
+                .checked_sub(1)
+                .and_then(|i| self.cycle.get(i)),
+            PressKind::LongPress => self.long_press_sequence.as_ref(),
+            PressKind::DoubleTap => self.double_tap_sequence.as_ref(),
+        };
+        own.unwrap_or(&self.sequence)
+    }
+
+    /// Every sequence the shortcut defines, the tap one first.
+    pub fn sequences(&self) -> impl Iterator<Item = &Vec<Step>> {
+        std::iter::once(&self.sequence)
+            .chain(&self.cycle)
+            .chain(self.long_press_sequence.as_ref())
+            .chain(self.double_tap_sequence.as_ref())
+    }
+
+    pub fn sequences_mut(&mut self) -> impl Iterator<Item = &mut Vec<Step>> {
+        std::iter::once(&mut self.sequence)
+            .chain(&mut self.cycle)
+            .chain(self.long_press_sequence.as_mut())
+            .chain(self.double_tap_sequence.as_mut())
+    }
+
+    /// Calls `f` on every step of every sequence, nested ones included, see
+    /// [`Step::visit`].
+    pub fn visit_steps(&self, mut f: impl FnMut(&Step)) {
+        for step in self.sequences().flatten() {
+            step.visit(&mut f);
+        }
+    }
+
+    /// Like [`Self::visit_steps`], but lets `f` change the steps.
+    pub fn visit_steps_mut(&mut self, mut f: impl FnMut(&mut Step)) {
+        for step in self.sequences_mut().flatten() {
+            step.visit_mut(&mut f);
+        }
+    }
+
+    /// Spells the modifiers of every key step the same way, see
+    /// [`normalize_keys`].
+    pub fn normalize_keys(&mut self) {
+        self.visit_steps_mut(|step| {
+            if let Step::Keys(keys) = step {
+                *keys = normalize_keys(keys);
+            }
+        });
+    }
+}
+
+/// A continuous control: `dial_rotate` and `slider_set` messages drive
+/// `target` instead of running the shortcut's sequence.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Control {
+    pub target: ControlTarget,
+    /// How far one detent of a dial moves the target: percentage points for
+    /// levels, lines for scrolling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<i32>,
+}
+
+impl Control {
+    pub fn step(&self) -> i32 {
+        self.step.unwrap_or(match self.target {
+            ControlTarget::Volume | ControlTarget::Brightness => 2,
+            ControlTarget::Scroll | ControlTarget::HorizontalScroll => 1,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlTarget {
+    Volume,
+    Brightness,
+    Scroll,
+    HorizontalScroll,
+}
+
+/// Where a toggle shortcut's on/off state comes from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateSource {
+    /// Flips each time the shortcut runs successfully.
+    Manual,
+    ObsRecording,
+    /// Whether `source` is shown in `scene`.
+    ObsSourceVisible {
+        scene: String,
+        source: String,
+    },
+    DiscordMute,
+    DiscordDeafen,
+}
+
+/// How a button was pressed on the phone, see [`Shortcut::sequence_for`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PressKind {
+    #[default]
+    Tap,
+    LongPress,
+    DoubleTap,
+}
+
+/// A change to the shortcut store, broadcast to connected devices. Carries
+/// only IDs, so a burst of changes stays cheap to queue; listeners read the
+/// shortcuts themselves from the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShortcutChange {
+    Added(u64),
+    Updated(u64),
+    Deleted(u64),
+    /// The whole list was replaced; clients should resync.
+    Reset,
+}
+
+/// What a sequence produced besides key presses, reported back to the
+/// device that triggered it.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SequenceOutput {
+    /// Files written by the steps, e.g. screenshots.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    /// Values steps kept for later steps, e.g. what was on the clipboard.
+    /// Never reported, as they can hold anything the user copied.
+    #[serde(skip)]
+    pub variables: BTreeMap<String, String>,
+}
+
+impl SequenceOutput {
+    /// Replaces `{{var:NAME}}` in `text` with the variable NAME. Unknown
+    /// variables are left as written.
+    pub fn substitute(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{{var:") {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + "{{var:".len()..];
+            let Some(end) = after.find("}}") else {
+                break;
+            };
+            match self.variables.get(after[..end].trim()) {
+                Some(value) => result.push_str(value),
+                None => result.push_str(&rest[start..start + "{{var:".len() + end + 2]),
+            }
+            rest = &after[end + 2..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+/// Timing applied while simulating a sequence, and how its keys are sent.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timing {
+    pub interval_ms: Option<u64>,
+    pub chars_per_second: Option<f64>,
+    /// See [`Shortcut::game_mode`].
+    pub game_mode: bool,
+}
+
+impl Timing {
+    /// Uses `interval_ms` unless an interval is already set.
+    pub fn or_default_interval(mut self, interval_ms: Option<u64>) -> Self {
+        self.interval_ms = self.interval_ms.or(interval_ms);
+        self
+    }
+}
+
+/// A single entry of a shortcut's sequence.
+///
+/// Plain strings are either a key combo (e.g. "Ctrl+S") or text to type;
+/// anything else is a tagged object such as `{"type": "secret_text", ...}`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Step {
+    Keys(String),
+    Action(ActionStep),
+}
+
+impl Step {
+    /// Calls `f` on this step, then on the steps nested in it, e.g. in the
+    /// choices of a `random_choice`. Nested steps are the items of any
+    /// `sequence` array among the step's fields, however deep.
+    pub fn visit(&self, f: &mut impl FnMut(&Step)) {
+        f(self);
+        // Nested steps are kept as JSON, so they are walked on a copy
+        if let Step::Action(step) = self {
+            let mut params = Value::Object(step.params.clone());
+            visit_nested(&mut params, &mut |step: &mut Step| f(step));
+        }
+    }
+
+    /// Like [`Self::visit`], but lets `f` change the steps. Changes to a
+    /// step are seen before its nested steps are visited.
+    pub fn visit_mut(&mut self, f: &mut impl FnMut(&mut Step)) {
+        f(self);
+        if let Step::Action(step) = self {
+            for value in step.params.values_mut() {
+                visit_nested(value, f);
+            }
+        }
+    }
+}
+
+/// Visits the steps of the `sequence` arrays in `value`.
+fn visit_nested(value: &mut Value, f: &mut impl FnMut(&mut Step)) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                match field {
+                    Value::Array(steps) if key == "sequence" => {
+                        for item in steps {
+                            // Steps that don't parse are left for validation
+                            // to refuse
+                            let Ok(mut step) = serde_json::from_value::<Step>(item.clone()) else {
+                                continue;
+                            };
+                            step.visit_mut(f);
+                            if let Ok(changed) = serde_json::to_value(step) {
+                                *item = changed;
+                            }
+                        }
+                    }
+                    _ => visit_nested(field, f),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| visit_nested(item, f)),
+        _ => {}
+    }
+}
+
+/// Any step but plain keys or text, run by whatever the app registered for
+/// its `type`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActionStep {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The step's other fields, as the action takes them.
+    #[serde(flatten)]
+    pub params: Map<String, Value>,
+}
+
+/// A save is written once no other save came in for this long...
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(300);
+/// ...but no later than this after the first unwritten one.
+const MAX_SAVE_DELAY: Duration = Duration::from_secs(2);
+
+enum SaveRequest<T> {
+    Save(T),
+    /// Writes the pending save right away and reports how that went.
+    Flush(mpsc::Sender<Result<(), Error>>),
+}
+
+fn write_pending<T: Serialize>(file_path: &Path, pending: &mut Option<T>) -> Result<(), Error> {
+    match pending.take() {
+        Some(value) => write_json(file_path, &value),
+        None => Ok(()),
+    }
+}
+
+/// Where the cycle positions are kept, next to the shortcuts. They change
+/// with every tap, so they are saved apart from the shortcuts.
+const CYCLE_POSITIONS_FILE: &str = "cycle_positions.json";
+
+/// A tap moved the cycle of a shortcut on, see [`ShortcutStore::next_sequence`].
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CycleMove {
+    pub shortcut_id: u64,
+    /// Which sequence of the cycle the next tap runs; 0 is `sequence`.
+    pub position: usize,
+}
+
+type CyclePositions = BTreeMap<u64, usize>;
+
+/// Where failed background saves are reported, see
+/// [`ShortcutStore::on_save_error`].
+type SaveErrorHandler = Arc<OnceLock<Box<dyn Fn(Error) + Send + Sync>>>;
+
+fn save_failed(on_error: &SaveErrorHandler, e: Error) {
+    match on_error.get() {
+        Some(report) => report(e),
+        None => error!("{}", e),
+    }
+}
+
+fn flush_writer<T>(writer: &Mutex<mpsc::Sender<SaveRequest<T>>>) -> Result<(), Error> {
+    let (done, result) = mpsc::channel();
+    if writer
+        .lock()
+        .unwrap()
+        .send(SaveRequest::Flush(done))
+        .is_err()
+    {
+        return Ok(());
+    }
+    result.recv().unwrap_or(Ok(()))
+}
+
+/// Writes saves on a background thread, so callers never wait for the disk.
+/// Of a burst of saves, e.g. from a bulk import, only the last is written.
+fn spawn_writer<T: Serialize + Send + 'static>(
+    file_path: PathBuf,
+    on_error: SaveErrorHandler,
+) -> mpsc::Sender<SaveRequest<T>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut pending = None;
+        // When the pending save has to be written
+        let mut deadline = Instant::now();
+        let mut latest_deadline = Instant::now();
+        loop {
+            let request = if pending.is_some() {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(request) => Some(request),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        if let Err(e) = write_pending(&file_path, &mut pending) {
+                            save_failed(&on_error, e);
+                        }
+                        return;
+                    }
+                }
+            } else {
+                match receiver.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return,
+                }
+            };
+
+            match request {
+                Some(SaveRequest::Save(value)) => {
+                    let now = Instant::now();
+                    if pending.is_none() {
+                        latest_deadline = now + MAX_SAVE_DELAY;
+                    }
+                    deadline = (now + SAVE_DEBOUNCE).min(latest_deadline);
+                    pending = Some(value);
+                }
+                Some(SaveRequest::Flush(done)) => {
+                    done.send(write_pending(&file_path, &mut pending)).ok();
+                }
+                None => {
+                    if let Err(e) = write_pending(&file_path, &mut pending) {
+                        save_failed(&on_error, e);
+                    }
+                }
+            }
+        }
+    });
+    sender
+}
+
+pub struct ShortcutStore {
+    /// Triggers, list sends and hotkeys only read, so they don't wait on each
+    /// other. A panic while holding it doesn't poison it for everyone else.
+    pub shortcuts: RwLock<Vec<Shortcut>>,
+    pub file_path: PathBuf,
+    pub broadcaster: Sender<ShortcutChange>,
+    writer: Mutex<mpsc::Sender<SaveRequest<Vec<Shortcut>>>>,
+    save_error: SaveErrorHandler,
+    /// Which sequence the next tap of each cycling shortcut runs.
+    cycle_positions: Mutex<CyclePositions>,
+    positions_writer: Mutex<mpsc::Sender<SaveRequest<CyclePositions>>>,
+    cycle_moves: Sender<CycleMove>,
+    /// Counts broadcast changes, so caches of the list know when to refresh.
+    revision: AtomicU64,
+}
+
+impl ShortcutStore {
+    pub fn new(file_path: PathBuf, broadcaster: Sender<ShortcutChange>) -> Self {
+        // Load existing shortcuts from the file
+        let shortcuts = read_json_or_default(&file_path);
+        let positions_path = file_path.with_file_name(CYCLE_POSITIONS_FILE);
+        let cycle_positions = read_json_or_default(&positions_path);
+        let save_error = SaveErrorHandler::default();
+
+        Self {
+            shortcuts: RwLock::new(shortcuts),
+            writer: Mutex::new(spawn_writer(file_path.clone(), Arc::clone(&save_error))),
+            cycle_positions: Mutex::new(cycle_positions),
+            positions_writer: Mutex::new(spawn_writer(positions_path, Arc::clone(&save_error))),
+            cycle_moves: broadcast::channel(64).0,
+            save_error,
+            file_path,
+            broadcaster,
+            revision: AtomicU64::new(0),
+        }
+    }
+
+    /// Has failures of background saves passed to `report` instead of only
+    /// logged, e.g. to show them to the user. Only the first call counts.
+    pub fn on_save_error(&self, report: impl Fn(Error) + Send + Sync + 'static) {
+        self.save_error.set(Box::new(report)).ok();
+    }
+
+    /// Queues the shortcuts to be written to disk in the background. Failures
+    /// are logged, or reported as set with [`Self::on_save_error`], since the
+    /// caller has moved on by then.
+    pub fn save(&self) {
+        // Held while queueing, so saves are queued in the order of the changes
+        let shortcuts = self.shortcuts.read();
+        self.writer
+            .lock()
+            .unwrap()
+            .send(SaveRequest::Save(shortcuts.clone()))
+            .ok();
+    }
+
+    /// Writes queued saves now and waits for them, e.g. before the app exits.
+    pub fn flush(&self) -> Result<(), Error> {
+        flush_writer(&self.writer)?;
+        flush_writer(&self.positions_writer)
+    }
+
+    pub fn get_shortcuts(&self) -> Vec<Shortcut> {
+        self.shortcuts.read().clone()
+    }
+
+    /// Which sequence the next tap of each cycling shortcut runs, by ID; 0,
+    /// or no entry, is `sequence`.
+    pub fn cycle_positions(&self) -> BTreeMap<u64, usize> {
+        self.cycle_positions.lock().unwrap().clone()
+    }
+
+    /// Tells about every tap that moves a cycle on, so clients can show
+    /// where the cycle is.
+    pub fn subscribe_cycle_moves(&self) -> Receiver<CycleMove> {
+        self.cycle_moves.subscribe()
+    }
+
+    /// The sequence to run for a press of `shortcut`. A tap on a cycling
+    /// shortcut also moves its cycle on; the new position is saved on its
+    /// own and sent to [`Self::subscribe_cycle_moves`].
+    pub fn next_sequence(&self, shortcut: &Shortcut, kind: PressKind) -> Vec<Step> {
+        if kind != PressKind::Tap || shortcut.cycle.is_empty() {
+            return shortcut.sequence_for(kind, 0).to_vec();
+        }
+        let (sequence, moved) = {
+            let mut positions = self.cycle_positions.lock().unwrap();
+            // Deleted shortcuts are forgotten along the way
+            {
+                let shortcuts = self.shortcuts.read();
+                positions.retain(|id, _| shortcuts.iter().any(|s| s.id == *id));
+            }
+            let sequences = shortcut.cycle.len() + 1;
+            // Past the end of a cycle that got shorter starts over
+            let current = positions
+                .get(&shortcut.id)
+                .copied()
+                .filter(|&position| position < sequences)
+                .unwrap_or(0);
+            let sequence = shortcut.sequence_for(kind, current).to_vec();
+            let moved = CycleMove {
+                shortcut_id: shortcut.id,
+                position: (current + 1) % sequences,
+            };
+            positions.insert(shortcut.id, moved.position);
+            // Queued while held, so saves are queued in the order of the taps
+            self.positions_writer
+                .lock()
+                .unwrap()
+                .send(SaveRequest::Save(positions.clone()))
+                .ok();
+            (sequence, moved)
+        };
+        // Nobody listening is fine
+        self.cycle_moves.send(moved).ok();
+        sequence
+    }
+
+    /// Finds the shortcut a recognized voice command refers to: the one named
+    /// exactly that, or else the one with the longest name spoken within it,
+    /// ignoring case and punctuation.
+    pub fn find_by_spoken_name(&self, text: &str) -> Result<Shortcut, String> {
+        let words = |text: &str| -> Vec<String> {
+            text.split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase)
+                .collect()
+        };
+        let spoken = words(text);
+        if spoken.is_empty() {
+            return Err("The voice command is empty".into());
+        }
+
+        let mut best: Option<(usize, Shortcut)> = None;
+        let mut tied = false;
+        for shortcut in self.get_shortcuts() {
+            let name = words(&shortcut.name);
+            if name == spoken {
+                return Ok(shortcut);
+            }
+            if name.is_empty() || !spoken.windows(name.len()).any(|window| window == name) {
+                continue;
+            }
+            match &best {
+                Some((length, _)) if *length > name.len() => {}
+                Some((length, _)) if *length == name.len() => tied = true,
+                _ => {
+                    tied = false;
+                    best = Some((name.len(), shortcut));
+                }
+            }
+        }
+        match best {
+            Some(_) if tied => Err(format!("\"{}\" matches several shortcuts", text)),
+            Some((_, shortcut)) => Ok(shortcut),
+            None => Err(format!("No shortcut matches \"{}\"", text)),
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    // Notify subscribers (connected devices) about a change
+    pub fn broadcast_change(&self, change: ShortcutChange) {
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        if let Err(e) = self.broadcaster.send(change) {
+            error!("Error broadcasting shortcuts: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::sync::broadcast;
+
+    #[test]
+    fn variables_fill_their_placeholders() {
+        let mut output = SequenceOutput::default();
+        output.variables.insert("selection".into(), "hello".into());
+        assert_eq!(output.substitute("**{{var:selection}}**"), "**hello**");
+        assert_eq!(
+            output.substitute("{{var:unknown}} {{timestamp}}"),
+            "{{var:unknown}} {{timestamp}}"
+        );
+        assert_eq!(output.substitute("{{var:selection"), "{{var:selection");
+    }
+
+    fn shortcut(id: u64, name: &str) -> Shortcut {
+        Shortcut {
+            id,
+            name: name.into(),
+            sequence: vec![],
+            long_press_sequence: None,
+            double_tap_sequence: None,
+            cycle: vec![],
+            interval_ms: None,
+            chars_per_second: None,
+            group: None,
+            tags: vec![],
+            game_mode: false,
+            control: None,
+            state_source: None,
+        }
+    }
+
+    fn store(names: &[&str]) -> ShortcutStore {
+        let (sender, _) = broadcast::channel(1);
+        ShortcutStore {
+            shortcuts: RwLock::new(
+                names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| shortcut(i as u64, name))
+                    .collect(),
+            ),
+            file_path: PathBuf::new(),
+            broadcaster: sender,
+            writer: Mutex::new(spawn_writer(PathBuf::new(), SaveErrorHandler::default())),
+            save_error: SaveErrorHandler::default(),
+            cycle_positions: Mutex::default(),
+            positions_writer: Mutex::new(spawn_writer(PathBuf::new(), SaveErrorHandler::default())),
+            cycle_moves: broadcast::channel(8).0,
+            revision: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn steps_keep_their_json_shape() {
+        let sequence = json!(["Ctrl+S", { "type": "obs_set_scene", "scene": "Live" }]);
+        let steps: Vec<Step> = serde_json::from_value(sequence.clone()).unwrap();
+        assert!(matches!(&steps[0], Step::Keys(keys) if keys == "Ctrl+S"));
+        assert!(matches!(&steps[1], Step::Action(step) if step.kind == "obs_set_scene"));
+        assert_eq!(serde_json::to_value(&steps).unwrap(), sequence);
+    }
+
+    #[test]
+    fn nested_steps_are_visited() {
+        let mut shortcut = shortcut(1, "Random");
+        shortcut.sequence = serde_json::from_value(json!([
+            "control+a",
+            {
+                "type": "random_choice",
+                "choices": [
+                    { "sequence": ["control+c"] },
+                    { "sequence": [{ "type": "random_choice", "choices": [{ "sequence": ["control+v"] }] }] },
+                ],
+            },
+        ]))
+        .unwrap();
+
+        let mut visited = Vec::new();
+        shortcut.visit_steps(|step| {
+            visited.push(match step {
+                Step::Keys(keys) => keys.clone(),
+                Step::Action(step) => step.kind.clone(),
+            })
+        });
+        assert_eq!(
+            visited,
+            [
+                "control+a",
+                "random_choice",
+                "control+c",
+                "random_choice",
+                "control+v"
+            ]
+        );
+
+        shortcut.normalize_keys();
+        let choices = &serde_json::to_value(&shortcut.sequence).unwrap()[1]["choices"];
+        assert_eq!(choices[0]["sequence"], json!(["Ctrl+c"]));
+        assert_eq!(
+            choices[1]["sequence"][0]["choices"][0]["sequence"],
+            json!(["Ctrl+v"])
+        );
+    }
+
+    #[test]
+    fn presses_without_their_own_sequence_run_the_tap_one() {
+        let mut shortcut = shortcut(1, "Scene");
+        shortcut.sequence = vec![Step::Keys("F1".into())];
+        shortcut.long_press_sequence = Some(vec![Step::Keys("F2".into())]);
+        let keys = |kind| match shortcut.sequence_for(kind, 0) {
+            [Step::Keys(keys)] => keys.clone(),
+            other => panic!("Unexpected sequence {:?}", other),
+        };
+        assert_eq!(keys(PressKind::Tap), "F1");
+        assert_eq!(keys(PressKind::LongPress), "F2");
+        assert_eq!(keys(PressKind::DoubleTap), "F1");
+    }
+
+    #[test]
+    fn taps_cycle_through_the_sequences() {
+        let store = store(&["Scenes"]);
+        let mut moves = store.subscribe_cycle_moves();
+        {
+            let mut shortcuts = store.shortcuts.write();
+            shortcuts[0].sequence = vec![Step::Keys("F1".into())];
+            shortcuts[0].cycle = vec![vec![Step::Keys("F2".into())]];
+            shortcuts[0].double_tap_sequence = Some(vec![Step::Keys("F3".into())]);
+        }
+        let press = |kind| {
+            let shortcut = store.get_shortcuts().remove(0);
+            match store.next_sequence(&shortcut, kind).as_slice() {
+                [Step::Keys(keys)] => keys.clone(),
+                other => panic!("Unexpected sequence {:?}", other),
+            }
+        };
+        assert_eq!(press(PressKind::Tap), "F1");
+        assert_eq!(press(PressKind::Tap), "F2");
+        // Other presses leave the cycle where it is
+        assert_eq!(press(PressKind::DoubleTap), "F3");
+        assert_eq!(press(PressKind::Tap), "F1");
+        assert_eq!(store.cycle_positions()[&0], 1);
+        let positions: Vec<usize> = std::iter::from_fn(|| moves.try_recv().ok())
+            .map(|moved| moved.position)
+            .collect();
+        assert_eq!(positions, [1, 0, 1]);
+
+        // A cycle that got shorter starts over
+        store.cycle_positions.lock().unwrap().insert(0, 5);
+        assert_eq!(press(PressKind::Tap), "F1");
+        assert_eq!(store.cycle_positions()[&0], 1);
+    }
+
+    #[test]
+    fn control_steps_default_per_target() {
+        let control: Control = serde_json::from_value(json!({ "target": "volume" })).unwrap();
+        assert_eq!(control.step(), 2);
+        let control: Control =
+            serde_json::from_value(json!({ "target": "horizontal_scroll", "step": 5 })).unwrap();
+        assert_eq!(control.step(), 5);
+    }
+
+    #[test]
+    fn spoken_name_prefers_exact_then_longest_match() {
+        let store = store(&["Standup", "Start standup", "Mute"]);
+        assert_eq!(store.find_by_spoken_name("mute!").unwrap().id, 2);
+        assert_eq!(
+            store
+                .find_by_spoken_name("please start standup now")
+                .unwrap()
+                .id,
+            1
+        );
+        assert!(store.find_by_spoken_name("record").is_err());
+    }
+
+    #[test]
+    fn spoken_name_rejects_ties() {
+        let store = store(&["Lights on", "Music on"]);
+        assert!(store.find_by_spoken_name("lights on music on").is_err());
+    }
+
+    #[test]
+    fn saves_in_a_burst_are_written_once_flushed() {
+        let file_path =
+            std::env::temp_dir().join(format!("button-beam-shortcuts-{}.json", std::process::id()));
+        let (sender, _) = broadcast::channel(1);
+        let store = ShortcutStore::new(file_path.clone(), sender);
+        store.shortcuts.write().push(shortcut(1, "First"));
+        store.save();
+        store.shortcuts.write().push(shortcut(2, "Second"));
+        store.save();
+
+        store.flush().unwrap();
+        let saved: Vec<Shortcut> =
+            serde_json::from_str(&std::fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(saved.len(), 2);
+        std::fs::remove_file(&file_path).ok();
+    }
+
+    #[test]
+    fn failed_saves_are_reported() {
+        // A file where the store's folder should be
+        let blocker =
+            std::env::temp_dir().join(format!("button-beam-blocker-{}", std::process::id()));
+        std::fs::write(&blocker, "").unwrap();
+        let (sender, _) = broadcast::channel(1);
+        let store = ShortcutStore::new(blocker.join("shortcuts.json"), sender);
+        let (reported, failures) = mpsc::channel();
+        store.on_save_error(move |e| reported.send(e.to_string()).unwrap());
+
+        store.save();
+        let failure = failures.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(failure.contains("shortcuts.json"));
+        std::fs::remove_file(&blocker).ok();
+    }
+
+    #[test]
+    fn cycle_positions_are_saved_apart_from_the_shortcuts() {
+        let dir = std::env::temp_dir().join(format!("button-beam-cycles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut scenes = shortcut(1, "Scenes");
+        scenes.cycle = vec![vec![Step::Keys("F2".into())]];
+        write_json(&dir.join("shortcuts.json"), &vec![scenes.clone()]).unwrap();
+
+        let (sender, _) = broadcast::channel(1);
+        let store = ShortcutStore::new(dir.join("shortcuts.json"), sender.clone());
+        store.next_sequence(&scenes, PressKind::Tap);
+        store.flush().unwrap();
+        let saved = std::fs::read_to_string(dir.join("shortcuts.json")).unwrap();
+        assert!(!saved.contains("position"));
+
+        let reloaded = ShortcutStore::new(dir.join("shortcuts.json"), sender);
+        assert_eq!(reloaded.cycle_positions()[&1], 1);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn timing_keeps_its_own_interval() {
+        let timing = Timing {
+            interval_ms: Some(50),
+            chars_per_second: None,
+            game_mode: false,
+        };
+        assert_eq!(timing.or_default_interval(Some(200)).interval_ms, Some(50));
+        assert_eq!(
+            Timing::default().or_default_interval(Some(200)).interval_ms,
+            Some(200)
+        );
+    }
+}
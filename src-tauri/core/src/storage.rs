@@ -0,0 +1,76 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+/// Failure to read or write one of the JSON files the app keeps its data in.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to access {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid data in {}: {source}", path.display())]
+    Json {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
+
+/// Reads a JSON file, or `None` if it doesn't exist yet.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => {
+            return Err(Error::Io {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    };
+    serde_json::from_reader(BufReader::new(file))
+        .map(Some)
+        .map_err(|source| Error::Json {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Reads a JSON file, falling back to the default if it is missing or
+/// unreadable. Problems are logged, so a corrupt file doesn't stop the app.
+pub fn read_json_or_default<T: DeserializeOwned + Default>(path: &Path) -> T {
+    read_json(path)
+        .unwrap_or_else(|e| {
+            error!("{}", e);
+            None
+        })
+        .unwrap_or_default()
+}
+
+/// Writes pretty-printed JSON, creating the parent directories if needed.
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), Error> {
+    let io_error = |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    let file = File::create(path).map_err(io_error)?;
+    serde_json::to_writer_pretty(BufWriter::new(file), value).map_err(|source| Error::Json {
+        path: path.to_path_buf(),
+        source,
+    })
+}
@@ -0,0 +1,131 @@
+// Finds a small image inside a bigger one, e.g. a button in a screenshot,
+// for the steps that wait for or click an image on screen. Images are rows
+// of 8-bit RGBA pixels. Template pixels that are mostly transparent match
+// anything, so an icon with transparent corners is found on any background.
+
+/// An RGBA image, borrowed from whatever decoded or captured it.
+#[derive(Clone, Copy, Debug)]
+pub struct RgbaImage<'a> {
+    pub width: u32,
+    pub height: u32,
+    /// `width * height` pixels of 4 bytes, row by row.
+    pub pixels: &'a [u8],
+}
+
+impl RgbaImage<'_> {
+    fn rgb(&self, x: u32, y: u32) -> &[u8] {
+        let start = (y as usize * self.width as usize + x as usize) * 4;
+        &self.pixels[start..start + 3]
+    }
+}
+
+/// Alpha from which a template pixel has to match.
+const OPAQUE: u8 = 128;
+
+/// The top-left corner of the first place, row by row, where `template`
+/// appears in `screen` with each color channel off by at most `tolerance`.
+/// A template without opaque pixels is found nowhere.
+pub fn find(screen: RgbaImage, template: RgbaImage, tolerance: u8) -> Option<(u32, u32)> {
+    if template.width > screen.width || template.height > screen.height {
+        return None;
+    }
+    let opaque: Vec<(u32, u32)> = (0..template.height)
+        .flat_map(|y| (0..template.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| template.pixels[(y * template.width + x) as usize * 4 + 3] >= OPAQUE)
+        .collect();
+    if opaque.is_empty() {
+        return None;
+    }
+
+    let matches_at = |left: u32, top: u32| {
+        // Gives up on a spot at its first differing pixel, so most are
+        // ruled out after one or two comparisons
+        opaque.iter().all(|&(x, y)| {
+            screen
+                .rgb(left + x, top + y)
+                .iter()
+                .zip(template.rgb(x, y))
+                .all(|(seen, wanted)| seen.abs_diff(*wanted) <= tolerance)
+        })
+    };
+    (0..=screen.height - template.height)
+        .flat_map(|top| (0..=screen.width - template.width).map(move |left| (left, top)))
+        .find(|&(left, top)| matches_at(left, top))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width` by `height` image filled with `background`, with `pixel`
+    /// set at each of `points`.
+    fn image(
+        width: u32,
+        height: u32,
+        background: [u8; 4],
+        points: &[(u32, u32, [u8; 4])],
+    ) -> Vec<u8> {
+        let mut pixels = background.repeat((width * height) as usize);
+        for &(x, y, pixel) in points {
+            let start = (y * width + x) as usize * 4;
+            pixels[start..start + 4].copy_from_slice(&pixel);
+        }
+        pixels
+    }
+
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const RED: [u8; 4] = [200, 20, 20, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    #[test]
+    fn finds_the_template_where_it_is() {
+        let screen = image(8, 6, WHITE, &[(5, 3, RED), (6, 4, RED)]);
+        let screen = RgbaImage {
+            width: 8,
+            height: 6,
+            pixels: &screen,
+        };
+        let template = image(2, 2, WHITE, &[(0, 0, RED), (1, 1, RED)]);
+        let template = RgbaImage {
+            width: 2,
+            height: 2,
+            pixels: &template,
+        };
+        assert_eq!(find(screen, template, 0), Some((5, 3)));
+
+        // Slightly off colors only match within the tolerance
+        let paler = image(2, 2, WHITE, &[(0, 0, [210, 30, 30, 255]), (1, 1, RED)]);
+        let paler = RgbaImage {
+            pixels: &paler,
+            ..template
+        };
+        assert_eq!(find(screen, paler, 0), None);
+        assert_eq!(find(screen, paler, 10), Some((5, 3)));
+    }
+
+    #[test]
+    fn transparent_pixels_match_anything() {
+        let screen = image(4, 4, [0, 0, 255, 255], &[(2, 1, RED)]);
+        let screen = RgbaImage {
+            width: 4,
+            height: 4,
+            pixels: &screen,
+        };
+        let template = image(2, 2, CLEAR, &[(1, 0, RED)]);
+        let template = RgbaImage {
+            width: 2,
+            height: 2,
+            pixels: &template,
+        };
+        assert_eq!(find(screen, template, 0), Some((1, 1)));
+
+        let clear = image(2, 2, CLEAR, &[]);
+        let clear = RgbaImage {
+            pixels: &clear,
+            ..template
+        };
+        assert_eq!(find(screen, clear, 0), None);
+        // Nor can a template bigger than the screen be found
+        assert_eq!(find(template, screen, 255), None);
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::keyboard::TypingSpeed;
+use crate::scancodes::char_key;
+
+// Key simulation on Wayland sessions. enigo talks to X11, which Wayland
+// compositors only offer to XWayland windows, so native Wayland windows never
+// see its keys. ydotool writes to the kernel's uinput device instead, through
+// its `ydotoold` daemon, which works under every compositor.
+//
+// Key codes are the kernel's (linux/input-event-codes.h) and assume a US
+// layout, like the rest of the key simulation. Characters share the game mode
+// table, since the kernel numbers those keys like PC scancodes.
+
+const LEFTCTRL: u16 = 29;
+const LEFTSHIFT: u16 = 42;
+const LEFTALT: u16 = 56;
+const LEFTMETA: u16 = 125;
+const RIGHTSHIFT: u16 = 54;
+
+/// Whether the desktop session is a Wayland one.
+pub fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland")
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// What ydotool needs, as found on this machine.
+#[derive(Clone, Debug)]
+pub struct YdotoolStatus {
+    /// Whether the `ydotool` client is on the `PATH`.
+    pub installed: bool,
+    /// The socket of a running `ydotoold`, if any.
+    pub socket: Option<PathBuf>,
+    /// Whether this user may write to `/dev/uinput`, which `ydotoold` needs
+    /// unless it runs as root.
+    pub uinput_writable: bool,
+}
+
+impl YdotoolStatus {
+    pub fn ready(&self) -> bool {
+        self.installed && self.socket.is_some()
+    }
+
+    /// What the user still has to do before keys can be sent through ydotool.
+    pub fn setup_steps(&self) -> Vec<String> {
+        let mut steps = Vec::new();
+        if !self.installed {
+            steps.push(
+                "Install ydotool (1.0 or newer) from your distribution's packages".to_string(),
+            );
+        }
+        if !self.uinput_writable && self.socket.is_none() {
+            steps.push(
+                "Allow access to /dev/uinput: add yourself to the input group \
+                 (sudo usermod -aG input $USER) and log in again"
+                    .to_string(),
+            );
+        }
+        if self.socket.is_none() {
+            steps.push(
+                "Start the ydotool daemon, e.g. systemctl --user enable --now ydotool".to_string(),
+            );
+        }
+        steps
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Where `ydotoold` listens: `YDOTOOL_SOCKET` if set, else its defaults for
+/// 1.0 and for older versions.
+fn ydotool_socket() -> Option<PathBuf> {
+    if let Some(socket) = std::env::var_os("YDOTOOL_SOCKET") {
+        return Some(PathBuf::from(socket)).filter(|socket| socket.exists());
+    }
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+    runtime_dir
+        .map(|dir| dir.join(".ydotool_socket"))
+        .into_iter()
+        .chain([PathBuf::from("/tmp/.ydotool_socket")])
+        .find(|socket| socket.exists())
+}
+
+pub fn ydotool_status() -> YdotoolStatus {
+    YdotoolStatus {
+        installed: on_path("ydotool"),
+        socket: ydotool_socket(),
+        uinput_writable: std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/uinput")
+            .is_ok(),
+    }
+}
+
+fn ydotool(socket: &Path, args: &[String]) -> Result<(), String> {
+    // Only the subcommand: the rest can be the text being typed
+    debug!(
+        "ydotool {} ({} arguments)",
+        args.first().map(String::as_str).unwrap_or_default(),
+        args.len().saturating_sub(1)
+    );
+    let output = Command::new("ydotool")
+        .args(args)
+        .env("YDOTOOL_SOCKET", socket)
+        .output()
+        .map_err(|e| format!("Failed to run ydotool: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "ydotool failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn modifier_code(name: &str) -> Option<u16> {
+    match name {
+        "Ctrl" | "Control" => Some(LEFTCTRL),
+        "Alt" => Some(LEFTALT),
+        "Shift" => Some(LEFTSHIFT),
+        "Cmd" | "Command" | "Meta" => Some(LEFTMETA),
+        "Primary" => Some(LEFTCTRL),
+        "LShift" => Some(LEFTSHIFT),
+        "RShift" => Some(RIGHTSHIFT),
+        "LCtrl" => Some(LEFTCTRL),
+        "RCtrl" => Some(97),
+        "LAlt" => Some(LEFTALT),
+        "RAlt" | "AltGr" => Some(100),
+        _ => None,
+    }
+}
+
+fn named_key_code(name: &str) -> Option<u16> {
+    let code = match name {
+        "Enter" => 28,
+        "Tab" => 15,
+        "Backspace" => 14,
+        "Space" => 57,
+        "Esc" | "Escape" => 1,
+        "Delete" => 111,
+        "Insert" => 110,
+        "Home" => 102,
+        "End" => 107,
+        "PageUp" => 104,
+        "PageDown" => 109,
+        "Up" => 103,
+        "Down" => 108,
+        "Left" => 105,
+        "Right" => 106,
+        "NumpadEnter" => 96,
+        _ if name.starts_with("Numpad") => {
+            const KEYPAD: [u16; 10] = [82, 79, 80, 81, 75, 76, 77, 71, 72, 73];
+            let digit = name["Numpad".len()..].parse::<usize>().ok()?;
+            return KEYPAD.get(digit).copied();
+        }
+        _ => {
+            let number: u16 = name.strip_prefix('F')?.parse().ok()?;
+            return match number {
+                1..=10 => Some(58 + number),
+                11 | 12 => Some(76 + number),
+                _ => None,
+            };
+        }
+    };
+    Some(code)
+}
+
+/// The `code:1`/`code:0` press and release events for one combo such as
+/// "Ctrl+Shift+T": modifiers down, each key clicked, modifiers up in reverse.
+fn combo_events(combo: &str) -> Result<Vec<String>, String> {
+    let keys: Vec<&str> = combo.split('+').map(|k| k.trim()).collect();
+    let mut modifiers: Vec<u16> = keys.iter().filter_map(|key| modifier_code(key)).collect();
+    let mut clicks = Vec::new();
+    for key in keys.iter().filter(|key| modifier_code(key).is_none()) {
+        let (code, shift) = match named_key_code(key) {
+            Some(code) => (code, false),
+            None => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    // A letter names its key, `Ctrl+S` doesn't mean Shift
+                    (Some(c), None) => char_key(c.to_ascii_lowercase())
+                        .ok_or_else(|| format!("\"{}\" can't be pressed through ydotool", key))?,
+                    (None, _) => continue,
+                    (Some(_), Some(_)) => return Err(format!("Unknown key \"{}\"", key)),
+                }
+            }
+        };
+        if shift && !modifiers.contains(&LEFTSHIFT) && !modifiers.contains(&RIGHTSHIFT) {
+            modifiers.push(LEFTSHIFT);
+        }
+        clicks.push(code);
+    }
+
+    let mut events: Vec<String> = modifiers.iter().map(|code| format!("{}:1", code)).collect();
+    for code in clicks {
+        events.push(format!("{}:1", code));
+        events.push(format!("{}:0", code));
+    }
+    events.extend(modifiers.iter().rev().map(|code| format!("{}:0", code)));
+    Ok(events)
+}
+
+/// Presses each combo in `sequence` through ydotool, waiting `interval`
+/// after each one.
+pub fn simulate_shortcut(
+    status: &YdotoolStatus,
+    sequence: Vec<String>,
+    interval: Duration,
+) -> Result<(), String> {
+    let socket = status.socket.as_deref().ok_or("ydotoold isn't running")?;
+    for combo in sequence {
+        debug!("Simulating shortcut through ydotool: {}", combo);
+        let events = combo_events(&combo)?;
+        let mut args = vec!["key".to_string()];
+        args.extend(events.iter().cloned());
+        if let Err(e) = ydotool(socket, &args) {
+            // ydotool may have stopped after pressing some of the keys
+            let mut releases = vec!["key".to_string()];
+            releases.extend(
+                events
+                    .iter()
+                    .rev()
+                    .filter_map(|event| event.strip_suffix(":1").map(|code| format!("{}:0", code))),
+            );
+            ydotool(socket, &releases).ok();
+            return Err(e);
+        }
+        std::thread::sleep(interval);
+    }
+    Ok(())
+}
+
+/// Types `text` through ydotool at `speed`, one ydotool call per chunk.
+pub fn simulate_text_typing(
+    status: &YdotoolStatus,
+    text: &str,
+    speed: TypingSpeed,
+) -> Result<(), String> {
+    let socket = status.socket.as_deref().ok_or("ydotoold isn't running")?;
+    let mut args = vec!["type".to_string()];
+    if let Some(delay) = speed.char_delay() {
+        args.push("--key-delay".to_string());
+        args.push(delay.as_millis().to_string());
+    }
+    args.push("--".to_string());
+
+    let chars: Vec<char> = text.chars().collect();
+    let (size, pause) = speed
+        .chunking()
+        .unwrap_or((chars.len().max(1), Duration::ZERO));
+    for (i, chunk) in chars.chunks(size).enumerate() {
+        if i > 0 {
+            std::thread::sleep(pause);
+        }
+        let mut chunk_args = args.clone();
+        chunk_args.push(chunk.iter().collect());
+        ydotool(socket, &chunk_args)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combos_press_modifiers_around_the_keys() {
+        assert_eq!(
+            combo_events("Ctrl+S").unwrap(),
+            ["29:1", "31:1", "31:0", "29:0"]
+        );
+        // Shifted characters bring their own Shift
+        assert_eq!(
+            combo_events("Ctrl+?").unwrap(),
+            ["29:1", "42:1", "53:1", "53:0", "42:0", "29:0"]
+        );
+        assert_eq!(
+            combo_events("Alt+F4").unwrap(),
+            ["56:1", "62:1", "62:0", "56:0"]
+        );
+        assert!(combo_events("Ctrl+Whatever").is_err());
+        assert_eq!(
+            combo_events("RAlt+Numpad1").unwrap(),
+            ["100:1", "79:1", "79:0", "100:0"]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use button_beam_core::keyboard::TypingSpeed;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+
+use crate::settings::{Settings, SettingsStore};
+use crate::shortcuts::{ActionStep, SequenceOutput, Step, Timing};
+use crate::{integrations, keyboard, mouse, scripting, secrets, shortcuts, snippets, system};
+
+// Every kind of step is an `Action`, implemented next to the code it drives
+// and registered under the step's `type`. Running a sequence looks each step
+// up here, so a new step type is a new module rather than another match arm.
+// Steps of unknown types are refused when a shortcut is saved and fail on
+// their own when run, e.g. after syncing from a newer version.
+
+/// What an action can use while running, and where it reports its results.
+pub struct ActionContext<'a> {
+    pub app_handle: &'a AppHandle,
+    pub timing: Timing,
+    pub output: &'a mut SequenceOutput,
+    /// The shortcuts whose `run_shortcut` steps led here, outermost first.
+    pub running: &'a [u64],
+}
+
+impl ActionContext<'_> {
+    pub fn settings(&self) -> Settings {
+        self.app_handle.state::<Arc<SettingsStore>>().get_settings()
+    }
+
+    /// The shortcut's typing speed, with what it leaves unset taken from the
+    /// settings. Steps may override it in turn.
+    pub fn typing_speed(&self) -> TypingSpeed {
+        let shortcut = TypingSpeed {
+            chars_per_second: self.timing.chars_per_second,
+            ..TypingSpeed::default()
+        };
+        shortcut.or(self
+            .app_handle
+            .state::<Arc<SettingsStore>>()
+            .default_typing_speed())
+    }
+}
+
+/// A step type. Implementors hold the step's fields and are deserialized from
+/// the step's JSON object, `type` aside.
+pub trait Action: DeserializeOwned + 'static {
+    /// The step's `type`, e.g. `obs_set_scene`.
+    const TYPE: &'static str;
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String>;
+
+    /// Checks what deserializing can't, before the step is saved.
+    fn check(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+type Runner = Box<dyn Fn(Value, &mut ActionContext) -> Result<(), String> + Send + Sync>;
+type Checker = Box<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+
+struct RegisteredAction {
+    run: Runner,
+    /// Checks the fields without running the step.
+    check: Checker,
+}
+
+/// The step types that can be run, by `type`.
+#[derive(Default)]
+pub struct ActionRegistry {
+    actions: HashMap<String, RegisteredAction>,
+}
+
+impl ActionRegistry {
+    /// A registry with every step type built into the app.
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        keyboard::register_actions(&mut registry);
+        mouse::register_actions(&mut registry);
+        secrets::register_actions(&mut registry);
+        integrations::register_actions(&mut registry);
+        scripting::register_actions(&mut registry);
+        shortcuts::register_actions(&mut registry);
+        snippets::register_actions(&mut registry);
+        system::register_actions(&mut registry);
+        registry
+    }
+
+    pub fn register<A: Action>(&mut self) {
+        self.actions.insert(
+            A::TYPE.to_string(),
+            RegisteredAction {
+                run: Box::new(|params, ctx| parse::<A>(params)?.run(ctx)),
+                check: Box::new(|params| parse::<A>(params.clone())?.check()),
+            },
+        );
+    }
+
+    /// Registers a step type implemented outside this crate, e.g. by a
+    /// plugin. Its fields are only checked once it runs.
+    pub fn register_fn(
+        &mut self,
+        kind: String,
+        run: impl Fn(Value, &mut ActionContext) -> Result<(), String> + Send + Sync + 'static,
+    ) {
+        self.actions.insert(
+            kind,
+            RegisteredAction {
+                run: Box::new(run),
+                check: Box::new(|_| Ok(())),
+            },
+        );
+    }
+
+    pub fn contains(&self, kind: &str) -> bool {
+        self.actions.contains_key(kind)
+    }
+
+    fn get(&self, kind: &str) -> Result<&RegisteredAction, String> {
+        self.actions
+            .get(kind)
+            .ok_or_else(|| format!("Unknown step type {}", kind))
+    }
+
+    pub fn run(&self, step: ActionStep, ctx: &mut ActionContext) -> Result<(), String> {
+        let action = self.get(&step.kind)?;
+        (action.run)(Value::Object(step.params), ctx)
+    }
+
+    /// Fails on the first step of an unknown type or with invalid fields,
+    /// nested steps included.
+    pub fn validate(&self, sequence: &[Step]) -> Result<(), String> {
+        let mut result = Ok(());
+        for step in sequence {
+            step.visit(&mut |step| {
+                let Step::Action(step) = step else { return };
+                if result.is_ok() {
+                    result = self
+                        .get(&step.kind)
+                        .and_then(|action| (action.check)(&Value::Object(step.params.clone())));
+                }
+            });
+        }
+        result
+    }
+}
+
+fn parse<A: Action>(params: Value) -> Result<A, String> {
+    serde_json::from_value(params).map_err(|e| format!("Invalid {} step: {}", A::TYPE, e))
+}
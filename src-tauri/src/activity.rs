@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use tauri::State;
+use tracing::error;
+
+use crate::devices::now_millis;
+use crate::events::{AppEvent, EventBus};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ActivityEvent {
+    Connected,
+    Disconnected,
+    ShortcutExecuted {
+        shortcut_id: u64,
+        ok: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActivityEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Unset for triggers that didn't come from a paired device, e.g. over HTTP.
+    pub device_id: Option<String>,
+    pub device_name: Option<String>,
+    #[serde(flatten)]
+    pub event: ActivityEvent,
+}
+
+/// Once the log grows past this, it is moved aside to `<file>.1`, replacing
+/// the one moved aside before, and a new one is started.
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+enum WriteRequest {
+    Append(ActivityEntry),
+    /// Answered once every entry sent before is written.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Append-only record of connections and executed shortcuts, one JSON object
+/// per line, so the user can see what happened while away from the keyboard.
+/// Entries are written on a background thread, so recording never waits for
+/// the disk.
+pub struct ActivityLog {
+    lock: Arc<Mutex<()>>,
+    pub file_path: PathBuf,
+    writer: Mutex<mpsc::Sender<WriteRequest>>,
+    /// Every recorded entry is published here too.
+    events: Arc<EventBus>,
+}
+
+impl ActivityLog {
+    pub fn new(file_path: PathBuf, events: Arc<EventBus>) -> Self {
+        let lock = Arc::new(Mutex::new(()));
+        let writer = spawn_writer(file_path.clone(), Arc::clone(&lock));
+        Self {
+            lock,
+            file_path,
+            writer: Mutex::new(writer),
+            events,
+        }
+    }
+
+    pub fn record(&self, device_id: Option<&str>, device_name: Option<&str>, event: ActivityEvent) {
+        let entry = ActivityEntry {
+            timestamp: now_millis(),
+            device_id: device_id.map(str::to_string),
+            device_name: device_name.map(str::to_string),
+            event,
+        };
+        let _ = self
+            .writer
+            .lock()
+            .unwrap()
+            .send(WriteRequest::Append(entry.clone()));
+        self.events.publish(AppEvent::Activity(entry));
+    }
+
+    /// Returns the most recent entries, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<ActivityEntry> {
+        // Entries recorded so far are included
+        let (done, written) = mpsc::channel();
+        if self
+            .writer
+            .lock()
+            .unwrap()
+            .send(WriteRequest::Flush(done))
+            .is_ok()
+        {
+            let _ = written.recv();
+        }
+        let _guard = self.lock.lock().unwrap();
+        // The file moved aside holds the older entries
+        let mut entries = read_entries(&rotated_path(&self.file_path));
+        entries.extend(read_entries(&self.file_path));
+        entries.into_iter().rev().take(limit).collect()
+    }
+}
+
+fn rotated_path(file_path: &Path) -> PathBuf {
+    let mut name = file_path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+fn read_entries(file_path: &Path) -> Vec<ActivityEntry> {
+    let Ok(file) = File::open(file_path) else {
+        return Vec::new();
+    };
+    // Lines that fail to parse, e.g. one cut short by a crash, are skipped
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+fn append(file_path: &Path, entry: &ActivityEntry) -> Result<(), String> {
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let size = fs::metadata(file_path).map_or(0, |metadata| metadata.len());
+    if size >= MAX_FILE_BYTES {
+        fs::rename(file_path, rotated_path(file_path)).map_err(|e| e.to_string())?;
+    }
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(file_path)
+        .map_err(|e| e.to_string())?;
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// Appends entries on a background thread, holding `lock` while it writes
+/// so readers never see the log halfway through being moved aside.
+fn spawn_writer(file_path: PathBuf, lock: Arc<Mutex<()>>) -> mpsc::Sender<WriteRequest> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for request in receiver {
+            match request {
+                WriteRequest::Append(entry) => {
+                    let _guard = lock.lock().unwrap();
+                    if let Err(e) = append(&file_path, &entry) {
+                        error!("Failed to write activity log: {}", e);
+                    }
+                }
+                WriteRequest::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    });
+    sender
+}
+
+// Activity-related Tauri commands
+
+/// Returns the latest connections and executed shortcuts, newest first.
+///
+/// # Arguments
+///
+/// * `limit` - Maximum number of entries to return; 200 when unset.
+/// * `activity` - The activity log.
+///
+/// # Returns
+///
+/// * `Result<Vec<ActivityEntry>, String>` - The entries.
+#[tauri::command]
+pub fn get_activity_log(
+    limit: Option<usize>,
+    activity: State<Arc<ActivityLog>>,
+) -> Result<Vec<ActivityEntry>, String> {
+    Ok(activity.recent(limit.unwrap_or(200)))
+}
@@ -0,0 +1,162 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::{ImageFormat, RgbaImage};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+use crate::error::emit;
+
+// Reference images for the steps that look for something on screen, kept as
+// PNGs in the `assets` folder of the data folder. Steps refer to them by
+// name; the desktop adds them from its editor and admin devices upload them
+// over the connection, both as base64.
+
+/// Largest image accepted, in bytes of PNG. Templates are small cut-outs of
+/// the screen.
+const MAX_ASSET_SIZE: usize = 4 << 20;
+
+const MAX_NAME_LENGTH: usize = 64;
+
+pub struct AssetStore {
+    dir: PathBuf,
+}
+
+impl AssetStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Where the asset `name` is kept. Names are limited to letters, digits,
+    /// `-` and `_`, so they can't point outside the folder.
+    fn path(&self, name: &str) -> Result<PathBuf, String> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAME_LENGTH
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!(
+                "Invalid asset name \"{}\": use up to {} letters, digits, - and _",
+                name, MAX_NAME_LENGTH
+            ));
+        }
+        Ok(self.dir.join(format!("{}.png", name)))
+    }
+
+    /// The names of the stored assets, sorted.
+    pub fn list(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "png" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Stores a PNG under `name`, replacing any asset of that name.
+    pub fn save(&self, name: &str, png: &[u8]) -> Result<(), String> {
+        let path = self.path(name)?;
+        if png.len() > MAX_ASSET_SIZE {
+            return Err(format!(
+                "Asset \"{}\" is larger than {} MB",
+                name,
+                MAX_ASSET_SIZE >> 20
+            ));
+        }
+        image::load_from_memory_with_format(png, ImageFormat::Png)
+            .map_err(|e| format!("Asset \"{}\" isn't a PNG image: {}", name, e))?;
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|()| std::fs::write(&path, png))
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+
+    pub fn load(&self, name: &str) -> Result<RgbaImage, String> {
+        let path = self.path(name)?;
+        if !path.is_file() {
+            return Err(format!("Asset \"{}\" not found", name));
+        }
+        image::open(&path)
+            .map(|image| image.to_rgba8())
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let path = self.path(name)?;
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to delete {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Stores an asset sent as base64, from the desktop or a device, and tells
+/// the frontend.
+pub async fn upload_asset(
+    name: String,
+    data: String,
+    store: Arc<AssetStore>,
+    app_handle: &AppHandle,
+) -> Result<(), String> {
+    // Decoding the whole image to check it takes too long for the runtime
+    let names = tokio::task::spawn_blocking(move || {
+        let png = BASE64
+            .decode(data.trim())
+            .map_err(|e| format!("Asset \"{}\" isn't valid base64: {}", name, e))?;
+        store.save(&name, &png)?;
+        Ok::<_, String>(store.list())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    emit(app_handle, "assets_updated", names);
+    Ok(())
+}
+
+// Asset-related Tauri commands
+
+#[tauri::command]
+pub fn list_assets(store: State<Arc<AssetStore>>) -> Result<Vec<String>, String> {
+    Ok(store.list())
+}
+
+/// Adds a reference image for image steps, replacing any of the same name.
+///
+/// # Arguments
+///
+/// * `name` - The name steps refer to it by.
+/// * `data` - The PNG, base64-encoded.
+/// * `store` - Shared state containing the assets.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn add_asset(
+    name: String,
+    data: String,
+    store: State<'_, Arc<AssetStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    upload_asset(name, data, store.inner().clone(), &app_handle).await
+}
+
+#[tauri::command]
+pub fn delete_asset(
+    name: String,
+    store: State<Arc<AssetStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    store.delete(&name)?;
+    emit(&app_handle, "assets_updated", store.list());
+    Ok(())
+}
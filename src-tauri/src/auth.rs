@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tracing::error;
+
+use crate::error::{read_json, write_json, Error};
+use crate::sockets::AppState;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct AuthData {
+    token: String,
+}
+
+/// Holds the token mobile clients must present before they may send commands.
+pub struct AuthStore {
+    pub token: Mutex<String>,
+    pub file_path: PathBuf,
+}
+
+fn generate_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+impl AuthStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        // Load the existing token, or generate one on first launch
+        let existing = read_json::<AuthData>(&file_path).unwrap_or_else(|e| {
+            error!("{}", e);
+            None
+        });
+
+        let store = Self {
+            token: Mutex::new(String::new()),
+            file_path,
+        };
+        match existing {
+            Some(data) => *store.token.lock().unwrap() = data.token,
+            None => {
+                *store.token.lock().unwrap() = generate_token();
+                if let Err(e) = store.save() {
+                    error!("{}", e);
+                }
+            }
+        }
+        store
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let data = AuthData {
+            token: self.get_token(),
+        };
+        write_json(&self.file_path, &data)
+    }
+
+    pub fn get_token(&self) -> String {
+        self.token.lock().unwrap().clone()
+    }
+
+    /// Compares in constant time so the token can't be guessed byte by byte.
+    pub fn verify(&self, candidate: &str) -> bool {
+        let token = self.token.lock().unwrap();
+        token.len() == candidate.len()
+            && token
+                .bytes()
+                .zip(candidate.bytes())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+// Auth-related Tauri commands
+
+/// Returns the token clients need to connect.
+#[tauri::command]
+pub fn get_auth_token(auth: State<Arc<AuthStore>>) -> Result<String, String> {
+    Ok(auth.get_token())
+}
+
+/// Replaces the token. Connected devices are dropped and their sessions
+/// forgotten, so they must reconnect with the new one.
+///
+/// # Returns
+///
+/// * `Result<String, String>` - The new token or an error message.
+#[tauri::command]
+pub async fn regenerate_auth_token(
+    auth: State<'_, Arc<AuthStore>>,
+    app_state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    {
+        let mut token = auth.token.lock().map_err(|e| e.to_string())?;
+        *token = generate_token();
+    }
+    auth.save()?;
+
+    // Connections and resumable sessions were granted under the old token
+    app_state.revoke_access("token_changed").await;
+    Ok(auth.get_token())
+}
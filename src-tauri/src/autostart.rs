@@ -0,0 +1,48 @@
+use auto_launch::{AutoLaunch, AutoLaunchBuilder};
+
+// Launch-at-login is stored by the OS itself (a registry Run key on Windows,
+// a LaunchAgent on macOS, an XDG autostart entry on Linux), so there is
+// nothing to persist on our side.
+
+const APP_NAME: &str = "Button Beam";
+
+fn auto_launch() -> Result<AutoLaunch, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let path = exe
+        .to_str()
+        .ok_or("The app path is not valid UTF-8")?
+        .to_string();
+    AutoLaunchBuilder::new()
+        .set_app_name(APP_NAME)
+        .set_app_path(&path)
+        .set_use_launch_agent(true)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+// Autostart-related Tauri commands
+
+#[tauri::command]
+pub fn get_autostart() -> Result<bool, String> {
+    auto_launch()?.is_enabled().map_err(|e| e.to_string())
+}
+
+/// Starts the app when the user logs in, so phones can connect after a reboot.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to launch at login.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn set_autostart(enabled: bool) -> Result<(), String> {
+    let auto_launch = auto_launch()?;
+    let result = if enabled {
+        auto_launch.enable()
+    } else {
+        auto_launch.disable()
+    };
+    result.map_err(|e| format!("Failed to change launch at login: {}", e))
+}
@@ -0,0 +1,330 @@
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+use crate::settings::SettingsStore;
+use crate::sockets::ServerContext;
+
+// Bluetooth LE transport: a GATT service carrying the same messages as the
+// WebSocket, for when the phone and desktop aren't on the same network.
+//
+// The phone writes to the RX characteristic and subscribes to notifications
+// on TX. Messages are split into chunks that fit a BLE packet; the first byte
+// of every chunk holds flags:
+//
+//   0x01 FINAL   - last chunk of the message
+//   0x02 BINARY  - MessagePack rather than JSON text
+//   0x04 PING    - heartbeat; the phone answers with a chunk carrying the same flag
+//
+// Each subscription to TX is one connection, with the same authentication and
+// pairing as a WebSocket client.
+//
+// Only macOS can act as a peripheral so far; elsewhere the transport can't be
+// turned on.
+
+/// Whether this OS can run the transport.
+pub const SUPPORTED: bool = cfg!(any(target_os = "macos", target_os = "ios"));
+
+/// Runs the BLE peripheral while the transport is enabled.
+pub struct BleTransport {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BleTransport {
+    pub fn new() -> Self {
+        Self {
+            task: Mutex::new(None),
+        }
+    }
+
+    pub async fn start(&self, ctx: ServerContext) -> Result<(), String> {
+        let mut task = self.task.lock().await;
+        if task.is_none() {
+            *task = Some(platform::spawn_peripheral(ctx)?);
+        }
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+            info!("Stopped the Bluetooth LE transport.");
+        }
+    }
+}
+
+impl Default for BleTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod platform {
+    use bluster::gatt::characteristic::{Characteristic, Properties, Secure, Write};
+    use bluster::gatt::event::{Event, Response};
+    use bluster::gatt::service::Service;
+    use bluster::Peripheral;
+    use futures::channel::mpsc;
+    use futures::{SinkExt, StreamExt};
+    use std::collections::HashSet;
+    use tokio::task::JoinHandle;
+    use tracing::{error, info, warn};
+    use uuid::Uuid;
+    use warp::ws::Message;
+
+    use crate::sockets::{run_connection, ServerContext, WsSender};
+
+    const SERVICE_UUID: Uuid = Uuid::from_u128(0xb7e3a000_5f4c_4b8e_9a3d_2c1f0e6d8a10);
+    const RX_UUID: Uuid = Uuid::from_u128(0xb7e3a001_5f4c_4b8e_9a3d_2c1f0e6d8a10);
+    const TX_UUID: Uuid = Uuid::from_u128(0xb7e3a002_5f4c_4b8e_9a3d_2c1f0e6d8a10);
+    const ADVERTISED_NAME: &str = "Button Beam";
+
+    const FLAG_FINAL: u8 = 0x01;
+    const FLAG_BINARY: u8 = 0x02;
+    const FLAG_PING: u8 = 0x04;
+    /// Payload bytes per chunk; fits the smallest MTU iOS and Android negotiate.
+    const CHUNK_SIZE: usize = 180;
+
+    fn encode_frames(message: &Message) -> Vec<Vec<u8>> {
+        if message.is_ping() {
+            return vec![vec![FLAG_PING | FLAG_FINAL]];
+        }
+        if !message.is_text() && !message.is_binary() {
+            return Vec::new();
+        }
+
+        let kind = if message.is_binary() { FLAG_BINARY } else { 0 };
+        let bytes = message.as_bytes();
+        let chunks: Vec<&[u8]> = if bytes.is_empty() {
+            vec![bytes]
+        } else {
+            bytes.chunks(CHUNK_SIZE).collect()
+        };
+        let last = chunks.len() - 1;
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let flags = kind | if i == last { FLAG_FINAL } else { 0 };
+                let mut frame = Vec::with_capacity(chunk.len() + 1);
+                frame.push(flags);
+                frame.extend_from_slice(chunk);
+                frame
+            })
+            .collect()
+    }
+
+    /// Reassembles chunks written by the phone into messages.
+    #[derive(Default)]
+    struct FrameAssembler {
+        buffer: Vec<u8>,
+    }
+
+    impl FrameAssembler {
+        fn push(&mut self, chunk: &[u8]) -> Option<Message> {
+            let (&flags, payload) = chunk.split_first()?;
+            if flags & FLAG_PING != 0 {
+                return Some(Message::pong(Vec::new()));
+            }
+
+            self.buffer.extend_from_slice(payload);
+            if flags & FLAG_FINAL == 0 {
+                return None;
+            }
+            let bytes = std::mem::take(&mut self.buffer);
+            if flags & FLAG_BINARY != 0 {
+                Some(Message::binary(bytes))
+            } else {
+                match String::from_utf8(bytes) {
+                    Ok(text) => Some(Message::text(text)),
+                    Err(e) => {
+                        warn!("Received invalid UTF-8 over BLE: {}", e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// The phone side of one BLE connection.
+    struct BleConnection {
+        incoming: mpsc::UnboundedSender<Result<Message, String>>,
+        assembler: FrameAssembler,
+    }
+
+    impl BleConnection {
+        fn close(&self) {
+            self.incoming.unbounded_send(Ok(Message::close())).ok();
+        }
+    }
+
+    /// Starts a connection whose replies go out as notifications.
+    fn open_connection(notification: mpsc::Sender<Vec<u8>>, ctx: &ServerContext) -> BleConnection {
+        let (incoming_tx, incoming_rx) = mpsc::unbounded();
+        let (outgoing_tx, mut outgoing_rx) = mpsc::unbounded::<Message>();
+
+        let closed = incoming_tx.clone();
+        tokio::spawn(async move {
+            let mut notification = notification;
+            'messages: while let Some(message) = outgoing_rx.next().await {
+                for frame in encode_frames(&message) {
+                    if notification.send(frame).await.is_err() {
+                        break 'messages;
+                    }
+                }
+            }
+            // Closing the sender, or losing the subscriber, ends the connection
+            closed.unbounded_send(Ok(Message::close())).ok();
+        });
+
+        tokio::spawn(run_connection(
+            incoming_rx,
+            WsSender::new(outgoing_tx),
+            false,
+            ctx.clone(),
+        ));
+        BleConnection {
+            incoming: incoming_tx,
+            assembler: FrameAssembler::default(),
+        }
+    }
+
+    pub fn spawn_peripheral(ctx: ServerContext) -> Result<JoinHandle<()>, String> {
+        Ok(tokio::spawn(async move {
+            if let Err(e) = run_peripheral(ctx).await {
+                error!("Bluetooth LE transport failed: {}", e);
+            }
+        }))
+    }
+
+    async fn run_peripheral(ctx: ServerContext) -> Result<(), String> {
+        let (events_tx, mut events) = mpsc::channel::<Event>(16);
+
+        let mut characteristics = HashSet::new();
+        characteristics.insert(Characteristic::new(
+            RX_UUID,
+            Properties::new(
+                None,
+                Some(Write::WithResponse(Secure::Insecure(events_tx.clone()))),
+                None,
+                None,
+            ),
+            None,
+            HashSet::new(),
+        ));
+        characteristics.insert(Characteristic::new(
+            TX_UUID,
+            Properties::new(None, None, Some(events_tx), None),
+            None,
+            HashSet::new(),
+        ));
+
+        let peripheral = Peripheral::new()
+            .await
+            .map_err(|e| format!("Bluetooth unavailable: {}", e))?;
+        peripheral
+            .add_service(&Service::new(SERVICE_UUID, true, characteristics))
+            .map_err(|e| format!("Failed to add GATT service: {}", e))?;
+        while !peripheral
+            .is_powered()
+            .await
+            .map_err(|e| format!("Bluetooth unavailable: {}", e))?
+        {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        }
+        peripheral
+            .start_advertising(ADVERTISED_NAME, &[SERVICE_UUID])
+            .await
+            .map_err(|e| format!("Failed to start advertising: {}", e))?;
+        info!("Bluetooth LE transport advertising as {}", ADVERTISED_NAME);
+
+        let mut connection: Option<BleConnection> = None;
+        while let Some(event) = events.next().await {
+            match event {
+                Event::NotifySubscribe(subscribe) => {
+                    // One phone at a time: a new subscriber replaces the previous one
+                    if let Some(previous) = connection.take() {
+                        previous.close();
+                    }
+                    connection = Some(open_connection(subscribe.notification, &ctx));
+                }
+                Event::NotifyUnsubscribe => {
+                    if let Some(previous) = connection.take() {
+                        previous.close();
+                    }
+                }
+                Event::WriteRequest(request) => {
+                    if let Some(current) = connection.as_mut() {
+                        if let Some(message) = current.assembler.push(&request.data) {
+                            current.incoming.unbounded_send(Ok(message)).ok();
+                        }
+                    }
+                    if !request.without_response {
+                        request.response.send(Response::Success(Vec::new())).ok();
+                    }
+                }
+                Event::ReadRequest(request) => {
+                    request.response.send(Response::Success(Vec::new())).ok();
+                }
+            }
+        }
+
+        peripheral.stop_advertising().await.ok();
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+mod platform {
+    use tokio::task::JoinHandle;
+
+    use crate::sockets::ServerContext;
+
+    pub fn spawn_peripheral(_ctx: ServerContext) -> Result<JoinHandle<()>, String> {
+        Err("The Bluetooth LE transport is not supported on this platform yet".into())
+    }
+}
+
+// BLE-related Tauri commands
+
+/// Returns whether the Bluetooth LE transport can run on this OS, so the
+/// frontend only offers it where it can.
+#[tauri::command]
+pub fn get_ble_supported() -> bool {
+    SUPPORTED
+}
+
+/// Turns the Bluetooth LE transport on or off and remembers the choice. It
+/// can't be turned on where it isn't supported.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to accept connections over Bluetooth LE.
+/// * `settings` - Shared state containing the settings.
+/// * `ble` - The Bluetooth LE transport.
+/// * `ctx` - Services shared with network connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_ble_transport(
+    enabled: bool,
+    settings: State<'_, Arc<SettingsStore>>,
+    ble: State<'_, Arc<BleTransport>>,
+    ctx: State<'_, ServerContext>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if enabled {
+        ble.start(ctx.inner().clone()).await?;
+    } else {
+        ble.stop().await;
+    }
+
+    settings.update(&app_handle, |current| current.ble_transport = enabled)?;
+    Ok(())
+}
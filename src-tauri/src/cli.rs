@@ -0,0 +1,146 @@
+use reqwest::blocking::{Client, RequestBuilder};
+use serde_json::Value;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::auth::AuthStore;
+use crate::settings::SettingsStore;
+use crate::shortcuts::Shortcut;
+
+// Subcommands that control the instance that is already running, through its
+// HTTP API, so scripts and other launchers can fire deck buttons:
+//
+//   button-beam list                - prints `<id>\t<name>` per shortcut
+//   button-beam trigger <id|name>   - runs a shortcut
+//
+// The address and token are read from the app's data folder, so this only
+// works for the same user on the same machine.
+
+const USAGE: &str = "Usage: button-beam list | button-beam trigger <id|name>";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The running instance's HTTP API.
+struct Api {
+    client: Client,
+    base_url: String,
+    token: String,
+}
+
+impl Api {
+    fn new(app_dir: &Path) -> Result<Self, String> {
+        let settings = SettingsStore::new(app_dir.join("settings.json"));
+        let port = settings
+            .get_settings()
+            .port
+            .ok_or("Button Beam hasn't been started yet")?;
+        // A server listening on every interface is reachable locally
+        let host = match settings.bind_address().parse::<IpAddr>() {
+            Ok(ip) if ip.is_unspecified() => "127.0.0.1".to_string(),
+            Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => settings.bind_address(),
+        };
+        let token = AuthStore::new(app_dir.join("auth.json")).get_token();
+        let client = Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            client,
+            base_url: format!("http://{}:{}", host, port),
+            token,
+        })
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<String, String> {
+        let response = request
+            .bearer_auth(&self.token)
+            .send()
+            .map_err(|e| format!("Is Button Beam running? {}", e))?;
+        let status = response.status();
+        let body = response.text().map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            let error = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|body| body["error"].as_str().map(str::to_string))
+                .unwrap_or_else(|| status.to_string());
+            return Err(error);
+        }
+        Ok(body)
+    }
+
+    fn shortcuts(&self) -> Result<Vec<Shortcut>, String> {
+        let url = format!("{}/shortcuts", self.base_url);
+        let body = self.send(self.client.get(url))?;
+        serde_json::from_str(&body).map_err(|e| e.to_string())
+    }
+
+    fn trigger(&self, id: u64) -> Result<(), String> {
+        let url = format!("{}/shortcuts/{}/trigger", self.base_url, id);
+        self.send(self.client.post(url)).map(|_| ())
+    }
+}
+
+/// Finds a shortcut by ID, or else by name, ignoring case.
+fn find<'a>(shortcuts: &'a [Shortcut], target: &str) -> Result<&'a Shortcut, String> {
+    if let Some(shortcut) = target
+        .parse::<u64>()
+        .ok()
+        .and_then(|id| shortcuts.iter().find(|s| s.id == id))
+    {
+        return Ok(shortcut);
+    }
+    let mut matches = shortcuts
+        .iter()
+        .filter(|s| s.name.eq_ignore_ascii_case(target.trim()));
+    match (matches.next(), matches.next()) {
+        (Some(shortcut), None) => Ok(shortcut),
+        (Some(_), Some(_)) => Err(format!(
+            "Several shortcuts are named \"{}\"; use the ID",
+            target
+        )),
+        (None, _) => Err(format!("No shortcut matches \"{}\"", target)),
+    }
+}
+
+fn run_command(command: &str, args: &[String], app_dir: &Path) -> Result<(), String> {
+    let api = Api::new(app_dir)?;
+    match (command, args) {
+        ("list", []) => {
+            for shortcut in api.shortcuts()? {
+                println!("{}\t{}", shortcut.id, shortcut.name);
+            }
+            Ok(())
+        }
+        ("trigger", [target]) => {
+            let shortcuts = api.shortcuts()?;
+            let shortcut = find(&shortcuts, target)?;
+            api.trigger(shortcut.id)?;
+            println!("Triggered \"{}\"", shortcut.name);
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Runs the subcommand in `args`, the process arguments without the program
+/// name. Returns the exit code, or `None` when there is no subcommand and the
+/// app should start as usual.
+pub fn run(args: &[String], app_dir: &Path) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    if !matches!(command.as_str(), "list" | "trigger" | "help" | "--help") {
+        return None;
+    }
+    if matches!(command.as_str(), "help" | "--help") {
+        println!("{}", USAGE);
+        return Some(0);
+    }
+    match run_command(command, rest, app_dir) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            Some(1)
+        }
+    }
+}
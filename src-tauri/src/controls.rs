@@ -0,0 +1,36 @@
+use button_beam_core::mouse;
+use tauri::AppHandle;
+
+use crate::shortcuts::{Control, ControlTarget};
+use crate::system::levels;
+
+// Dials and sliders on the phone. Rather than running a sequence, they drive
+// a level or the scroll wheel by an amount: a dial reports the detents it was
+// turned by, a slider the position it was moved to, in percent.
+
+/// Turns `control` by `detents`, negative for counterclockwise.
+pub fn rotate(app_handle: &AppHandle, control: Control, detents: i32) -> Result<(), String> {
+    let amount = detents.saturating_mul(control.step());
+    match control.target {
+        ControlTarget::Volume => levels::adjust_volume(amount).map(|_| levels::report(app_handle)),
+        ControlTarget::Brightness => {
+            levels::adjust_brightness(amount).map(|_| levels::report(app_handle))
+        }
+        ControlTarget::Scroll => mouse::scroll(amount, false),
+        ControlTarget::HorizontalScroll => mouse::scroll(amount, true),
+    }
+}
+
+/// Moves `control` to `percent` of its range.
+pub fn set(app_handle: &AppHandle, control: Control, percent: f64) -> Result<(), String> {
+    let level = percent.round().clamp(0.0, 100.0) as u8;
+    match control.target {
+        ControlTarget::Volume => levels::set_volume(level).map(|_| levels::report(app_handle)),
+        ControlTarget::Brightness => {
+            levels::set_brightness(level).map(|_| levels::report(app_handle))
+        }
+        ControlTarget::Scroll | ControlTarget::HorizontalScroll => {
+            Err("Scrolling can only be driven by a dial".to_string())
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+use tauri::Config;
+
+// Where shortcuts, settings, logs and plugins live. Normally the OS's app
+// data folder; portable installs keep everything next to the executable
+// instead, e.g. to run off a USB stick on machines where nothing may be
+// installed:
+//
+//   --data-dir <path>   - any folder, for this run
+//   `portable` file     - an empty file named `portable` next to the
+//                         executable makes it use the `data` folder beside it
+
+const PORTABLE_MARKER: &str = "portable";
+const PORTABLE_DIR: &str = "data";
+
+/// Removes `--data-dir <path>` or `--data-dir=<path>` from `args`, so the
+/// subcommands don't see it, and returns the path.
+fn take_data_dir_arg(args: &mut Vec<String>) -> Result<Option<PathBuf>, String> {
+    let Some(index) = args
+        .iter()
+        .position(|arg| arg == "--data-dir" || arg.starts_with("--data-dir="))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(index);
+    let path = match arg.strip_prefix("--data-dir=") {
+        Some(path) => path.to_string(),
+        None if index < args.len() => args.remove(index),
+        None => return Err("--data-dir needs a folder".to_string()),
+    };
+    Ok(Some(PathBuf::from(path)))
+}
+
+/// The `data` folder next to the executable, if a `portable` file is there.
+fn portable_dir() -> Option<PathBuf> {
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    exe_dir
+        .join(PORTABLE_MARKER)
+        .is_file()
+        .then(|| exe_dir.join(PORTABLE_DIR))
+}
+
+/// Picks the data folder for this run and creates it. `args` are the
+/// process arguments without the program name; `--data-dir` is taken out
+/// of them.
+pub fn resolve(args: &mut Vec<String>, config: &Config) -> Result<PathBuf, String> {
+    let dir = match take_data_dir_arg(args)? {
+        Some(dir) => dir,
+        None => match portable_dir() {
+            Some(dir) => dir,
+            None => {
+                tauri::api::path::app_data_dir(config).ok_or("Cannot locate app data directory")?
+            }
+        },
+    };
+    // Relative paths are taken from where the app was started, once
+    let dir = std::env::current_dir()
+        .map_err(|e| e.to_string())?
+        .join(dir);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Cannot create data directory {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
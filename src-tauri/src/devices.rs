@@ -0,0 +1,408 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, State};
+
+use crate::error::{read_json_or_default, write_json, Error};
+use crate::events::{publish, AppEvent};
+use crate::layouts::Layout;
+use crate::shortcuts::ShortcutStore;
+use crate::sockets::{approve_pending_device, disconnect_device_connections, AppState};
+
+pub use button_beam_core::protocol::DeviceRole;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustState {
+    Trusted,
+    Blocked,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KnownDevice {
+    pub id: String,
+    /// Name reported by the device itself.
+    pub name: String,
+    /// Name given by the user on the desktop; shown instead of `name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// Unset until the user trusts or blocks the device.
+    #[serde(default)]
+    pub trust: Option<TrustState>,
+    /// Milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub first_seen: u64,
+    #[serde(default)]
+    pub last_seen: u64,
+    /// Button grid assigned to this device; the client's default when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    #[serde(default)]
+    pub role: DeviceRole,
+    /// SHA-256 of the secret issued when the device was paired without the
+    /// token; it presents the secret to be recognised again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_hash: Option<String>,
+}
+
+fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Every device that has connected, with the user's trust decision, persisted
+/// across restarts.
+pub struct DeviceRegistry {
+    pub devices: Mutex<Vec<KnownDevice>>,
+    pub file_path: PathBuf,
+}
+
+impl DeviceRegistry {
+    pub fn new(file_path: PathBuf) -> Self {
+        let devices = read_json_or_default(&file_path);
+
+        Self {
+            devices: Mutex::new(devices),
+            file_path,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let devices = self.devices.lock().unwrap();
+        write_json(&self.file_path, &*devices)
+    }
+
+    pub fn get_devices(&self) -> Vec<KnownDevice> {
+        self.devices.lock().unwrap().clone()
+    }
+
+    pub fn trust_state(&self, id: &str) -> Option<TrustState> {
+        let devices = self.devices.lock().unwrap();
+        devices.iter().find(|d| d.id == id).and_then(|d| d.trust)
+    }
+
+    pub fn is_blocked(&self, id: &str) -> bool {
+        self.trust_state(id) == Some(TrustState::Blocked)
+    }
+
+    /// Records the trust decision for a device, adding it if it is new.
+    pub fn set_trust(&self, id: &str, name: Option<&str>, trust: TrustState) -> Result<(), Error> {
+        self.upsert(id, name, |device| device.trust = Some(trust))
+    }
+
+    /// Notes that a device has just been connected, adding it if it is new.
+    pub fn record_seen(&self, id: &str, name: &str) -> Result<(), Error> {
+        self.upsert(id, Some(name), |device| device.last_seen = now_millis())
+    }
+
+    pub fn nickname(&self, id: &str) -> Option<String> {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .iter()
+            .find(|d| d.id == id)
+            .and_then(|d| d.nickname.clone())
+    }
+
+    pub fn set_nickname(&self, id: &str, nickname: Option<String>) -> Result<(), Error> {
+        self.update(id, |device| device.nickname = nickname)
+    }
+
+    pub fn layout(&self, id: &str) -> Option<Layout> {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .iter()
+            .find(|d| d.id == id)
+            .and_then(|d| d.layout.clone())
+    }
+
+    pub fn set_layout(&self, id: &str, layout: Option<Layout>) -> Result<(), Error> {
+        self.update(id, |device| device.layout = layout)
+    }
+
+    pub fn set_role(&self, id: &str, role: DeviceRole) -> Result<(), Error> {
+        self.update(id, |device| device.role = role)
+    }
+
+    /// Issues a fresh secret to a device that was paired without the token,
+    /// replacing any earlier one. Only its hash is kept. Returns `None` for
+    /// devices that aren't known, as they can't be recognised again anyway.
+    pub fn issue_secret(&self, id: &str) -> Result<Option<String>, Error> {
+        let secret = uuid::Uuid::new_v4().simple().to_string();
+        {
+            let mut devices = self.devices.lock().unwrap();
+            let Some(device) = devices.iter_mut().find(|d| d.id == id) else {
+                return Ok(None);
+            };
+            device.secret_hash = Some(hash_secret(&secret));
+        }
+        self.save()?;
+        Ok(Some(secret))
+    }
+
+    /// Whether `secret` is the one issued to the device at pairing.
+    pub fn verify_secret(&self, id: &str, secret: &str) -> bool {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .iter()
+            .find(|d| d.id == id)
+            .and_then(|d| d.secret_hash.as_deref())
+            .is_some_and(|hash| hash == hash_secret(secret))
+    }
+
+    /// Role of a device; unknown devices are trigger-only.
+    pub fn role(&self, id: &str) -> DeviceRole {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .iter()
+            .find(|d| d.id == id)
+            .map_or(DeviceRole::default(), |d| d.role)
+    }
+
+    /// Updates the last-seen time of a device that is already known.
+    pub fn touch(&self, id: &str) -> Result<(), Error> {
+        let known = {
+            let mut devices = self.devices.lock().unwrap();
+            match devices.iter_mut().find(|d| d.id == id) {
+                Some(device) => {
+                    device.last_seen = now_millis();
+                    true
+                }
+                None => false,
+            }
+        };
+        if known {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Removes everything known about a device, including its trust decision.
+    /// Returns whether it was known.
+    pub fn forget(&self, id: &str) -> Result<bool, Error> {
+        let removed = {
+            let mut devices = self.devices.lock().unwrap();
+            let count = devices.len();
+            devices.retain(|d| d.id != id);
+            devices.len() != count
+        };
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Changes a known device; settings for devices that never connected
+    /// would only pile up.
+    fn update(&self, id: &str, change: impl FnOnce(&mut KnownDevice)) -> Result<(), Error> {
+        {
+            let mut devices = self.devices.lock().unwrap();
+            let device = devices
+                .iter_mut()
+                .find(|d| d.id == id)
+                .ok_or_else(|| Error::UnknownDevice(id.to_string()))?;
+            change(device);
+        }
+        self.save()
+    }
+
+    /// Changes a device, adding it first if it is new.
+    fn upsert(
+        &self,
+        id: &str,
+        name: Option<&str>,
+        change: impl FnOnce(&mut KnownDevice),
+    ) -> Result<(), Error> {
+        {
+            let mut devices = self.devices.lock().unwrap();
+            let index = match devices.iter().position(|d| d.id == id) {
+                Some(index) => index,
+                None => {
+                    let now = now_millis();
+                    devices.push(KnownDevice {
+                        id: id.to_string(),
+                        name: name.unwrap_or(id).to_string(),
+                        nickname: None,
+                        trust: None,
+                        first_seen: now,
+                        last_seen: now,
+                        layout: None,
+                        role: DeviceRole::default(),
+                        secret_hash: None,
+                    });
+                    devices.len() - 1
+                }
+            };
+            let device = &mut devices[index];
+            if let Some(name) = name {
+                device.name = name.to_string();
+            }
+            change(device);
+        }
+        self.save()
+    }
+}
+
+// Device-related Tauri commands
+
+/// Lists every device that has ever connected.
+#[tauri::command]
+pub fn list_known_devices(
+    registry: State<Arc<DeviceRegistry>>,
+) -> Result<Vec<KnownDevice>, String> {
+    Ok(registry.get_devices())
+}
+
+/// Marks a device as trusted, approving it right away if it is waiting for pairing.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the device to trust.
+/// * `registry` - Shared state containing the known devices.
+/// * `store` - Shared state containing the shortcuts.
+/// * `app_state` - Shared state containing the connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn trust_device(
+    device_id: String,
+    registry: State<'_, Arc<DeviceRegistry>>,
+    store: State<'_, Arc<ShortcutStore>>,
+    app_state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let name = app_state.device_name(&device_id).await;
+    registry.set_trust(&device_id, name.as_deref(), TrustState::Trusted)?;
+
+    // Nothing to do if the device isn't currently waiting for approval
+    approve_pending_device(&device_id, &registry, &store, &app_state, &app_handle)
+        .await
+        .ok();
+
+    publish(&app_handle, AppEvent::KnownDevicesChanged);
+    Ok(())
+}
+
+/// Blocks a device: it is disconnected now and refused whenever it reconnects.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the device to block.
+/// * `registry` - Shared state containing the known devices.
+/// * `app_state` - Shared state containing the connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn block_device(
+    device_id: String,
+    registry: State<'_, Arc<DeviceRegistry>>,
+    app_state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let name = app_state.device_name(&device_id).await;
+    registry.set_trust(&device_id, name.as_deref(), TrustState::Blocked)?;
+
+    disconnect_device_connections(&device_id, "device_blocked", &app_state).await;
+
+    publish(&app_handle, AppEvent::KnownDevicesChanged);
+    Ok(())
+}
+
+/// Forgets a device: its history and trust decision are removed, so it has to
+/// pair again next time. Open connections are left alone.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the device to forget.
+/// * `registry` - Shared state containing the known devices.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn forget_device(
+    device_id: String,
+    registry: State<Arc<DeviceRegistry>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if !registry.forget(&device_id)? {
+        return Err(Error::UnknownDevice(device_id).into());
+    }
+    publish(&app_handle, AppEvent::KnownDevicesChanged);
+    Ok(())
+}
+
+/// Gives a device a nickname that is used in events and logs instead of the
+/// name it reports. An empty or missing nickname restores the reported name.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the device to rename.
+/// * `nickname` - The new name, or `None` to clear it.
+/// * `registry` - Shared state containing the known devices.
+/// * `app_state` - Shared state containing the connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn rename_device(
+    device_id: String,
+    nickname: Option<String>,
+    registry: State<'_, Arc<DeviceRegistry>>,
+    app_state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let nickname = nickname
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    registry.set_nickname(&device_id, nickname.clone())?;
+
+    // Connected devices pick the new name up right away
+    let reported = registry
+        .get_devices()
+        .into_iter()
+        .find(|d| d.id == device_id)
+        .map(|d| d.name);
+    if let Some(name) = nickname.or(reported) {
+        app_state.rename_device(&device_id, &name).await;
+    }
+
+    for device in app_state.devices().await {
+        if device.id == device_id {
+            publish(&app_handle, AppEvent::DeviceUpdated(device));
+        }
+    }
+    publish(&app_handle, AppEvent::KnownDevicesChanged);
+    Ok(())
+}
+
+/// Sets what a device may do: fire shortcuts only, or also manage them.
+#[tauri::command]
+pub fn set_device_role(
+    device_id: String,
+    role: DeviceRole,
+    registry: State<Arc<DeviceRegistry>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    registry.set_role(&device_id, role)?;
+    publish(&app_handle, AppEvent::KnownDevicesChanged);
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use tracing::info;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::activity::ActivityLog;
+use crate::devices::{now_millis, DeviceRegistry};
+use crate::logging::LogBuffer;
+use crate::server::ServerHandle;
+use crate::settings::{network_interfaces, SettingsStore};
+
+// Bundle for bug reports. Deliberately leaves out the auth token, sync
+// credentials, stored secrets and the shortcuts themselves, which may contain
+// typed passwords. Anything in the settings that identifies an account or
+// carries a token, like a webhook URL, is redacted.
+
+/// Number of activity and log entries included in the bundle.
+const ACTIVITY_ENTRIES: usize = 500;
+const LOG_ENTRIES: usize = 2000;
+
+/// Settings keys whose values are replaced in the bundle, at any depth.
+const REDACTED_KEYS: &[&str] = &[
+    "password",
+    "token",
+    "secret",
+    "api_key",
+    "username",
+    "client_id",
+    "url",
+];
+
+#[derive(Serialize)]
+struct SystemInfo {
+    app_version: String,
+    os: &'static str,
+    os_family: &'static str,
+    arch: &'static str,
+    keyboard_layout: Option<String>,
+    server_address: Option<(String, u16)>,
+    known_devices: usize,
+}
+
+/// Best-effort description of the active keyboard layout, from the tools
+/// each OS ships with.
+fn keyboard_layout() -> Option<String> {
+    let output = if cfg!(target_os = "windows") {
+        Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                "(Get-WinUserLanguageList).InputMethodTips",
+            ])
+            .output()
+    } else if cfg!(target_os = "macos") {
+        Command::new("defaults")
+            .args(["read", "com.apple.HIToolbox", "AppleSelectedInputSources"])
+            .output()
+    } else {
+        Command::new("setxkbmap").arg("-query").output()
+    };
+    let output = output.ok().filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Replaces the values of `REDACTED_KEYS` and of any key containing one.
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if REDACTED_KEYS.iter().any(|redacted| key.contains(redacted)) {
+                    if !value.is_null() {
+                        *value = serde_json::json!("[redacted]");
+                    }
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn add_json<W: Write + std::io::Seek, T: Serialize>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    value: &T,
+) -> Result<(), String> {
+    zip.start_file(name, SimpleFileOptions::default())
+        .map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(&mut *zip, value).map_err(|e| e.to_string())
+}
+
+// Diagnostics-related Tauri commands
+
+/// Writes a zip with system, network and settings details plus recent
+/// activity and logs, for attaching to bug reports.
+///
+/// # Arguments
+///
+/// * `path` - Where to write the zip; the downloads folder when unset.
+/// * `settings` - Shared state containing the settings.
+/// * `registry` - Shared state containing the known devices.
+/// * `activity` - The activity log.
+/// * `logs` - The in-memory log buffer.
+/// * `server` - The running server.
+/// * `app_handle` - Handle to read the app version.
+///
+/// # Returns
+///
+/// * `Result<String, String>` - The path of the written file or an error message.
+#[tauri::command]
+pub async fn export_diagnostics(
+    path: Option<String>,
+    settings: State<'_, Arc<SettingsStore>>,
+    registry: State<'_, Arc<DeviceRegistry>>,
+    activity: State<'_, Arc<ActivityLog>>,
+    logs: State<'_, Arc<LogBuffer>>,
+    server: State<'_, Arc<ServerHandle>>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => tauri::api::path::download_dir()
+            .ok_or("Cannot locate the downloads folder")?
+            .join(format!("button-beam-diagnostics-{}.zip", now_millis())),
+    };
+
+    let system = SystemInfo {
+        app_version: app_handle.package_info().version.to_string(),
+        os: std::env::consts::OS,
+        os_family: std::env::consts::FAMILY,
+        arch: std::env::consts::ARCH,
+        keyboard_layout: tokio::task::spawn_blocking(keyboard_layout)
+            .await
+            .ok()
+            .flatten(),
+        server_address: server.advertised_addr().await,
+        known_devices: registry.get_devices().len(),
+    };
+    let mut settings = serde_json::to_value(settings.get_settings()).map_err(|e| e.to_string())?;
+    redact(&mut settings);
+    let interfaces = network_interfaces()
+        .map(|interfaces| serde_json::json!(interfaces))
+        .unwrap_or_else(|e| serde_json::json!({ "error": e }));
+
+    let file =
+        File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut zip = ZipWriter::new(file);
+    add_json(&mut zip, "system.json", &system)?;
+    add_json(&mut zip, "network_interfaces.json", &interfaces)?;
+    add_json(&mut zip, "settings.json", &settings)?;
+    add_json(
+        &mut zip,
+        "activity.json",
+        &activity.recent(ACTIVITY_ENTRIES),
+    )?;
+    add_json(
+        &mut zip,
+        "logs.json",
+        &logs.recent(tracing::Level::TRACE, LOG_ENTRIES),
+    )?;
+    zip.finish().map_err(|e| e.to_string())?;
+
+    info!("Wrote diagnostics to {}", path.display());
+    Ok(path.display().to_string())
+}
@@ -0,0 +1,132 @@
+use button_beam_core::protocol::PROTOCOL_VERSION;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::server::ServerHandle;
+use crate::settings::SettingsStore;
+
+/// Well-known port phones broadcast discovery requests to.
+pub const DISCOVERY_PORT: u16 = 41234;
+/// Payload of a discovery request; anything else is ignored.
+const DISCOVERY_REQUEST: &[u8] = b"button-beam-discover";
+
+/// Answers UDP discovery broadcasts with where to reach the WebSocket server.
+/// A fallback for networks that block mDNS, enabled through the settings.
+pub struct DiscoveryResponder {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl DiscoveryResponder {
+    pub fn new() -> Self {
+        Self {
+            task: Mutex::new(None),
+        }
+    }
+
+    pub async fn start(&self, server: Arc<ServerHandle>) -> Result<(), String> {
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            return Ok(());
+        }
+
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))
+            .await
+            .map_err(|e| format!("Failed to bind discovery port {}: {}", DISCOVERY_PORT, e))?;
+        info!(
+            "Answering discovery requests on UDP port {}",
+            DISCOVERY_PORT
+        );
+        *task = Some(tokio::spawn(respond(socket, server)));
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+            info!("Stopped answering discovery requests.");
+        }
+    }
+}
+
+impl Default for DiscoveryResponder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn respond(socket: UdpSocket, server: Arc<ServerHandle>) {
+    let mut buffer = [0u8; 64];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                error!("Discovery socket error: {}", e);
+                continue;
+            }
+        };
+        if &buffer[..len] != DISCOVERY_REQUEST {
+            continue;
+        }
+
+        // Nothing to announce while the server is stopped
+        let Some((ip, port)) = server.advertised_addr().await else {
+            continue;
+        };
+        let reply = announcement(ip, port);
+        if let Err(e) = socket.send_to(reply.to_string().as_bytes(), from).await {
+            warn!("Error answering discovery request from {}: {}", from, e);
+        }
+    }
+}
+
+fn announcement(ip: String, port: u16) -> serde_json::Value {
+    let name = hostname::get()
+        .ok()
+        .and_then(|name| name.into_string().ok())
+        .unwrap_or_else(|| "Button Beam".to_string());
+    serde_json::json!({
+        "service": "button-beam",
+        "name": name,
+        "ip": ip,
+        "port": port,
+        "protocol_version": PROTOCOL_VERSION,
+    })
+}
+
+// Discovery-related Tauri commands
+
+/// Turns the UDP discovery responder on or off and remembers the choice.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to answer discovery broadcasts.
+/// * `settings` - Shared state containing the settings.
+/// * `discovery` - The discovery responder.
+/// * `server` - The running server, whose address is announced.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_udp_discovery(
+    enabled: bool,
+    settings: State<'_, Arc<SettingsStore>>,
+    discovery: State<'_, Arc<DiscoveryResponder>>,
+    server: State<'_, Arc<ServerHandle>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if enabled {
+        discovery.start(Arc::clone(&server)).await?;
+    } else {
+        discovery.stop().await;
+    }
+
+    settings.update(&app_handle, |current| current.udp_discovery = enabled)?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+use tracing::error;
+
+pub use button_beam_core::storage::{read_json, read_json_or_default};
+
+/// Failures that should be reported to the user rather than crash the app.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Storage(#[from] button_beam_core::storage::Error),
+    #[error("Failed to register hotkeys: {0}")]
+    Hotkeys(String),
+    #[error("Unknown device {0}")]
+    UnknownDevice(String),
+    #[error(transparent)]
+    Tauri(#[from] tauri::Error),
+}
+
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
+
+/// Writes pretty-printed JSON, creating the parent directories if needed.
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), Error> {
+    Ok(button_beam_core::storage::write_json(path, value)?)
+}
+
+/// Logs an error that has no caller to return to and shows it in the
+/// frontend through an `app_error` event.
+pub fn report(app_handle: &AppHandle, error: &Error) {
+    error!("{}", error);
+    app_handle.emit_all("app_error", error.to_string()).ok();
+}
+
+/// Emits an event to every window, logging instead of panicking on failure.
+pub fn emit<S: Serialize + Clone>(app_handle: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app_handle.emit_all(event, payload) {
+        error!("Failed to emit {}: {}", event, e);
+    }
+}
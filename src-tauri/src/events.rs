@@ -0,0 +1,245 @@
+use button_beam_core::protocol::{
+    cycle_position_message, cycle_positions_message, CAP_CYCLE_POSITIONS, CAP_HOTKEY_CONFLICTS,
+};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use crate::activity::ActivityEntry;
+use crate::error::emit;
+use crate::hotkeys::{conflicts_message, refresh_global_shortcuts, HotkeyConflict, HotkeyStore};
+use crate::shortcuts::ShortcutChange;
+use crate::sockets::{Device, PairingRequest, ServerContext};
+
+// Everything other parts of the app may want to react to is published here
+// once, by whoever caused it. Listeners subscribe instead of being called
+// from each place the event happens, so a new listener can't be forgotten at
+// one of them:
+//
+//   frontend      - the Tauri events the windows listen to
+//   devices       - shortcut changes and hotkey conflicts pushed to
+//                   connected phones, along with cycle moves straight from
+//                   the store
+//   hotkeys       - global shortcuts re-registered after changes
+//   log           - every event at debug level
+//   integrations  - webhooks, MQTT and Home Assistant subscribe themselves
+//                   while they run
+
+const CAPACITY: usize = 256;
+
+#[derive(Clone, Debug)]
+pub enum AppEvent {
+    /// The shortcut list was saved with a change.
+    ShortcutsChanged(ShortcutChange),
+    /// A paired device can now trigger shortcuts.
+    DeviceConnected(Device),
+    DeviceDisconnected(Device),
+    /// An unknown device waits for the user to approve it.
+    DevicePendingApproval(Device),
+    /// Same as `DevicePendingApproval`, with the PIN to compare.
+    DevicePairingRequested(PairingRequest),
+    DeviceRejected(Device),
+    /// A connected device's name or status, e.g. its battery, changed.
+    DeviceUpdated(Device),
+    /// Trust, names, roles or layouts of known devices changed.
+    KnownDevicesChanged,
+    /// A global hotkey was bound, unbound or moved to another shortcut.
+    HotkeyBindingsChanged,
+    /// Global hotkeys the OS refused to register changed; carries them all.
+    HotkeyConflictsChanged(Vec<HotkeyConflict>),
+    /// A connection or executed shortcut was written to the activity log.
+    Activity(ActivityEntry),
+}
+
+pub struct EventBus {
+    sender: broadcast::Sender<AppEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: AppEvent) {
+        // Nobody listening is fine, e.g. during startup
+        self.sender.send(event).ok();
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AppEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Publishes an event on the bus managed by the app.
+pub fn publish(app_handle: &AppHandle, event: AppEvent) {
+    app_handle.state::<Arc<EventBus>>().publish(event);
+}
+
+/// Waits for the next event, skipping over any that were missed by falling
+/// behind. Returns `None` once the bus is gone.
+pub async fn next(events: &mut broadcast::Receiver<AppEvent>, listener: &str) -> Option<AppEvent> {
+    loop {
+        match events.recv().await {
+            Ok(event) => return Some(event),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("{} skipped {} events", listener, skipped)
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Starts the listeners that live as long as the app. Integrations subscribe
+/// on their own when they start.
+pub fn spawn_subscribers(ctx: &ServerContext) {
+    spawn_device_subscribers(ctx);
+    tokio::spawn(forward_to_frontend(ctx.clone()));
+    tokio::spawn(refresh_hotkeys(ctx.clone()));
+    tokio::spawn(log_events(ctx.events.subscribe()));
+}
+
+/// Starts the listeners connected devices depend on: store changes reach the
+/// bus, and from there the devices. The protocol tests run with only these,
+/// since they have no window or global hotkeys.
+pub fn spawn_device_subscribers(ctx: &ServerContext) {
+    // The store keeps its own channel so it doesn't depend on the app
+    let mut changes = ctx.store.broadcaster.subscribe();
+    let events = Arc::clone(&ctx.events);
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(change) => events.publish(AppEvent::ShortcutsChanged(change)),
+                Err(RecvError::Lagged(_)) => {
+                    events.publish(AppEvent::ShortcutsChanged(ShortcutChange::Reset))
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+
+    tokio::spawn(forward_to_devices(ctx.clone()));
+    tokio::spawn(forward_cycle_moves(ctx.clone()));
+}
+
+async fn forward_to_frontend(ctx: ServerContext) {
+    let mut events = ctx.events.subscribe();
+    let app_handle = &ctx.app_handle;
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            // Whatever was missed, the lists the windows show are sent again
+            Err(RecvError::Lagged(_)) => {
+                emit(app_handle, "shortcuts_updated", ctx.store.get_shortcuts());
+                emit(
+                    app_handle,
+                    "known_devices_updated",
+                    ctx.devices.get_devices(),
+                );
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        match event {
+            AppEvent::ShortcutsChanged(_) => {
+                emit(app_handle, "shortcuts_updated", ctx.store.get_shortcuts())
+            }
+            AppEvent::DeviceConnected(device) => emit(app_handle, "device_connected", device),
+            AppEvent::DeviceDisconnected(device) => emit(app_handle, "device_disconnected", device),
+            AppEvent::DevicePendingApproval(device) => {
+                emit(app_handle, "device_pending_approval", device)
+            }
+            AppEvent::DevicePairingRequested(request) => {
+                emit(app_handle, "device_pairing_requested", request)
+            }
+            AppEvent::DeviceRejected(device) => emit(app_handle, "device_rejected", device),
+            AppEvent::DeviceUpdated(device) => emit(app_handle, "device_updated", device),
+            AppEvent::KnownDevicesChanged => emit(
+                app_handle,
+                "known_devices_updated",
+                ctx.devices.get_devices(),
+            ),
+            AppEvent::HotkeyBindingsChanged => emit(
+                app_handle,
+                "hotkey_bindings_updated",
+                app_handle.state::<Arc<HotkeyStore>>().get_bindings(),
+            ),
+            AppEvent::HotkeyConflictsChanged(conflicts) => {
+                emit(app_handle, "hotkey_conflicts", conflicts)
+            }
+            AppEvent::Activity(entry) => emit(app_handle, "activity_recorded", entry),
+        }
+    }
+}
+
+/// Pushes shortcut changes and hotkey conflicts to every connected device.
+/// Runs independently of server restarts.
+async fn forward_to_devices(ctx: ServerContext) {
+    let mut events = ctx.events.subscribe();
+    loop {
+        let change = match events.recv().await {
+            Ok(AppEvent::ShortcutsChanged(change)) => change,
+            Ok(AppEvent::HotkeyConflictsChanged(conflicts)) => {
+                ctx.app_state
+                    .broadcast_to(CAP_HOTKEY_CONFLICTS, &conflicts_message(&conflicts))
+                    .await;
+                continue;
+            }
+            Ok(_) => continue,
+            // Devices that missed changes have to resync the whole list
+            Err(RecvError::Lagged(_)) => ShortcutChange::Reset,
+            Err(RecvError::Closed) => return,
+        };
+        ctx.app_state
+            .broadcast_shortcut_change(&change, &ctx.store)
+            .await;
+    }
+}
+
+/// Tells devices where a cycle is after each tap that moved it.
+async fn forward_cycle_moves(ctx: ServerContext) {
+    let mut moves = ctx.store.subscribe_cycle_moves();
+    loop {
+        let message = match moves.recv().await {
+            Ok(moved) => cycle_position_message(&moved),
+            // Devices that missed moves get every position
+            Err(RecvError::Lagged(_)) => cycle_positions_message(&ctx.store.cycle_positions()),
+            Err(RecvError::Closed) => return,
+        };
+        ctx.app_state
+            .broadcast_to(CAP_CYCLE_POSITIONS, &message)
+            .await;
+    }
+}
+
+async fn refresh_hotkeys(ctx: ServerContext) {
+    let mut events = ctx.events.subscribe();
+    loop {
+        match events.recv().await {
+            Ok(AppEvent::ShortcutsChanged(_)) | Err(RecvError::Lagged(_)) => {
+                refresh_global_shortcuts(&ctx.app_handle, &ctx.store)
+            }
+            Ok(_) => {}
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+async fn log_events(mut events: broadcast::Receiver<AppEvent>) {
+    while let Some(event) = next(&mut events, "Event log").await {
+        match event {
+            // Keeps the PIN out of the log file
+            AppEvent::DevicePairingRequested(request) => {
+                debug!("Event: pairing requested by {}", request.device.name)
+            }
+            event => debug!("Event: {:?}", event),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+// Caps how many sequences devices may run at the same time, overall and per
+// device, so two people on two phones can't fight over the keyboard. A
+// trigger over the limit is refused with an error saying which limit it hit,
+// or waits for a running sequence to finish, as the settings say.
+
+/// How long a queued trigger waits for a free slot before it is refused.
+const MAX_QUEUE_WAIT: Duration = Duration::from_secs(30);
+
+/// Limits on the sequences devices run at once; unset means unlimited.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ExecutionLimits {
+    #[serde(default)]
+    pub max_running: Option<usize>,
+    #[serde(default)]
+    pub max_running_per_device: Option<usize>,
+    #[serde(default)]
+    pub when_busy: WhenBusy,
+}
+
+impl ExecutionLimits {
+    /// Refuses a limit of zero, which would keep every shortcut from running.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_running == Some(0) || self.max_running_per_device == Some(0) {
+            return Err("Execution limits must allow at least one running shortcut".into());
+        }
+        Ok(())
+    }
+}
+
+/// What happens to a trigger over the limit.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WhenBusy {
+    /// Refused right away.
+    #[default]
+    Reject,
+    /// Runs once a slot is free, or is refused after `MAX_QUEUE_WAIT`.
+    Queue,
+}
+
+#[derive(Default)]
+struct Counts {
+    total: usize,
+    per_device: HashMap<String, usize>,
+}
+
+/// The sequences running now, counted against the limits.
+#[derive(Default)]
+pub struct RunningSequences {
+    counts: Mutex<Counts>,
+    freed: Notify,
+}
+
+impl RunningSequences {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a slot for a sequence triggered by `device_id`, or says which
+    /// limit is reached. The slot is given back when dropped.
+    pub fn try_start(
+        self: &Arc<Self>,
+        device_id: Option<&str>,
+        limits: &ExecutionLimits,
+    ) -> Result<RunningSlot, String> {
+        let mut counts = self.counts.lock().unwrap();
+        if let Some(max) = limits.max_running.filter(|max| counts.total >= *max) {
+            return Err(format!(
+                "{} shortcuts are already running, the most allowed at once",
+                max
+            ));
+        }
+        if let Some(id) = device_id {
+            let running = counts.per_device.get(id).copied().unwrap_or(0);
+            if let Some(max) = limits.max_running_per_device.filter(|max| running >= *max) {
+                return Err(format!(
+                    "This device already runs {} shortcuts, the most allowed per device",
+                    max
+                ));
+            }
+            *counts.per_device.entry(id.to_string()).or_default() += 1;
+        }
+        counts.total += 1;
+        Ok(RunningSlot {
+            running: Arc::clone(self),
+            device_id: device_id.map(str::to_string),
+        })
+    }
+
+    /// Takes a slot for a trigger as `limits` say: right away, refusing the
+    /// trigger over the limits, or later, see [`Reservation::wait`].
+    pub fn reserve(
+        self: &Arc<Self>,
+        device_id: Option<&str>,
+        limits: ExecutionLimits,
+    ) -> Result<Reservation, String> {
+        match limits.when_busy {
+            WhenBusy::Reject => self.try_start(device_id, &limits).map(Reservation::Taken),
+            WhenBusy::Queue => Ok(Reservation::Queued {
+                running: Arc::clone(self),
+                device_id: device_id.map(str::to_string),
+                limits,
+            }),
+        }
+    }
+
+    /// Like [`Self::try_start`], but waits up to `MAX_QUEUE_WAIT` for a slot.
+    pub async fn start(
+        self: &Arc<Self>,
+        device_id: Option<&str>,
+        limits: &ExecutionLimits,
+    ) -> Result<RunningSlot, String> {
+        let deadline = Instant::now() + MAX_QUEUE_WAIT;
+        loop {
+            // Created before checking, so a slot freed in between isn't missed
+            let freed = self.freed.notified();
+            match self.try_start(device_id, limits) {
+                Ok(slot) => return Ok(slot),
+                Err(e) if Instant::now() >= deadline => {
+                    return Err(format!("{} (waited {} s)", e, MAX_QUEUE_WAIT.as_secs()))
+                }
+                Err(_) => {}
+            }
+            tokio::time::timeout_at(deadline, freed).await.ok();
+        }
+    }
+}
+
+/// A triggered sequence's claim on a slot.
+pub enum Reservation {
+    Taken(RunningSlot),
+    /// Waits for a slot once the sequence is about to run.
+    Queued {
+        running: Arc<RunningSequences>,
+        device_id: Option<String>,
+        limits: ExecutionLimits,
+    },
+}
+
+impl Reservation {
+    /// The slot, once there is one.
+    pub async fn wait(self) -> Result<RunningSlot, String> {
+        match self {
+            Reservation::Taken(slot) => Ok(slot),
+            Reservation::Queued {
+                running,
+                device_id,
+                limits,
+            } => running.start(device_id.as_deref(), &limits).await,
+        }
+    }
+}
+
+/// A running sequence's place in the counts.
+pub struct RunningSlot {
+    running: Arc<RunningSequences>,
+    device_id: Option<String>,
+}
+
+impl Drop for RunningSlot {
+    fn drop(&mut self) {
+        let mut counts = self.running.counts.lock().unwrap();
+        counts.total -= 1;
+        if let Some(id) = &self.device_id {
+            if let Some(running) = counts.per_device.get_mut(id) {
+                *running -= 1;
+                if *running == 0 {
+                    counts.per_device.remove(id);
+                }
+            }
+        }
+        drop(counts);
+        self.running.freed.notify_waiters();
+    }
+}
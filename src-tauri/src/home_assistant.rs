@@ -0,0 +1,145 @@
+use rumqttc::{AsyncClient, QoS};
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::events::{AppEvent, EventBus};
+use crate::mqtt::MqttSettings;
+use crate::shortcuts::{Shortcut, ShortcutChange, ShortcutStore};
+
+// Publishes Home Assistant MQTT discovery configs over the MQTT bridge, so
+// every shortcut shows up as a button entity. Pressing it publishes the
+// shortcut ID to the bridge's trigger topic. Configs are retained, and an
+// empty retained config removes the entity again.
+
+const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+
+/// Identifies this desktop in entity and device IDs, so several desktops on
+/// one broker don't overwrite each other's buttons.
+fn node_id() -> String {
+    let hostname = hostname::get()
+        .ok()
+        .and_then(|name| name.into_string().ok())
+        .unwrap_or_else(|| "desktop".to_string());
+    let sanitized: String = hostname
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("button_beam_{}", sanitized.to_lowercase())
+}
+
+struct Discovery {
+    client: AsyncClient,
+    settings: MqttSettings,
+    node_id: String,
+    /// Shortcuts that currently have a config on the broker.
+    published: HashSet<u64>,
+}
+
+impl Discovery {
+    fn config_topic(&self, shortcut_id: u64) -> String {
+        let prefix = self
+            .settings
+            .discovery_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_DISCOVERY_PREFIX);
+        format!(
+            "{}/button/{}/{}/config",
+            prefix.trim_end_matches('/'),
+            self.node_id,
+            shortcut_id
+        )
+    }
+
+    async fn publish(&self, topic: String, payload: String) {
+        if let Err(e) = self
+            .client
+            .publish(topic, QoS::AtLeastOnce, true, payload)
+            .await
+        {
+            warn!("Failed to publish Home Assistant discovery: {}", e);
+        }
+    }
+
+    async fn add(&mut self, shortcut: &Shortcut) {
+        let config = json!({
+            "name": shortcut.name,
+            "unique_id": format!("{}_{}", self.node_id, shortcut.id),
+            "command_topic": self.settings.topic("trigger"),
+            "payload_press": shortcut.id.to_string(),
+            "availability_topic": self.settings.topic("status"),
+            "icon": "mdi:gesture-tap-button",
+            "device": {
+                "identifiers": [self.node_id],
+                "name": "Button Beam",
+                "manufacturer": "Button Beam",
+            },
+        });
+        self.publish(self.config_topic(shortcut.id), config.to_string())
+            .await;
+        self.published.insert(shortcut.id);
+    }
+
+    async fn remove(&mut self, shortcut_id: u64) {
+        self.publish(self.config_topic(shortcut_id), String::new())
+            .await;
+        self.published.remove(&shortcut_id);
+    }
+
+    /// Publishes every shortcut and removes entities of deleted ones.
+    async fn sync_all(&mut self, store: &ShortcutStore) {
+        let shortcuts = store.get_shortcuts();
+        let current: HashSet<u64> = shortcuts.iter().map(|s| s.id).collect();
+        let stale: Vec<u64> = self.published.difference(&current).copied().collect();
+        for id in stale {
+            self.remove(id).await;
+        }
+        for shortcut in &shortcuts {
+            self.add(shortcut).await;
+        }
+    }
+}
+
+/// Keeps the broker's discovery configs in step with the shortcuts.
+/// `connected` is notified on every (re)connect, since a broker without
+/// persistence forgets retained messages when it restarts.
+pub async fn sync_discovery(
+    client: AsyncClient,
+    settings: MqttSettings,
+    store: Arc<ShortcutStore>,
+    events: Arc<EventBus>,
+    connected: Arc<Notify>,
+) {
+    let mut events = events.subscribe();
+    let mut discovery = Discovery {
+        client,
+        settings,
+        node_id: node_id(),
+        published: HashSet::new(),
+    };
+
+    loop {
+        tokio::select! {
+            _ = connected.notified() => discovery.sync_all(&store).await,
+            event = events.recv() => match event {
+                Ok(AppEvent::ShortcutsChanged(change)) => match change {
+                    ShortcutChange::Added(id) | ShortcutChange::Updated(id) => {
+                        match store.get_shortcuts().into_iter().find(|s| s.id == id) {
+                            Some(shortcut) => discovery.add(&shortcut).await,
+                            None => discovery.remove(id).await,
+                        }
+                    }
+                    ShortcutChange::Deleted(id) => discovery.remove(id).await,
+                    ShortcutChange::Reset => discovery.sync_all(&store).await,
+                },
+                Ok(_) => {}
+                // Missed changes are caught up on like a reset
+                Err(RecvError::Lagged(_)) => discovery.sync_all(&store).await,
+                Err(RecvError::Closed) => return,
+            },
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, GlobalShortcutManager, Manager, State};
+use tracing::{error, warn};
+
+use crate::error::{read_json, report, write_json, Error};
+use crate::events::{publish, AppEvent};
+use crate::shortcuts::{Shortcut, ShortcutStore};
+use crate::sockets::{toggle_paused, ServerContext};
+use crate::triggers::{self, TriggerSource};
+
+// Global hotkeys that run shortcuts from the desktop keyboard. Each shortcut
+// can have one hotkey, chosen by the user and kept in `hotkeys.json`.
+// Hotkeys are stored normalized, e.g. `Ctrl+Shift+K`, with `CmdOrCtrl`
+// resolved for this OS, so two spellings of the same combo are recognized as
+// a conflict. Hotkeys the OS refuses, usually because another app registered
+// them first, are published as conflicts so the frontend and devices can
+// show which buttons have no hotkey.
+
+/// Pauses or resumes all triggering, from anywhere.
+const PAUSE_HOTKEY: &str = "CmdOrCtrl+Alt+P";
+
+/// Combos the OS or nearly every app relies on, refused as bindings.
+const RESERVED_HOTKEYS: &[&str] = &[
+    "Alt+Tab",
+    "Alt+F4",
+    "Ctrl+Alt+Delete",
+    "Ctrl+Shift+Escape",
+    "CmdOrCtrl+A",
+    "CmdOrCtrl+C",
+    "CmdOrCtrl+V",
+    "CmdOrCtrl+X",
+    "CmdOrCtrl+Z",
+    "CmdOrCtrl+S",
+    "CmdOrCtrl+Q",
+    "CmdOrCtrl+W",
+    "CmdOrCtrl+Tab",
+    "Super+Tab",
+    "Super+Space",
+    "Super+D",
+    "Super+H",
+    "Super+L",
+    "Super+M",
+];
+
+/// In the order they are written in normalized hotkeys.
+const MODIFIERS: [&str; 4] = ["Ctrl", "Alt", "Shift", "Super"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HotkeyBinding {
+    pub hotkey: String,
+    pub shortcut_id: u64,
+}
+
+/// Brings a hotkey such as `shift+cmdorctrl+k` into the form bindings are
+/// stored and compared in. Hotkeys without a modifier are refused unless the
+/// key is F1-F24, since they would fire while typing.
+pub fn normalize_hotkey(hotkey: &str) -> Result<String, String> {
+    let parts: Vec<&str> = hotkey.split('+').map(str::trim).collect();
+    let (key, modifiers) = parts.split_last().ok_or("The hotkey is empty")?;
+    if key.is_empty() {
+        return Err(format!("Invalid hotkey: {}", hotkey));
+    }
+
+    let mut used = [false; MODIFIERS.len()];
+    for modifier in modifiers {
+        let index = match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => 0,
+            "alt" | "option" => 1,
+            "shift" => 2,
+            "super" | "cmd" | "command" | "meta" => 3,
+            "cmdorctrl" | "commandorcontrol" if cfg!(target_os = "macos") => 3,
+            "cmdorctrl" | "commandorcontrol" => 0,
+            _ => return Err(format!("Unknown modifier {} in {}", modifier, hotkey)),
+        };
+        used[index] = true;
+    }
+
+    let mut chars = key.chars();
+    let key: String = chars
+        .next()
+        .into_iter()
+        .flat_map(char::to_uppercase)
+        .chain(chars.flat_map(char::to_lowercase))
+        .collect();
+    let is_function_key = key
+        .strip_prefix('F')
+        .and_then(|number| number.parse::<u8>().ok())
+        .is_some_and(|number| (1..=24).contains(&number));
+    if !used.contains(&true) && !is_function_key {
+        return Err(format!(
+            "{} needs a modifier such as Ctrl, or it would fire while typing",
+            hotkey
+        ));
+    }
+
+    let mut normalized: Vec<&str> = MODIFIERS
+        .iter()
+        .zip(used)
+        .filter(|(_, used)| *used)
+        .map(|(modifier, _)| *modifier)
+        .collect();
+    normalized.push(&key);
+    Ok(normalized.join("+"))
+}
+
+fn pause_hotkey() -> String {
+    normalize_hotkey(PAUSE_HOTKEY).expect("the pause hotkey is valid")
+}
+
+/// Before bindings were configurable, the first ten shortcuts were bound to
+/// Ctrl+1 to Ctrl+0 and the next ten to Ctrl+Shift+1 to Ctrl+Shift+0.
+fn positional_bindings(shortcuts: &[Shortcut]) -> Vec<HotkeyBinding> {
+    shortcuts
+        .iter()
+        .take(20)
+        .enumerate()
+        .map(|(i, shortcut)| {
+            // 0 represents 10
+            let digit = (i % 10 + 1) % 10;
+            let hotkey = if i < 10 {
+                format!("Ctrl+{}", digit)
+            } else {
+                format!("Ctrl+Shift+{}", digit)
+            };
+            HotkeyBinding {
+                hotkey,
+                shortcut_id: shortcut.id,
+            }
+        })
+        .collect()
+}
+
+pub struct HotkeyStore {
+    pub bindings: Mutex<Vec<HotkeyBinding>>,
+    pub file_path: PathBuf,
+}
+
+impl HotkeyStore {
+    /// Loads the bindings. Without a file yet, the positional bindings of
+    /// earlier versions are kept so existing hotkeys go on working.
+    pub fn new(file_path: PathBuf, shortcuts: &[Shortcut]) -> Self {
+        let (bindings, seeded) = match read_json(&file_path) {
+            Ok(Some(bindings)) => (bindings, false),
+            Ok(None) => (positional_bindings(shortcuts), true),
+            Err(e) => {
+                error!("{}", e);
+                (Vec::new(), false)
+            }
+        };
+
+        let store = Self {
+            bindings: Mutex::new(bindings),
+            file_path,
+        };
+        if seeded {
+            if let Err(e) = store.save() {
+                error!("{}", e);
+            }
+        }
+        store
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let bindings = self.bindings.lock().unwrap();
+        write_json(&self.file_path, &*bindings)
+    }
+
+    pub fn get_bindings(&self) -> Vec<HotkeyBinding> {
+        self.bindings.lock().unwrap().clone()
+    }
+
+    fn set_bindings(&self, bindings: Vec<HotkeyBinding>) -> Result<(), Error> {
+        *self.bindings.lock().unwrap() = bindings;
+        self.save()
+    }
+
+    /// Drops bindings of shortcuts that no longer exist. Returns whether any
+    /// were dropped.
+    fn retain_shortcuts(&self, shortcuts: &[Shortcut]) -> Result<bool, Error> {
+        let removed = {
+            let mut bindings = self.bindings.lock().unwrap();
+            let count = bindings.len();
+            bindings.retain(|b| shortcuts.iter().any(|s| s.id == b.shortcut_id));
+            bindings.len() != count
+        };
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+}
+
+/// A hotkey the OS refused to register.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct HotkeyConflict {
+    pub hotkey: String,
+    /// The shortcut bound to it, or `None` for the pause hotkey.
+    pub shortcut_id: Option<u64>,
+    pub shortcut_name: Option<String>,
+    pub reason: String,
+}
+
+/// The `hotkey_conflicts` message sent to devices that support it.
+pub fn conflicts_message(conflicts: &[HotkeyConflict]) -> serde_json::Value {
+    serde_json::json!({ "type": "hotkey_conflicts", "conflicts": conflicts })
+}
+
+/// What a global hotkey does. Shortcuts are looked up when the hotkey is
+/// pressed, so editing one doesn't require registering its hotkey again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HotkeyTarget {
+    Pause,
+    Shortcut(u64),
+}
+
+/// The global hotkeys currently registered with the OS, and the ones it
+/// refused.
+#[derive(Default)]
+pub struct RegisteredHotkeys {
+    targets: Mutex<HashMap<String, HotkeyTarget>>,
+    conflicts: Mutex<Vec<HotkeyConflict>>,
+}
+
+impl RegisteredHotkeys {
+    pub fn conflicts(&self) -> Vec<HotkeyConflict> {
+        self.conflicts.lock().unwrap().clone()
+    }
+}
+
+fn run_hotkey_shortcut(app_handle: &AppHandle, id: u64) {
+    let Some(ctx) = app_handle.try_state::<ServerContext>() else {
+        return;
+    };
+    let ctx = ctx.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = triggers::trigger_shortcut(id, TriggerSource::Hotkey, &ctx).await {
+            warn!("Ignoring hotkey for shortcut {}: {}", id, e);
+        }
+    });
+}
+
+/// Brings the registered global hotkeys in line with `bindings`. Only
+/// hotkeys that now do something else are unregistered and registered again,
+/// so the rest keep working throughout. Returns the hotkeys that couldn't be
+/// registered, e.g. because another app owns them, with the reason; they are
+/// retried on the next call.
+fn register_hotkeys(app_handle: &AppHandle, bindings: &[HotkeyBinding]) -> Vec<(String, String)> {
+    let mut wanted: HashMap<String, HotkeyTarget> = bindings
+        .iter()
+        .map(|b| (b.hotkey.clone(), HotkeyTarget::Shortcut(b.shortcut_id)))
+        .collect();
+    wanted.insert(pause_hotkey(), HotkeyTarget::Pause);
+
+    let registered_hotkeys = app_handle.state::<Arc<RegisteredHotkeys>>();
+    let mut registered = registered_hotkeys.targets.lock().unwrap();
+    let mut shortcut_manager = app_handle.global_shortcut_manager();
+
+    let stale: Vec<String> = registered
+        .iter()
+        .filter(|(hotkey, target)| wanted.get(*hotkey) != Some(*target))
+        .map(|(hotkey, _)| hotkey.clone())
+        .collect();
+    for hotkey in stale {
+        registered.remove(&hotkey);
+        if let Err(e) = shortcut_manager.unregister(&hotkey) {
+            error!("Failed to unregister global shortcut {}: {}", hotkey, e);
+        }
+    }
+
+    let mut failed = Vec::new();
+    for (hotkey, target) in wanted {
+        if registered.contains_key(&hotkey) {
+            continue;
+        }
+        let handle = app_handle.clone();
+        let result = match target {
+            HotkeyTarget::Pause => {
+                shortcut_manager.register(&hotkey, move || toggle_paused(&handle))
+            }
+            HotkeyTarget::Shortcut(id) => {
+                shortcut_manager.register(&hotkey, move || run_hotkey_shortcut(&handle, id))
+            }
+        };
+        match result {
+            Ok(()) => {
+                registered.insert(hotkey, target);
+            }
+            Err(e) => {
+                error!("Failed to register global shortcut {}: {}", hotkey, e);
+                failed.push((hotkey, e.to_string()));
+            }
+        }
+    }
+    failed.sort();
+    failed
+}
+
+/// Keeps the hotkeys that failed to register as the current conflicts and
+/// publishes them when they changed. Newly failing hotkeys are also reported
+/// as an error, once.
+fn record_conflicts(
+    app_handle: &AppHandle,
+    failed: Vec<(String, String)>,
+    bindings: &[HotkeyBinding],
+    shortcuts: &[Shortcut],
+) {
+    let conflicts: Vec<HotkeyConflict> = failed
+        .into_iter()
+        .map(|(hotkey, reason)| {
+            let shortcut_id = bindings
+                .iter()
+                .find(|b| b.hotkey == hotkey)
+                .map(|b| b.shortcut_id);
+            let shortcut_name = shortcut_id
+                .and_then(|id| shortcuts.iter().find(|s| s.id == id))
+                .map(|s| s.name.clone());
+            HotkeyConflict {
+                hotkey,
+                shortcut_id,
+                shortcut_name,
+                reason,
+            }
+        })
+        .collect();
+
+    let registered_hotkeys = app_handle.state::<Arc<RegisteredHotkeys>>();
+    let previous = std::mem::replace(
+        &mut *registered_hotkeys.conflicts.lock().unwrap(),
+        conflicts.clone(),
+    );
+    if previous == conflicts {
+        return;
+    }
+    let new: Vec<&str> = conflicts
+        .iter()
+        .filter(|c| !previous.iter().any(|p| p.hotkey == c.hotkey))
+        .map(|c| c.hotkey.as_str())
+        .collect();
+    if !new.is_empty() {
+        report(
+            app_handle,
+            &Error::Hotkeys(format!("{} may be in use by another app", new.join(", "))),
+        );
+    }
+    publish(app_handle, AppEvent::HotkeyConflictsChanged(conflicts));
+}
+
+/// Re-registers the global hotkeys after the shortcut list or the bindings
+/// changed, publishing failures as conflicts instead of failing the change
+/// itself.
+pub fn refresh_global_shortcuts(app_handle: &AppHandle, store: &Arc<ShortcutStore>) {
+    let hotkeys = app_handle.state::<Arc<HotkeyStore>>();
+    let shortcuts = store.get_shortcuts();
+    match hotkeys.retain_shortcuts(&shortcuts) {
+        Ok(true) => publish(app_handle, AppEvent::HotkeyBindingsChanged),
+        Ok(false) => {}
+        Err(e) => report(app_handle, &e),
+    }
+
+    let bindings = hotkeys.get_bindings();
+    let failed = register_hotkeys(app_handle, &bindings);
+    record_conflicts(app_handle, failed, &bindings, &shortcuts);
+}
+
+/// Checks that `hotkey` is free to bind to `shortcut_id`.
+fn check_conflicts(
+    hotkey: &str,
+    shortcut_id: u64,
+    bindings: &[HotkeyBinding],
+    shortcuts: &[Shortcut],
+) -> Result<(), String> {
+    if hotkey == pause_hotkey() {
+        return Err(format!("{} pauses triggering", hotkey));
+    }
+    if RESERVED_HOTKEYS
+        .iter()
+        .any(|reserved| normalize_hotkey(reserved).as_deref() == Ok(hotkey))
+    {
+        return Err(format!("{} is reserved by the system", hotkey));
+    }
+    if let Some(binding) = bindings
+        .iter()
+        .find(|b| b.hotkey == hotkey && b.shortcut_id != shortcut_id)
+    {
+        let name = shortcuts
+            .iter()
+            .find(|s| s.id == binding.shortcut_id)
+            .map_or("another shortcut", |s| s.name.as_str());
+        return Err(format!("{} is already bound to {}", hotkey, name));
+    }
+    Ok(())
+}
+
+// Hotkey-related Tauri commands
+
+#[tauri::command]
+pub fn get_hotkey_bindings(hotkeys: State<Arc<HotkeyStore>>) -> Result<Vec<HotkeyBinding>, String> {
+    Ok(hotkeys.get_bindings())
+}
+
+/// Lists the hotkeys the OS refused to register, usually because another app
+/// already uses them. Changes are also sent as `hotkey_conflicts` events.
+///
+/// # Arguments
+///
+/// * `registered` - Shared state containing the registered hotkeys.
+///
+/// # Returns
+///
+/// * `Result<Vec<HotkeyConflict>, String>` - The hotkeys without effect, with the shortcuts bound to them.
+#[tauri::command]
+pub fn get_hotkey_conflicts(
+    registered: State<Arc<RegisteredHotkeys>>,
+) -> Result<Vec<HotkeyConflict>, String> {
+    Ok(registered.conflicts())
+}
+
+/// Binds a global hotkey to a shortcut, replacing the hotkey it had. The
+/// hotkey is refused if it clashes with another binding, the pause hotkey or
+/// a combo the system relies on, or if the OS won't register it.
+///
+/// # Arguments
+///
+/// * `shortcut_id` - The ID of the shortcut.
+/// * `hotkey` - The hotkey, e.g. `Ctrl+Alt+1`, or `None` to unbind the shortcut.
+/// * `hotkeys` - Shared state containing the bindings.
+/// * `store` - Shared state containing the shortcuts.
+/// * `app_handle` - Handle to register the hotkey and emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn set_hotkey_binding(
+    shortcut_id: u64,
+    hotkey: Option<String>,
+    hotkeys: State<Arc<HotkeyStore>>,
+    store: State<Arc<ShortcutStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let shortcuts = store.get_shortcuts();
+    if !shortcuts.iter().any(|s| s.id == shortcut_id) {
+        return Err(format!("Shortcut with ID {} not found.", shortcut_id));
+    }
+    let hotkey = hotkey
+        .filter(|hotkey| !hotkey.trim().is_empty())
+        .map(|hotkey| normalize_hotkey(&hotkey))
+        .transpose()?;
+
+    let previous = hotkeys.get_bindings();
+    let mut bindings: Vec<HotkeyBinding> = previous
+        .iter()
+        .filter(|b| b.shortcut_id != shortcut_id)
+        .cloned()
+        .collect();
+    if let Some(hotkey) = &hotkey {
+        check_conflicts(hotkey, shortcut_id, &previous, &shortcuts)?;
+        bindings.push(HotkeyBinding {
+            hotkey: hotkey.clone(),
+            shortcut_id,
+        });
+    }
+
+    let failed = register_hotkeys(&app_handle, &bindings);
+    if let Some((hotkey, reason)) = failed
+        .iter()
+        .find(|(failed, _)| hotkey.as_ref() == Some(failed))
+    {
+        // Another app owns it or the OS doesn't know the key
+        register_hotkeys(&app_handle, &previous);
+        return Err(format!("Can't register {}: {}", hotkey, reason));
+    }
+    // Unbinding a shortcut resolves its conflict
+    record_conflicts(&app_handle, failed, &bindings, &shortcuts);
+
+    hotkeys.set_bindings(bindings)?;
+    publish(&app_handle, AppEvent::HotkeyBindingsChanged);
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::sockets::ServerContext;
+use crate::triggers::{self, TriggerError, TriggerSource};
+
+// Plain HTTP endpoints served next to the WebSocket route, for tools like curl,
+// Shortcuts.app or home automation that don't speak the WS protocol:
+//
+//   GET  /shortcuts              - list all shortcuts
+//   GET  /shortcuts/:id          - a single shortcut
+//   POST /shortcuts/:id/trigger  - run a shortcut
+//
+// Requests authenticate with `Authorization: Bearer <token>` or `?token=<token>`.
+
+fn json_error(status: StatusCode, message: impl Into<String>) -> Response {
+    let body = serde_json::json!({ "error": message.into() });
+    warp::reply::with_status(warp::reply::json(&body), status).into_response()
+}
+
+fn is_authorized(
+    header: Option<&str>,
+    query: &HashMap<String, String>,
+    ctx: &ServerContext,
+) -> bool {
+    header
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| query.get("token").map(String::as_str))
+        .is_some_and(|token| ctx.auth.verify(token.trim()))
+}
+
+pub fn routes(
+    ctx: ServerContext,
+) -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone {
+    let auth = warp::header::optional::<String>("authorization")
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::any().map(move || ctx.clone()))
+        .map(
+            |header: Option<String>, query: HashMap<String, String>, ctx: ServerContext| {
+                let authorized = is_authorized(header.as_deref(), &query, &ctx);
+                (authorized, ctx)
+            },
+        )
+        .untuple_one();
+
+    let list = warp::path!("shortcuts")
+        .and(warp::get())
+        .and(auth.clone())
+        .map(|authorized: bool, ctx: ServerContext| {
+            if !authorized {
+                return json_error(StatusCode::UNAUTHORIZED, "Invalid or missing token");
+            }
+            warp::reply::json(&ctx.store.get_shortcuts()).into_response()
+        });
+
+    let get = warp::path!("shortcuts" / u64)
+        .and(warp::get())
+        .and(auth.clone())
+        .map(|id: u64, authorized: bool, ctx: ServerContext| {
+            if !authorized {
+                return json_error(StatusCode::UNAUTHORIZED, "Invalid or missing token");
+            }
+            match ctx.store.get_shortcuts().into_iter().find(|s| s.id == id) {
+                Some(shortcut) => warp::reply::json(&shortcut).into_response(),
+                None => json_error(
+                    StatusCode::NOT_FOUND,
+                    format!("Shortcut with ID {} not found.", id),
+                ),
+            }
+        });
+
+    let trigger = warp::path!("shortcuts" / u64 / "trigger")
+        .and(warp::post())
+        .and(auth)
+        .and_then(|id: u64, authorized: bool, ctx: ServerContext| async move {
+            Ok::<_, Infallible>(trigger_shortcut(id, authorized, ctx).await)
+        });
+
+    list.or(get).unify().or(trigger).unify()
+}
+
+async fn trigger_shortcut(id: u64, authorized: bool, ctx: ServerContext) -> Response {
+    if !authorized {
+        return json_error(StatusCode::UNAUTHORIZED, "Invalid or missing token");
+    }
+    // All HTTP callers share one rate limit, since requests carry no connection identity
+    match triggers::trigger_shortcut(id, TriggerSource::Http, &ctx).await {
+        Ok(()) => warp::reply::json(&serde_json::json!({ "ok": true })).into_response(),
+        Err(e) => {
+            let status = match e {
+                TriggerError::Paused => StatusCode::SERVICE_UNAVAILABLE,
+                TriggerError::RateLimited(_) | TriggerError::Busy(_) => {
+                    StatusCode::TOO_MANY_REQUESTS
+                }
+                TriggerError::NotFound(_) => StatusCode::NOT_FOUND,
+            };
+            json_error(status, e.to_string())
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use tracing::info;
+
+use crate::actions::Action;
+use crate::keyboard::{is_text_string, Text};
+use crate::shortcuts::{add_shortcuts_to_store, ActionStep, Shortcut, ShortcutStore, Step};
+
+// Converts simple macros from other tools into shortcuts:
+//
+// - AutoHotkey: hotstrings (`::btw::by the way`) and hotkeys that only send
+//   keys, either on one line (`^!s::Send ^c`) or in a block ending in `return`.
+// - Karabiner-Elements: `to` events of complex modifications, from a rule
+//   file or a whole `karabiner.json`.
+//
+// Anything the sequence runner can't reproduce, like other AHK commands or
+// keys it doesn't know, is skipped and reported rather than imported wrongly.
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ImportResult {
+    pub imported: Vec<Shortcut>,
+    /// Hotkeys, hotstrings or rules that were left out, as written in the file.
+    pub skipped: Vec<String>,
+}
+
+/// Collects key presses into steps, merging unmodified characters into a
+/// single text step. Text a plain string step would press as a key combo,
+/// like `C++`, becomes an explicit `text` step.
+#[derive(Default)]
+struct StepBuilder {
+    steps: Vec<Step>,
+    text: String,
+}
+
+impl StepBuilder {
+    fn push_char(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    fn push_combo(&mut self, modifiers: &[&str], key: &str) {
+        self.flush_text();
+        let mut combo: Vec<&str> = modifiers.to_vec();
+        combo.push(key);
+        self.steps.push(Step::Keys(combo.join("+")));
+    }
+
+    fn flush_text(&mut self) {
+        if self.text.is_empty() {
+            return;
+        }
+        let text = std::mem::take(&mut self.text);
+        if is_text_string(&text) {
+            self.steps.push(Step::Keys(text));
+        } else {
+            let mut params = serde_json::Map::new();
+            params.insert("text".into(), Value::String(text));
+            self.steps.push(Step::Action(ActionStep {
+                kind: Text::TYPE.to_string(),
+                params,
+            }));
+        }
+    }
+
+    /// The steps, or `None` if there are none.
+    fn finish(mut self) -> Option<Vec<Step>> {
+        self.flush_text();
+        if self.steps.is_empty() {
+            return None;
+        }
+        Some(self.steps)
+    }
+}
+
+/// A named key that can be typed as a character when pressed on its own.
+fn named_key(name: &str) -> Option<(&'static str, Option<char>)> {
+    match name.to_ascii_lowercase().as_str() {
+        "enter" | "return" | "return_or_enter" => Some(("Enter", Some('\n'))),
+        "tab" => Some(("Tab", Some('\t'))),
+        "space" | "spacebar" => Some(("Space", Some(' '))),
+        "backspace" | "bs" | "delete_or_backspace" => Some(("Backspace", None)),
+        _ => None,
+    }
+}
+
+fn push_key(builder: &mut StepBuilder, modifiers: &[&str], key: &str) -> Option<()> {
+    if let Some((name, typed)) = named_key(key) {
+        match (modifiers.is_empty(), typed) {
+            (true, Some(c)) => builder.push_char(c),
+            // The runner would type the key's name instead of pressing it
+            (true, None) => return None,
+            (false, _) => builder.push_combo(modifiers, name),
+        }
+        return Some(());
+    }
+
+    let mut chars = key.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    if modifiers.is_empty() {
+        builder.push_char(c);
+    } else {
+        builder.push_combo(modifiers, &c.to_string());
+    }
+    Some(())
+}
+
+// AutoHotkey
+
+fn ahk_modifier(c: char) -> Option<&'static str> {
+    match c {
+        '^' => Some("Ctrl"),
+        '!' => Some("Alt"),
+        '+' => Some("Shift"),
+        '#' => Some("Cmd"),
+        _ => None,
+    }
+}
+
+/// Converts the keys of a `Send` command, e.g. `^c{Enter}hello`.
+fn ahk_send_steps(keys: &str, raw: bool) -> Option<Vec<Step>> {
+    let mut builder = StepBuilder::default();
+    let lower = keys.to_ascii_lowercase();
+    if raw || lower.starts_with("{raw}") || lower.starts_with("{text}") {
+        let text = if raw {
+            keys
+        } else {
+            &keys[keys.find('}')? + 1..]
+        };
+        text.chars().for_each(|c| builder.push_char(c));
+        return builder.finish();
+    }
+
+    let mut modifiers = Vec::new();
+    let mut chars = keys.chars();
+    while let Some(c) = chars.next() {
+        if let Some(modifier) = ahk_modifier(c) {
+            modifiers.push(modifier);
+            continue;
+        }
+        if c == '{' {
+            // `{{}` and `{}}` send the braces themselves
+            let mut name = String::new();
+            for c in chars.by_ref() {
+                if c == '}' && !name.is_empty() {
+                    break;
+                }
+                name.push(c);
+            }
+            // `{Enter 2}` presses the key twice
+            let (name, count) = match name.rsplit_once(' ') {
+                Some((name, count)) => (name.to_string(), count.parse::<usize>().ok()?),
+                None => (name, 1),
+            };
+            for _ in 0..count {
+                push_key(&mut builder, &modifiers, &name)?;
+            }
+        } else {
+            push_key(&mut builder, &modifiers, &c.to_string())?;
+        }
+        modifiers.clear();
+    }
+    builder.finish()
+}
+
+/// Parses `Send ^c`, `SendInput, ^c`, `SendRaw text` or v2's `Send("^c")`.
+fn ahk_send_command(line: &str) -> Option<Vec<Step>> {
+    let end = line.find([' ', ',', '(']).unwrap_or(line.len());
+    let raw = match line[..end].to_ascii_lowercase().as_str() {
+        "send" | "sendinput" | "sendplay" | "sendevent" => false,
+        "sendraw" => true,
+        _ => return None,
+    };
+    let mut keys = line[end..].trim_start_matches([' ', ',']).trim();
+    if let Some(inner) = keys.strip_prefix('(').and_then(|k| k.strip_suffix(')')) {
+        keys = inner.trim();
+    }
+    if let Some(inner) = keys.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        keys = inner;
+    }
+    ahk_send_steps(keys, raw)
+}
+
+/// A readable name for a hotkey like `~^!s`, e.g. `Ctrl+Alt+s`.
+fn ahk_hotkey_name(hotkey: &str) -> String {
+    let hotkey = hotkey.trim_start_matches(['~', '*', '$']);
+    let key_start = hotkey
+        .char_indices()
+        .find(|(_, c)| ahk_modifier(*c).is_none())
+        .map_or(hotkey.len(), |(i, _)| i);
+    let mut parts: Vec<&str> = hotkey[..key_start]
+        .chars()
+        .filter_map(ahk_modifier)
+        .collect();
+    parts.push(&hotkey[key_start..]);
+    parts.join("+")
+}
+
+fn strip_ahk_comment(line: &str) -> &str {
+    match line.find(" ;") {
+        Some(i) => line[..i].trim_end(),
+        None => line,
+    }
+}
+
+pub fn parse_ahk(source: &str) -> ImportResult {
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    let mut lines = source.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        let line = strip_ahk_comment(line);
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        // Hotstring: `:options:abbreviation::replacement`
+        if let Some(rest) = line.strip_prefix(':') {
+            let parsed = rest.split_once(':').and_then(|(options, rest)| {
+                let (abbreviation, replacement) = rest.split_once("::")?;
+                let options = options.to_ascii_uppercase();
+                // X runs code instead of sending text
+                if options.contains('X') || replacement.is_empty() {
+                    return None;
+                }
+                // Most replacements are plain text, so `::c++::C++` types
+                // `C++`; only ones naming keys in braces are read like `Send`
+                let raw =
+                    options.contains('R') || options.contains('T') || !replacement.contains('{');
+                Some((abbreviation, ahk_send_steps(replacement, raw)?))
+            });
+            match parsed {
+                Some((abbreviation, sequence)) => {
+                    imported.push(imported_shortcut(abbreviation, sequence, "ahk"))
+                }
+                None => skipped.push(line.to_string()),
+            }
+            continue;
+        }
+
+        // Hotkey: `keys::action`, or `keys::` followed by a block ending in `return`
+        let Some((hotkey, action)) = line.split_once("::") else {
+            continue;
+        };
+        let sequence = if action.trim().is_empty() {
+            let mut steps = Some(Vec::new());
+            for line in lines.by_ref() {
+                let line = strip_ahk_comment(line);
+                if line.eq_ignore_ascii_case("return") {
+                    break;
+                }
+                if line.is_empty() || line.starts_with(';') {
+                    continue;
+                }
+                steps = steps.and_then(|mut steps: Vec<Step>| {
+                    steps.extend(ahk_send_command(line)?);
+                    Some(steps)
+                });
+            }
+            steps.filter(|steps| !steps.is_empty())
+        } else {
+            ahk_send_command(action.trim())
+        };
+        match sequence {
+            Some(sequence) => {
+                imported.push(imported_shortcut(&ahk_hotkey_name(hotkey), sequence, "ahk"))
+            }
+            None => skipped.push(line.to_string()),
+        }
+    }
+
+    ImportResult { imported, skipped }
+}
+
+// Karabiner-Elements
+
+fn karabiner_modifier(name: &str) -> Option<&'static str> {
+    let name = name
+        .trim_start_matches("left_")
+        .trim_start_matches("right_");
+    match name {
+        "command" => Some("Cmd"),
+        "control" => Some("Ctrl"),
+        "option" => Some("Alt"),
+        "shift" => Some("Shift"),
+        _ => None,
+    }
+}
+
+fn karabiner_key(key_code: &str) -> &str {
+    match key_code {
+        "hyphen" => "-",
+        "equal_sign" => "=",
+        "open_bracket" => "[",
+        "close_bracket" => "]",
+        "backslash" => "\\",
+        "semicolon" => ";",
+        "quote" => "'",
+        "comma" => ",",
+        "period" => ".",
+        "slash" => "/",
+        "grave_accent_and_tilde" => "`",
+        other => other,
+    }
+}
+
+/// Converts a manipulator's `to` events, which must all be plain key presses.
+fn karabiner_steps(to: &Value) -> Option<Vec<Step>> {
+    let mut builder = StepBuilder::default();
+    for event in to.as_array()? {
+        let key = karabiner_key(event.get("key_code")?.as_str()?);
+        let modifiers: Vec<&str> = match event.get("modifiers") {
+            Some(Value::String(modifier)) => vec![karabiner_modifier(modifier)?],
+            Some(Value::Array(list)) => list
+                .iter()
+                .map(|modifier| karabiner_modifier(modifier.as_str()?))
+                .collect::<Option<_>>()?,
+            _ => Vec::new(),
+        };
+        push_key(&mut builder, &modifiers, key)?;
+    }
+    builder.finish()
+}
+
+pub fn parse_karabiner(source: &str) -> Result<ImportResult, String> {
+    let config: Value =
+        serde_json::from_str(source).map_err(|e| format!("Invalid Karabiner file: {}", e))?;
+
+    // A rule file has `rules` at the top; karabiner.json nests them per profile
+    let mut rules: Vec<&Value> = config["rules"].as_array().into_iter().flatten().collect();
+    for profile in config["profiles"].as_array().into_iter().flatten() {
+        rules.extend(
+            profile["complex_modifications"]["rules"]
+                .as_array()
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    for rule in rules {
+        let description = rule["description"].as_str().unwrap_or("Karabiner rule");
+        let manipulators = rule["manipulators"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+        for (i, manipulator) in manipulators.iter().enumerate() {
+            let name = if manipulators.len() > 1 {
+                format!("{} ({})", description, i + 1)
+            } else {
+                description.to_string()
+            };
+            match karabiner_steps(&manipulator["to"]) {
+                Some(sequence) => imported.push(imported_shortcut(&name, sequence, "karabiner")),
+                None => skipped.push(name),
+            }
+        }
+    }
+
+    Ok(ImportResult { imported, skipped })
+}
+
+fn imported_shortcut(name: &str, sequence: Vec<Step>, source: &str) -> Shortcut {
+    Shortcut {
+        id: 0,
+        name: name.to_string(),
+        sequence,
+        long_press_sequence: None,
+        double_tap_sequence: None,
+        cycle: vec![],
+        interval_ms: None,
+        chars_per_second: None,
+        group: None,
+        tags: vec![source.to_string()],
+        game_mode: false,
+        control: None,
+        state_source: None,
+    }
+}
+
+// Import-related Tauri commands
+
+/// Imports the macros of an AutoHotkey script (`.ahk`) or a Karabiner-Elements
+/// configuration (`.json`) as new shortcuts.
+///
+/// # Arguments
+///
+/// * `path` - The file to import.
+/// * `store` - Shared state containing the shortcuts.
+/// * `app_handle` - Handle to the app, used to check the imported steps.
+///
+/// # Returns
+///
+/// * `Result<ImportResult, String>` - The added shortcuts and what was skipped, or an error message.
+#[tauri::command]
+pub fn import_shortcuts(
+    path: String,
+    store: State<Arc<ShortcutStore>>,
+    app_handle: AppHandle,
+) -> Result<ImportResult, String> {
+    let source =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let extension = Path::new(&path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let result = match extension.as_deref() {
+        Some("ahk") => parse_ahk(&source),
+        Some("json") => parse_karabiner(&source)?,
+        _ => return Err("Only .ahk and Karabiner .json files can be imported".into()),
+    };
+
+    let imported = add_shortcuts_to_store(result.imported, &store, &app_handle)?;
+    info!(
+        "Imported {} shortcuts from {}, skipped {}",
+        imported.len(),
+        path,
+        result.skipped.len()
+    );
+    Ok(ImportResult {
+        imported,
+        skipped: result.skipped,
+    })
+}
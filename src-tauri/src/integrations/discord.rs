@@ -0,0 +1,502 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tracing::info;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::secrets::{delete_secret, read_secret, store_secret};
+use crate::settings::SettingsStore;
+
+// Talks to the Discord desktop client over its local RPC socket, which
+// avoids Discord's global hotkeys clashing with games.
+//
+// Rich presence only needs the ID of a Discord application the user creates
+// in the developer portal. Voice control also needs the application's client
+// secret: the first voice step makes Discord ask the user to authorize it,
+// and the resulting token is kept in the OS keychain for later runs.
+//
+// The connection stays open because Discord clears the presence as soon as
+// the connection that set it closes.
+
+/// Keychain entry holding the application's client secret.
+const CLIENT_SECRET_ID: &str = "discord-client-secret";
+/// Keychain entry holding the access token from the last authorization.
+const ACCESS_TOKEN_ID: &str = "discord-access-token";
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+const OP_CLOSE: u32 = 2;
+
+/// Discord listens on the first free one of ten sockets.
+const IPC_SOCKETS: u32 = 10;
+
+/// How long Discord may take to answer a command.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the user has to approve an authorization in Discord.
+const AUTHORIZE_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DiscordSettings {
+    /// ID of the Discord application shown as the presence; the client
+    /// secret is kept in the OS keychain.
+    pub client_id: Option<String>,
+}
+
+trait IpcStream: Read + Write + Send {
+    /// Limits how long a read or write may block.
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()>;
+}
+
+#[cfg(unix)]
+impl IpcStream for std::os::unix::net::UnixStream {
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.set_read_timeout(Some(timeout))?;
+        self.set_write_timeout(Some(timeout))
+    }
+}
+
+#[cfg(unix)]
+fn open_socket(index: u32) -> std::io::Result<Box<dyn IpcStream>> {
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok())
+        .unwrap_or_else(|| "/tmp".to_string());
+    let path = std::path::Path::new(&dir).join(format!("discord-ipc-{}", index));
+    Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?))
+}
+
+/// A named pipe client. Pipes opened as files have no timeouts, so reads
+/// wait for data to arrive by peeking at the pipe first.
+#[cfg(windows)]
+struct Pipe {
+    file: std::fs::File,
+    timeout: Duration,
+}
+
+#[cfg(windows)]
+impl Pipe {
+    fn available(&self) -> std::io::Result<u32> {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::System::Pipes::PeekNamedPipe;
+
+        let mut available = 0;
+        let ok = unsafe {
+            PeekNamedPipe(
+                HANDLE(self.file.as_raw_handle() as isize),
+                std::ptr::null_mut(),
+                0,
+                std::ptr::null_mut(),
+                &mut available,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok.as_bool() {
+            Ok(available)
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let deadline = std::time::Instant::now() + self.timeout;
+        while self.available()? == 0 {
+            if std::time::Instant::now() >= deadline {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.file.read(buf)
+    }
+}
+
+#[cfg(windows)]
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(windows)]
+impl IpcStream for Pipe {
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+fn open_socket(index: u32) -> std::io::Result<Box<dyn IpcStream>> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(format!(r"\\.\pipe\discord-ipc-{}", index))?;
+    Ok(Box::new(Pipe {
+        file,
+        timeout: IO_TIMEOUT,
+    }))
+}
+
+struct DiscordConnection {
+    stream: Box<dyn IpcStream>,
+    client_id: String,
+    authenticated: bool,
+}
+
+impl DiscordConnection {
+    fn open(client_id: &str) -> Result<Self, String> {
+        let mut stream = (0..IPC_SOCKETS)
+            .find_map(|index| open_socket(index).ok())
+            .ok_or("Discord isn't running")?;
+        stream
+            .set_timeout(IO_TIMEOUT)
+            .map_err(|e| format!("Failed to connect to Discord: {}", e))?;
+        let mut connection = Self {
+            stream,
+            client_id: client_id.to_string(),
+            authenticated: false,
+        };
+        connection.send(OP_HANDSHAKE, &json!({ "v": 1, "client_id": client_id }))?;
+        // The READY event
+        connection.receive()?;
+        Ok(connection)
+    }
+
+    fn send(&mut self, op: u32, payload: &Value) -> Result<(), String> {
+        let body = payload.to_string();
+        let mut frame = Vec::with_capacity(8 + body.len());
+        frame.extend_from_slice(&op.to_le_bytes());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(body.as_bytes());
+        self.stream
+            .write_all(&frame)
+            .map_err(|e| format!("Failed to send to Discord: {}", e))
+    }
+
+    fn receive(&mut self) -> Result<Value, String> {
+        let lost = |e: std::io::Error| format!("Lost connection to Discord: {}", e);
+        let mut header = [0u8; 8];
+        self.stream.read_exact(&mut header).map_err(lost)?;
+        let op = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let mut body = vec![0u8; len as usize];
+        self.stream.read_exact(&mut body).map_err(lost)?;
+        let payload: Value = serde_json::from_slice(&body)
+            .map_err(|e| format!("Invalid message from Discord: {}", e))?;
+        if op == OP_CLOSE {
+            return Err(format!(
+                "Discord closed the connection: {}",
+                payload["message"].as_str().unwrap_or("unknown reason")
+            ));
+        }
+        Ok(payload)
+    }
+
+    /// Sends an RPC command and returns its `data`, or Discord's reason for
+    /// rejecting it.
+    fn command(&mut self, command: &str, args: Value) -> Result<Value, String> {
+        self.command_within(command, args, IO_TIMEOUT)
+    }
+
+    /// Like `command`, waiting up to `timeout` for the answer, e.g. for
+    /// commands Discord answers once the user made a choice.
+    fn command_within(
+        &mut self,
+        command: &str,
+        args: Value,
+        timeout: Duration,
+    ) -> Result<Value, String> {
+        self.stream
+            .set_timeout(timeout)
+            .map_err(|e| format!("Lost connection to Discord: {}", e))?;
+        let nonce = uuid::Uuid::new_v4().to_string();
+        self.send(
+            OP_FRAME,
+            &json!({ "cmd": command, "args": args, "nonce": nonce }),
+        )?;
+        loop {
+            let mut response = self.receive()?;
+            // Skip events Discord pushes in between
+            if response["nonce"] != nonce.as_str() {
+                continue;
+            }
+            if response["evt"] == "ERROR" {
+                return Err(format!(
+                    "Discord rejected {}: {}",
+                    command,
+                    response["data"]["message"]
+                        .as_str()
+                        .unwrap_or("unknown error")
+                ));
+            }
+            return Ok(response["data"].take());
+        }
+    }
+
+    /// Authenticates with the token of an earlier authorization, never asking
+    /// the user. Returns whether the connection is authenticated.
+    fn authenticate_with_stored_token(&mut self) -> bool {
+        if !self.authenticated {
+            if let Ok(token) = read_secret(ACCESS_TOKEN_ID) {
+                self.authenticated = self
+                    .command("AUTHENTICATE", json!({ "access_token": token }))
+                    .is_ok();
+            }
+        }
+        self.authenticated
+    }
+
+    /// Authenticates with the stored token, asking the user to authorize the
+    /// application again when there is none or it has expired.
+    fn authenticate(&mut self) -> Result<(), String> {
+        if self.authenticate_with_stored_token() {
+            return Ok(());
+        }
+
+        let client_secret = read_secret(CLIENT_SECRET_ID)
+            .map_err(|_| "Voice control needs the Discord client secret".to_string())?;
+        info!("Asking Discord to authorize voice control");
+        let code = self.command_within(
+            "AUTHORIZE",
+            json!({
+                "client_id": self.client_id,
+                "scopes": ["rpc", "rpc.voice.read", "rpc.voice.write"],
+            }),
+            AUTHORIZE_TIMEOUT,
+        )?["code"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or("Discord didn't return an authorization code")?;
+        let token = exchange_code(&self.client_id, &client_secret, &code)?;
+        store_secret(ACCESS_TOKEN_ID, &token)?;
+        self.command("AUTHENTICATE", json!({ "access_token": token }))?;
+        self.authenticated = true;
+        Ok(())
+    }
+}
+
+/// Trades an authorization code for an access token.
+fn exchange_code(client_id: &str, client_secret: &str, code: &str) -> Result<String, String> {
+    let response: Value = Client::new()
+        .post("https://discord.com/api/oauth2/token")
+        .form(&[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("grant_type", "authorization_code"),
+            ("code", code),
+        ])
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .map_err(|e| format!("Failed to get a Discord access token: {}", e))
+        .and_then(|body| serde_json::from_str(&body).map_err(|e| e.to_string()))?;
+    response["access_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Discord didn't return an access token".to_string())
+}
+
+/// The open connection to Discord, if any.
+pub struct DiscordClient {
+    connection: Mutex<Option<DiscordConnection>>,
+}
+
+impl DiscordClient {
+    pub fn new() -> Self {
+        Self {
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Runs `f` on the connection, opening it first if needed. A connection
+    /// that failed is dropped, so the next step reconnects.
+    fn with_connection<T>(
+        &self,
+        settings: &DiscordSettings,
+        f: impl FnOnce(&mut DiscordConnection) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let client_id = settings
+            .client_id
+            .as_deref()
+            .ok_or("No Discord application ID is set")?;
+        let mut connection = self.connection.lock().unwrap();
+        if connection
+            .as_ref()
+            .is_some_and(|connection| connection.client_id != client_id)
+        {
+            *connection = None;
+        }
+        let open = match connection.as_mut() {
+            Some(open) => open,
+            None => connection.insert(DiscordConnection::open(client_id)?),
+        };
+        let result = f(open);
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+
+    pub fn disconnect(&self) {
+        *self.connection.lock().unwrap() = None;
+    }
+
+    /// Shows `details` and `state` as the user's activity; clears it when
+    /// both are unset.
+    pub fn set_activity(
+        &self,
+        settings: &DiscordSettings,
+        details: Option<&str>,
+        state: Option<&str>,
+    ) -> Result<(), String> {
+        let activity = (details.is_some() || state.is_some()).then(|| {
+            json!({
+                "details": details,
+                "state": state,
+                "timestamps": { "start": crate::devices::now_millis() / 1000 },
+            })
+        });
+        self.with_connection(settings, |connection| {
+            connection.command(
+                "SET_ACTIVITY",
+                json!({ "pid": std::process::id(), "activity": activity }),
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Reads `mute` or `deaf` from the voice settings. Unlike the voice steps
+    /// this never asks the user to authorize the app, so it can be polled.
+    pub fn voice_setting(&self, settings: &DiscordSettings, key: &str) -> Result<bool, String> {
+        self.with_connection(settings, |connection| {
+            if !connection.authenticate_with_stored_token() {
+                return Err("Discord voice control hasn't been authorized yet".to_string());
+            }
+            Ok(connection.command("GET_VOICE_SETTINGS", json!({}))?[key]
+                .as_bool()
+                .unwrap_or(false))
+        })
+    }
+
+    /// Flips `mute` or `deaf` in the voice settings.
+    pub fn toggle_voice_setting(
+        &self,
+        settings: &DiscordSettings,
+        key: &str,
+    ) -> Result<(), String> {
+        self.with_connection(settings, |connection| {
+            connection.authenticate()?;
+            let current = connection.command("GET_VOICE_SETTINGS", json!({}))?[key]
+                .as_bool()
+                .unwrap_or(false);
+            connection.command("SET_VOICE_SETTINGS", json!({ key: !current }))
+        })
+        .map(|_| ())
+    }
+}
+
+impl Default for DiscordClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn client(ctx: &ActionContext) -> Arc<DiscordClient> {
+    Arc::clone(&ctx.app_handle.state::<Arc<DiscordClient>>())
+}
+
+/// Shows what the user is doing on their Discord profile; clears it when
+/// both are unset.
+#[derive(Deserialize)]
+struct DiscordSetActivity {
+    details: Option<String>,
+    state: Option<String>,
+}
+
+impl Action for DiscordSetActivity {
+    const TYPE: &'static str = "discord_set_activity";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        client(ctx).set_activity(
+            &ctx.settings().discord,
+            self.details.as_deref(),
+            self.state.as_deref(),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct DiscordToggleMute {}
+
+impl Action for DiscordToggleMute {
+    const TYPE: &'static str = "discord_toggle_mute";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        client(ctx).toggle_voice_setting(&ctx.settings().discord, "mute")
+    }
+}
+
+#[derive(Deserialize)]
+struct DiscordToggleDeafen {}
+
+impl Action for DiscordToggleDeafen {
+    const TYPE: &'static str = "discord_toggle_deafen";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        client(ctx).toggle_voice_setting(&ctx.settings().discord, "deaf")
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<DiscordSetActivity>();
+    registry.register::<DiscordToggleMute>();
+    registry.register::<DiscordToggleDeafen>();
+}
+
+// Discord-related Tauri commands
+
+/// Sets the Discord application used for presence and voice control.
+///
+/// # Arguments
+///
+/// * `client_id` - The application ID, or `None` to turn the integration off.
+/// * `client_secret` - A new client secret; empty to remove it, `None` to keep it.
+/// * `settings` - Shared state containing the settings.
+/// * `discord` - The Discord connection, closed so the next step uses the new application.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn set_discord_app(
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    settings: State<Arc<SettingsStore>>,
+    discord: State<Arc<DiscordClient>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    match client_secret.as_deref() {
+        Some("") => delete_secret(CLIENT_SECRET_ID),
+        Some(secret) => store_secret(CLIENT_SECRET_ID, secret)?,
+        None => {}
+    }
+    // A token from another application is useless
+    if client_secret.is_some() || client_id != settings.get_settings().discord.client_id {
+        delete_secret(ACCESS_TOKEN_ID);
+    }
+    discord.disconnect();
+    settings.update(&app_handle, |current| current.discord.client_id = client_id)?;
+    Ok(())
+}
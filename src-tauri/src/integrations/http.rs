@@ -0,0 +1,136 @@
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::devices::now_millis;
+
+// Sends the request of an `http_request` step, so a button can call web APIs
+// such as smart light bridges. The URL, header values and body may contain
+// placeholders:
+//
+//   {{timestamp}}  - milliseconds since the Unix epoch
+//   {{hostname}}   - name of this computer
+//
+// Environment variables are deliberately not available, as any paired device
+// allowed to edit shortcuts could send them to a server of its choosing.
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn variable(name: &str) -> Option<String> {
+    match name {
+        "timestamp" => Some(now_millis().to_string()),
+        "hostname" => hostname::get()
+            .ok()
+            .and_then(|name| name.into_string().ok()),
+        _ => None,
+    }
+}
+
+/// Replaces `{{name}}` placeholders; unknown ones are left as written.
+pub fn substitute(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                match variable(name) {
+                    Some(value) => result.push_str(&value),
+                    None => result.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Sends the request and fails on anything but a 2xx response.
+pub fn send_request(
+    method: &str,
+    url: &str,
+    headers: &BTreeMap<String, String>,
+    body: Option<&str>,
+) -> Result<(), String> {
+    let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("Invalid HTTP method: {}", method))?;
+    let url = substitute(url);
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut request = client.request(method.clone(), &url);
+    for (name, value) in headers {
+        request = request.header(name, substitute(value));
+    }
+    if let Some(body) = body {
+        request = request.body(substitute(body));
+    }
+
+    let response = request
+        .send()
+        .map_err(|e| format!("{} {} failed: {}", method, url, e))?;
+    debug!("{} {} returned {}", method, url, response.status());
+    if !response.status().is_success() {
+        return Err(format!("{} {} returned {}", method, url, response.status()));
+    }
+    Ok(())
+}
+
+/// Fetches `url` and returns the response body, failing on anything but a
+/// 2xx response.
+pub fn get_text(url: &str) -> Result<String, String> {
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .send()
+        .map_err(|e| format!("GET {} failed: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("GET {} returned {}", url, response.status()));
+    }
+    response.text().map_err(|e| e.to_string())
+}
+
+/// Calls a web API. The URL, header values and body may use the placeholders
+/// listed above.
+#[derive(Deserialize)]
+struct HttpRequest {
+    /// `GET` when unset.
+    method: Option<String>,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+}
+
+impl Action for HttpRequest {
+    const TYPE: &'static str = "http_request";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        send_request(
+            self.method.as_deref().unwrap_or("GET"),
+            &self.url,
+            &self.headers,
+            self.body.as_deref(),
+        )
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<HttpRequest>();
+}
@@ -0,0 +1,263 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tracing::info;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::secrets::{read_secret, store_secret};
+use crate::settings::SettingsStore;
+
+// Switches Philips Hue lights through the bridge's local REST API, e.g. to
+// turn an on-air light red from a "recording" button.
+//
+// Pairing needs the link button on the bridge pressed shortly before; the
+// key the bridge hands out is kept in the OS keychain.
+
+/// Keychain entry holding the key the bridge issued when pairing.
+const APP_KEY_SECRET_ID: &str = "hue-app-key";
+
+/// Lists the bridges on the local network, by their Hue account.
+const DISCOVERY_URL: &str = "https://discovery.meethue.com";
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HueSettings {
+    /// Address of the paired bridge.
+    pub bridge_ip: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HueLight {
+    pub id: String,
+    pub name: String,
+    pub on: bool,
+}
+
+fn client() -> Result<Client, String> {
+    Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Sends a request to the bridge. The bridge answers with HTTP 200 even on
+/// failure, listing errors in the body instead.
+fn call(method: reqwest::Method, url: &str, body: Option<Value>) -> Result<Value, String> {
+    let mut request = client()?.request(method, url);
+    if let Some(body) = body {
+        request = request.body(body.to_string());
+    }
+    let response: Value = request
+        .send()
+        .and_then(|response| response.text())
+        .map_err(|e| format!("Failed to reach the Hue bridge: {}", e))
+        .and_then(|body| serde_json::from_str(&body).map_err(|e| e.to_string()))?;
+
+    let error = response
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find_map(|item| item["error"]["description"].as_str());
+    match error {
+        Some(error) => Err(format!("The Hue bridge refused: {}", error)),
+        None => Ok(response),
+    }
+}
+
+fn discover_bridge() -> Result<String, String> {
+    let bridges: Value = client()?
+        .get(DISCOVERY_URL)
+        .send()
+        .and_then(|response| response.text())
+        .map_err(|e| format!("Failed to look for Hue bridges: {}", e))
+        .and_then(|body| serde_json::from_str(&body).map_err(|e| e.to_string()))?;
+    bridges[0]["internalipaddress"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "No Hue bridge found on this network".to_string())
+}
+
+/// Base URL of the API for the paired bridge.
+fn api_url(settings: &HueSettings) -> Result<String, String> {
+    let bridge_ip = settings
+        .bridge_ip
+        .as_deref()
+        .ok_or("No Hue bridge is paired")?;
+    let key = read_secret(APP_KEY_SECRET_ID).map_err(|_| "No Hue bridge is paired".to_string())?;
+    Ok(format!("http://{}/api/{}", bridge_ip, key))
+}
+
+/// Converts `#rrggbb` to the CIE xy coordinates the bridge takes, with
+/// Philips' recommended gamma correction.
+fn hex_to_xy(color: &str) -> Result<[f64; 2], String> {
+    let hex = color.trim_start_matches('#');
+    let channel = |i: usize| -> Result<f64, String> {
+        let value = hex
+            .get(i..i + 2)
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(|| format!("Invalid color {}, expected #rrggbb", color))?;
+        let value = value as f64 / 255.0;
+        Ok(if value > 0.04045 {
+            ((value + 0.055) / 1.055).powf(2.4)
+        } else {
+            value / 12.92
+        })
+    };
+    if hex.len() != 6 {
+        return Err(format!("Invalid color {}, expected #rrggbb", color));
+    }
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+    let x = r * 0.664511 + g * 0.154324 + b * 0.162028;
+    let y = r * 0.283881 + g * 0.668433 + b * 0.047685;
+    let z = r * 0.000088 + g * 0.072310 + b * 0.986039;
+    let sum = x + y + z;
+    if sum == 0.0 {
+        return Ok([0.0, 0.0]);
+    }
+    Ok([x / sum, y / sum])
+}
+
+/// Changes a light; unset fields are left as they are. `brightness` is a
+/// percentage.
+pub fn set_light(
+    settings: &HueSettings,
+    light: &str,
+    on: Option<bool>,
+    brightness: Option<u8>,
+    color: Option<&str>,
+) -> Result<(), String> {
+    // IDs are numbers; anything else would change the path of the request
+    if light.is_empty() || !light.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid Hue light ID: {}", light));
+    }
+    let mut state = Map::new();
+    if let Some(on) = on {
+        state.insert("on".into(), json!(on));
+    }
+    if let Some(brightness) = brightness {
+        // The bridge's scale is 1-254
+        let bri = (brightness.min(100) as f64 * 2.54).round().max(1.0) as u8;
+        state.insert("bri".into(), json!(bri));
+    }
+    if let Some(color) = color {
+        state.insert("xy".into(), json!(hex_to_xy(color)?));
+    }
+    let url = format!("{}/lights/{}/state", api_url(settings)?, light);
+    call(reqwest::Method::PUT, &url, Some(Value::Object(state))).map(|_| ())
+}
+
+/// Changes a light on the paired Hue bridge; unset fields are left as they
+/// are.
+#[derive(Deserialize)]
+struct HueSetLight {
+    light: String,
+    on: Option<bool>,
+    /// Percentage.
+    brightness: Option<u8>,
+    /// `#rrggbb`.
+    color: Option<String>,
+}
+
+impl Action for HueSetLight {
+    const TYPE: &'static str = "hue_set_light";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        set_light(
+            &ctx.settings().hue,
+            &self.light,
+            self.on,
+            self.brightness,
+            self.color.as_deref(),
+        )
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<HueSetLight>();
+}
+
+// Hue-related Tauri commands
+
+/// Pairs with a Hue bridge. The link button on the bridge has to be pressed
+/// first; until then this fails with the bridge's reason.
+///
+/// # Arguments
+///
+/// * `bridge_ip` - The bridge's address, or `None` to look it up online.
+/// * `settings` - Shared state containing the settings.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<String, String>` - The address of the paired bridge, or an error message.
+#[tauri::command]
+pub async fn pair_hue_bridge(
+    bridge_ip: Option<String>,
+    settings: State<'_, Arc<SettingsStore>>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let (bridge_ip, key) = tokio::task::spawn_blocking(move || {
+        let bridge_ip = match bridge_ip {
+            Some(bridge_ip) => bridge_ip,
+            None => discover_bridge()?,
+        };
+        let response = call(
+            reqwest::Method::POST,
+            &format!("http://{}/api", bridge_ip),
+            Some(json!({ "devicetype": "button_beam#desktop" })),
+        )?;
+        let key = response[0]["success"]["username"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or("The Hue bridge didn't return a key")?;
+        Ok::<_, String>((bridge_ip, key))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    store_secret(APP_KEY_SECRET_ID, &key)?;
+    info!("Paired with the Hue bridge at {}", bridge_ip);
+    settings.update(&app_handle, |current| {
+        current.hue.bridge_ip = Some(bridge_ip.clone())
+    })?;
+    Ok(bridge_ip)
+}
+
+/// Lists the lights on the paired bridge, for picking one in a step.
+///
+/// # Arguments
+///
+/// * `settings` - Shared state containing the settings.
+///
+/// # Returns
+///
+/// * `Result<Vec<HueLight>, String>` - The lights or an error message.
+#[tauri::command]
+pub async fn list_hue_lights(
+    settings: State<'_, Arc<SettingsStore>>,
+) -> Result<Vec<HueLight>, String> {
+    let hue = settings.get_settings().hue;
+    tokio::task::spawn_blocking(move || {
+        let lights = call(
+            reqwest::Method::GET,
+            &format!("{}/lights", api_url(&hue)?),
+            None,
+        )?;
+        Ok(lights
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(id, light)| HueLight {
+                id: id.clone(),
+                name: light["name"].as_str().unwrap_or(id).to_string(),
+                on: light["state"]["on"].as_bool().unwrap_or(false),
+            })
+            .collect())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
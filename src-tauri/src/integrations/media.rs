@@ -0,0 +1,322 @@
+use button_beam_core::protocol::CAP_NOW_PLAYING;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::error::emit;
+use crate::sockets::ServerContext;
+
+// Controls whichever media player the OS considers active, through MPRIS on
+// Linux and the System Media Transport Controls on Windows. macOS has no
+// public API for this, so Spotify and Music are scripted directly.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaAction {
+    PlayPause,
+    Play,
+    Pause,
+    Next,
+    Previous,
+}
+
+/// The track the active player is on.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct NowPlaying {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub playing: bool,
+    pub position_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+}
+
+impl NowPlaying {
+    /// Whether devices need an update; the position moves on its own while
+    /// playing, so only jumps count.
+    fn differs_from(&self, previous: &NowPlaying, elapsed: Duration) -> bool {
+        let expected = previous.position_ms.map(|ms| {
+            ms + if previous.playing {
+                elapsed.as_millis() as u64
+            } else {
+                0
+            }
+        });
+        let jumped = match (self.position_ms, expected) {
+            (Some(position), Some(expected)) => position.abs_diff(expected) > 3000,
+            (position, expected) => position.is_some() != expected.is_some(),
+        };
+        self.title != previous.title
+            || self.artist != previous.artist
+            || self.album != previous.album
+            || self.playing != previous.playing
+            || self.duration_ms != previous.duration_ms
+            || jumped
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{MediaAction, NowPlaying};
+    use mpris::{PlaybackStatus, Player, PlayerFinder};
+    use std::time::Duration;
+
+    fn player() -> Result<Player, String> {
+        PlayerFinder::new()
+            .map_err(|e| e.to_string())?
+            .find_active()
+            .map_err(|e| format!("No media player found: {}", e))
+    }
+
+    pub fn now_playing() -> Result<Option<NowPlaying>, String> {
+        let Ok(player) = player() else {
+            return Ok(None);
+        };
+        let metadata = player.get_metadata().map_err(|e| e.to_string())?;
+        Ok(Some(NowPlaying {
+            title: metadata.title().map(str::to_string),
+            artist: metadata.artists().map(|artists| artists.join(", ")),
+            album: metadata.album_name().map(str::to_string),
+            playing: player.get_playback_status().ok() == Some(PlaybackStatus::Playing),
+            position_ms: player.get_position().ok().map(|p| p.as_millis() as u64),
+            duration_ms: metadata.length().map(|d| d.as_millis() as u64),
+        }))
+    }
+
+    pub fn control(action: MediaAction) -> Result<(), String> {
+        let player = player()?;
+        match action {
+            MediaAction::PlayPause => player.play_pause(),
+            MediaAction::Play => player.play(),
+            MediaAction::Pause => player.pause(),
+            MediaAction::Next => player.next(),
+            MediaAction::Previous => player.previous(),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn seek(position_ms: u64) -> Result<(), String> {
+        let player = player()?;
+        let track_id = player
+            .get_metadata()
+            .map_err(|e| e.to_string())?
+            .track_id()
+            .ok_or("The player doesn't support seeking")?;
+        player
+            .set_position(track_id, &Duration::from_millis(position_ms))
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{MediaAction, NowPlaying};
+    use windows::Media::Control::{
+        GlobalSystemMediaTransportControlsSession as Session,
+        GlobalSystemMediaTransportControlsSessionManager as SessionManager,
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus as PlaybackStatus,
+    };
+
+    /// Timeline positions are in 100ns ticks.
+    const TICKS_PER_MS: i64 = 10_000;
+
+    fn session() -> windows::core::Result<Session> {
+        SessionManager::RequestAsync()?.get()?.GetCurrentSession()
+    }
+
+    pub fn now_playing() -> Result<Option<NowPlaying>, String> {
+        let Ok(session) = session() else {
+            return Ok(None);
+        };
+        let read = || -> windows::core::Result<NowPlaying> {
+            let properties = session.TryGetMediaPropertiesAsync()?.get()?;
+            let timeline = session.GetTimelineProperties()?;
+            let non_empty = |text: String| Some(text).filter(|text| !text.is_empty());
+            Ok(NowPlaying {
+                title: non_empty(properties.Title()?.to_string()),
+                artist: non_empty(properties.Artist()?.to_string()),
+                album: non_empty(properties.AlbumTitle()?.to_string()),
+                playing: session.GetPlaybackInfo()?.PlaybackStatus()? == PlaybackStatus::Playing,
+                position_ms: Some((timeline.Position()?.Duration / TICKS_PER_MS) as u64),
+                duration_ms: Some((timeline.EndTime()?.Duration / TICKS_PER_MS) as u64),
+            })
+        };
+        read().map(Some).map_err(|e| e.to_string())
+    }
+
+    fn accepted(result: windows::core::Result<bool>) -> Result<(), String> {
+        match result {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("The media player ignored the request".into()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn control(action: MediaAction) -> Result<(), String> {
+        let session = session().map_err(|_| "No media player found".to_string())?;
+        accepted(
+            match action {
+                MediaAction::PlayPause => session.TryTogglePlayPauseAsync(),
+                MediaAction::Play => session.TryPlayAsync(),
+                MediaAction::Pause => session.TryPauseAsync(),
+                MediaAction::Next => session.TrySkipNextAsync(),
+                MediaAction::Previous => session.TrySkipPreviousAsync(),
+            }
+            .and_then(|operation| operation.get()),
+        )
+    }
+
+    pub fn seek(position_ms: u64) -> Result<(), String> {
+        let session = session().map_err(|_| "No media player found".to_string())?;
+        accepted(
+            session
+                .TryChangePlaybackPositionAsync(position_ms as i64 * TICKS_PER_MS)
+                .and_then(|operation| operation.get()),
+        )
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    use super::{MediaAction, NowPlaying};
+    use crate::system::run_command;
+
+    const PLAYERS: [&str; 2] = ["Spotify", "Music"];
+
+    fn running_player() -> Option<&'static str> {
+        PLAYERS.into_iter().find(|app| {
+            let script = format!("application \"{}\" is running", app);
+            run_command("osascript", &["-e", &script]).ok().as_deref() == Some("true")
+        })
+    }
+
+    fn tell(app: &str, command: &str) -> Result<String, String> {
+        let script = format!("tell application \"{}\" to {}", app, command);
+        run_command("osascript", &["-e", &script])
+    }
+
+    pub fn now_playing() -> Result<Option<NowPlaying>, String> {
+        let Some(app) = running_player() else {
+            return Ok(None);
+        };
+        let info = tell(
+            app,
+            "(name of current track) & linefeed & (artist of current track) & linefeed & \
+             (album of current track) & linefeed & (player state as string) & linefeed & \
+             (player position as string) & linefeed & (duration of current track as string)",
+        )?;
+        let lines: Vec<&str> = info.lines().collect();
+        let field = |i: usize| lines.get(i).map(|line| line.to_string());
+        let number = |i: usize| {
+            lines
+                .get(i)
+                .and_then(|line| line.replace(',', ".").parse::<f64>().ok())
+        };
+        // Spotify reports the duration in milliseconds, Music in seconds
+        let duration_scale = if app == "Spotify" { 1.0 } else { 1000.0 };
+        Ok(Some(NowPlaying {
+            title: field(0),
+            artist: field(1),
+            album: field(2),
+            playing: field(3).as_deref() == Some("playing"),
+            position_ms: number(4).map(|seconds| (seconds * 1000.0) as u64),
+            duration_ms: number(5).map(|duration| (duration * duration_scale) as u64),
+        }))
+    }
+
+    pub fn control(action: MediaAction) -> Result<(), String> {
+        let app = running_player().ok_or("Neither Spotify nor Music is running")?;
+        let command = match action {
+            MediaAction::PlayPause => "playpause",
+            MediaAction::Play => "play",
+            MediaAction::Pause => "pause",
+            MediaAction::Next => "next track",
+            MediaAction::Previous => "previous track",
+        };
+        tell(app, command).map(|_| ())
+    }
+
+    pub fn seek(position_ms: u64) -> Result<(), String> {
+        let app = running_player().ok_or("Neither Spotify nor Music is running")?;
+        let command = format!("set player position to {}", position_ms as f64 / 1000.0);
+        tell(app, &command).map(|_| ())
+    }
+}
+
+pub use platform::{control, now_playing, seek};
+
+/// Pushes a `now_playing` message to paired devices announcing
+/// `now_playing`, and `now_playing_changed` to the frontend, whenever the
+/// track or playback state changes. The player is only polled while such a
+/// device is connected.
+pub fn spawn_now_playing_reporter(ctx: &ServerContext) {
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut previous: Option<NowPlaying> = None;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if !ctx.app_state.any_supports(CAP_NOW_PLAYING).await {
+                // Whoever subscribes next is told the track again
+                previous = None;
+                continue;
+            }
+            let current = match tokio::task::spawn_blocking(now_playing).await {
+                Ok(Ok(current)) => current,
+                Ok(Err(e)) => {
+                    debug!("Failed to read now playing: {}", e);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let changed = match (&current, &previous) {
+                (Some(current), Some(previous)) => current.differs_from(previous, POLL_INTERVAL),
+                (current, previous) => current.is_some() != previous.is_some(),
+            };
+            if changed {
+                let message = serde_json::json!({
+                    "type": "now_playing",
+                    "track": current,
+                });
+                ctx.app_state.broadcast_to(CAP_NOW_PLAYING, &message).await;
+                emit(&ctx.app_handle, "now_playing_changed", &current);
+            }
+            previous = current;
+        }
+    });
+}
+
+/// Play/pause, skip or go back in the active media player.
+#[derive(Deserialize)]
+struct MediaControl {
+    action: MediaAction,
+}
+
+impl Action for MediaControl {
+    const TYPE: &'static str = "media_control";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        control(self.action)
+    }
+}
+
+/// Jumps to a position in the current track.
+#[derive(Deserialize)]
+struct MediaSeek {
+    position_ms: u64,
+}
+
+impl Action for MediaSeek {
+    const TYPE: &'static str = "media_seek";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        seek(self.position_ms)
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<MediaControl>();
+    registry.register::<MediaSeek>();
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::keyboard::simulate_shortcut;
+use crate::system::run_command;
+
+// Meeting controls that work the same for Zoom and Microsoft Teams. Neither
+// app offers a local API, so the app's own shortcut is pressed: in the
+// focused meeting app, or else in a running one, which is brought to the
+// front first.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MeetingAction {
+    ToggleMute,
+    ToggleCamera,
+    Leave,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MeetingApp {
+    Zoom,
+    Teams,
+}
+
+/// Brief pause after bringing an app to the front, so the keys reach it.
+const ACTIVATE_DELAY: Duration = Duration::from_millis(300);
+
+impl MeetingApp {
+    const ALL: [MeetingApp; 2] = [MeetingApp::Zoom, MeetingApp::Teams];
+
+    /// Lowercase process names the app runs as, without `.exe`, across
+    /// platforms and the classic and new Teams clients.
+    fn process_names(self) -> &'static [&'static str] {
+        match self {
+            MeetingApp::Zoom => &["zoom", "zoom.us"],
+            MeetingApp::Teams => &[
+                "ms-teams",
+                "teams",
+                "msteams",
+                "microsoft teams",
+                "teams-for-linux",
+            ],
+        }
+    }
+
+    fn matches(self, process: &str) -> bool {
+        let process = process.to_lowercase();
+        let process = process.strip_suffix(".exe").unwrap_or(&process);
+        self.process_names().contains(&process)
+    }
+
+    fn keys(self, action: MeetingAction) -> &'static str {
+        let mac = cfg!(target_os = "macos");
+        match (self, action, mac) {
+            (MeetingApp::Zoom, MeetingAction::ToggleMute, false) => "Alt+a",
+            (MeetingApp::Zoom, MeetingAction::ToggleMute, true) => "Cmd+Shift+a",
+            (MeetingApp::Zoom, MeetingAction::ToggleCamera, false) => "Alt+v",
+            (MeetingApp::Zoom, MeetingAction::ToggleCamera, true) => "Cmd+Shift+v",
+            (MeetingApp::Zoom, MeetingAction::Leave, false) => "Alt+q",
+            (MeetingApp::Zoom, MeetingAction::Leave, true) => "Cmd+w",
+            (MeetingApp::Teams, MeetingAction::ToggleMute, false) => "Ctrl+Shift+m",
+            (MeetingApp::Teams, MeetingAction::ToggleMute, true) => "Cmd+Shift+m",
+            (MeetingApp::Teams, MeetingAction::ToggleCamera, false) => "Ctrl+Shift+o",
+            (MeetingApp::Teams, MeetingAction::ToggleCamera, true) => "Cmd+Shift+o",
+            (MeetingApp::Teams, MeetingAction::Leave, false) => "Ctrl+Shift+h",
+            (MeetingApp::Teams, MeetingAction::Leave, true) => "Cmd+Shift+h",
+        }
+    }
+
+    /// Brings the app's window to the front.
+    fn activate(self) -> Result<(), String> {
+        if cfg!(target_os = "macos") {
+            let app = match self {
+                MeetingApp::Zoom => "zoom.us",
+                MeetingApp::Teams => "Microsoft Teams",
+            };
+            let script = format!("tell application \"{}\" to activate", app);
+            run_command("osascript", &["-e", &script])?;
+        } else if cfg!(target_os = "windows") {
+            let names = match self {
+                MeetingApp::Zoom => "Zoom",
+                MeetingApp::Teams => "ms-teams,Teams",
+            };
+            let script = format!(
+                "$p = Get-Process -Name {} -ErrorAction SilentlyContinue | \
+                 Where-Object MainWindowHandle -ne 0 | Select-Object -First 1; \
+                 (New-Object -ComObject WScript.Shell).AppActivate($p.Id)",
+                names
+            );
+            run_command("powershell", &["-NoProfile", "-Command", &script])?;
+        } else {
+            let class = match self {
+                MeetingApp::Zoom => "zoom",
+                MeetingApp::Teams => "teams",
+            };
+            run_command(
+                "xdotool",
+                &[
+                    "search",
+                    "--onlyvisible",
+                    "--class",
+                    class,
+                    "windowactivate",
+                ],
+            )?;
+        }
+        std::thread::sleep(ACTIVATE_DELAY);
+        Ok(())
+    }
+}
+
+/// File name of the process owning the focused window.
+fn focused_process() -> Option<String> {
+    let window = active_win_pos_rs::get_active_window().ok()?;
+    let name = window.process_path.file_name()?;
+    Some(name.to_string_lossy().into_owned())
+}
+
+fn running_processes() -> Result<Vec<String>, String> {
+    if cfg!(target_os = "windows") {
+        // One `"name","pid",...` line per process
+        Ok(run_command("tasklist", &["/NH", "/FO", "CSV"])?
+            .lines()
+            .filter_map(|line| line.split(',').next())
+            .map(|name| name.trim_matches('"').to_string())
+            .collect())
+    } else {
+        // Full paths on macOS, bare names on Linux
+        Ok(run_command("ps", &["-A", "-o", "comm="])?
+            .lines()
+            .map(|path| path.rsplit('/').next().unwrap_or(path).trim().to_string())
+            .collect())
+    }
+}
+
+/// Presses the shortcut for `action` in the focused meeting app, or in the
+/// first running one.
+pub fn control(action: MeetingAction) -> Result<(), String> {
+    let focused = focused_process().and_then(|process| {
+        MeetingApp::ALL
+            .into_iter()
+            .find(|app| app.matches(&process))
+    });
+    let app = match focused {
+        Some(app) => app,
+        None => {
+            let running = running_processes()?;
+            let app = MeetingApp::ALL
+                .into_iter()
+                .find(|app| running.iter().any(|process| app.matches(process)))
+                .ok_or("Neither Zoom nor Teams is running")?;
+            app.activate()?;
+            app
+        }
+    };
+    debug!("Sending {:?} to {:?}", action, app);
+    simulate_shortcut(vec![app.keys(action).to_string()], None)
+}
+
+/// Mutes, turns the camera on or off or leaves the call in Zoom or Teams,
+/// whichever is focused or running.
+#[derive(Deserialize)]
+struct Meeting {
+    action: MeetingAction,
+}
+
+impl Action for Meeting {
+    const TYPE: &'static str = "meeting";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        control(self.action)
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<Meeting>();
+}
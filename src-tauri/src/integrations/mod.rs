@@ -0,0 +1,22 @@
+//! Action steps that drive other apps and devices directly instead of through
+//! their keyboard shortcuts.
+
+pub mod discord;
+pub mod http;
+pub mod hue;
+pub mod media;
+pub mod meetings;
+pub mod obs;
+pub mod wake_on_lan;
+
+use crate::actions::ActionRegistry;
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    discord::register_actions(registry);
+    http::register_actions(registry);
+    hue::register_actions(registry);
+    media::register_actions(registry);
+    meetings::register_actions(registry);
+    obs::register_actions(registry);
+    wake_on_lan::register_actions(registry);
+}
@@ -0,0 +1,413 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{Manager, State};
+use tungstenite::{Message, WebSocket};
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::secrets::{delete_secret, read_secret, store_secret};
+use crate::settings::SettingsStore;
+
+// Talks to OBS Studio over obs-websocket v5, which ships with OBS 28 and
+// later. Steps and the toggle states share one connection, which is dropped
+// when a request on it fails, so the next one reconnects after OBS was
+// restarted.
+
+/// Keychain entry holding the obs-websocket password.
+const PASSWORD_SECRET_ID: &str = "obs-websocket-password";
+
+const OP_HELLO: u64 = 0;
+const OP_IDENTIFY: u64 = 1;
+const OP_IDENTIFIED: u64 = 2;
+const OP_REQUEST: u64 = 6;
+const OP_REQUEST_RESPONSE: u64 = 7;
+
+/// How long connecting, and waiting for any one message, may take.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where obs-websocket listens. The password is kept in the OS keychain.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ObsSettings {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for ObsSettings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 4455,
+        }
+    }
+}
+
+struct ObsConnection {
+    socket: WebSocket<TcpStream>,
+    /// The `host:port` connected to.
+    address: String,
+    next_request_id: u64,
+}
+
+impl ObsConnection {
+    /// Connects and identifies, authenticating if OBS asks for it.
+    fn open(settings: &ObsSettings) -> Result<Self, String> {
+        let address = format!("{}:{}", settings.host, settings.port);
+        let url = format!("ws://{}", address);
+        let failed =
+            |e: &dyn std::fmt::Display| format!("Failed to connect to OBS at {}: {}", url, e);
+        let addr = address
+            .to_socket_addrs()
+            .map_err(|e| failed(&e))?
+            .next()
+            .ok_or_else(|| failed(&"unknown host"))?;
+        let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT).map_err(|e| failed(&e))?;
+        stream
+            .set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+            .map_err(|e| failed(&e))?;
+        let (socket, _) = tungstenite::client(url.as_str(), stream).map_err(|e| failed(&e))?;
+        let mut connection = Self {
+            socket,
+            address,
+            next_request_id: 0,
+        };
+
+        let hello = connection.receive(OP_HELLO)?;
+        // No events, which would pile up while the connection is idle
+        let mut identify = json!({ "rpcVersion": 1, "eventSubscriptions": 0 });
+        if let Some(auth) = hello.get("authentication") {
+            let password = read_secret(PASSWORD_SECRET_ID)
+                .map_err(|_| "OBS requires a password, but none is set".to_string())?;
+            identify["authentication"] = json!(auth_response(
+                &password,
+                auth["salt"].as_str().unwrap_or_default(),
+                auth["challenge"].as_str().unwrap_or_default(),
+            ));
+        }
+        connection.send(OP_IDENTIFY, identify)?;
+        connection.receive(OP_IDENTIFIED)?;
+        Ok(connection)
+    }
+
+    fn send(&mut self, op: u64, data: Value) -> Result<(), String> {
+        let message = json!({ "op": op, "d": data }).to_string();
+        self.socket
+            .send(Message::Text(message))
+            .map_err(|e| format!("Failed to send to OBS: {}", e))
+    }
+
+    /// Reads until a message with opcode `op` arrives and returns its data,
+    /// skipping the events OBS sends in between.
+    fn receive(&mut self, op: u64) -> Result<Value, String> {
+        loop {
+            let text = match self.socket.read() {
+                Ok(Message::Text(text)) => text,
+                // OBS closes with a reason, e.g. when the password is wrong
+                Ok(Message::Close(Some(frame))) => {
+                    return Err(format!("OBS closed the connection: {}", frame.reason))
+                }
+                Ok(_) => continue,
+                Err(e) => return Err(format!("Lost connection to OBS: {}", e)),
+            };
+            let mut message: Value = serde_json::from_str(&text)
+                .map_err(|e| format!("Invalid message from OBS: {}", e))?;
+            if message["op"] == op {
+                return Ok(message["d"].take());
+            }
+        }
+    }
+
+    /// Sends a request and returns its `responseData`, or OBS's reason for
+    /// rejecting it.
+    fn request(&mut self, request_type: &str, data: Value) -> Result<Value, String> {
+        self.next_request_id += 1;
+        let request_id = self.next_request_id.to_string();
+        self.send(
+            OP_REQUEST,
+            json!({
+                "requestType": request_type,
+                "requestId": request_id,
+                "requestData": data,
+            }),
+        )?;
+
+        loop {
+            let mut response = self.receive(OP_REQUEST_RESPONSE)?;
+            if response["requestId"] != request_id.as_str() {
+                continue;
+            }
+            let status = &response["requestStatus"];
+            if status["result"].as_bool() != Some(true) {
+                return Err(format!(
+                    "OBS rejected {}: {}",
+                    request_type,
+                    status["comment"].as_str().unwrap_or("unknown error")
+                ));
+            }
+            return Ok(response["responseData"].take());
+        }
+    }
+}
+
+impl Drop for ObsConnection {
+    fn drop(&mut self) {
+        self.socket.close(None).ok();
+    }
+}
+
+/// The obs-websocket challenge response:
+/// `base64(sha256(base64(sha256(password + salt)) + challenge))`.
+fn auth_response(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(Sha256::digest(format!("{}{}", password, salt)));
+    BASE64.encode(Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+/// The ID of `source` in `scene`, and whether it is shown.
+fn scene_item(
+    connection: &mut ObsConnection,
+    scene: &str,
+    source: &str,
+) -> Result<(Value, bool), String> {
+    let item_id = connection.request(
+        "GetSceneItemId",
+        json!({ "sceneName": scene, "sourceName": source }),
+    )?["sceneItemId"]
+        .take();
+    let enabled = connection.request(
+        "GetSceneItemEnabled",
+        json!({ "sceneName": scene, "sceneItemId": item_id }),
+    )?["sceneItemEnabled"]
+        .as_bool()
+        .unwrap_or(false);
+    Ok((item_id, enabled))
+}
+
+/// The open connection to OBS, if any.
+pub struct ObsClient {
+    connection: Mutex<Option<ObsConnection>>,
+}
+
+impl ObsClient {
+    pub fn new() -> Self {
+        Self {
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Runs `f` on the connection, opening it first if needed. A connection
+    /// that failed is dropped, so the next request reconnects.
+    fn with_connection<T>(
+        &self,
+        settings: &ObsSettings,
+        f: impl FnOnce(&mut ObsConnection) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let address = format!("{}:{}", settings.host, settings.port);
+        let mut connection = self.connection.lock().unwrap();
+        if connection
+            .as_ref()
+            .is_some_and(|connection| connection.address != address)
+        {
+            *connection = None;
+        }
+        if connection.is_none() {
+            *connection = Some(ObsConnection::open(settings)?);
+        }
+        let result = f(connection.as_mut().expect("opened above"));
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+
+    pub fn set_scene(&self, settings: &ObsSettings, scene: &str) -> Result<(), String> {
+        self.with_connection(settings, |connection| {
+            connection.request("SetCurrentProgramScene", json!({ "sceneName": scene }))
+        })
+        .map(|_| ())
+    }
+
+    /// Whether `source` is shown in `scene`.
+    pub fn source_visible(
+        &self,
+        settings: &ObsSettings,
+        scene: &str,
+        source: &str,
+    ) -> Result<bool, String> {
+        self.with_connection(settings, |connection| {
+            scene_item(connection, scene, source).map(|(_, enabled)| enabled)
+        })
+    }
+
+    /// Shows `source` in `scene` if it is hidden, hides it otherwise.
+    pub fn toggle_source(
+        &self,
+        settings: &ObsSettings,
+        scene: &str,
+        source: &str,
+    ) -> Result<(), String> {
+        self.with_connection(settings, |connection| {
+            let (item_id, enabled) = scene_item(connection, scene, source)?;
+            connection.request(
+                "SetSceneItemEnabled",
+                json!({
+                    "sceneName": scene,
+                    "sceneItemId": item_id,
+                    "sceneItemEnabled": !enabled,
+                }),
+            )
+        })
+        .map(|_| ())
+    }
+
+    pub fn set_recording(&self, settings: &ObsSettings, recording: bool) -> Result<(), String> {
+        let request_type = if recording {
+            "StartRecord"
+        } else {
+            "StopRecord"
+        };
+        self.with_connection(settings, |connection| {
+            connection.request(request_type, json!({}))
+        })
+        .map(|_| ())
+    }
+
+    pub fn recording(&self, settings: &ObsSettings) -> Result<bool, String> {
+        self.with_connection(settings, |connection| {
+            Ok(
+                connection.request("GetRecordStatus", json!({}))?["outputActive"]
+                    .as_bool()
+                    .unwrap_or(false),
+            )
+        })
+    }
+
+    pub fn scene_names(&self, settings: &ObsSettings) -> Result<Vec<String>, String> {
+        let scenes = self.with_connection(settings, |connection| {
+            connection.request("GetSceneList", json!({}))
+        })?;
+        Ok(scenes["scenes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|scene| scene["sceneName"].as_str().map(str::to_string))
+            .collect())
+    }
+}
+
+impl Default for ObsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn client(ctx: &ActionContext) -> Arc<ObsClient> {
+    Arc::clone(&ctx.app_handle.state::<Arc<ObsClient>>())
+}
+
+/// Switches OBS to another scene.
+#[derive(Deserialize)]
+struct ObsSetScene {
+    scene: String,
+}
+
+impl Action for ObsSetScene {
+    const TYPE: &'static str = "obs_set_scene";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        client(ctx).set_scene(&ctx.settings().obs, &self.scene)
+    }
+}
+
+/// Shows an OBS source if it is hidden in the scene, hides it otherwise.
+#[derive(Deserialize)]
+struct ObsToggleSource {
+    scene: String,
+    source: String,
+}
+
+impl Action for ObsToggleSource {
+    const TYPE: &'static str = "obs_toggle_source";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        client(ctx).toggle_source(&ctx.settings().obs, &self.scene, &self.source)
+    }
+}
+
+#[derive(Deserialize)]
+struct ObsStartRecording {}
+
+impl Action for ObsStartRecording {
+    const TYPE: &'static str = "obs_start_recording";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        client(ctx).set_recording(&ctx.settings().obs, true)
+    }
+}
+
+#[derive(Deserialize)]
+struct ObsStopRecording {}
+
+impl Action for ObsStopRecording {
+    const TYPE: &'static str = "obs_stop_recording";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        client(ctx).set_recording(&ctx.settings().obs, false)
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<ObsSetScene>();
+    registry.register::<ObsToggleSource>();
+    registry.register::<ObsStartRecording>();
+    registry.register::<ObsStopRecording>();
+}
+
+// OBS-related Tauri commands
+
+/// Stores the obs-websocket password in the OS keychain.
+///
+/// # Arguments
+///
+/// * `password` - The password, or `None` if OBS doesn't require one.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn set_obs_password(password: Option<String>) -> Result<(), String> {
+    match password.filter(|password| !password.is_empty()) {
+        Some(password) => store_secret(PASSWORD_SECRET_ID, &password),
+        None => {
+            delete_secret(PASSWORD_SECRET_ID);
+            Ok(())
+        }
+    }
+}
+
+/// Lists the scenes of the running OBS instance, for picking one in a step.
+/// Also serves as a connection test for the OBS settings.
+///
+/// # Arguments
+///
+/// * `settings` - Shared state containing the OBS connection settings.
+/// * `obs` - The connection to OBS.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>, String>` - The scene names or an error message.
+#[tauri::command]
+pub async fn list_obs_scenes(
+    settings: State<'_, Arc<SettingsStore>>,
+    obs: State<'_, Arc<ObsClient>>,
+) -> Result<Vec<String>, String> {
+    let settings = settings.get_settings().obs;
+    let obs = Arc::clone(&obs);
+    tokio::task::spawn_blocking(move || obs.scene_names(&settings))
+        .await
+        .map_err(|e| e.to_string())?
+}
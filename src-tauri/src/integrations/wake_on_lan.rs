@@ -0,0 +1,73 @@
+use serde::Deserialize;
+use std::net::{Ipv4Addr, UdpSocket};
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+
+// Wakes other machines on the LAN with a magic packet: six 0xFF bytes
+// followed by the target's MAC address repeated sixteen times, broadcast
+// over UDP.
+
+const DEFAULT_PORT: u16 = 9;
+
+/// Parses `AA:BB:CC:DD:EE:FF`, also accepting `-` separators or none.
+fn parse_mac(mac: &str) -> Result<[u8; 6], String> {
+    let hex: String = mac.chars().filter(|c| !matches!(c, ':' | '-')).collect();
+    let invalid = || format!("Invalid MAC address: {}", mac);
+    if hex.len() != 12 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 6];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+/// Sends a magic packet for `mac` to `broadcast` (the whole local network by
+/// default), e.g. a subnet's broadcast address when the desktop has several.
+pub fn wake(mac: &str, broadcast: Option<&str>, port: Option<u16>) -> Result<(), String> {
+    let mac = parse_mac(mac)?;
+    let broadcast = match broadcast {
+        Some(address) => address
+            .parse::<Ipv4Addr>()
+            .map_err(|_| format!("Invalid broadcast address: {}", address))?,
+        None => Ipv4Addr::BROADCAST,
+    };
+
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .map_err(|e| format!("Failed to open a UDP socket: {}", e))?;
+    socket
+        .set_broadcast(true)
+        .map_err(|e| format!("Failed to enable broadcast: {}", e))?;
+    socket
+        .send_to(&packet, (broadcast, port.unwrap_or(DEFAULT_PORT)))
+        .map_err(|e| format!("Failed to send the magic packet: {}", e))?;
+    Ok(())
+}
+
+/// Wakes another machine on the LAN with a magic packet.
+#[derive(Deserialize)]
+struct WakeOnLan {
+    mac: String,
+    /// Broadcast address; 255.255.255.255 when unset.
+    broadcast: Option<String>,
+    /// UDP port; 9 when unset.
+    port: Option<u16>,
+}
+
+impl Action for WakeOnLan {
+    const TYPE: &'static str = "wake_on_lan";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        wake(&self.mac, self.broadcast.as_deref(), self.port)
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<WakeOnLan>();
+}
@@ -0,0 +1,133 @@
+use button_beam_core::keyboard::{self, TypingSpeed};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::error::emit;
+use crate::permissions::ensure_can_send_keys;
+use crate::shortcuts::Timing;
+
+pub use button_beam_core::keyboard::{is_text_string, InputBackendStatus};
+
+// Presses key combos and types text through enigo, for plain string steps.
+// Shortcuts in game mode send scancodes instead, see `scancodes` in core.
+
+/// A key combo such as "Ctrl+S", or text to type when it has no modifiers.
+/// Text may contain `{{var:NAME}}`, filled in from variables earlier steps
+/// set. Plain string steps run as this action.
+#[derive(Deserialize)]
+pub struct Keys {
+    pub keys: String,
+    /// Overrides the shortcut's and the settings' typing speed for text.
+    #[serde(flatten)]
+    pub speed: TypingSpeed,
+}
+
+impl Action for Keys {
+    const TYPE: &'static str = "keys";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        if let Err(e) = ensure_can_send_keys() {
+            // The trigger may have come from a phone; the desktop should still say why
+            emit(ctx.app_handle, "input_blocked", &e);
+            return Err(e);
+        }
+        let Timing {
+            interval_ms,
+            game_mode,
+            ..
+        } = ctx.timing;
+        if is_text_string(&self.keys) {
+            type_text(ctx, &self.keys, self.speed)
+        } else {
+            debug!("text is key sequence {}", &self.keys);
+            // Treat as key sequence
+            let pressed = if game_mode {
+                keyboard::simulate_shortcut_game_mode(vec![self.keys], interval_ms)
+            } else {
+                keyboard::simulate_shortcut(vec![self.keys], interval_ms)
+            };
+            pressed.map_err(|e| format!("Error simulating shortcut: {}", e))
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.speed.validate()
+    }
+}
+
+/// Text that is always typed, even where a plain string step would read it
+/// as a key combo, like `C++` or `alt text`.
+#[derive(Deserialize)]
+pub struct Text {
+    pub text: String,
+    #[serde(flatten)]
+    pub speed: TypingSpeed,
+}
+
+impl Action for Text {
+    const TYPE: &'static str = "text";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        if let Err(e) = ensure_can_send_keys() {
+            emit(ctx.app_handle, "input_blocked", &e);
+            return Err(e);
+        }
+        type_text(ctx, &self.text, self.speed)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.speed.validate()
+    }
+}
+
+/// Types `text` with any variables filled in.
+fn type_text(ctx: &ActionContext, text: &str, speed: TypingSpeed) -> Result<(), String> {
+    // The text itself stays out of the log, it can be a password
+    debug!("text is string of {} characters", text.chars().count());
+    let text = ctx.output.substitute(text);
+    let speed = speed.or(ctx.typing_speed());
+    let typed = if ctx.timing.game_mode {
+        keyboard::simulate_text_typing_game_mode(&text, speed)
+    } else {
+        keyboard::simulate_text_typing(&text, speed)
+    };
+    typed.map_err(|e| format!("Error typing text: {}", e))
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<Keys>();
+    registry.register::<Text>();
+}
+
+/// Simulates a keyboard shortcut based on the provided keys.
+///
+/// # Arguments
+///
+/// * `shortcut_keys` - A string representing the keyboard shortcut keys (e.g., "Ctrl+S").
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn simulate_shortcut(sequence: Vec<String>, interval_ms: Option<u64>) -> Result<(), String> {
+    ensure_can_send_keys()?;
+    keyboard::simulate_shortcut(sequence, interval_ms)
+}
+
+/// Types `text` at `speed`.
+pub fn simulate_text_typing(text: &str, speed: TypingSpeed) -> Result<(), String> {
+    ensure_can_send_keys()?;
+    keyboard::simulate_text_typing(text, speed)
+}
+
+/// Reports how key presses are sent. On Wayland they go through ydotool once
+/// it is set up, and `setup_steps` lists what is still missing.
+///
+/// # Returns
+///
+/// * `Result<InputBackendStatus, String>` - The backend in use and any setup left to do.
+#[tauri::command]
+pub fn get_input_backend() -> Result<InputBackendStatus, String> {
+    Ok(keyboard::input_backend_status())
+}
@@ -0,0 +1,110 @@
+use button_beam_core::protocol::CAP_LAYOUTS;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+use crate::devices::DeviceRegistry;
+use crate::events::{publish, AppEvent};
+use crate::sockets::AppState;
+
+/// Button grid shown on one device, e.g. 2x4 on a phone or 8x4 on a tablet.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub columns: u32,
+    pub rows: u32,
+    pub buttons: Vec<LayoutButton>,
+}
+
+/// Places a shortcut in a grid cell. Pages let a small grid hold more buttons.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LayoutButton {
+    pub shortcut_id: u64,
+    #[serde(default)]
+    pub page: u32,
+    pub row: u32,
+    pub column: u32,
+}
+
+/// Largest grid side accepted, to keep clients from laying out absurd grids.
+const MAX_GRID_SIZE: u32 = 16;
+
+impl Layout {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.columns == 0 || self.rows == 0 {
+            return Err("A layout needs at least one row and one column".into());
+        }
+        if self.columns > MAX_GRID_SIZE || self.rows > MAX_GRID_SIZE {
+            return Err(format!(
+                "A layout can have at most {} rows and columns",
+                MAX_GRID_SIZE
+            ));
+        }
+
+        let mut taken = HashSet::new();
+        for button in &self.buttons {
+            if button.row >= self.rows || button.column >= self.columns {
+                return Err(format!(
+                    "Button for shortcut {} is outside the {}x{} grid",
+                    button.shortcut_id, self.columns, self.rows
+                ));
+            }
+            if !taken.insert((button.page, button.row, button.column)) {
+                return Err(format!(
+                    "Two buttons share page {}, row {}, column {}",
+                    button.page, button.row, button.column
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `layout` message sent to devices that support layouts. `None` tells
+/// the client to fall back to its own default grid.
+pub fn layout_message(layout: Option<&Layout>) -> serde_json::Value {
+    serde_json::json!({ "type": "layout", "layout": layout })
+}
+
+// Layout-related Tauri commands
+
+#[tauri::command]
+pub fn get_device_layout(
+    device_id: String,
+    registry: State<Arc<DeviceRegistry>>,
+) -> Result<Option<Layout>, String> {
+    Ok(registry.layout(&device_id))
+}
+
+/// Assigns a button layout to a device and pushes it if the device is connected.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the device.
+/// * `layout` - The layout to use, or `None` for the client's default.
+/// * `registry` - Shared state containing the known devices.
+/// * `app_state` - Shared state containing the connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_device_layout(
+    device_id: String,
+    layout: Option<Layout>,
+    registry: State<'_, Arc<DeviceRegistry>>,
+    app_state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if let Some(layout) = &layout {
+        layout.validate()?;
+    }
+    registry.set_layout(&device_id, layout.clone())?;
+
+    app_state
+        .send_to_device(&device_id, CAP_LAYOUTS, &layout_message(layout.as_ref()))
+        .await;
+    publish(&app_handle, AppEvent::KnownDevicesChanged);
+    Ok(())
+}
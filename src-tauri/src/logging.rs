@@ -0,0 +1,149 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::devices::now_millis;
+
+/// Entries kept in memory for the log panel; older ones are only on disk.
+const BUFFER_SIZE: usize = 2000;
+/// Daily log files kept under the app data dir.
+const MAX_LOG_FILES: usize = 7;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LogEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// The most recent log entries, for `get_recent_logs`.
+#[derive(Default)]
+pub struct LogBuffer {
+    entries: Mutex<VecDeque<(Level, LogEntry)>>,
+}
+
+impl LogBuffer {
+    fn push(&self, level: Level, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == BUFFER_SIZE {
+            entries.pop_front();
+        }
+        entries.push_back((level, entry));
+    }
+
+    /// Returns up to `limit` entries at `level` or more severe, newest first.
+    pub fn recent(&self, level: Level, limit: usize) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .rev()
+            .filter(|(entry_level, _)| *entry_level <= level)
+            .take(limit)
+            .map(|(_, entry)| entry.clone())
+            .collect()
+    }
+}
+
+/// Collects an event's message and any extra fields into one line.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            write!(self.fields, " {}={:?}", field.name(), value).ok();
+        }
+    }
+}
+
+struct BufferLayer(Arc<LogBuffer>);
+
+impl<S: Subscriber> Layer<S> for BufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        self.0.push(
+            *metadata.level(),
+            LogEntry {
+                timestamp: now_millis(),
+                level: metadata.level().to_string(),
+                target: metadata.target().to_string(),
+                message: visitor.message + visitor.fields.as_str(),
+            },
+        );
+    }
+}
+
+/// Logs to stdout, to daily files in `log_dir` and to `buffer`. The level
+/// defaults to info and can be changed with `RUST_LOG`. The returned guard
+/// flushes the file writer when dropped, so keep it alive until exit.
+pub fn init(log_dir: &Path, buffer: Arc<LogBuffer>) -> Result<WorkerGuard, String> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("button-beam")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir)
+        .map_err(|e| format!("Failed to open log files in {}: {}", log_dir.display(), e))?;
+    let (file_writer, guard) = tracing_appender::non_blocking(appender);
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_subscriber::fmt::layer())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(file_writer)
+                .with_ansi(false),
+        )
+        .with(BufferLayer(buffer))
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    Ok(guard)
+}
+
+// Logging-related Tauri commands
+
+/// Returns recent log entries for the log panel, newest first.
+///
+/// # Arguments
+///
+/// * `level` - Least severe level to include, e.g. `"warn"`; info when unset.
+/// * `limit` - Maximum number of entries; 200 when unset.
+/// * `buffer` - The in-memory log buffer.
+///
+/// # Returns
+///
+/// * `Result<Vec<LogEntry>, String>` - The entries or an error message.
+#[tauri::command]
+pub fn get_recent_logs(
+    level: Option<String>,
+    limit: Option<usize>,
+    buffer: State<Arc<LogBuffer>>,
+) -> Result<Vec<LogEntry>, String> {
+    let level = match level {
+        Some(level) => {
+            Level::from_str(&level).map_err(|_| format!("Unknown log level {}", level))?
+        }
+        None => Level::INFO,
+    };
+    Ok(buffer.recent(level, limit.unwrap_or(200)))
+}
@@ -1,28 +1,120 @@
 /// ./src-tauri/src/main.rs
+mod actions;
+mod activity;
+mod assets;
+mod auth;
+mod autostart;
+mod ble;
+mod cli;
+mod controls;
+mod data_dir;
+mod devices;
+mod diagnostics;
+mod discovery;
+mod error;
+mod events;
+mod execution_limits;
+mod home_assistant;
+mod hotkeys;
+mod http_api;
+mod importer;
+mod integrations;
+mod keyboard;
+mod layouts;
+mod logging;
+mod mouse;
+mod mqtt;
+mod notifications;
+mod onboarding;
+mod performance;
+mod permissions;
+mod plugins;
+mod rate_limit;
+mod recorder;
+mod scheduler;
+mod scripting;
+mod secrets;
+mod serial;
+mod server;
+mod settings;
+mod shortcut_states;
 mod shortcuts;
+mod snippets;
 mod sockets;
+mod sync;
+mod system;
+#[cfg(all(test, any(target_os = "windows", target_os = "linux")))]
+mod testing;
+mod tray;
+mod triggers;
+mod twitch;
+mod webhook;
+mod youtube;
 
+use crate::shortcut_states::{get_shortcut_states, spawn_state_reporter, ShortcutStates};
 use crate::shortcuts::{
-    add_shortcut, delete_shortcut, get_shortcuts_command, register_global_shortcuts,
-    simulate_shortcut, simulate_shortcut_by_id, update_shortcut, Shortcut, ShortcutStore,
+    add_shortcut, delete_shortcut, get_shortcuts_command, simulate_shortcut_by_id, update_shortcut,
+    ShortcutChange, ShortcutStore,
 };
 
-use crate::sockets::{start_websocket_server, AppState};
-use std::net::{Ipv4Addr, TcpListener};
+use crate::actions::ActionRegistry;
+use crate::activity::{get_activity_log, ActivityLog};
+use crate::assets::{add_asset, delete_asset, list_assets, AssetStore};
+use crate::auth::{get_auth_token, regenerate_auth_token, AuthStore};
+use crate::autostart::{get_autostart, set_autostart};
+use crate::ble::{get_ble_supported, set_ble_transport, BleTransport};
+use crate::devices::{
+    block_device, forget_device, list_known_devices, rename_device, set_device_role, trust_device,
+    DeviceRegistry,
+};
+use crate::diagnostics::export_diagnostics;
+use crate::discovery::{set_udp_discovery, DiscoveryResponder};
+use crate::error::report;
+use crate::events::{spawn_subscribers, EventBus};
+use crate::hotkeys::{
+    get_hotkey_bindings, get_hotkey_conflicts, refresh_global_shortcuts, set_hotkey_binding,
+    HotkeyStore, RegisteredHotkeys,
+};
+use crate::importer::import_shortcuts;
+use crate::integrations::discord::{set_discord_app, DiscordClient};
+use crate::integrations::hue::{list_hue_lights, pair_hue_bridge};
+use crate::integrations::media::spawn_now_playing_reporter;
+use crate::integrations::obs::{list_obs_scenes, set_obs_password, ObsClient};
+use crate::keyboard::{get_input_backend, simulate_shortcut};
+use crate::layouts::{get_device_layout, set_device_layout};
+use crate::logging::{get_recent_logs, LogBuffer};
+use crate::mouse::list_displays;
+use crate::mqtt::{set_mqtt_bridge, MqttBridge};
+use crate::onboarding::get_onboarding_status;
+use crate::performance::{get_performance_stats, PerformanceMonitor};
+use crate::permissions::{check_input_permissions, open_permission_settings};
+use crate::plugins::get_plugins;
+use crate::recorder::{start_recording, stop_recording, Recorder};
+use crate::scheduler::{
+    add_schedule, delete_schedule, get_schedules, set_schedule_enabled, spawn_scheduler,
+    update_schedule, ScheduleStore,
+};
+use crate::serial::{list_serial_ports, set_serial_trigger, SerialTrigger};
+use crate::server::{get_server_config, restart_server, stop_server, ServerHandle};
+use crate::settings::{
+    get_settings, list_network_interfaces, set_server_settings, update_settings, SettingsStore,
+};
+use crate::snippets::{add_snippet, delete_snippet, get_snippets, update_snippet, SnippetStore};
+use crate::sockets::{
+    approve_device, deny_device, disconnect_device, get_connected_devices, get_connection_stats,
+    get_max_triggers_per_second, get_triggering_paused, set_max_triggers_per_second,
+    set_triggering_paused, spawn_stats_reporter, AppState, ServerContext,
+};
+use crate::sync::{get_sync_config, set_sync_config, sync_pull, sync_push, SyncStore};
+use crate::system::screenshot::ScreenshotFolders;
+use crate::tray::{handle_tray_event, system_tray};
+use crate::twitch::{set_twitch_bridge, TwitchBridge};
+use crate::webhook::{set_webhook, WebhookForwarder};
+use crate::youtube::{set_youtube_bridge, YouTubeBridge};
 use std::sync::Arc;
-use tauri::{Manager, State};
+use tauri::{Manager, WindowEvent};
 use tokio::sync::broadcast;
-
-struct ServerConfig {
-    ip: String,
-    port: u16,
-}
-
-#[derive(serde::Serialize)]
-struct ServerConfigData {
-    ip: String,
-    port: u16,
-}
+use tracing::error;
 
 #[tauri::command]
 fn get_local_ip() -> Result<String, String> {
@@ -32,91 +124,296 @@ fn get_local_ip() -> Result<String, String> {
     }
 }
 
-#[tauri::command]
-fn find_free_port() -> Result<u16, String> {
-    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))
-        .map_err(|e| format!("Failed to bind to a free port: {}", e))?;
-    let port = listener
-        .local_addr()
-        .map_err(|e| format!("Failed to get local address: {}", e))?
-        .port();
-    Ok(port)
-}
-
-#[tauri::command]
-fn get_server_config(server_config: State<Arc<ServerConfig>>) -> ServerConfigData {
-    ServerConfigData {
-        ip: server_config.ip.clone(),
-        port: server_config.port,
-    }
-}
-
 fn main() {
     let context = tauri::generate_context!();
 
-    let app_dir = tauri::api::path::app_data_dir(&context.config())
-        .expect("Cannot locate app data directory");
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let app_dir = match data_dir::resolve(&mut args, context.config()) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // `list` and `trigger` talk to the running instance instead of starting one
+    if let Some(code) = cli::run(&args, &app_dir) {
+        std::process::exit(code);
+    }
     let shortcuts_file = app_dir.join("shortcuts.json");
+    let sync_file = app_dir.join("sync.json");
+    let auth_file = app_dir.join("auth.json");
+    let devices_file = app_dir.join("devices.json");
+    let settings_file = app_dir.join("settings.json");
+    let activity_file = app_dir.join("activity.jsonl");
+    let schedules_file = app_dir.join("schedules.json");
+    let hotkeys_file = app_dir.join("hotkeys.json");
+    let snippets_file = app_dir.join("snippets.json");
+    let assets_dir = app_dir.join("assets");
+
+    let log_buffer = Arc::new(LogBuffer::default());
+    // Flushes the log file on exit, so it must live as long as `main`
+    let _log_guard = match logging::init(&app_dir.join("logs"), Arc::clone(&log_buffer)) {
+        Ok(guard) => Some(guard),
+        Err(e) => {
+            eprintln!("Logging to file is unavailable: {}", e);
+            None
+        }
+    };
 
-    let (sender, _receiver) = broadcast::channel::<Vec<Shortcut>>(16);
+    let (sender, _receiver) = broadcast::channel::<ShortcutChange>(64);
 
     let store = Arc::new(ShortcutStore::new(shortcuts_file, sender.clone()));
+    let event_bus = Arc::new(EventBus::new());
     let app_state = Arc::new(AppState::new());
+    let sync_store = Arc::new(SyncStore::new(sync_file));
+    let auth_store = Arc::new(AuthStore::new(auth_file));
+    let device_registry = Arc::new(DeviceRegistry::new(devices_file));
+    let settings_store = Arc::new(SettingsStore::new(settings_file));
+    let activity_log = Arc::new(ActivityLog::new(activity_file, Arc::clone(&event_bus)));
+    let schedule_store = Arc::new(ScheduleStore::new(schedules_file));
+    let hotkey_store = Arc::new(HotkeyStore::new(hotkeys_file, &store.get_shortcuts()));
+    let snippet_store = Arc::new(SnippetStore::new(snippets_file));
+    let asset_store = Arc::new(AssetStore::new(assets_dir));
+    let screenshot_folders = Arc::new(ScreenshotFolders {
+        data_dir: app_dir.clone(),
+    });
+
+    let mut action_registry = ActionRegistry::builtin();
+    let loaded_plugins = plugins::load(&app_dir.join("plugins"), &mut action_registry);
 
     let store_clone = Arc::clone(&store); // Clone store here
     let app_state_clone = Arc::clone(&app_state); // Clone app_state here
+    let auth_store_clone = Arc::clone(&auth_store);
+    let device_registry_clone = Arc::clone(&device_registry);
+    let settings_store_clone = Arc::clone(&settings_store);
+    let activity_log_clone = Arc::clone(&activity_log);
+    let schedule_store_clone = Arc::clone(&schedule_store);
+    let event_bus_clone = Arc::clone(&event_bus);
 
     tauri::Builder::default()
+        .system_tray(system_tray())
+        .on_system_tray_event(handle_tray_event)
+        .on_window_event(|event| {
+            // Closing the window keeps the server running in the tray; Quit exits
+            if let WindowEvent::CloseRequested { api, .. } = event.event() {
+                event.window().hide().ok();
+                api.prevent_close();
+            }
+        })
         .setup(move |app| {
-            let ip = get_local_ip().unwrap_or_else(|_| "127.0.0.1".to_string());
-            let port = find_free_port().unwrap_or(3000);
-
-            let server_config = Arc::new(ServerConfig {
-                ip: ip.clone(),
-                port,
-            });
-            app.manage(server_config);
+            let bind_address = settings_store_clone.bind_address();
+            let port = settings_store_clone.port();
+            let settings = settings_store_clone.get_settings();
 
             let app_handle = app.handle();
-            let ip_clone = ip.clone();
+
+            let save_handle = app_handle.clone();
+            store_clone.on_save_error(move |e| report(&save_handle, &e.into()));
 
             // Clone variables before moving into the closure
-            let store_clone_for_ws = Arc::clone(&store_clone);
-            let app_handle_for_ws = app_handle.clone();
-            let app_state_clone_for_ws = Arc::clone(&app_state_clone);
+            let ws_context = ServerContext {
+                store: Arc::clone(&store_clone),
+                app_state: Arc::clone(&app_state_clone),
+                auth: Arc::clone(&auth_store_clone),
+                devices: Arc::clone(&device_registry_clone),
+                activity: Arc::clone(&activity_log_clone),
+                settings: Arc::clone(&settings_store_clone),
+                events: Arc::clone(&event_bus_clone),
+                app_handle: app_handle.clone(),
+            };
+
+            let server = Arc::new(ServerHandle::new(ws_context.clone()));
+            app.manage(Arc::clone(&server));
+            let discovery = Arc::new(DiscoveryResponder::new());
+            app.manage(Arc::clone(&discovery));
+            let ble = Arc::new(BleTransport::new());
+            app.manage(Arc::clone(&ble));
+            let mqtt = Arc::new(MqttBridge::new());
+            app.manage(Arc::clone(&mqtt));
+            let twitch = Arc::new(TwitchBridge::new());
+            app.manage(Arc::clone(&twitch));
+            let youtube = Arc::new(YouTubeBridge::new());
+            app.manage(Arc::clone(&youtube));
+            let webhook = Arc::new(WebhookForwarder::new());
+            app.manage(Arc::clone(&webhook));
+            let serial = Arc::new(SerialTrigger::new());
+            app.manage(Arc::clone(&serial));
+            if settings.serial.enabled {
+                if let Err(e) = serial.start(settings.serial.clone(), ws_context.clone()) {
+                    error!("{}", e);
+                }
+            }
+            app.manage(ws_context.clone());
 
             tauri::async_runtime::spawn(async move {
-                start_websocket_server(
-                    &ip,
-                    port,
-                    store_clone_for_ws,
-                    app_state_clone_for_ws,
-                    app_handle_for_ws,
-                )
-                .await;
+                spawn_subscribers(&ws_context);
+                spawn_stats_reporter(&ws_context);
+                spawn_now_playing_reporter(&ws_context);
+                spawn_state_reporter(&ws_context);
+                spawn_scheduler(&ws_context, schedule_store_clone);
+                let advertise_address = settings.advertise_address.as_deref();
+                if let Err(e) = server.start(&bind_address, port, advertise_address).await {
+                    error!("{}", e);
+                }
+                if settings.udp_discovery {
+                    if let Err(e) = discovery.start(server).await {
+                        error!("{}", e);
+                    }
+                }
+                if settings.ble_transport && crate::ble::SUPPORTED {
+                    if let Err(e) = ble.start(ws_context.clone()).await {
+                        error!("{}", e);
+                    }
+                }
+                if settings.webhook.enabled {
+                    if let Err(e) = webhook
+                        .start(settings.webhook.clone(), ws_context.clone())
+                        .await
+                    {
+                        error!("{}", e);
+                    }
+                }
+                if settings.twitch.enabled {
+                    if let Err(e) = twitch
+                        .start(settings.twitch.clone(), ws_context.clone())
+                        .await
+                    {
+                        error!("{}", e);
+                    }
+                }
+                if settings.youtube.enabled {
+                    if let Err(e) = youtube
+                        .start(settings.youtube.clone(), ws_context.clone())
+                        .await
+                    {
+                        error!("{}", e);
+                    }
+                }
+                if settings.mqtt.enabled {
+                    if let Err(e) = mqtt.start(settings.mqtt.clone(), ws_context).await {
+                        error!("{}", e);
+                    }
+                }
             });
 
-            println!("WebSocket server started at ws://{}:{}", ip_clone, port);
-
             // Register global shortcuts
-            let store_clone_for_shortcuts = Arc::clone(&store_clone);
-            let app_handle_for_shortcuts = app_handle.clone();
-            register_global_shortcuts(app_handle_for_shortcuts, store_clone_for_shortcuts);
+            refresh_global_shortcuts(&app_handle, &store_clone);
 
             Ok(())
         })
         .manage(Arc::clone(&store)) // Use cloned `store` here
         .manage(Arc::clone(&app_state)) // Use cloned `app_state` here
+        .manage(sync_store)
+        .manage(auth_store)
+        .manage(device_registry)
+        .manage(settings_store)
+        .manage(activity_log)
+        .manage(event_bus)
+        .manage(schedule_store)
+        .manage(log_buffer)
+        .manage(hotkey_store)
+        .manage(snippet_store)
+        .manage(asset_store)
+        .manage(screenshot_folders)
+        .manage(Arc::new(RegisteredHotkeys::default()))
+        .manage(Arc::new(Recorder::new()))
+        .manage(Arc::new(PerformanceMonitor::new()))
+        .manage(Arc::new(ShortcutStates::new()))
+        .manage(Arc::new(DiscordClient::new()))
+        .manage(Arc::new(ObsClient::new()))
+        .manage(Arc::new(action_registry))
+        .manage(loaded_plugins)
         .invoke_handler(tauri::generate_handler![
             get_shortcuts_command,
             add_shortcut,
             update_shortcut,
             delete_shortcut,
             simulate_shortcut,
+            get_input_backend,
+            list_displays,
             simulate_shortcut_by_id,
+            import_shortcuts,
             get_local_ip,
             get_server_config,
+            restart_server,
+            stop_server,
+            get_sync_config,
+            set_sync_config,
+            sync_push,
+            sync_pull,
+            get_auth_token,
+            regenerate_auth_token,
+            approve_device,
+            deny_device,
+            disconnect_device,
+            trust_device,
+            block_device,
+            list_known_devices,
+            forget_device,
+            rename_device,
+            set_device_role,
+            get_device_layout,
+            set_device_layout,
+            get_max_triggers_per_second,
+            set_max_triggers_per_second,
+            get_connected_devices,
+            get_connection_stats,
+            get_performance_stats,
+            get_triggering_paused,
+            set_triggering_paused,
+            get_activity_log,
+            export_diagnostics,
+            get_recent_logs,
+            get_onboarding_status,
+            check_input_permissions,
+            open_permission_settings,
+            get_plugins,
+            get_settings,
+            list_network_interfaces,
+            set_server_settings,
+            update_settings,
+            set_udp_discovery,
+            get_ble_supported,
+            set_ble_transport,
+            get_autostart,
+            set_autostart,
+            start_recording,
+            stop_recording,
+            set_obs_password,
+            list_obs_scenes,
+            set_discord_app,
+            pair_hue_bridge,
+            list_hue_lights,
+            set_mqtt_bridge,
+            set_twitch_bridge,
+            set_youtube_bridge,
+            set_webhook,
+            list_serial_ports,
+            set_serial_trigger,
+            get_schedules,
+            add_schedule,
+            update_schedule,
+            set_schedule_enabled,
+            delete_schedule,
+            get_hotkey_bindings,
+            get_hotkey_conflicts,
+            set_hotkey_binding,
+            get_shortcut_states,
+            get_snippets,
+            add_snippet,
+            update_snippet,
+            delete_snippet,
+            list_assets,
+            add_asset,
+            delete_asset,
         ])
-        .run(context)
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                let server = app_handle.state::<Arc<ServerHandle>>().inner().clone();
+                tauri::async_runtime::block_on(async move { server.shut_down().await });
+            }
+        });
 }
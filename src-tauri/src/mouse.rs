@@ -0,0 +1,146 @@
+use button_beam_core::mouse::{self, MouseButton};
+use serde::{Deserialize, Serialize};
+use xcap::Monitor;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+
+// Mouse steps. Positions can be given relative to a display, in pixels or in
+// percent of its size, so recorded clicks still land on the same spot after
+// the displays are rearranged or change resolution.
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Display {
+    pub id: u32,
+    pub name: String,
+    /// The display's top-left corner in desktop coordinates.
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub is_primary: bool,
+}
+
+fn displays() -> Result<Vec<Display>, String> {
+    let monitors = Monitor::all().map_err(|e| format!("Failed to list displays: {}", e))?;
+    Ok(monitors
+        .iter()
+        .map(|monitor| Display {
+            id: monitor.id(),
+            name: monitor.name().to_string(),
+            x: monitor.x(),
+            y: monitor.y(),
+            width: monitor.width(),
+            height: monitor.height(),
+            scale_factor: monitor.scale_factor(),
+            is_primary: monitor.is_primary(),
+        })
+        .collect())
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Unit {
+    #[default]
+    Pixels,
+    Percent,
+}
+
+/// A point on the screen. With a `display`, `x` and `y` count from its
+/// top-left corner. Without one, pixels are desktop coordinates and
+/// percentages refer to the primary display.
+#[derive(Deserialize, Clone, Debug)]
+struct Position {
+    x: f64,
+    y: f64,
+    /// `primary`, or a display's name or ID as listed by `list_displays`.
+    display: Option<String>,
+    #[serde(default)]
+    unit: Unit,
+}
+
+impl Position {
+    /// The point in desktop coordinates.
+    fn resolve(&self, displays: &[Display]) -> Result<(i32, i32), String> {
+        let display = match self.display.as_deref() {
+            None if self.unit == Unit::Pixels => {
+                return Ok((self.x.round() as i32, self.y.round() as i32))
+            }
+            None | Some("primary") => displays
+                .iter()
+                .find(|display| display.is_primary)
+                .or(displays.first())
+                .ok_or("No display found")?,
+            Some(wanted) => displays
+                .iter()
+                .find(|display| display.name == wanted || display.id.to_string() == wanted)
+                .ok_or_else(|| format!("Display \"{}\" isn't connected", wanted))?,
+        };
+        let (x, y) = match self.unit {
+            Unit::Pixels => (self.x.round() as i32, self.y.round() as i32),
+            // 100% is the last pixel, not the one past the edge
+            Unit::Percent => (
+                percent_of(self.x, display.width),
+                percent_of(self.y, display.height),
+            ),
+        };
+        Ok((display.x + x, display.y + y))
+    }
+}
+
+fn percent_of(percent: f64, size: u32) -> i32 {
+    let max = size.saturating_sub(1) as f64;
+    (percent / 100.0 * size as f64).round().clamp(0.0, max) as i32
+}
+
+/// Moves the pointer.
+#[derive(Deserialize)]
+struct MouseMove {
+    #[serde(flatten)]
+    position: Position,
+}
+
+impl Action for MouseMove {
+    const TYPE: &'static str = "mouse_move";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        let (x, y) = self.position.resolve(&displays()?)?;
+        mouse::move_to(x, y)
+    }
+}
+
+/// Clicks at a point, `count` times for double or triple clicks.
+#[derive(Deserialize)]
+struct MouseClick {
+    #[serde(flatten)]
+    position: Position,
+    #[serde(default)]
+    button: MouseButton,
+    count: Option<u32>,
+}
+
+impl Action for MouseClick {
+    const TYPE: &'static str = "mouse_click";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        let (x, y) = self.position.resolve(&displays()?)?;
+        mouse::click_at(x, y, self.button, self.count.unwrap_or(1))
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<MouseMove>();
+    registry.register::<MouseClick>();
+}
+
+// Display-related Tauri commands
+
+/// Lists the connected displays, for picking where mouse steps point.
+///
+/// # Returns
+///
+/// * `Result<Vec<Display>, String>` - The displays, with their position in desktop coordinates.
+#[tauri::command]
+pub fn list_displays() -> Result<Vec<Display>, String> {
+    displays()
+}
@@ -0,0 +1,260 @@
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::events::{next, AppEvent};
+use crate::home_assistant::sync_discovery;
+use crate::secrets::{delete_secret, read_secret, store_secret};
+use crate::settings::SettingsStore;
+use crate::sockets::ServerContext;
+use crate::triggers::{self, TriggerSource};
+
+// Bridges the deck to an MQTT broker for Home Assistant, Node-RED and the like:
+//
+//   <prefix>/status          - "online", or "offline" as the retained last will
+//   <prefix>/events/<event>  - every activity entry, e.g. `shortcut_executed`
+//   <prefix>/trigger         - publish a shortcut ID here to run it
+//
+// With `home_assistant` set, every shortcut is also announced as a Home
+// Assistant button entity through MQTT discovery.
+//
+// Anyone who can publish to the broker can run shortcuts, so the broker is
+// expected to be private or to require credentials.
+
+/// Keychain entry holding the broker password.
+const PASSWORD_SECRET_ID: &str = "mqtt-password";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// The password is kept in the OS keychain.
+    pub username: Option<String>,
+    /// Prepended to every topic, so several desktops can share a broker.
+    pub topic_prefix: String,
+    /// Publish Home Assistant discovery configs for the shortcuts.
+    #[serde(default)]
+    pub home_assistant: bool,
+    /// Home Assistant's discovery prefix; `homeassistant` when unset.
+    #[serde(default)]
+    pub discovery_prefix: Option<String>,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 1883,
+            username: None,
+            topic_prefix: "button-beam".to_string(),
+            home_assistant: false,
+            discovery_prefix: None,
+        }
+    }
+}
+
+impl MqttSettings {
+    pub fn topic(&self, suffix: &str) -> String {
+        format!("{}/{}", self.topic_prefix.trim_end_matches('/'), suffix)
+    }
+}
+
+/// Keeps the broker connection and the activity forwarder running while the
+/// bridge is enabled.
+pub struct MqttBridge {
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl MqttBridge {
+    pub fn new() -> Self {
+        Self {
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub async fn start(&self, settings: MqttSettings, ctx: ServerContext) -> Result<(), String> {
+        let mut tasks = self.tasks.lock().await;
+        if !tasks.is_empty() {
+            return Ok(());
+        }
+
+        let client_id = format!("button-beam-{}", uuid::Uuid::new_v4().simple());
+        let mut options = MqttOptions::new(client_id, settings.host.clone(), settings.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            settings.topic("status"),
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &settings.username {
+            let password = read_secret(PASSWORD_SECRET_ID).unwrap_or_default();
+            options.set_credentials(username.clone(), password);
+        }
+
+        let (client, eventloop) = AsyncClient::new(options, 16);
+        let connected = Arc::new(Notify::new());
+        info!(
+            "Bridging to MQTT broker at {}:{}",
+            settings.host, settings.port
+        );
+        tasks.push(tokio::spawn(poll(
+            client.clone(),
+            eventloop,
+            settings.clone(),
+            ctx.clone(),
+            Arc::clone(&connected),
+        )));
+        if settings.home_assistant {
+            tasks.push(tokio::spawn(sync_discovery(
+                client.clone(),
+                settings.clone(),
+                Arc::clone(&ctx.store),
+                Arc::clone(&ctx.events),
+                connected,
+            )));
+        }
+        tasks.push(tokio::spawn(forward_activity(client, settings, ctx)));
+        Ok(())
+    }
+
+    /// Drops the connection without a clean disconnect, so the broker
+    /// publishes the "offline" last will.
+    pub async fn stop(&self) {
+        let mut tasks = self.tasks.lock().await;
+        if tasks.is_empty() {
+            return;
+        }
+        for task in tasks.drain(..) {
+            task.abort();
+        }
+        info!("Stopped the MQTT bridge.");
+    }
+}
+
+impl Default for MqttBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drives the connection, reconnecting after errors, and runs the shortcuts
+/// published to the trigger topic.
+async fn poll(
+    client: AsyncClient,
+    mut eventloop: EventLoop,
+    settings: MqttSettings,
+    ctx: ServerContext,
+    connected: Arc<Notify>,
+) {
+    let trigger_topic = settings.topic("trigger");
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("Connected to MQTT broker");
+                // Subscriptions don't survive a reconnect with a clean session
+                if let Err(e) = client.try_subscribe(&trigger_topic, QoS::AtLeastOnce) {
+                    warn!("Failed to subscribe to {}: {}", trigger_topic, e);
+                }
+                if let Err(e) =
+                    client.try_publish(settings.topic("status"), QoS::AtLeastOnce, true, "online")
+                {
+                    warn!("Failed to publish MQTT status: {}", e);
+                }
+                connected.notify_one();
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == trigger_topic => {
+                let id = std::str::from_utf8(&publish.payload)
+                    .ok()
+                    .and_then(|payload| payload.trim().parse::<u64>().ok());
+                match id {
+                    Some(id) => {
+                        tokio::spawn(trigger_shortcut(id, ctx.clone()));
+                    }
+                    None => warn!("Ignoring MQTT trigger without a valid shortcut ID"),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!("MQTT connection error: {}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
+
+/// Publishes every activity entry under `<prefix>/events/<event>`.
+async fn forward_activity(client: AsyncClient, settings: MqttSettings, ctx: ServerContext) {
+    let mut events = ctx.events.subscribe();
+    while let Some(event) = next(&mut events, "MQTT").await {
+        let AppEvent::Activity(entry) = event else {
+            continue;
+        };
+        let Ok(payload) = serde_json::to_value(&entry) else {
+            continue;
+        };
+        let event = payload["event"].as_str().unwrap_or("unknown");
+        let topic = settings.topic(&format!("events/{}", event));
+        if let Err(e) = client
+            .publish(topic, QoS::AtLeastOnce, false, payload.to_string())
+            .await
+        {
+            warn!("Failed to publish activity to MQTT: {}", e);
+        }
+    }
+}
+
+async fn trigger_shortcut(id: u64, ctx: ServerContext) {
+    if let Err(e) = triggers::trigger_shortcut(id, TriggerSource::Mqtt, &ctx).await {
+        warn!("Ignoring MQTT trigger for {}: {}", id, e);
+    }
+}
+
+// MQTT-related Tauri commands
+
+/// Saves the broker settings and starts, restarts or stops the bridge to
+/// match.
+///
+/// # Arguments
+///
+/// * `mqtt` - The broker connection and whether the bridge is enabled.
+/// * `password` - A new broker password; empty to remove it, `None` to keep it.
+/// * `settings` - Shared state containing the settings.
+/// * `bridge` - The MQTT bridge.
+/// * `ctx` - The server context, used to run triggered shortcuts.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_mqtt_bridge(
+    mqtt: MqttSettings,
+    password: Option<String>,
+    settings: State<'_, Arc<SettingsStore>>,
+    bridge: State<'_, Arc<MqttBridge>>,
+    ctx: State<'_, ServerContext>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    match password.as_deref() {
+        Some("") => delete_secret(PASSWORD_SECRET_ID),
+        Some(password) => store_secret(PASSWORD_SECRET_ID, password)?,
+        None => {}
+    }
+
+    // Saved first, so the bridge never runs with settings that aren't kept
+    settings.update(&app_handle, |current| current.mqtt = mqtt.clone())?;
+
+    bridge.stop().await;
+    if mqtt.enabled {
+        bridge.start(mqtt, ctx.inner().clone()).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use tauri::api::notification::Notification;
+use tauri::{AppHandle, Manager};
+use tracing::error;
+
+use crate::settings::SettingsStore;
+
+/// Remote activity the user can choose to be notified about.
+#[derive(Clone, Copy, Debug)]
+pub enum NotificationKind {
+    DeviceConnected,
+    DeviceRejected,
+    ShortcutTriggered,
+}
+
+impl NotificationKind {
+    fn title(self) -> &'static str {
+        match self {
+            NotificationKind::DeviceConnected => "Device connected",
+            NotificationKind::DeviceRejected => "Device rejected",
+            NotificationKind::ShortcutTriggered => "Shortcut triggered",
+        }
+    }
+}
+
+/// Shows a native notification if the user enabled this kind in the settings.
+pub fn notify(app_handle: &AppHandle, kind: NotificationKind, body: impl Into<String>) {
+    let notifications = app_handle
+        .state::<Arc<SettingsStore>>()
+        .get_settings()
+        .notifications;
+    let enabled = match kind {
+        NotificationKind::DeviceConnected => notifications.device_connected,
+        NotificationKind::DeviceRejected => notifications.device_rejected,
+        NotificationKind::ShortcutTriggered => notifications.shortcut_triggered,
+    };
+    if !enabled {
+        return;
+    }
+
+    let identifier = &app_handle.config().tauri.bundle.identifier;
+    if let Err(e) = Notification::new(identifier)
+        .title(kind.title())
+        .body(body)
+        .show()
+    {
+        error!("Failed to show notification: {}", e);
+    }
+}
@@ -0,0 +1,49 @@
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::State;
+
+use crate::devices::{DeviceRegistry, TrustState};
+use crate::permissions::input_permissions;
+use crate::server::ServerHandle;
+
+/// What the setup guide still has to walk a new user through.
+#[derive(Serialize, Clone, Debug)]
+pub struct OnboardingStatus {
+    /// Whether the OS lets the app send synthetic key presses, see
+    /// [`crate::permissions::InputPermissions::accessibility`].
+    pub input_permission: bool,
+    /// The port the server is listening on, or `None` if it failed to bind.
+    pub port: Option<u16>,
+    /// Whether any phone has ever been approved.
+    pub has_paired_device: bool,
+}
+
+// Onboarding-related Tauri commands
+
+/// Reports which setup steps are done, so the frontend can guide new users.
+///
+/// # Arguments
+///
+/// * `server` - The WebSocket server, to check whether it is listening.
+/// * `registry` - Shared state containing the known devices.
+///
+/// # Returns
+///
+/// * `Result<OnboardingStatus, String>` - The setup status or an error message.
+#[tauri::command]
+pub async fn get_onboarding_status(
+    server: State<'_, Arc<ServerHandle>>,
+    registry: State<'_, Arc<DeviceRegistry>>,
+) -> Result<OnboardingStatus, String> {
+    let port = server.advertised_addr().await.map(|(_, port)| port);
+    let has_paired_device = registry
+        .get_devices()
+        .iter()
+        .any(|device| device.trust == Some(TrustState::Trusted));
+
+    Ok(OnboardingStatus {
+        input_permission: input_permissions().accessibility,
+        port,
+        has_paired_device,
+    })
+}
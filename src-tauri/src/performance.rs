@@ -0,0 +1,123 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+// Timings of shortcuts triggered by devices, from the moment the message
+// arrived until the last key event was sent, so the user can see where a
+// trigger that feels sluggish spends its time:
+//
+//   queue      - receipt until the sequence starts: decoding, rate limiting,
+//                looking the shortcut up and waiting for a blocking thread
+//   execution  - running the steps, including their intervals
+//
+// Only the most recent triggers are kept, in memory.
+
+/// Triggers kept for `get_performance_stats`.
+const SAMPLES: usize = 200;
+
+/// How long one trigger took, in milliseconds.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct TriggerLatency {
+    pub queue_ms: f64,
+    pub execution_ms: f64,
+    pub total_ms: f64,
+}
+
+impl TriggerLatency {
+    pub fn new(received: Instant, started: Instant, finished: Instant) -> Self {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        Self {
+            queue_ms: ms(started.duration_since(received)),
+            execution_ms: ms(finished.duration_since(started)),
+            total_ms: ms(finished.duration_since(received)),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct StageStats {
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl StageStats {
+    fn of(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        // Nearest rank, so small sample counts still pick a real measurement
+        let percentile = |p: f64| values[((values.len() as f64 * p).ceil() as usize).max(1) - 1];
+        Some(Self {
+            min_ms: values[0],
+            median_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: values[values.len() - 1],
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PerformanceStats {
+    /// Number of triggers the figures are taken from.
+    pub samples: usize,
+    pub queue: Option<StageStats>,
+    pub execution: Option<StageStats>,
+    pub total: Option<StageStats>,
+    /// The most recent trigger.
+    pub last: Option<TriggerLatency>,
+}
+
+/// The latencies of the most recent triggers.
+#[derive(Default)]
+pub struct PerformanceMonitor {
+    samples: Mutex<VecDeque<TriggerLatency>>,
+}
+
+impl PerformanceMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, latency: TriggerLatency) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    pub fn stats(&self) -> PerformanceStats {
+        let samples = self.samples.lock().unwrap();
+        let stage = |f: fn(&TriggerLatency) -> f64| StageStats::of(samples.iter().map(f).collect());
+        PerformanceStats {
+            samples: samples.len(),
+            queue: stage(|l| l.queue_ms),
+            execution: stage(|l| l.execution_ms),
+            total: stage(|l| l.total_ms),
+            last: samples.back().copied(),
+        }
+    }
+}
+
+// Performance-related Tauri commands
+
+/// Returns how long recent triggers took from receipt to the last key event.
+///
+/// # Arguments
+///
+/// * `monitor` - Shared state holding the recent timings.
+///
+/// # Returns
+///
+/// * `Result<PerformanceStats, String>` - Statistics per stage over the recent triggers.
+#[tauri::command]
+pub fn get_performance_stats(
+    monitor: State<Arc<PerformanceMonitor>>,
+) -> Result<PerformanceStats, String> {
+    Ok(monitor.stats())
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+
+// The OS permissions key simulation and the recorder depend on. macOS drops
+// synthetic key events from apps without the Accessibility permission
+// without any error, so key steps are refused with an explanation instead,
+// and the frontend can open the pane where the permission is granted.
+// Windows drops them the same way when the window in front runs as
+// administrator and the app doesn't.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Sending key presses to other apps.
+    Accessibility,
+    /// Listening to key presses, for the recorder.
+    InputMonitoring,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct InputPermissions {
+    /// Whether the app may send key presses: the Accessibility permission on
+    /// macOS, write access to `/dev/uinput` (usually through the `input`
+    /// group) on Linux, or a running ydotool daemon on Wayland. Always true
+    /// on Windows.
+    pub accessibility: bool,
+    /// Whether the app may listen to key presses: the Input Monitoring
+    /// permission on macOS. Always true elsewhere.
+    pub input_monitoring: bool,
+    /// Whether the app runs as administrator on Windows, which it has to for
+    /// keys to reach windows that do.
+    pub elevated: bool,
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOHIDCheckAccess(request_type: u32) -> u32;
+    }
+
+    const K_IOHID_REQUEST_TYPE_LISTEN_EVENT: u32 = 1;
+    const K_IOHID_ACCESS_TYPE_GRANTED: u32 = 0;
+
+    pub fn accessibility() -> bool {
+        unsafe { AXIsProcessTrusted() }
+    }
+
+    pub fn input_monitoring() -> bool {
+        unsafe {
+            IOHIDCheckAccess(K_IOHID_REQUEST_TYPE_LISTEN_EVENT) == K_IOHID_ACCESS_TYPE_GRANTED
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod elevation {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    /// Whether `process` runs elevated, or `None` if its token can't be read.
+    unsafe fn is_elevated(process: HANDLE) -> Option<bool> {
+        let mut token = HANDLE::default();
+        if !OpenProcessToken(process, TOKEN_QUERY, &mut token).as_bool() {
+            return None;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut size = 0;
+        let read = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        )
+        .as_bool();
+        CloseHandle(token);
+        read.then(|| elevation.TokenIsElevated != 0)
+    }
+
+    pub fn app_is_elevated() -> bool {
+        unsafe { is_elevated(GetCurrentProcess()) }.unwrap_or(false)
+    }
+
+    /// Whether the window in front belongs to an elevated process. Their
+    /// tokens can't be read without being elevated, so an unreadable token
+    /// counts as elevated.
+    pub fn foreground_is_elevated() -> bool {
+        unsafe {
+            let window = GetForegroundWindow();
+            if window.is_invalid() {
+                return false;
+            }
+            let mut process_id = 0;
+            GetWindowThreadProcessId(window, &mut process_id);
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id);
+            if process.is_invalid() {
+                return false;
+            }
+            let elevated = is_elevated(process);
+            CloseHandle(process);
+            elevated.unwrap_or(true)
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn input_permissions() -> InputPermissions {
+    InputPermissions {
+        accessibility: macos::accessibility(),
+        input_monitoring: macos::input_monitoring(),
+        elevated: false,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn input_permissions() -> InputPermissions {
+    use button_beam_core::wayland;
+
+    let accessibility = if wayland::is_wayland_session() {
+        wayland::ydotool_status().ready()
+    } else {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/uinput")
+            .is_ok()
+    };
+    InputPermissions {
+        accessibility,
+        input_monitoring: true,
+        elevated: false,
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn input_permissions() -> InputPermissions {
+    InputPermissions {
+        accessibility: true,
+        input_monitoring: true,
+        elevated: elevation::app_is_elevated(),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub fn input_permissions() -> InputPermissions {
+    InputPermissions {
+        accessibility: true,
+        input_monitoring: true,
+        elevated: false,
+    }
+}
+
+/// Fails with what to do when the OS would drop the key presses: on macOS
+/// without the Accessibility permission, on Windows when the window in front
+/// runs as administrator. Elsewhere keys are sent regardless, since X11
+/// doesn't need a permission and Wayland falls back to XWayland windows.
+pub fn ensure_can_send_keys() -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    if elevation::foreground_is_elevated() && !elevation::app_is_elevated() {
+        return Err(
+            "The window in front runs as administrator, so Windows doesn't let \
+             Button Beam type into it. Run Button Beam as administrator to control it"
+                .to_string(),
+        );
+    }
+    if cfg!(target_os = "macos") && !input_permissions().accessibility {
+        return Err(
+            "Button Beam isn't allowed to control the keyboard. Turn it on in \
+             System Settings > Privacy & Security > Accessibility, then try again"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Fails with what to do when macOS would hide key presses from the recorder.
+pub fn ensure_can_listen_to_keys() -> Result<(), String> {
+    if cfg!(target_os = "macos") && !input_permissions().input_monitoring {
+        return Err(
+            "Button Beam isn't allowed to see key presses. Turn it on in \
+             System Settings > Privacy & Security > Input Monitoring, then try again"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+// Permission-related Tauri commands
+
+/// Reports which input permissions the app has.
+///
+/// # Returns
+///
+/// * `Result<InputPermissions, String>` - Whether keys may be sent and listened to.
+#[tauri::command]
+pub fn check_input_permissions() -> Result<InputPermissions, String> {
+    Ok(input_permissions())
+}
+
+/// Opens the System Settings pane where `permission` is granted.
+///
+/// # Arguments
+///
+/// * `permission` - The permission to open the settings for.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn open_permission_settings(permission: Permission) -> Result<(), String> {
+    if !cfg!(target_os = "macos") {
+        return Err("This system doesn't ask for input permissions".to_string());
+    }
+    let pane = match permission {
+        Permission::Accessibility => "Privacy_Accessibility",
+        Permission::InputMonitoring => "Privacy_ListenEvent",
+    };
+    std::process::Command::new("open")
+        .arg(format!(
+            "x-apple.systempreferences:com.apple.preference.security?{}",
+            pane
+        ))
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to open System Settings: {}", e))
+}
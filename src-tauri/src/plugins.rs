@@ -0,0 +1,180 @@
+use extism::{Manifest, Plugin, Wasm};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::State;
+use tracing::{info, warn};
+
+use crate::actions::ActionRegistry;
+
+// Loads `.wasm` plugins from the `plugins` folder in the app data folder, so
+// others can add step types without forking the app. Plugins are Extism
+// modules exporting two functions:
+//
+//   describe   - takes nothing and returns JSON such as
+//                `{"name": "Stream Tools", "actions": [{"type": "...",
+//                "name": "...", "settings": ...}]}`; `settings` is optional
+//                and passed through to the frontend to build the step editor
+//   run_action - takes JSON `{"type": "...", "params": {...}}` with the
+//                step's fields, and fails with a message if the step failed
+//
+// Plugins run in a sandbox without access to files or the network, and are
+// loaded once at startup. A call taking longer than `CALL_TIMEOUT` is stopped.
+// Plugins built against WASI must say so in a `.json` file of the same name,
+// e.g. `stream-tools.json` next to `stream-tools.wasm`, holding
+// `{"wasi": true}`; others are loaded without it.
+
+/// How long `describe` or a `run_action` call may run.
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PluginAction {
+    /// The step `type` this action runs.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    /// How the frontend should present the step's fields, as the plugin
+    /// describes them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Value>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PluginInfo {
+    pub file: PathBuf,
+    pub name: String,
+    /// The actions that were registered; those clashing with an existing step
+    /// type are left out.
+    pub actions: Vec<PluginAction>,
+}
+
+/// The optional `.json` file next to a plugin.
+#[derive(Deserialize, Default)]
+struct PluginOptions {
+    #[serde(default)]
+    wasi: bool,
+}
+
+impl PluginOptions {
+    fn read(file: &Path) -> Result<Self, String> {
+        let path = file.with_extension("json");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+}
+
+#[derive(Deserialize)]
+struct Description {
+    name: String,
+    #[serde(default)]
+    actions: Vec<PluginAction>,
+}
+
+/// The plugins loaded at startup.
+pub struct LoadedPlugins(pub Vec<PluginInfo>);
+
+/// Loads every plugin in `dir` and registers its actions. Plugins that fail
+/// to load are logged and skipped.
+pub fn load(dir: &Path, registry: &mut ActionRegistry) -> LoadedPlugins {
+    // Created up front so users know where plugins go
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        warn!(
+            "Failed to create the plugins folder {}: {}",
+            dir.display(),
+            e
+        );
+        return LoadedPlugins(Vec::new());
+    }
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read the plugins folder {}: {}", dir.display(), e);
+            return LoadedPlugins(Vec::new());
+        }
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+        .collect();
+    files.sort();
+
+    let mut plugins = Vec::new();
+    for file in files {
+        match load_plugin(&file, registry) {
+            Ok(plugin) => {
+                info!(
+                    "Loaded plugin {} with {} actions",
+                    plugin.name,
+                    plugin.actions.len()
+                );
+                plugins.push(plugin);
+            }
+            Err(e) => warn!("Failed to load plugin {}: {}", file.display(), e),
+        }
+    }
+    LoadedPlugins(plugins)
+}
+
+fn load_plugin(file: &Path, registry: &mut ActionRegistry) -> Result<PluginInfo, String> {
+    let options = PluginOptions::read(file)?;
+    let manifest = Manifest::new([Wasm::file(file)]).with_timeout(CALL_TIMEOUT);
+    let mut plugin = Plugin::new(&manifest, [], options.wasi).map_err(|e| e.to_string())?;
+    let description = plugin
+        .call::<&str, String>("describe", "")
+        .map_err(|e| format!("describe failed: {}", e))?;
+    let description: Description = serde_json::from_str(&description)
+        .map_err(|e| format!("Invalid plugin description: {}", e))?;
+
+    let plugin = Arc::new(Mutex::new(plugin));
+    let mut actions = Vec::new();
+    for action in description.actions {
+        if registry.contains(&action.kind) {
+            warn!(
+                "Plugin {} can't add step type {}, it already exists",
+                description.name, action.kind
+            );
+            continue;
+        }
+        let plugin = Arc::clone(&plugin);
+        let kind = action.kind.clone();
+        registry.register_fn(action.kind.clone(), move |params, _ctx| {
+            let input = json!({ "type": kind, "params": params }).to_string();
+            plugin
+                .lock()
+                .unwrap()
+                .call::<&str, &str>("run_action", &input)
+                .map(|_| ())
+                .map_err(|e| format!("Plugin step {} failed: {}", kind, e))
+        });
+        actions.push(action);
+    }
+
+    Ok(PluginInfo {
+        file: file.to_path_buf(),
+        name: description.name,
+        actions,
+    })
+}
+
+// Plugin-related Tauri commands
+
+/// Lists the loaded plugins and the step types they add.
+///
+/// # Arguments
+///
+/// * `plugins` - The plugins loaded at startup.
+///
+/// # Returns
+///
+/// * `Result<Vec<PluginInfo>, String>` - The plugins or an error message.
+#[tauri::command]
+pub fn get_plugins(plugins: State<LoadedPlugins>) -> Result<Vec<PluginInfo>, String> {
+    Ok(plugins.0.clone())
+}
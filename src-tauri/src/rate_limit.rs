@@ -0,0 +1,43 @@
+use std::time::Instant;
+
+/// Token bucket allowing bursts of up to one second's worth of events.
+pub struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new() -> Self {
+        Self {
+            // Starts full; clamped to the bucket size on first use
+            tokens: f64::INFINITY,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes one token if available. `rate` is the number of events allowed
+    /// per second; zero or less disables limiting.
+    pub fn try_take(&mut self, rate: f64) -> bool {
+        if rate <= 0.0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed * rate).min(rate.max(1.0));
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for TokenBucket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,234 @@
+use rdev::{listen, Event, EventType, Key};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+use tracing::error;
+
+use crate::permissions::ensure_can_listen_to_keys;
+
+// Captures key presses while the user is creating a shortcut, so they can
+// press the combo instead of typing "Ctrl+Shift+F12". Combos use the same
+// names the sequence runner understands.
+
+#[derive(Default)]
+struct RecordingState {
+    active: bool,
+    /// Modifiers currently held down, in press order.
+    modifiers: Vec<&'static str>,
+    combos: Vec<String>,
+}
+
+/// Owns the global keyboard hook. rdev's hook can't be removed once
+/// installed, so it is started on first use and ignores input while idle.
+pub struct Recorder {
+    state: Arc<Mutex<RecordingState>>,
+    hooked: Mutex<bool>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RecordingState::default())),
+            hooked: Mutex::new(false),
+        }
+    }
+
+    fn ensure_hooked(&self, app_handle: &AppHandle) -> Result<(), String> {
+        let mut hooked = self.hooked.lock().map_err(|e| e.to_string())?;
+        if *hooked {
+            return Ok(());
+        }
+
+        let state = Arc::clone(&self.state);
+        let app_handle = app_handle.clone();
+        std::thread::spawn(move || {
+            let callback_handle = app_handle.clone();
+            let result = listen(move |event| handle_event(event, &state, &callback_handle));
+            // `listen` only returns if the hook could not be installed
+            if let Err(e) = result {
+                error!("Failed to listen to the keyboard: {:?}", e);
+                app_handle
+                    .emit_all("recording_failed", format!("{:?}", e))
+                    .ok();
+            }
+        });
+        *hooked = true;
+        Ok(())
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn modifier_name(key: Key) -> Option<&'static str> {
+    match key {
+        Key::ControlLeft | Key::ControlRight => Some("Ctrl"),
+        Key::Alt | Key::AltGr => Some("Alt"),
+        Key::ShiftLeft | Key::ShiftRight => Some("Shift"),
+        Key::MetaLeft | Key::MetaRight => Some("Cmd"),
+        _ => None,
+    }
+}
+
+fn key_name(key: Key) -> Option<String> {
+    let name = match key {
+        Key::Return | Key::KpReturn => "Enter",
+        Key::Tab => "Tab",
+        Key::Backspace => "Backspace",
+        Key::Space => "Space",
+        Key::Escape => "Escape",
+        Key::Delete => "Delete",
+        Key::Insert => "Insert",
+        Key::Home => "Home",
+        Key::End => "End",
+        Key::PageUp => "PageUp",
+        Key::PageDown => "PageDown",
+        Key::UpArrow => "Up",
+        Key::DownArrow => "Down",
+        Key::LeftArrow => "Left",
+        Key::RightArrow => "Right",
+        Key::F1 => "F1",
+        Key::F2 => "F2",
+        Key::F3 => "F3",
+        Key::F4 => "F4",
+        Key::F5 => "F5",
+        Key::F6 => "F6",
+        Key::F7 => "F7",
+        Key::F8 => "F8",
+        Key::F9 => "F9",
+        Key::F10 => "F10",
+        Key::F11 => "F11",
+        Key::F12 => "F12",
+        Key::Num0 | Key::Kp0 => "0",
+        Key::Num1 | Key::Kp1 => "1",
+        Key::Num2 | Key::Kp2 => "2",
+        Key::Num3 | Key::Kp3 => "3",
+        Key::Num4 | Key::Kp4 => "4",
+        Key::Num5 | Key::Kp5 => "5",
+        Key::Num6 | Key::Kp6 => "6",
+        Key::Num7 | Key::Kp7 => "7",
+        Key::Num8 | Key::Kp8 => "8",
+        Key::Num9 | Key::Kp9 => "9",
+        Key::Minus | Key::KpMinus => "-",
+        Key::Equal => "=",
+        Key::LeftBracket => "[",
+        Key::RightBracket => "]",
+        Key::SemiColon => ";",
+        Key::Quote => "'",
+        Key::BackQuote => "`",
+        Key::BackSlash | Key::IntlBackslash => "\\",
+        Key::Comma => ",",
+        Key::Dot => ".",
+        Key::Slash | Key::KpDivide => "/",
+        Key::KpPlus => "Plus",
+        Key::KpMultiply => "*",
+        _ => return letter_name(key),
+    };
+    Some(name.to_string())
+}
+
+fn letter_name(key: Key) -> Option<String> {
+    let letter = match key {
+        Key::KeyA => 'a',
+        Key::KeyB => 'b',
+        Key::KeyC => 'c',
+        Key::KeyD => 'd',
+        Key::KeyE => 'e',
+        Key::KeyF => 'f',
+        Key::KeyG => 'g',
+        Key::KeyH => 'h',
+        Key::KeyI => 'i',
+        Key::KeyJ => 'j',
+        Key::KeyK => 'k',
+        Key::KeyL => 'l',
+        Key::KeyM => 'm',
+        Key::KeyN => 'n',
+        Key::KeyO => 'o',
+        Key::KeyP => 'p',
+        Key::KeyQ => 'q',
+        Key::KeyR => 'r',
+        Key::KeyS => 's',
+        Key::KeyT => 't',
+        Key::KeyU => 'u',
+        Key::KeyV => 'v',
+        Key::KeyW => 'w',
+        Key::KeyX => 'x',
+        Key::KeyY => 'y',
+        Key::KeyZ => 'z',
+        _ => return None,
+    };
+    Some(letter.to_string())
+}
+
+fn handle_event(event: Event, state: &Mutex<RecordingState>, app_handle: &AppHandle) {
+    let Ok(mut state) = state.lock() else {
+        return;
+    };
+    if !state.active {
+        return;
+    }
+
+    match event.event_type {
+        EventType::KeyPress(key) => {
+            if let Some(modifier) = modifier_name(key) {
+                if !state.modifiers.contains(&modifier) {
+                    state.modifiers.push(modifier);
+                }
+            } else if let Some(name) = key_name(key) {
+                let mut parts: Vec<&str> = ["Ctrl", "Alt", "Shift", "Cmd"]
+                    .into_iter()
+                    .filter(|m| state.modifiers.contains(m))
+                    .collect();
+                parts.push(&name);
+                let combo = parts.join("+");
+                state.combos.push(combo);
+                app_handle.emit_all("recording_updated", &state.combos).ok();
+            }
+        }
+        EventType::KeyRelease(key) => {
+            if let Some(modifier) = modifier_name(key) {
+                state.modifiers.retain(|m| *m != modifier);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Recorder-related Tauri commands
+
+/// Starts capturing key presses. Each combo pressed is reported with a
+/// `recording_updated` event carrying everything captured so far.
+///
+/// # Arguments
+///
+/// * `recorder` - The key recorder.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn start_recording(
+    recorder: State<Arc<Recorder>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    ensure_can_listen_to_keys()?;
+    recorder.ensure_hooked(&app_handle)?;
+    let mut state = recorder.state.lock().map_err(|e| e.to_string())?;
+    *state = RecordingState {
+        active: true,
+        ..RecordingState::default()
+    };
+    Ok(())
+}
+
+/// Stops capturing and returns the recorded sequence, e.g. `["Ctrl+Shift+F12"]`.
+#[tauri::command]
+pub fn stop_recording(recorder: State<Arc<Recorder>>) -> Result<Vec<String>, String> {
+    let mut state = recorder.state.lock().map_err(|e| e.to_string())?;
+    state.active = false;
+    state.modifiers.clear();
+    Ok(std::mem::take(&mut state.combos))
+}
@@ -0,0 +1,502 @@
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeDelta, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::devices::now_millis;
+use crate::error::{read_json_or_default, write_json, Error};
+use crate::shortcuts::ShortcutStore;
+use crate::sockets::ServerContext;
+use crate::triggers::{self, TriggerSource};
+
+// Runs shortcuts on a timetable, e.g. typing a standup template every weekday
+// at 9:55. Cron expressions use the usual five fields in local time:
+//
+//   minute hour day-of-month month day-of-week
+//
+// with `*`, lists, ranges, `/` steps and three-letter month and day names.
+// As in cron, a day matches if either day field does when both are set. Only
+// a bare `*` leaves a day field unset; a step like `*/2` sets it.
+
+/// How often a schedule fires.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScheduleTrigger {
+    Cron {
+        expression: String,
+    },
+    /// Counted from when the schedule was enabled or the app started.
+    Interval {
+        seconds: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Schedule {
+    pub id: u64,
+    pub shortcut_id: u64,
+    pub trigger: ScheduleTrigger,
+    pub enabled: bool,
+}
+
+/// Runs that are this late, e.g. because the computer was asleep, are
+/// skipped rather than typing into whatever is open now.
+const MAX_LATENESS: TimeDelta = TimeDelta::minutes(1);
+
+/// Upper bound on a single wait, so changes to the wall clock are noticed.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day fields are a bare `*`, see `matches_day`.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Parses one field into a bit set of the values it matches.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let value = match names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(text))
+        {
+            Some(index) => min + index as u32,
+            None => text
+                .parse()
+                .map_err(|_| format!("Invalid cron value: {}", text))?,
+        };
+        if value < min || value > max {
+            return Err(format!("{} is outside {}-{}", value, min, max));
+        }
+        Ok(value)
+    };
+
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|&step| step > 0)
+                    .ok_or_else(|| format!("Invalid cron step: {}", part))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // `5/15` means from 5 to the end in steps of 15
+                None if step.is_some() => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return Err(format!("Invalid cron range: {}", range));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronExpression {
+    fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "A cron expression needs 5 fields, got {}: {}",
+                fields.len(),
+                expression
+            ));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAYS)?;
+        // Both 0 and 7 are Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, &[])?,
+            hours: parse_field(hour, 0, 23, &[])?,
+            days: parse_field(day, 1, 31, &[])?,
+            months: parse_field(month, 1, 12, &MONTHS)?,
+            weekdays,
+            // `*/2` restricts the days even though it starts with `*`
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        let day = match (self.any_day, self.any_weekday) {
+            (true, _) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        };
+        day && self.months & (1 << time.month()) != 0
+    }
+
+    /// First matching minute after `after`, searching a few years ahead so
+    /// that e.g. the 29th of February is still found.
+    fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)?;
+        let mut time = start + TimeDelta::minutes(1);
+        let limit = start + TimeDelta::days(4 * 366);
+        while time < limit {
+            if !self.matches_day(&time) {
+                time = (time.date() + TimeDelta::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + TimeDelta::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += TimeDelta::minutes(1);
+            } else {
+                // Times skipped by a daylight saving change don't exist
+                match Local.from_local_datetime(&time).earliest() {
+                    Some(time) => return Some(time),
+                    None => time += TimeDelta::minutes(1),
+                }
+            }
+        }
+        None
+    }
+}
+
+impl ScheduleTrigger {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ScheduleTrigger::Cron { expression } => CronExpression::parse(expression).map(|_| ()),
+            ScheduleTrigger::Interval { seconds: 0 } => {
+                Err("An interval must be at least one second".into())
+            }
+            ScheduleTrigger::Interval { .. } => Ok(()),
+        }
+    }
+
+    fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            ScheduleTrigger::Cron { expression } => {
+                CronExpression::parse(expression).ok()?.next_after(after)
+            }
+            ScheduleTrigger::Interval { seconds } => {
+                Some(after + TimeDelta::seconds(*seconds as i64))
+            }
+        }
+    }
+}
+
+pub struct ScheduleStore {
+    pub schedules: Mutex<Vec<Schedule>>,
+    pub file_path: PathBuf,
+    /// Wakes the scheduler task to pick up edits.
+    changed: Notify,
+}
+
+impl ScheduleStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        let schedules = read_json_or_default(&file_path);
+
+        Self {
+            schedules: Mutex::new(schedules),
+            file_path,
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let schedules = self.schedules.lock().unwrap();
+        write_json(&self.file_path, &*schedules)
+    }
+
+    pub fn get_schedules(&self) -> Vec<Schedule> {
+        self.schedules.lock().unwrap().clone()
+    }
+
+    /// Applies `change` to the list, then saves it and notifies the scheduler
+    /// and the frontend.
+    fn update<R>(
+        &self,
+        app_handle: &AppHandle,
+        change: impl FnOnce(&mut Vec<Schedule>) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let result = change(&mut self.schedules.lock().unwrap())?;
+        self.save()?;
+        self.changed.notify_one();
+        app_handle
+            .emit_all("schedules_updated", self.get_schedules())
+            .map_err(|e| e.to_string())?;
+        Ok(result)
+    }
+}
+
+/// Runs due schedules for the lifetime of the app.
+pub fn spawn_scheduler(ctx: &ServerContext, store: Arc<ScheduleStore>) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        // Next run of each enabled schedule, with the trigger it was computed for
+        let mut next_runs: HashMap<u64, (ScheduleTrigger, DateTime<Local>)> = HashMap::new();
+        loop {
+            let schedules = store.get_schedules();
+            let now = Local::now();
+            next_runs.retain(|id, (trigger, _)| {
+                schedules
+                    .iter()
+                    .any(|s| s.id == *id && s.enabled && s.trigger == *trigger)
+            });
+            for schedule in schedules.iter().filter(|s| s.enabled) {
+                if next_runs.contains_key(&schedule.id) {
+                    continue;
+                }
+                if let Some(next) = schedule.trigger.next_after(now) {
+                    next_runs.insert(schedule.id, (schedule.trigger.clone(), next));
+                }
+            }
+
+            let wait = next_runs
+                .values()
+                .map(|(_, next)| (*next - now).to_std().unwrap_or_default())
+                .min()
+                .unwrap_or(MAX_WAIT)
+                .min(MAX_WAIT);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = store.changed.notified() => continue,
+            }
+
+            let now = Local::now();
+            next_runs.retain(|id, (trigger, next)| {
+                if *next > now {
+                    return true;
+                }
+                if now - *next > MAX_LATENESS {
+                    warn!("Skipping schedule {} missed at {}", id, next);
+                } else if let Some(schedule) = schedules.iter().find(|s| s.id == *id) {
+                    tokio::spawn(run_schedule(schedule.clone(), ctx.clone()));
+                }
+                // Schedules that never fire again are dropped
+                trigger.next_after(now).map(|time| *next = time).is_some()
+            });
+        }
+    });
+}
+
+async fn run_schedule(schedule: Schedule, ctx: ServerContext) {
+    let source = TriggerSource::Schedule { id: schedule.id };
+    if let Err(e) = triggers::trigger_shortcut(schedule.shortcut_id, source, &ctx).await {
+        warn!("Skipping schedule {}: {}", schedule.id, e);
+    }
+}
+
+fn validate(schedule: &Schedule, shortcuts: &ShortcutStore) -> Result<(), String> {
+    schedule.trigger.validate()?;
+    if !shortcuts
+        .get_shortcuts()
+        .iter()
+        .any(|s| s.id == schedule.shortcut_id)
+    {
+        return Err(format!(
+            "Shortcut with id {} not found",
+            schedule.shortcut_id
+        ));
+    }
+    Ok(())
+}
+
+// Schedule-related Tauri commands
+
+#[tauri::command]
+pub fn get_schedules(store: State<Arc<ScheduleStore>>) -> Result<Vec<Schedule>, String> {
+    Ok(store.get_schedules())
+}
+
+/// Adds a schedule with a fresh ID.
+///
+/// # Arguments
+///
+/// * `schedule` - The schedule to add.
+/// * `store` - Shared state containing the schedules.
+/// * `shortcuts` - Shared state containing the shortcuts.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<Schedule, String>` - The stored schedule, or an error message.
+#[tauri::command]
+pub fn add_schedule(
+    mut schedule: Schedule,
+    store: State<Arc<ScheduleStore>>,
+    shortcuts: State<Arc<ShortcutStore>>,
+    app_handle: AppHandle,
+) -> Result<Schedule, String> {
+    validate(&schedule, &shortcuts)?;
+    store.update(&app_handle, |schedules| {
+        schedule.id = schedules
+            .iter()
+            .map(|s| s.id + 1)
+            .max()
+            .unwrap_or(0)
+            .max(now_millis());
+        schedules.push(schedule.clone());
+        Ok(schedule)
+    })
+}
+
+/// Replaces an existing schedule.
+///
+/// # Arguments
+///
+/// * `schedule` - The schedule to update, matched by ID.
+/// * `store` - Shared state containing the schedules.
+/// * `shortcuts` - Shared state containing the shortcuts.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn update_schedule(
+    schedule: Schedule,
+    store: State<Arc<ScheduleStore>>,
+    shortcuts: State<Arc<ShortcutStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    validate(&schedule, &shortcuts)?;
+    store.update(&app_handle, |schedules| {
+        let existing = schedules
+            .iter_mut()
+            .find(|s| s.id == schedule.id)
+            .ok_or_else(|| format!("Schedule with id {} not found", schedule.id))?;
+        *existing = schedule;
+        Ok(())
+    })
+}
+
+/// Turns a schedule on or off without changing its timing.
+///
+/// # Arguments
+///
+/// * `id` - The ID of the schedule.
+/// * `enabled` - Whether the schedule should run.
+/// * `store` - Shared state containing the schedules.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn set_schedule_enabled(
+    id: u64,
+    enabled: bool,
+    store: State<Arc<ScheduleStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    store.update(&app_handle, |schedules| {
+        let schedule = schedules
+            .iter_mut()
+            .find(|s| s.id == id)
+            .ok_or_else(|| format!("Schedule with id {} not found", id))?;
+        schedule.enabled = enabled;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn delete_schedule(
+    id: u64,
+    store: State<Arc<ScheduleStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    store.update(&app_handle, |schedules| {
+        schedules.retain(|s| s.id != id);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_lists_ranges_steps_and_names() {
+        assert_eq!(parse_field("1,3-5", 0, 59, &[]).unwrap(), 0b111010);
+        assert_eq!(
+            parse_field("*/20", 0, 59, &[]).unwrap(),
+            1 | 1 << 20 | 1 << 40
+        );
+        assert_eq!(parse_field("50/5", 0, 59, &[]).unwrap(), 1 << 50 | 1 << 55);
+        assert_eq!(parse_field("mar-may", 1, 12, &MONTHS).unwrap(), 0b111000);
+        let sundays = CronExpression::parse("0 9 * * 7").unwrap();
+        assert_eq!(sundays.weekdays & 1, 1);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!(CronExpression::parse("0 9 * *").is_err());
+        assert!(CronExpression::parse("60 9 * * *").is_err());
+        assert!(CronExpression::parse("0 9 0 * *").is_err());
+        assert!(CronExpression::parse("0 9 * * */0").is_err());
+        assert!(CronExpression::parse("0 9 5-1 * *").is_err());
+        assert!(CronExpression::parse("0 9 * foo *").is_err());
+    }
+
+    #[test]
+    fn either_day_field_matches_when_both_are_set() {
+        // The 13th, and every Friday
+        let cron = CronExpression::parse("0 9 13 * fri").unwrap();
+        assert!(cron.matches_day(&day(2024, 9, 13)));
+        assert!(cron.matches_day(&day(2024, 9, 20)));
+        assert!(!cron.matches_day(&day(2024, 9, 19)));
+        // Only Fridays when the day of the month is a bare `*`
+        let cron = CronExpression::parse("0 9 * * fri").unwrap();
+        assert!(cron.matches_day(&day(2024, 9, 20)));
+        assert!(!cron.matches_day(&day(2024, 9, 19)));
+    }
+
+    #[test]
+    fn stepped_day_fields_count_as_set() {
+        // Odd days of the month, and every Monday
+        let cron = CronExpression::parse("0 9 */2 * mon").unwrap();
+        assert!(!cron.any_day);
+        assert!(cron.matches_day(&day(2024, 9, 1)));
+        assert!(cron.matches_day(&day(2024, 9, 16)));
+        assert!(!cron.matches_day(&day(2024, 9, 18)));
+        // Odd days only, whatever the weekday
+        let cron = CronExpression::parse("0 9 */2 * *").unwrap();
+        assert!(cron.matches_day(&day(2024, 9, 17)));
+        assert!(!cron.matches_day(&day(2024, 9, 16)));
+    }
+
+    #[test]
+    fn finds_the_next_leap_day() {
+        let cron = CronExpression::parse("30 12 29 feb *").unwrap();
+        let after = Local.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        let next = cron.next_after(after).unwrap().naive_local();
+        assert_eq!(next, day(2028, 2, 29).with_minute(30).unwrap());
+    }
+}
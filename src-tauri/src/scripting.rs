@@ -0,0 +1,90 @@
+use rhai::{Engine, EvalAltResult};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::integrations::http;
+use crate::keyboard::{simulate_shortcut, simulate_text_typing};
+use crate::system::clipboard::with_clipboard;
+
+// Runs `script` steps in an embedded Rhai engine, for macros that need
+// conditions or loops. Besides the language itself, scripts can only use:
+//
+//   press(keys)     - presses a key combo, e.g. press("Ctrl+S")
+//   type_text(text) - types text
+//   sleep(ms)       - waits
+//   clipboard()     - the text on the clipboard
+//   http_get(url)   - the body of a web page or API response
+//
+// They can't reach files or other programs, and are stopped when they run
+// longer than MAX_RUNTIME or run away with loops or memory.
+
+const MAX_RUNTIME: Duration = Duration::from_secs(60);
+
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn engine(ctx: &ActionContext) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(1 << 20);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+
+    let deadline = Instant::now() + MAX_RUNTIME;
+    engine.on_progress(move |_| {
+        (Instant::now() > deadline).then(|| "The script ran for too long".into())
+    });
+    engine.on_print(|text| info!("Script: {}", text));
+
+    let interval_ms = ctx.timing.interval_ms;
+    let speed = ctx.typing_speed();
+    engine.register_fn("press", move |keys: &str| -> ScriptResult<()> {
+        simulate_shortcut(vec![keys.to_string()], interval_ms).map_err(Into::into)
+    });
+    engine.register_fn("type_text", move |text: &str| -> ScriptResult<()> {
+        simulate_text_typing(text, speed).map_err(Into::into)
+    });
+    engine.register_fn("sleep", move |ms: i64| {
+        // Never past the deadline, where the script is stopped anyway
+        let duration = Duration::from_millis(ms.max(0) as u64)
+            .min(deadline.saturating_duration_since(Instant::now()));
+        std::thread::sleep(duration);
+    });
+    engine.register_fn("clipboard", || -> ScriptResult<String> {
+        with_clipboard(|clipboard| {
+            clipboard
+                .get_text()
+                .map_err(|e| format!("Failed to read the clipboard: {}", e))
+        })
+        .map_err(Into::into)
+    });
+    engine.register_fn("http_get", |url: &str| -> ScriptResult<String> {
+        http::get_text(url).map_err(Into::into)
+    });
+    engine
+}
+
+/// Runs a Rhai script with the functions listed above.
+#[derive(Deserialize)]
+struct Script {
+    script: String,
+}
+
+impl Action for Script {
+    const TYPE: &'static str = "script";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        engine(ctx)
+            .run(&self.script)
+            .map_err(|e| format!("Script failed: {}", e))
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<Script>();
+}
@@ -0,0 +1,136 @@
+use keyring::Entry;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::keyboard::simulate_text_typing;
+use crate::shortcuts::{Shortcut, Step};
+
+// Service name under which secret text steps are stored in the OS keychain
+const KEYRING_SERVICE: &str = "button-beam-desktop";
+
+fn entry(secret_id: &str) -> Result<Entry, String> {
+    Entry::new(KEYRING_SERVICE, secret_id).map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+pub fn store_secret(secret_id: &str, text: &str) -> Result<(), String> {
+    entry(secret_id)?
+        .set_password(text)
+        .map_err(|e| format!("Failed to store secret {}: {}", secret_id, e))
+}
+
+pub fn read_secret(secret_id: &str) -> Result<String, String> {
+    entry(secret_id)?
+        .get_password()
+        .map_err(|e| format!("Failed to read secret {}: {}", secret_id, e))
+}
+
+pub fn delete_secret(secret_id: &str) {
+    let result = entry(secret_id).and_then(|entry| {
+        entry
+            .delete_password()
+            .map_err(|e| format!("Failed to delete secret {}: {}", secret_id, e))
+    });
+    if let Err(e) = result {
+        error!("{}", e);
+    }
+}
+
+/// Text typed like a plain text step, but kept in the OS keychain.
+///
+/// `text` is only accepted from the frontend when creating or changing the
+/// secret; `extract_secrets` moves it to the keychain before the step is
+/// stored, so it is never written to disk or sent to devices. A step may
+/// only name a secret its own shortcut already uses, see `check_secret_ids`.
+#[derive(Deserialize)]
+struct SecretText {
+    secret_id: Option<String>,
+}
+
+impl Action for SecretText {
+    const TYPE: &'static str = "secret_text";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        // Never log the secret itself
+        self.secret_id
+            .ok_or_else(|| "Secret text step has no secret id".to_string())
+            .and_then(|id| read_secret(&id))
+            .and_then(|text| simulate_text_typing(&text, ctx.typing_speed()))
+            .map_err(|e| format!("Error typing secret text: {}", e))
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<SecretText>();
+}
+
+/// Moves the plaintext of any secret text steps into the keychain, leaving
+/// only their `secret_id` in the shortcut. Steps nested in others, e.g. in a
+/// `random_choice`, are included.
+pub fn extract_secrets(shortcut: &mut Shortcut) -> Result<(), String> {
+    let mut result = Ok(());
+    shortcut.visit_steps_mut(|step| {
+        if result.is_ok() {
+            result = extract_secret(step);
+        }
+    });
+    result
+}
+
+fn extract_secret(step: &mut Step) -> Result<(), String> {
+    let Step::Action(step) = step else {
+        return Ok(());
+    };
+    if step.kind != SecretText::TYPE {
+        return Ok(());
+    }
+    if let Some(text) = step.params.remove("text") {
+        let text = text.as_str().ok_or("Secret text must be a string")?;
+        // Always a new entry; the one it replaces is deleted once the
+        // shortcut is saved without it
+        let id = uuid::Uuid::new_v4().to_string();
+        store_secret(&id, text)?;
+        step.params.insert("secret_id".into(), Value::String(id));
+    }
+    Ok(())
+}
+
+/// Returns the ids of all secrets referenced by a shortcut.
+pub fn secret_ids(shortcut: &Shortcut) -> Vec<String> {
+    let mut ids = Vec::new();
+    shortcut.visit_steps(|step| match step {
+        Step::Action(step) if step.kind == SecretText::TYPE => {
+            ids.extend(
+                step.params
+                    .get("secret_id")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            );
+        }
+        _ => {}
+    });
+    ids
+}
+
+/// Refuses secret text steps naming a secret another shortcut in `shortcuts`
+/// uses, or a keychain entry that was never a step's secret, such as an
+/// integration's token. Whoever may edit the shortcut could have either typed
+/// out, and a shared secret would be deleted along with either shortcut.
+pub fn check_secret_ids(shortcut: &Shortcut, shortcuts: &[Shortcut]) -> Result<(), String> {
+    let ids = secret_ids(shortcut);
+    if ids.iter().any(|id| uuid::Uuid::parse_str(id).is_err()) {
+        return Err("Secret text steps can only use secrets created for them".into());
+    }
+    let shared = shortcuts
+        .iter()
+        .filter(|other| other.id != shortcut.id)
+        .any(|other| secret_ids(other).iter().any(|id| ids.contains(id)));
+    if shared {
+        return Err(format!(
+            "\"{}\" uses the secret of another shortcut",
+            shortcut.name
+        ));
+    }
+    Ok(())
+}
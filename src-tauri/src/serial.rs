@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+use tracing::{info, warn};
+
+use crate::error::emit;
+use crate::settings::SettingsStore;
+use crate::sockets::ServerContext;
+use crate::triggers::{self, TriggerSource};
+
+// Reads lines from a serial port so DIY boards, e.g. an Arduino or ESP32
+// foot pedal, can run shortcuts by printing a token such as `PEDAL1` per
+// press. Every received token is emitted to the frontend as
+// `serial_token_received`, so mappings can be set up by pressing the button.
+
+/// How long a read waits before checking whether to stop.
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SerialSettings {
+    pub enabled: bool,
+    /// e.g. `COM3` or `/dev/ttyUSB0`.
+    pub port: String,
+    pub baud_rate: u32,
+    /// Token to the shortcut it runs.
+    #[serde(default)]
+    pub mappings: BTreeMap<String, u64>,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: String::new(),
+            baud_rate: 9600,
+            mappings: BTreeMap::new(),
+        }
+    }
+}
+
+/// The thread reading the port.
+struct Reader {
+    /// Set to stop the thread.
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// Keeps the reader thread running while serial triggers are enabled.
+pub struct SerialTrigger {
+    reader: Mutex<Option<Reader>>,
+}
+
+impl SerialTrigger {
+    pub fn new() -> Self {
+        Self {
+            reader: Mutex::new(None),
+        }
+    }
+
+    pub fn start(&self, settings: SerialSettings, ctx: ServerContext) -> Result<(), String> {
+        if settings.port.is_empty() {
+            return Err("No serial port is set".into());
+        }
+        let mut current = self.reader.lock().unwrap();
+        if current.is_some() {
+            return Ok(());
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        info!(
+            "Reading triggers from {} at {} baud",
+            settings.port, settings.baud_rate
+        );
+        let thread = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || read_port(settings, ctx, stop))
+        };
+        *current = Some(Reader { stop, thread });
+        Ok(())
+    }
+
+    /// Stops the reader and waits for it to close the port, so the port can
+    /// be opened again right away.
+    pub fn stop(&self) {
+        if let Some(reader) = self.reader.lock().unwrap().take() {
+            reader.stop.store(true, Ordering::SeqCst);
+            if reader.thread.join().is_err() {
+                warn!("The serial reader panicked");
+            }
+            info!("Stopped reading serial triggers.");
+        }
+    }
+}
+
+impl Default for SerialTrigger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Waits `delay`, or less once `stop` is set.
+fn sleep_unless_stopped(delay: Duration, stop: &AtomicBool) {
+    let deadline = Instant::now() + delay;
+    while !stop.load(Ordering::SeqCst) && Instant::now() < deadline {
+        std::thread::sleep(READ_TIMEOUT.min(deadline - Instant::now()));
+    }
+}
+
+/// Reads tokens until stopped, reopening the port after it goes away, e.g.
+/// when the board is unplugged.
+fn read_port(settings: SerialSettings, ctx: ServerContext, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::SeqCst) {
+        let port = match serialport::new(&settings.port, settings.baud_rate)
+            .timeout(READ_TIMEOUT)
+            .open()
+        {
+            Ok(port) => port,
+            Err(e) => {
+                warn!("Failed to open serial port {}: {}", settings.port, e);
+                sleep_unless_stopped(RECONNECT_DELAY, &stop);
+                continue;
+            }
+        };
+
+        let mut reader = BufReader::new(port);
+        let mut line = String::new();
+        while !stop.load(Ordering::SeqCst) {
+            match reader.read_line(&mut line) {
+                // A timeout keeps a partial line in `line` for the next read
+                Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+                Ok(0) => break,
+                Ok(_) => {
+                    handle_token(line.trim(), &settings, &ctx);
+                    line.clear();
+                }
+                Err(e) => {
+                    warn!("Lost serial port {}: {}", settings.port, e);
+                    break;
+                }
+            }
+        }
+        sleep_unless_stopped(RECONNECT_DELAY, &stop);
+    }
+}
+
+fn handle_token(token: &str, settings: &SerialSettings, ctx: &ServerContext) {
+    if token.is_empty() {
+        return;
+    }
+    emit(&ctx.app_handle, "serial_token_received", token);
+    if let Some(&id) = settings.mappings.get(token) {
+        tauri::async_runtime::spawn(trigger_shortcut(id, ctx.clone()));
+    }
+}
+
+async fn trigger_shortcut(id: u64, ctx: ServerContext) {
+    if let Err(e) = triggers::trigger_shortcut(id, TriggerSource::Serial, &ctx).await {
+        warn!("Ignoring serial trigger for {}: {}", id, e);
+    }
+}
+
+// Serial-related Tauri commands
+
+/// Lists the serial ports on this computer, for picking one in the settings.
+#[tauri::command]
+pub fn list_serial_ports() -> Result<Vec<String>, String> {
+    serialport::available_ports()
+        .map(|ports| ports.into_iter().map(|port| port.port_name).collect())
+        .map_err(|e| format!("Failed to list serial ports: {}", e))
+}
+
+/// Saves the serial settings and starts, restarts or stops reading to match.
+///
+/// # Arguments
+///
+/// * `serial` - The port, its speed, token mappings and whether it is read.
+/// * `settings` - Shared state containing the settings.
+/// * `trigger` - The serial reader.
+/// * `ctx` - The server context, used to run triggered shortcuts.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn set_serial_trigger(
+    serial: SerialSettings,
+    settings: State<Arc<SettingsStore>>,
+    trigger: State<Arc<SerialTrigger>>,
+    ctx: State<ServerContext>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    // Saved first, so the reader never runs with settings that aren't kept
+    settings.update(&app_handle, |current| current.serial = serial.clone())?;
+
+    trigger.stop();
+    if serial.enabled {
+        trigger.start(serial, ctx.inner().clone())?;
+    }
+    Ok(())
+}
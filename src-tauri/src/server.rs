@@ -0,0 +1,196 @@
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tauri::{Manager, State};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use warp::Filter;
+
+use crate::auth::AuthStore;
+use crate::http_api;
+use crate::settings::{advertised_ip, SettingsStore};
+use crate::sockets::{self, ServerContext};
+
+struct RunningServer {
+    addr: SocketAddr,
+    /// Address phones are told to connect to.
+    advertised_ip: String,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+/// Owns the WebSocket server so it can be stopped and rebound at runtime.
+pub struct ServerHandle {
+    ctx: ServerContext,
+    running: Mutex<Option<RunningServer>>,
+}
+
+impl ServerHandle {
+    pub fn new(ctx: ServerContext) -> Self {
+        Self {
+            ctx,
+            running: Mutex::new(None),
+        }
+    }
+
+    /// Binds and starts serving. Returns the address actually bound.
+    pub async fn start(
+        &self,
+        bind_address: &str,
+        port: u16,
+        advertise_address: Option<&str>,
+    ) -> Result<SocketAddr, String> {
+        let mut running = self.running.lock().await;
+        if running.is_some() {
+            return Err("Server is already running".into());
+        }
+
+        let ip = bind_address
+            .parse::<IpAddr>()
+            .map_err(|e| format!("Invalid bind address {}: {}", bind_address, e))?;
+        let (shutdown, signal) = oneshot::channel::<()>();
+
+        let routes = sockets::routes(self.ctx.clone())
+            .or(http_api::routes(self.ctx.clone()))
+            .unify();
+
+        // A user-chosen port may already be taken, so report instead of panicking
+        let (addr, server) = warp::serve(routes)
+            .try_bind_with_graceful_shutdown(SocketAddr::new(ip, port), async {
+                signal.await.ok();
+            })
+            .map_err(|e| format!("Failed to start WebSocket server on {}:{}: {}", ip, port, e))?;
+
+        info!("WebSocket server listening on ws://{}", addr);
+        *running = Some(RunningServer {
+            addr,
+            advertised_ip: advertised_ip(&addr.ip().to_string(), advertise_address),
+            shutdown,
+            task: tokio::spawn(server),
+        });
+        Ok(addr)
+    }
+
+    /// Notifies and disconnects every client with a message of type `reason`,
+    /// then stops listening.
+    pub async fn stop(&self, reason: &str) -> Result<(), String> {
+        let server = self
+            .running
+            .lock()
+            .await
+            .take()
+            .ok_or("Server is not running")?;
+
+        // Upgraded sockets outlive graceful shutdown, so close them explicitly
+        self.ctx.app_state.close_all(reason).await;
+
+        server.shutdown.send(()).ok();
+        server.task.await.map_err(|e| e.to_string())?;
+        info!("WebSocket server on {} stopped.", server.addr);
+        Ok(())
+    }
+
+    /// Writes pending changes and tells phones the app is going away. Runs
+    /// before the app exits, however it was quit.
+    pub async fn shut_down(&self) {
+        // Saves are written in the background; don't lose the last one
+        if let Err(e) = self.ctx.store.flush() {
+            error!("{}", e);
+        }
+        // Tell phones right away instead of letting them time out
+        self.ctx.app_state.close_all("server_shutting_down").await;
+        self.stop("server_shutting_down").await.ok();
+    }
+
+    /// The IP and port phones should connect to, if the server is running.
+    pub async fn advertised_addr(&self) -> Option<(String, u16)> {
+        let running = self.running.lock().await;
+        running
+            .as_ref()
+            .map(|server| (server.advertised_ip.clone(), server.addr.port()))
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct ServerConfigData {
+    ip: String,
+    port: u16,
+    token: String,
+}
+
+// Server-related Tauri commands
+
+/// Returns the address and token clients should use to connect.
+#[tauri::command]
+pub async fn get_server_config(
+    server: State<'_, Arc<ServerHandle>>,
+    auth: State<'_, Arc<AuthStore>>,
+) -> Result<ServerConfigData, String> {
+    let (ip, port) = server
+        .advertised_addr()
+        .await
+        .ok_or("Server is not running")?;
+    Ok(ServerConfigData {
+        ip,
+        port,
+        token: auth.get_token(),
+    })
+}
+
+/// Stops the WebSocket server, telling connected clients it is going away.
+#[tauri::command]
+pub async fn stop_server(
+    server: State<'_, Arc<ServerHandle>>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    server.stop("server_stopping").await?;
+    app_handle
+        .emit_all("server_status_changed", Option::<ServerConfigData>::None)
+        .map_err(|e| e.to_string())
+}
+
+/// Stops the WebSocket server if it is running and starts it again with the
+/// current port and bind address settings.
+///
+/// # Arguments
+///
+/// * `server` - The running server.
+/// * `settings` - Shared state containing the settings.
+/// * `auth` - Shared state containing the auth token.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<ServerConfigData, String>` - The new connection details or an error message.
+#[tauri::command]
+pub async fn restart_server(
+    server: State<'_, Arc<ServerHandle>>,
+    settings: State<'_, Arc<SettingsStore>>,
+    auth: State<'_, Arc<AuthStore>>,
+    app_handle: tauri::AppHandle,
+) -> Result<ServerConfigData, String> {
+    // Not running is fine, e.g. after stop_server or a failed bind at launch
+    server.stop("server_restarting").await.ok();
+
+    server
+        .start(
+            &settings.bind_address(),
+            settings.port(),
+            settings.advertise_address().as_deref(),
+        )
+        .await?;
+    let (ip, port) = server
+        .advertised_addr()
+        .await
+        .ok_or("Server is not running")?;
+    let config = ServerConfigData {
+        ip,
+        port,
+        token: auth.get_token(),
+    };
+    app_handle
+        .emit_all("server_status_changed", Some(&config))
+        .map_err(|e| e.to_string())?;
+    Ok(config)
+}
@@ -0,0 +1,378 @@
+use button_beam_core::keyboard::TypingSpeed;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, State};
+use tracing::error;
+
+use crate::ble;
+use crate::error::{emit, read_json_or_default, write_json, Error};
+use crate::execution_limits::ExecutionLimits;
+use crate::integrations::discord::DiscordSettings;
+use crate::integrations::hue::HueSettings;
+use crate::integrations::obs::ObsSettings;
+use crate::mqtt::MqttSettings;
+use crate::serial::SerialSettings;
+use crate::twitch::TwitchSettings;
+use crate::webhook::WebhookSettings;
+use crate::youtube::YouTubeSettings;
+
+/// User-configurable application settings, persisted across restarts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Settings {
+    /// Port the WebSocket server listens on. Chosen once on first launch and
+    /// then kept, so saved connections on the phone stay valid.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Address to bind to, e.g. `0.0.0.0` for every interface or the IP of a
+    /// specific one. Defaults to the detected LAN address.
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    /// Address given to phones in the QR code and discovery replies, for
+    /// machines with several interfaces. Defaults to the bind address, or the
+    /// detected LAN address when binding to every interface.
+    #[serde(default)]
+    pub advertise_address: Option<String>,
+    /// Answer UDP discovery broadcasts, for networks where mDNS is blocked.
+    #[serde(default)]
+    pub udp_discovery: bool,
+    /// Accept phone connections over Bluetooth LE as well as the network.
+    /// Always off where the OS doesn't support it, see [`ble::SUPPORTED`].
+    #[serde(default)]
+    pub ble_transport: bool,
+    #[serde(default)]
+    pub auth_mode: AuthMode,
+    /// Delay between steps for shortcuts that don't set their own interval.
+    #[serde(default)]
+    pub default_interval_ms: Option<u64>,
+    /// How fast text steps type when neither the step nor its shortcut says.
+    #[serde(default)]
+    pub default_typing_speed: TypingSpeed,
+    /// How many shortcuts devices may run at once, overall and each.
+    #[serde(default)]
+    pub execution_limits: ExecutionLimits,
+    /// Maximum triggers per second for each connection and each other
+    /// trigger source; 0 disables the limit. 10 when unset.
+    #[serde(default)]
+    pub max_triggers_per_second: Option<f64>,
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    /// Preferred appearance of the desktop window; only read by the frontend.
+    #[serde(default)]
+    pub theme: Theme,
+    /// Connection used by the OBS action steps.
+    #[serde(default)]
+    pub obs: ObsSettings,
+    /// Application used by the Discord action steps.
+    #[serde(default)]
+    pub discord: DiscordSettings,
+    /// Bridge used by the Hue light steps.
+    #[serde(default)]
+    pub hue: HueSettings,
+    #[serde(default)]
+    pub mqtt: MqttSettings,
+    /// Chat commands and channel-point rewards that run shortcuts.
+    #[serde(default)]
+    pub twitch: TwitchSettings,
+    /// Chat commands in a YouTube live stream that run shortcuts.
+    #[serde(default)]
+    pub youtube: YouTubeSettings,
+    /// Serial port that DIY hardware sends trigger tokens on.
+    #[serde(default)]
+    pub serial: SerialSettings,
+    /// URL that activity is posted to for cloud automation services.
+    #[serde(default)]
+    pub webhook: WebhookSettings,
+    /// Run sleep, shutdown and restart steps without asking on the desktop.
+    #[serde(default)]
+    pub unconfirmed_power_actions: bool,
+}
+
+/// Default for [`Settings::max_triggers_per_second`].
+const DEFAULT_MAX_TRIGGERS_PER_SECOND: f64 = 10.0;
+
+/// How phones prove they may connect.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// The token from the QR code is required, then new devices are paired.
+    #[default]
+    Token,
+    /// Any device on the network may ask to pair; approving its PIN on the
+    /// desktop is the only check. Paired devices get a secret of their own
+    /// that stands in for the token. The HTTP API still requires the token.
+    PairingOnly,
+}
+
+/// Which remote activity shows a desktop notification.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NotificationSettings {
+    #[serde(default)]
+    pub device_connected: bool,
+    #[serde(default)]
+    pub device_rejected: bool,
+    #[serde(default)]
+    pub shortcut_triggered: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+pub struct SettingsStore {
+    pub settings: Mutex<Settings>,
+    pub file_path: PathBuf,
+}
+
+impl SettingsStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        let settings = read_json_or_default(&file_path);
+
+        Self {
+            settings: Mutex::new(settings),
+            file_path,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let settings = self.settings.lock().unwrap();
+        write_json(&self.file_path, &*settings)
+    }
+
+    pub fn get_settings(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// Applies a change, saves it and tells the frontend with `settings_changed`.
+    pub fn update(
+        &self,
+        app_handle: &AppHandle,
+        change: impl FnOnce(&mut Settings),
+    ) -> Result<Settings, String> {
+        change(&mut *self.settings.lock().map_err(|e| e.to_string())?);
+        self.save()?;
+        let settings = self.get_settings();
+        emit(app_handle, "settings_changed", &settings);
+        Ok(settings)
+    }
+
+    /// Returns the configured port, picking and persisting a free one if none is set yet.
+    pub fn port(&self) -> u16 {
+        if let Some(port) = self.settings.lock().unwrap().port {
+            return port;
+        }
+        let port = find_free_port().unwrap_or(3000);
+        self.settings.lock().unwrap().port = Some(port);
+        // Still usable for this run; a new port is picked next launch
+        if let Err(e) = self.save() {
+            error!("{}", e);
+        }
+        port
+    }
+
+    /// Returns the address to bind to, falling back to the detected LAN address.
+    pub fn bind_address(&self) -> String {
+        self.settings
+            .lock()
+            .unwrap()
+            .bind_address
+            .clone()
+            .unwrap_or_else(local_ip)
+    }
+
+    pub fn advertise_address(&self) -> Option<String> {
+        self.settings.lock().unwrap().advertise_address.clone()
+    }
+
+    pub fn auth_mode(&self) -> AuthMode {
+        self.settings.lock().unwrap().auth_mode
+    }
+
+    pub fn default_interval_ms(&self) -> Option<u64> {
+        self.settings.lock().unwrap().default_interval_ms
+    }
+
+    pub fn default_typing_speed(&self) -> TypingSpeed {
+        self.settings.lock().unwrap().default_typing_speed
+    }
+
+    pub fn execution_limits(&self) -> ExecutionLimits {
+        self.settings.lock().unwrap().execution_limits.clone()
+    }
+
+    pub fn max_triggers_per_second(&self) -> f64 {
+        self.settings
+            .lock()
+            .unwrap()
+            .max_triggers_per_second
+            .unwrap_or(DEFAULT_MAX_TRIGGERS_PER_SECOND)
+    }
+}
+
+/// A non-loopback address of one of the machine's network adapters.
+#[derive(Serialize, Clone, Debug)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub ip: String,
+    pub ipv6: bool,
+}
+
+pub fn network_interfaces() -> Result<Vec<NetworkInterface>, String> {
+    let interfaces = if_addrs::get_if_addrs()
+        .map_err(|e| format!("Failed to list network interfaces: {}", e))?;
+    Ok(interfaces
+        .into_iter()
+        .filter(|interface| !interface.is_loopback())
+        .map(|interface| NetworkInterface {
+            ip: interface.ip().to_string(),
+            ipv6: interface.ip().is_ipv6(),
+            name: interface.name,
+        })
+        .collect())
+}
+
+pub fn local_ip() -> String {
+    local_ipaddress::get().unwrap_or_else(|| "127.0.0.1".to_string())
+}
+
+pub fn find_free_port() -> Result<u16, String> {
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .map_err(|e| format!("Failed to bind to a free port: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to get local address: {}", e))?
+        .port();
+    Ok(port)
+}
+
+/// Address clients should connect to: the configured advertise address if
+/// any, else the bind address, unless it is the wildcard, in which case the
+/// LAN address is advertised instead.
+pub fn advertised_ip(bind_address: &str, advertise_address: Option<&str>) -> String {
+    if let Some(address) = advertise_address {
+        return address.to_string();
+    }
+    match bind_address.parse::<IpAddr>() {
+        Ok(ip) if ip.is_unspecified() => local_ip(),
+        _ => bind_address.to_string(),
+    }
+}
+
+// Settings-related Tauri commands
+
+/// Lists the addresses of all network adapters, to choose which one to bind
+/// to or advertise.
+#[tauri::command]
+pub fn list_network_interfaces() -> Result<Vec<NetworkInterface>, String> {
+    network_interfaces()
+}
+
+#[tauri::command]
+pub fn get_settings(settings: State<Arc<SettingsStore>>) -> Result<Settings, String> {
+    Ok(settings.get_settings())
+}
+
+/// Sets the server port, bind address and advertised address. Takes effect
+/// the next time the server starts, e.g. through `restart_server`.
+///
+/// # Arguments
+///
+/// * `port` - The port to listen on, or `None` to pick a free one.
+/// * `bind_address` - The address to bind to, or `None` for the LAN address.
+/// * `advertise_address` - The address phones should connect to, or `None` to derive it.
+/// * `settings` - Shared state containing the settings.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<Settings, String>` - The updated settings or an error message.
+#[tauri::command]
+pub fn set_server_settings(
+    port: Option<u16>,
+    bind_address: Option<String>,
+    advertise_address: Option<String>,
+    settings: State<Arc<SettingsStore>>,
+    app_handle: AppHandle,
+) -> Result<Settings, String> {
+    validate_port(port)?;
+    let bind_address = parse_address(bind_address, "bind")?;
+    let advertise_address = parse_address(advertise_address, "advertise")?;
+
+    settings.update(&app_handle, |current| {
+        current.port = port;
+        current.bind_address = bind_address;
+        current.advertise_address = advertise_address;
+    })
+}
+
+/// Replaces all settings at once. Network changes take effect the next time
+/// the server starts; UDP discovery and BLE are only started or stopped by
+/// their own commands, so changing those flags here applies at next launch.
+///
+/// # Arguments
+///
+/// * `new_settings` - The complete settings.
+/// * `settings` - Shared state containing the settings.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<Settings, String>` - The saved settings or an error message.
+#[tauri::command]
+pub fn update_settings(
+    new_settings: Settings,
+    settings: State<Arc<SettingsStore>>,
+    app_handle: AppHandle,
+) -> Result<Settings, String> {
+    validate_port(new_settings.port)?;
+    if let Some(limit) = new_settings.max_triggers_per_second {
+        validate_trigger_rate(limit)?;
+    }
+    new_settings.default_typing_speed.validate()?;
+    new_settings.execution_limits.validate()?;
+    let bind_address = parse_address(new_settings.bind_address.clone(), "bind")?;
+    let advertise_address = parse_address(new_settings.advertise_address.clone(), "advertise")?;
+
+    settings.update(&app_handle, |current| {
+        *current = Settings {
+            bind_address,
+            advertise_address,
+            ble_transport: new_settings.ble_transport && ble::SUPPORTED,
+            ..new_settings
+        };
+    })
+}
+
+fn validate_port(port: Option<u16>) -> Result<(), String> {
+    if port == Some(0) {
+        return Err("Port must be between 1 and 65535".into());
+    }
+    Ok(())
+}
+
+pub fn validate_trigger_rate(limit: f64) -> Result<(), String> {
+    if !limit.is_finite() || limit < 0.0 {
+        return Err("Rate limit must be a non-negative number".into());
+    }
+    Ok(())
+}
+
+/// Treats blank input as unset and checks that anything else is an IP address.
+fn parse_address(address: Option<String>, kind: &str) -> Result<Option<String>, String> {
+    match address.map(|address| address.trim().to_string()) {
+        Some(address) if address.is_empty() => Ok(None),
+        Some(address) => {
+            address
+                .parse::<IpAddr>()
+                .map_err(|_| format!("Invalid {} address: {}", kind, address))?;
+            Ok(Some(address))
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,220 @@
+use button_beam_core::protocol::CAP_SHORTCUT_STATES;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+
+use crate::activity::ActivityEvent;
+use crate::error::emit;
+use crate::events::AppEvent;
+use crate::integrations::discord::DiscordClient;
+use crate::integrations::obs::ObsClient;
+use crate::settings::SettingsStore;
+use crate::shortcuts::{Shortcut, StateSource};
+use crate::sockets::ServerContext;
+
+// On/off state of toggle shortcuts, so the button on the phone can light up
+// while the thing it controls is active. Manual toggles flip each time the
+// shortcut runs; the others are read from their integration every few
+// seconds while a device shows them, and right after the shortcut ran. The
+// integrations are read over the connections their steps use. States live in
+// memory only: the integrations are read again after a restart, manual
+// toggles start off.
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The last known state of each toggle shortcut.
+pub struct ShortcutStates {
+    states: Mutex<HashMap<u64, bool>>,
+}
+
+impl ShortcutStates {
+    pub fn new() -> Self {
+        Self {
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, shortcut_id: u64) -> Option<bool> {
+        self.states.lock().unwrap().get(&shortcut_id).copied()
+    }
+
+    pub fn all(&self) -> HashMap<u64, bool> {
+        self.states.lock().unwrap().clone()
+    }
+
+    /// Records a state and returns whether it changed.
+    fn set(&self, shortcut_id: u64, active: bool) -> bool {
+        self.states.lock().unwrap().insert(shortcut_id, active) != Some(active)
+    }
+
+    /// Forgets shortcuts that were deleted or are no longer toggles.
+    fn retain(&self, shortcuts: &[Shortcut]) {
+        self.states.lock().unwrap().retain(|id, _| {
+            shortcuts
+                .iter()
+                .any(|s| s.id == *id && s.state_source.is_some())
+        });
+    }
+}
+
+impl Default for ShortcutStates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a state from its integration; `None` for manual toggles, which have
+/// nothing to read.
+fn read(app_handle: &AppHandle, source: &StateSource) -> Result<Option<bool>, String> {
+    let settings = app_handle.state::<Arc<SettingsStore>>().get_settings();
+    let discord = || app_handle.state::<Arc<DiscordClient>>();
+    let obs = || app_handle.state::<Arc<ObsClient>>();
+    let active = match source {
+        StateSource::Manual => return Ok(None),
+        StateSource::ObsRecording => obs().recording(&settings.obs)?,
+        StateSource::ObsSourceVisible { scene, source } => {
+            obs().source_visible(&settings.obs, scene, source)?
+        }
+        StateSource::DiscordMute => discord().voice_setting(&settings.discord, "mute")?,
+        StateSource::DiscordDeafen => discord().voice_setting(&settings.discord, "deaf")?,
+    };
+    Ok(Some(active))
+}
+
+/// The `shortcut_states` message sent to devices after the shortcut list.
+pub fn states_message(states: &HashMap<u64, bool>) -> Value {
+    let states: Vec<Value> = states
+        .iter()
+        .map(|(id, active)| json!({ "shortcut_id": id, "active": active }))
+        .collect();
+    json!({ "type": "shortcut_states", "states": states })
+}
+
+/// Records a state and, if it changed, tells the frontend and devices.
+async fn update(ctx: &ServerContext, shortcut_id: u64, active: bool) {
+    let states = ctx
+        .app_handle
+        .state::<Arc<ShortcutStates>>()
+        .inner()
+        .clone();
+    if !states.set(shortcut_id, active) {
+        return;
+    }
+    let message = json!({
+        "type": "shortcut_state_changed",
+        "shortcut_id": shortcut_id,
+        "active": active,
+    });
+    emit(&ctx.app_handle, "shortcut_state_changed", &message);
+    ctx.app_state
+        .broadcast_to(CAP_SHORTCUT_STATES, &message)
+        .await;
+}
+
+/// Reads the states of `shortcuts` that come from an integration. A state
+/// that can't be read, e.g. while OBS isn't running, keeps its last value.
+async fn poll(ctx: &ServerContext, shortcuts: Vec<Shortcut>) {
+    for shortcut in shortcuts {
+        let Some(source) = shortcut.state_source else {
+            continue;
+        };
+        let app_handle = ctx.app_handle.clone();
+        match tokio::task::spawn_blocking(move || read(&app_handle, &source)).await {
+            Ok(Ok(Some(active))) => update(ctx, shortcut.id, active).await,
+            Ok(Ok(None)) | Err(_) => {}
+            Ok(Err(e)) => debug!(
+                "Failed to read the state of shortcut {}: {}",
+                shortcut.id, e
+            ),
+        }
+    }
+}
+
+/// Flips a manual toggle, or reads the state of any other toggle right away,
+/// once the shortcut ran.
+async fn executed(ctx: &ServerContext, shortcut_id: u64) {
+    let Some(shortcut) = ctx
+        .store
+        .get_shortcuts()
+        .into_iter()
+        .find(|s| s.id == shortcut_id)
+    else {
+        return;
+    };
+    match shortcut.state_source {
+        Some(StateSource::Manual) => {
+            let states = ctx
+                .app_handle
+                .state::<Arc<ShortcutStates>>()
+                .inner()
+                .clone();
+            let active = !states.get(shortcut_id).unwrap_or(false);
+            update(ctx, shortcut_id, active).await;
+        }
+        Some(_) => poll(ctx, vec![shortcut]).await,
+        None => {}
+    }
+}
+
+/// Keeps the toggle states current, see the top of this file.
+pub fn spawn_state_reporter(ctx: &ServerContext) {
+    let poll_ctx = ctx.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            // Only devices show the states between runs
+            if !poll_ctx.app_state.any_supports(CAP_SHORTCUT_STATES).await {
+                continue;
+            }
+            let shortcuts = poll_ctx.store.get_shortcuts();
+            poll_ctx
+                .app_handle
+                .state::<Arc<ShortcutStates>>()
+                .retain(&shortcuts);
+            poll(&poll_ctx, shortcuts).await;
+        }
+    });
+
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut events = ctx.events.subscribe();
+        loop {
+            match events.recv().await {
+                Ok(AppEvent::Activity(entry)) => {
+                    if let ActivityEvent::ShortcutExecuted {
+                        shortcut_id,
+                        ok: true,
+                        ..
+                    } = entry.event
+                    {
+                        executed(&ctx, shortcut_id).await;
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
+// Shortcut state-related Tauri commands
+
+/// Returns the on/off state of every toggle shortcut.
+///
+/// # Arguments
+///
+/// * `states` - The last known states.
+///
+/// # Returns
+///
+/// * `Result<HashMap<u64, bool>, String>` - Whether each toggle is on, by shortcut ID.
+#[tauri::command]
+pub fn get_shortcut_states(
+    states: State<Arc<ShortcutStates>>,
+) -> Result<HashMap<u64, bool>, String> {
+    Ok(states.all())
+}
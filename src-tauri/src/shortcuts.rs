@@ -1,75 +1,21 @@
-use serde::{Deserialize, Serialize};
-use std::fs::{self, File, OpenOptions};
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{AppHandle, GlobalShortcutManager, Manager, State};
-use tokio::sync::broadcast::Sender;
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Shortcut {
-    pub id: u64,
-    pub name: String,
-    pub sequence: Vec<String>,
-}
-
-pub struct ShortcutStore {
-    pub shortcuts: Mutex<Vec<Shortcut>>,
-    pub file_path: PathBuf,
-    pub broadcaster: Sender<Vec<Shortcut>>,
-}
-
-impl ShortcutStore {
-    pub fn new(file_path: PathBuf, broadcaster: Sender<Vec<Shortcut>>) -> Self {
-        // Create the directory if it doesn't exist
-        if let Some(parent) = file_path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent).expect("Failed to create directories for shortcuts");
-            }
-        }
-
-        // Load existing shortcuts from the file
-        let shortcuts = if file_path.exists() {
-            let file = File::open(&file_path).expect("Failed to open shortcuts file");
-            let reader = BufReader::new(file);
-            serde_json::from_reader(reader).unwrap_or_else(|_| Vec::new())
-        } else {
-            Vec::new()
-        };
-
-        Self {
-            shortcuts: Mutex::new(shortcuts),
-            file_path,
-            broadcaster,
-        }
-    }
-
-    pub fn save(&self) {
-        let shortcuts = self.shortcuts.lock().unwrap();
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.file_path)
-            .expect("Failed to open shortcuts file for writing");
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &*shortcuts).expect("Failed to write shortcuts");
-    }
-
-    pub fn get_shortcuts(&self) -> Vec<Shortcut> {
-        let shortcuts = self.shortcuts.lock().unwrap();
-        shortcuts.clone()
-    }
-
-    // New method to broadcast the updated shortcuts list
-    pub fn broadcast_shortcuts(&self) {
-        let shortcuts = self.get_shortcuts();
-        // Send the updated list to all subscribers
-        if let Err(e) = self.broadcaster.send(shortcuts) {
-            eprintln!("Error broadcasting shortcuts: {}", e);
-        }
-    }
-}
+use button_beam_core::keyboard::{validate_chars_per_second, TypingSpeed};
+use rand::distributions::{Distribution, WeightedIndex};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tracing::{debug, error, warn};
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::devices::now_millis;
+use crate::keyboard::Keys;
+use crate::secrets::{check_secret_ids, delete_secret, extract_secrets, secret_ids};
+
+pub use button_beam_core::shortcuts::{
+    ActionStep, Control, ControlTarget, PressKind, SequenceOutput, Shortcut, ShortcutChange,
+    ShortcutStore, StateSource, Step, Timing,
+};
 
 // Shortcut-related Tauri commands
 
@@ -105,53 +51,77 @@ pub fn update_shortcut(
     store: State<Arc<ShortcutStore>>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
-    println!("Received shortcut to update: {:?}", shortcut);
+    update_shortcut_in_store(shortcut, &store, &app_handle).map(|_| ())
+}
 
-    {
-        let mut shortcuts = store.shortcuts.lock().map_err(|e| {
-            let error = format!("Failed to acquire lock on shortcuts: {}", e);
-            println!("{}", error);
-            error
-        })?;
+/// Applies an update to the store and notifies the frontend and devices.
+/// Shared by the Tauri command and remote editing over WS.
+pub fn update_shortcut_in_store(
+    mut shortcut: Shortcut,
+    store: &Arc<ShortcutStore>,
+    app_handle: &AppHandle,
+) -> Result<Shortcut, String> {
+    debug!("Received shortcut to update: {:?}", shortcut);
+
+    let current = store.get_shortcuts();
+    check_for_cycles(&shortcut, &current)?;
+    check_secret_ids(&shortcut, &current)?;
+    prepare_shortcut(&mut shortcut, &app_handle.state::<Arc<ActionRegistry>>())?;
 
-        println!("Current shortcuts: {:?}", *shortcuts);
+    let removed_secrets = {
+        let mut shortcuts = store.shortcuts.write();
+
+        debug!("Current shortcuts: {:?}", *shortcuts);
 
         if let Some(existing) = shortcuts.iter_mut().find(|s| s.id == shortcut.id) {
-            println!(
+            debug!(
                 "Found matching shortcut with id {}: {:?}",
                 shortcut.id, existing
             );
 
+            let kept_secrets = secret_ids(&shortcut);
+            let removed_secrets: Vec<String> = secret_ids(existing)
+                .into_iter()
+                .filter(|id| !kept_secrets.contains(id))
+                .collect();
+
             existing.sequence = shortcut.sequence.clone();
+            existing.long_press_sequence = shortcut.long_press_sequence.clone();
+            existing.double_tap_sequence = shortcut.double_tap_sequence.clone();
+            existing.cycle = shortcut.cycle.clone();
             existing.name = shortcut.name.clone();
-
-            println!("Updated shortcut: {:?}", existing);
+            existing.interval_ms = shortcut.interval_ms;
+            existing.chars_per_second = shortcut.chars_per_second;
+            existing.group = shortcut.group.clone();
+            existing.tags = shortcut.tags.clone();
+            existing.game_mode = shortcut.game_mode;
+            existing.control = shortcut.control;
+            existing.state_source = shortcut.state_source.clone();
+
+            debug!("Updated shortcut: {:?}", existing);
+            shortcut = existing.clone();
+            removed_secrets
         } else {
             let error = format!("Shortcut with id {} not found", shortcut.id);
-            println!("{}", error);
-            return Err(error.into());
+            warn!("{}", error);
+            return Err(error);
         }
+    };
+
+    for secret_id in removed_secrets {
+        delete_secret(&secret_id);
     }
 
-    println!("Saving updated shortcuts to store...");
+    debug!("Saving updated shortcuts to store...");
     store.save();
-    println!("Shortcuts saved successfully.");
+    debug!("Shortcuts saved successfully.");
 
-    // Broadcast the updated shortcuts list
-    println!("Broadcasting shortcuts to frontend...");
-    store.broadcast_shortcuts();
+    // The frontend, devices and hotkeys pick the change up from the event bus
+    debug!("Broadcasting updated shortcut...");
+    store.broadcast_change(ShortcutChange::Updated(shortcut.id));
 
-    // Emit an event to notify frontend about the update
-    println!("Emitting 'shortcuts_updated' event...");
-    app_handle
-        .emit_all("shortcuts_updated", store.get_shortcuts())
-        .map_err(|e| e.to_string())?;
-
-    println!("Registering global shortcuts...");
-    register_global_shortcuts(app_handle.clone(), Arc::clone(&store));
-
-    println!("Shortcut update completed successfully.");
-    Ok(())
+    debug!("Shortcut update completed successfully.");
+    Ok(shortcut)
 }
 
 /// Adds a new shortcut.
@@ -167,34 +137,103 @@ pub fn update_shortcut(
 /// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
 #[tauri::command]
 pub fn add_shortcut(
-    mut shortcut: Shortcut,
+    shortcut: Shortcut,
     store: State<Arc<ShortcutStore>>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
+    add_shortcut_to_store(shortcut, &store, &app_handle).map(|_| ())
+}
+
+/// Gets a shortcut from the frontend, a device or another machine ready to
+/// be stored: refuses invalid steps, moves secrets to the keychain and
+/// spells key combos the same way.
+pub fn prepare_shortcut(shortcut: &mut Shortcut, registry: &ActionRegistry) -> Result<(), String> {
+    validate_chars_per_second(shortcut.chars_per_second)?;
+    for sequence in shortcut.sequences() {
+        registry.validate(sequence)?;
+    }
+    extract_secrets(shortcut)?;
+    shortcut.normalize_keys();
+    Ok(())
+}
+
+/// Adds a shortcut with a fresh ID and notifies the frontend and devices.
+/// Returns the stored shortcut.
+pub fn add_shortcut_to_store(
+    mut shortcut: Shortcut,
+    store: &Arc<ShortcutStore>,
+    app_handle: &AppHandle,
+) -> Result<Shortcut, String> {
+    prepare_shortcut(&mut shortcut, &app_handle.state::<Arc<ActionRegistry>>())?;
+
     {
-        let mut shortcuts = store.shortcuts.lock().map_err(|e| e.to_string())?;
+        let mut shortcuts = store.shortcuts.write();
 
-        // Generate a unique ID based on the current time
-        shortcut.id = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+        shortcut.id = next_id(&shortcuts);
+        check_for_cycles(&shortcut, &shortcuts)?;
+        check_secret_ids(&shortcut, &shortcuts)?;
 
         shortcuts.push(shortcut.clone());
     }
 
     store.save();
 
-    // Broadcast the updated shortcuts list
-    store.broadcast_shortcuts();
+    // Broadcast the new shortcut
+    store.broadcast_change(ShortcutChange::Added(shortcut.id));
 
-    // Emit an event to notify frontend about the addition
-    app_handle
-        .emit_all("shortcuts_updated", store.get_shortcuts())
-        .map_err(|e| e.to_string())?;
-    register_global_shortcuts(app_handle.clone(), Arc::clone(&store));
+    Ok(shortcut)
+}
 
-    Ok(())
+/// A unique ID for a new shortcut. IDs are timestamps, but two shortcuts
+/// added in the same millisecond count up past the newest.
+fn next_id(shortcuts: &[Shortcut]) -> u64 {
+    shortcuts
+        .iter()
+        .map(|s| s.id + 1)
+        .max()
+        .unwrap_or(0)
+        .max(now_millis())
+}
+
+/// Adds several shortcuts at once, e.g. from an import, and tells devices to
+/// resync. Nothing is added if one of them is invalid. Returns the stored
+/// shortcuts.
+pub fn add_shortcuts_to_store(
+    mut new_shortcuts: Vec<Shortcut>,
+    store: &Arc<ShortcutStore>,
+    app_handle: &AppHandle,
+) -> Result<Vec<Shortcut>, String> {
+    let registry = app_handle.state::<Arc<ActionRegistry>>();
+    for shortcut in &mut new_shortcuts {
+        prepare_shortcut(shortcut, &registry)
+            .map_err(|e| format!("Shortcut \"{}\" is invalid: {}", shortcut.name, e))?;
+    }
+
+    let added = {
+        let mut shortcuts = store.shortcuts.write();
+
+        let mut id = next_id(&shortcuts);
+        let added: Vec<Shortcut> = new_shortcuts
+            .into_iter()
+            .map(|mut shortcut| {
+                shortcut.id = id;
+                id += 1;
+                shortcut
+            })
+            .collect();
+        let all: Vec<Shortcut> = shortcuts.iter().chain(&added).cloned().collect();
+        for shortcut in &added {
+            check_for_cycles(shortcut, &all)?;
+            check_secret_ids(shortcut, &all)?;
+        }
+        shortcuts.extend(added.iter().cloned());
+        added
+    };
+
+    store.save();
+    store.broadcast_change(ShortcutChange::Reset);
+
+    Ok(added)
 }
 
 /// Deletes an existing shortcut by ID.
@@ -203,22 +242,25 @@ pub fn add_shortcut(
 ///
 /// * `id` - The ID of the shortcut to delete.
 /// * `store` - Shared state containing the shortcuts.
-/// * `app_handle` - Handle to emit events to the frontend.
 ///
 /// # Returns
 ///
 /// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
 #[tauri::command]
-pub fn delete_shortcut(
-    id: u64,
-    store: State<Arc<ShortcutStore>>,
-    app_handle: AppHandle,
-) -> Result<(), String> {
+pub fn delete_shortcut(id: u64, store: State<Arc<ShortcutStore>>) -> Result<(), String> {
+    delete_shortcut_from_store(id, &store)
+}
+
+/// Removes a shortcut and its secrets and notifies the frontend and devices.
+pub fn delete_shortcut_from_store(id: u64, store: &Arc<ShortcutStore>) -> Result<(), String> {
     {
-        let mut shortcuts = store.shortcuts.lock().map_err(|e| e.to_string())?;
+        let mut shortcuts = store.shortcuts.write();
 
         if let Some(pos) = shortcuts.iter().position(|s| s.id == id) {
-            shortcuts.remove(pos);
+            let removed = shortcuts.remove(pos);
+            for secret_id in secret_ids(&removed) {
+                delete_secret(&secret_id);
+            }
         } else {
             return Err("Shortcut not found".into());
         }
@@ -226,133 +268,12 @@ pub fn delete_shortcut(
 
     store.save();
 
-    // Broadcast the updated shortcuts list
-    store.broadcast_shortcuts();
-
-    // Emit an event to notify frontend about the deletion
-    app_handle
-        .emit_all("shortcuts_updated", store.get_shortcuts())
-        .map_err(|e| e.to_string())?;
-    register_global_shortcuts(app_handle.clone(), Arc::clone(&store));
-
-    Ok(())
-}
-
-/// Simulates a keyboard shortcut based on the provided keys.
-///
-/// # Arguments
-///
-/// * `shortcut_keys` - A string representing the keyboard shortcut keys (e.g., "Ctrl+S").
-///
-/// # Returns
-///
-/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
-#[tauri::command]
-pub fn simulate_shortcut(sequence: Vec<String>, interval_ms: Option<u64>) -> Result<(), String> {
-    // println!("Simulating shortcut sequence: {:?}", sequence);
-
-    use enigo::{Direction, Enigo, Key, Keyboard, Settings};
-
-    // Create Enigo instance (keeping the initialization as it was)
-    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
-
-    let interval = std::time::Duration::from_millis(interval_ms.unwrap_or(100)); // Default interval is 100ms
-
-    for shortcut_keys in sequence {
-        println!("Simulating shortcut: {}", shortcut_keys);
-
-        // Keep track of pressed modifiers
-        let mut pressed_modifiers = vec![];
-
-        // Split the shortcut keys and trim whitespace
-        let keys: Vec<&str> = shortcut_keys.split('+').map(|k| k.trim()).collect();
-
-        // Press down modifier keys first
-        for key in &keys {
-            let result = match *key {
-                "Ctrl" | "Control" => enigo
-                    .key(Key::Control, Direction::Press)
-                    .map(|_| pressed_modifiers.push(Key::Control)),
-                "Alt" => enigo
-                    .key(Key::Alt, Direction::Press)
-                    .map(|_| pressed_modifiers.push(Key::Alt)),
-                "Shift" => enigo
-                    .key(Key::Shift, Direction::Press)
-                    .map(|_| pressed_modifiers.push(Key::Shift)),
-                "Cmd" | "Command" | "Meta" => enigo
-                    .key(Key::Meta, Direction::Press)
-                    .map(|_| pressed_modifiers.push(Key::Meta)),
-                _ => Ok(()),
-            };
-
-            if let Err(e) = result {
-                eprintln!("Error pressing key {}: {}", key, e);
-            }
-        }
-
-        // Press the main key(s)
-        for key in &keys {
-            if !["Ctrl", "Control", "Alt", "Shift", "Cmd", "Command", "Meta"].contains(&key) {
-                let key_str = key.trim();
-                let result = match key_str {
-                    "Enter" => enigo.key(Key::Return, Direction::Click),
-                    "Tab" => enigo.key(Key::Tab, Direction::Click),
-                    "Backspace" => enigo.key(Key::Backspace, Direction::Click),
-                    "Space" => enigo.key(Key::Space, Direction::Click),
-                    // Add other special keys as needed
-                    _ => {
-                        // Handle character keys
-                        let character = key_str.chars().next().unwrap();
-                        let mut need_shift = false;
-                        let mut char_to_use = character;
-
-                        // Check if character is uppercase or requires Shift
-                        if character.is_uppercase() || is_special_character(character) {
-                            need_shift = true;
-                            char_to_use = character.to_ascii_lowercase();
-                        }
-
-                        // Press Shift if needed and not already pressed
-                        if need_shift && !pressed_modifiers.contains(&Key::Shift) {
-                            enigo
-                                .key(Key::Shift, Direction::Press)
-                                .map(|_| pressed_modifiers.push(Key::Shift))
-                                .map_err(|e| format!("Error pressing Shift key: {}", e))?;
-                        }
-
-                        enigo.key(Key::Unicode(char_to_use), Direction::Click)
-                    }
-                };
-
-                if let Err(e) = result {
-                    eprintln!("Error pressing key {}: {}", key_str, e);
-                }
-            }
-        }
-
-        // Release modifier keys in reverse order
-        for key in pressed_modifiers.iter().rev() {
-            if let Err(e) = enigo.key(*key, Direction::Release) {
-                eprintln!("Error releasing key {:?}: {}", key, e);
-            }
-        }
-
-        // Wait for the specified interval before the next shortcut
-        std::thread::sleep(interval);
-    }
+    // Broadcast the deletion
+    store.broadcast_change(ShortcutChange::Deleted(id));
 
     Ok(())
 }
 
-// Helper function to check if a character is a special character that requires Shift
-fn is_special_character(c: char) -> bool {
-    match c {
-        '!' | '@' | '#' | '$' | '%' | '^' | '&' | '*' | '(' | ')' | '_' | '+' | '{' | '}' | '|'
-        | ':' | '"' | '<' | '>' | '?' => true,
-        _ => false,
-    }
-}
-
 #[tauri::command]
 pub fn simulate_shortcut_by_id(
     id: u64,
@@ -361,96 +282,208 @@ pub fn simulate_shortcut_by_id(
 ) -> Result<(), String> {
     let shortcuts = store.get_shortcuts();
     if let Some(shortcut) = shortcuts.iter().find(|s| s.id == id) {
-        simulate_sequence(shortcut.sequence.clone());
+        let sequence = store.next_sequence(shortcut, PressKind::Tap);
+        simulate_sequence(&app_handle, id, sequence, shortcut.timing());
         Ok(())
     } else {
         Err(format!("Shortcut with ID {} not found.", id))
     }
 }
 
-fn simulate_sequence(sequence: Vec<String>) {
+pub fn simulate_sequence(
+    app_handle: &AppHandle,
+    shortcut_id: u64,
+    sequence: Vec<Step>,
+    timing: Timing,
+) -> std::thread::JoinHandle<Result<SequenceOutput, String>> {
     // Use a separate thread to avoid blocking
-    std::thread::spawn(move || {
-        for item in sequence {
-            if is_text_string(&item) {
-                println!("text is string: {}", &item);
-                // Treat as text to type out
-                if let Err(e) = simulate_text_typing(&item) {
-                    eprintln!("Error typing text '{}': {}", item, e);
-                }
-            } else {
-                println!("text is key sequence {}", &item);
-                // Treat as key sequence
-                if let Err(e) = simulate_shortcut(vec![item], None) {
-                    eprintln!("Error simulating shortcut: {}", e);
-                }
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || run_sequence(&app_handle, shortcut_id, sequence, timing))
+}
+
+/// Runs every step of a sequence of shortcut `shortcut_id` on the current
+/// thread. A failing step is logged and skipped; the first error is returned
+/// once the sequence is done.
+pub fn run_sequence(
+    app_handle: &AppHandle,
+    shortcut_id: u64,
+    sequence: Vec<Step>,
+    timing: Timing,
+) -> Result<SequenceOutput, String> {
+    let mut output = SequenceOutput::default();
+    run_steps(app_handle, sequence, timing, &[shortcut_id], &mut output).map(|()| output)
+}
+
+/// [`run_sequence`] for a sequence run by `run_shortcut` steps of the
+/// `running` shortcuts, adding to `output`.
+fn run_steps(
+    app_handle: &AppHandle,
+    sequence: Vec<Step>,
+    timing: Timing,
+    running: &[u64],
+    output: &mut SequenceOutput,
+) -> Result<(), String> {
+    let registry = app_handle.state::<Arc<ActionRegistry>>();
+    let mut first_error = None;
+    for step in sequence {
+        let mut ctx = ActionContext {
+            app_handle,
+            timing,
+            output,
+            running,
+        };
+        let result = match step {
+            Step::Keys(keys) => Keys {
+                keys,
+                speed: TypingSpeed::default(),
             }
+            .run(&mut ctx),
+            Step::Action(step) => registry.run(step, &mut ctx),
+        };
+        if let Err(e) = result {
+            error!("{}", e);
+            first_error.get_or_insert(e);
         }
-    });
+    }
+    first_error.map_or(Ok(()), Err)
 }
 
-fn is_text_string(input: &str) -> bool {
-    // If the string does not contain any modifier keys or '+', treat it as text
-    !input.contains('+')
-        && !input.to_lowercase().contains("ctrl")
-        && !input.to_lowercase().contains("control")
-        && !input.to_lowercase().contains("shift")
-        && !input.to_lowercase().contains("alt")
-        && !input.to_lowercase().contains("cmd")
-        && !input.to_lowercase().contains("command")
-        && !input.to_lowercase().contains("meta")
-}
+/// How deeply `run_shortcut` steps may nest, as a backstop to the check for
+/// cycles on save.
+const MAX_NESTING: usize = 16;
 
-fn simulate_text_typing(text: &str) -> Result<(), String> {
-    use enigo::{Enigo, Keyboard, Settings};
+/// Runs another stored shortcut, so steps shared by several buttons, e.g.
+/// focusing OBS, live in one place.
+#[derive(Deserialize)]
+struct RunShortcut {
+    shortcut_id: u64,
+}
 
-    // Create Enigo instance (keeping the initialization as it was)
-    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+impl Action for RunShortcut {
+    const TYPE: &'static str = "run_shortcut";
 
-    // Type each character in the text
-    for c in text.chars() {
-        enigo
-            .text(&c.to_string())
-            .map_err(|e| format!("Error typing character '{}': {}", c, e))?;
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        if ctx.running.contains(&self.shortcut_id) {
+            return Err(format!(
+                "Shortcut {} runs itself through other shortcuts",
+                self.shortcut_id
+            ));
+        }
+        if ctx.running.len() >= MAX_NESTING {
+            return Err(format!(
+                "Shortcuts run other shortcuts more than {} deep",
+                MAX_NESTING
+            ));
+        }
+        let store = ctx.app_handle.state::<Arc<ShortcutStore>>();
+        let shortcut = store
+            .get_shortcuts()
+            .into_iter()
+            .find(|s| s.id == self.shortcut_id)
+            .ok_or_else(|| format!("Shortcut with ID {} not found.", self.shortcut_id))?;
+        let sequence = store.next_sequence(&shortcut, PressKind::Tap);
+        // Its own timing, falling back to the calling shortcut's
+        let timing = shortcut
+            .timing()
+            .or_default_interval(ctx.timing.interval_ms);
+        let running: Vec<u64> = ctx.running.iter().copied().chain([shortcut.id]).collect();
+        run_nested(ctx, sequence, timing, &running)
+            .map_err(|e| format!("Error in \"{}\": {}", shortcut.name, e))
     }
+}
 
-    Ok(())
+/// One of the sequences a `random_choice` step picks from.
+#[derive(Deserialize)]
+struct Choice {
+    sequence: Vec<Step>,
+    /// How likely this one is relative to the others; 1 when unset.
+    weight: Option<f64>,
 }
 
-pub fn register_global_shortcuts(app_handle: AppHandle, store: Arc<ShortcutStore>) {
-    let shortcuts = store.get_shortcuts();
-    let mut shortcut_manager = app_handle.global_shortcut_manager();
-
-    // First, unregister all existing global shortcuts
-    shortcut_manager.unregister_all().unwrap();
-
-    // Register Ctrl+1 to Ctrl+0 (0 represents 10)
-    for i in 0..10 {
-        let hotkey = format!("Ctrl+{}", (i + 1) % 10);
-        if let Some(shortcut) = shortcuts.get(i) {
-            let sequence = shortcut.sequence.clone();
-            shortcut_manager
-                .register(&hotkey, move || {
-                    simulate_sequence(sequence.clone());
-                })
-                .unwrap_or_else(|e| {
-                    eprintln!("Failed to register global shortcut {}: {}", hotkey, e);
-                });
+/// Runs one of several sequences, picked at random, e.g. a different sound
+/// or scene reaction on each press.
+#[derive(Deserialize)]
+struct RandomChoice {
+    choices: Vec<Choice>,
+}
+
+impl RandomChoice {
+    /// Fails when there is nothing to pick, or a weight is negative or
+    /// infinite or they are all zero.
+    fn weights(&self) -> Result<WeightedIndex<f64>, String> {
+        let weights: Vec<f64> = self
+            .choices
+            .iter()
+            .map(|choice| choice.weight.unwrap_or(1.0))
+            .collect();
+        if weights.iter().any(|weight| !weight.is_finite()) {
+            return Err("Random choice weights must be finite numbers".to_string());
         }
+        WeightedIndex::new(weights)
+            .map_err(|e| format!("Random choice step can't pick a sequence: {}", e))
+    }
+}
+
+impl Action for RandomChoice {
+    const TYPE: &'static str = "random_choice";
+
+    fn check(&self) -> Result<(), String> {
+        self.weights().map(|_| ())
     }
 
-    // Register Ctrl+Shift+1 to Ctrl+Shift+0 (for shortcuts 11-20)
-    for i in 10..20 {
-        let hotkey = format!("Ctrl+Shift+{}", (i - 9) % 10);
-        if let Some(shortcut) = shortcuts.get(i) {
-            let sequence = shortcut.sequence.clone();
-            shortcut_manager
-                .register(&hotkey, move || {
-                    simulate_sequence(sequence.clone());
-                })
-                .unwrap_or_else(|e| {
-                    eprintln!("Failed to register global shortcut {}: {}", hotkey, e);
-                });
+    fn run(mut self, ctx: &mut ActionContext) -> Result<(), String> {
+        let picked = self.weights()?.sample(&mut rand::thread_rng());
+        let sequence = self.choices.swap_remove(picked).sequence;
+        let (timing, running) = (ctx.timing, ctx.running);
+        run_nested(ctx, sequence, timing, running)
+    }
+}
+
+/// Runs `sequence` as part of the step `ctx` belongs to, sharing its output:
+/// the nested steps see the variables set so far, and their files are
+/// reported with the step's.
+fn run_nested(
+    ctx: &mut ActionContext,
+    sequence: Vec<Step>,
+    timing: Timing,
+    running: &[u64],
+) -> Result<(), String> {
+    run_steps(ctx.app_handle, sequence, timing, running, ctx.output)
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<RunShortcut>();
+    registry.register::<RandomChoice>();
+}
+
+/// The shortcuts that `shortcut`'s `run_shortcut` steps run, including those
+/// nested in other steps such as `random_choice`.
+fn called_shortcuts(shortcut: &Shortcut) -> Vec<u64> {
+    let mut ids = Vec::new();
+    shortcut.visit_steps(|step| match step {
+        Step::Action(step) if step.kind == RunShortcut::TYPE => {
+            ids.extend(step.params.get("shortcut_id").and_then(Value::as_u64));
+        }
+        _ => {}
+    });
+    ids
+}
+
+/// Refuses a shortcut that would end up running itself through
+/// `run_shortcut` steps, directly or through `shortcuts`.
+pub fn check_for_cycles(shortcut: &Shortcut, shortcuts: &[Shortcut]) -> Result<(), String> {
+    let mut pending = called_shortcuts(shortcut);
+    let mut checked = HashSet::new();
+    while let Some(id) = pending.pop() {
+        if id == shortcut.id {
+            return Err(format!("\"{}\" would end up running itself", shortcut.name));
+        }
+        if !checked.insert(id) {
+            continue;
+        }
+        if let Some(called) = shortcuts.iter().find(|s| s.id == id) {
+            pending.extend(called_shortcuts(called));
         }
     }
+    Ok(())
 }
@@ -0,0 +1,205 @@
+use button_beam_core::keyboard::{self, split_at_cursor, TypingSpeed};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::devices::now_millis;
+use crate::error::{emit, read_json_or_default, write_json, Error};
+use crate::permissions::ensure_can_send_keys;
+
+// Text snippets, kept in a library so the same signature or reply template
+// can be used from several shortcuts and edited in one place. A `{cursor}`
+// in the text marks where the caret ends up: once the text is typed, Left is
+// pressed until the caret is back at the marker, like a text expander does.
+
+/// Pause between the Left presses that place the caret. Short, as a long
+/// snippet can need many of them.
+const CARET_INTERVAL_MS: u64 = 5;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snippet {
+    #[serde(default)]
+    pub id: u64,
+    pub name: String,
+    /// The text to type, with at most one `{cursor}`.
+    pub text: String,
+}
+
+pub struct SnippetStore {
+    pub snippets: Mutex<Vec<Snippet>>,
+    pub file_path: PathBuf,
+}
+
+impl SnippetStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self {
+            snippets: Mutex::new(read_json_or_default(&file_path)),
+            file_path,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let snippets = self.snippets.lock().unwrap();
+        write_json(&self.file_path, &*snippets)
+    }
+
+    pub fn get_snippets(&self) -> Vec<Snippet> {
+        self.snippets.lock().unwrap().clone()
+    }
+
+    pub fn get_snippet(&self, id: u64) -> Option<Snippet> {
+        self.snippets
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|snippet| snippet.id == id)
+            .cloned()
+    }
+
+    /// Applies `change` to the library, then saves it and notifies the
+    /// frontend.
+    fn update<R>(
+        &self,
+        app_handle: &AppHandle,
+        change: impl FnOnce(&mut Vec<Snippet>) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let result = change(&mut self.snippets.lock().unwrap())?;
+        self.save()?;
+        emit(app_handle, "snippets_updated", self.get_snippets());
+        Ok(result)
+    }
+}
+
+/// Types a snippet from the library, or the `text` given with the step, and
+/// places the caret at its `{cursor}`.
+#[derive(Deserialize)]
+struct TypeSnippet {
+    snippet_id: Option<u64>,
+    text: Option<String>,
+    /// Overrides the shortcut's and the settings' typing speed.
+    #[serde(flatten)]
+    speed: TypingSpeed,
+}
+
+impl Action for TypeSnippet {
+    const TYPE: &'static str = "snippet";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        let text = match (self.snippet_id, self.text) {
+            (Some(id), _) => {
+                ctx.app_handle
+                    .state::<Arc<SnippetStore>>()
+                    .get_snippet(id)
+                    .ok_or_else(|| format!("Snippet with id {} not found", id))?
+                    .text
+            }
+            (None, Some(text)) => text,
+            (None, None) => return Err("Snippet step has no snippet or text".to_string()),
+        };
+        let (text, presses) = split_at_cursor(&ctx.output.substitute(&text))?;
+        if let Err(e) = ensure_can_send_keys() {
+            emit(ctx.app_handle, "input_blocked", &e);
+            return Err(e);
+        }
+
+        let speed = self.speed.or(ctx.typing_speed());
+        let caret = vec!["Left".to_string(); presses];
+        let typed = if ctx.timing.game_mode {
+            keyboard::simulate_text_typing_game_mode(&text, speed).and_then(|()| {
+                keyboard::simulate_shortcut_game_mode(caret, Some(CARET_INTERVAL_MS))
+            })
+        } else {
+            keyboard::simulate_text_typing(&text, speed)
+                .and_then(|()| keyboard::simulate_shortcut(caret, Some(CARET_INTERVAL_MS)))
+        };
+        typed.map_err(|e| format!("Error typing snippet: {}", e))
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.speed.validate()
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<TypeSnippet>();
+}
+
+// Snippet-related Tauri commands
+
+#[tauri::command]
+pub fn get_snippets(store: State<Arc<SnippetStore>>) -> Result<Vec<Snippet>, String> {
+    Ok(store.get_snippets())
+}
+
+/// Adds a snippet to the library with a fresh ID.
+///
+/// # Arguments
+///
+/// * `snippet` - The snippet to add.
+/// * `store` - Shared state containing the snippets.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<Snippet, String>` - The stored snippet, or an error message.
+#[tauri::command]
+pub fn add_snippet(
+    mut snippet: Snippet,
+    store: State<Arc<SnippetStore>>,
+    app_handle: AppHandle,
+) -> Result<Snippet, String> {
+    split_at_cursor(&snippet.text)?;
+    store.update(&app_handle, |snippets| {
+        snippet.id = snippets
+            .iter()
+            .map(|s| s.id + 1)
+            .max()
+            .unwrap_or(0)
+            .max(now_millis());
+        snippets.push(snippet.clone());
+        Ok(snippet)
+    })
+}
+
+/// Replaces a snippet's name and text. Shortcuts using it type the new text
+/// from then on.
+///
+/// # Arguments
+///
+/// * `snippet` - The snippet to update, matched by ID.
+/// * `store` - Shared state containing the snippets.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub fn update_snippet(
+    snippet: Snippet,
+    store: State<Arc<SnippetStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    split_at_cursor(&snippet.text)?;
+    store.update(&app_handle, |snippets| {
+        let existing = snippets
+            .iter_mut()
+            .find(|s| s.id == snippet.id)
+            .ok_or_else(|| format!("Snippet with id {} not found", snippet.id))?;
+        *existing = snippet;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn delete_snippet(
+    id: u64,
+    store: State<Arc<SnippetStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    store.update(&app_handle, |snippets| {
+        snippets.retain(|s| s.id != id);
+        Ok(())
+    })
+}
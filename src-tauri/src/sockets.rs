@@ -1,196 +1,2277 @@
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tauri::Manager;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
 use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
 use warp::filters::ws::WebSocket;
 use warp::ws::Message;
-use warp::Filter;
+use warp::{Filter, Reply};
 
-use crate::shortcuts::{simulate_shortcut, ShortcutStore};
+use button_beam_core::protocol::{
+    cycle_positions_message, shortcut_message, shortcut_page, ClientMessage, DeviceStatus,
+    Response, CAP_CYCLE_POSITIONS, CAP_EXECUTION_RESULTS, CAP_HOTKEY_CONFLICTS, CAP_LATENCY,
+    CAP_LAYOUTS, CAP_MSGPACK, CAP_SHORTCUT_DIFFS, CAP_SHORTCUT_STATES, MIN_PROTOCOL_VERSION,
+    PROTOCOL_VERSION, SERVER_CAPABILITIES,
+};
 
+use crate::activity::{ActivityEvent, ActivityLog};
+use crate::assets::{upload_asset, AssetStore};
+use crate::auth::AuthStore;
+use crate::controls;
+use crate::devices::{DeviceRegistry, DeviceRole, TrustState};
+use crate::error::{emit, report};
+use crate::events::{publish, AppEvent, EventBus};
+use crate::execution_limits::RunningSequences;
+use crate::hotkeys::{conflicts_message, RegisteredHotkeys};
+use crate::integrations::media;
+use crate::layouts::layout_message;
+use crate::notifications::{notify, NotificationKind};
+use crate::performance::{PerformanceMonitor, TriggerLatency};
+use crate::rate_limit::TokenBucket;
+use crate::settings::{validate_trigger_rate, AuthMode, SettingsStore};
+use crate::shortcut_states::{states_message, ShortcutStates};
+use crate::shortcuts::{
+    add_shortcut_to_store, delete_shortcut_from_store, run_sequence, update_shortcut_in_store,
+    PressKind, ShortcutChange, ShortcutStore,
+};
+use crate::system::levels;
+use crate::tray::set_pause_item_title;
+
+/// Wire encoding of messages on a connection, negotiated in `hello`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+}
+
+type MessageSink = Pin<Box<dyn Sink<Message, Error = String> + Send>>;
+
+/// Write half of a client connection; encodes messages the way the client
+/// negotiated. Backed by a WebSocket or by another transport such as BLE.
+#[derive(Clone)]
+pub struct WsSender {
+    sink: Arc<Mutex<MessageSink>>,
+    encoding: Arc<std::sync::Mutex<Encoding>>,
+    /// Count of protocol messages sent, excluding pings.
+    messages_sent: Arc<AtomicU64>,
+    /// Set when a shortcut update didn't reach the client; it gets the whole
+    /// list with the next one.
+    needs_resync: Arc<AtomicBool>,
+}
+
+impl WsSender {
+    pub fn new<S>(sink: S) -> Self
+    where
+        S: Sink<Message> + Send + 'static,
+        S::Error: Display,
+    {
+        Self {
+            sink: Arc::new(Mutex::new(Box::pin(sink.sink_map_err(|e| e.to_string())))),
+            encoding: Arc::new(std::sync::Mutex::new(Encoding::Json)),
+            messages_sent: Arc::new(AtomicU64::new(0)),
+            needs_resync: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        *self.encoding.lock().unwrap()
+    }
+
+    pub fn set_encoding(&self, encoding: Encoding) {
+        *self.encoding.lock().unwrap() = encoding;
+    }
+
+    pub async fn send_message(&self, message: Message) -> Result<(), String> {
+        self.sink.lock().await.send(message).await
+    }
+
+    /// Serializes `value` with the connection's encoding and sends it.
+    pub async fn send_value<T: Serialize>(&self, value: &T) {
+        if let Err(e) = self.try_send_value(value).await {
+            error!("{}", e);
+        }
+    }
+
+    async fn try_send_value<T: Serialize>(&self, value: &T) -> Result<(), String> {
+        let message = encode(value, self.encoding())?;
+        self.send_encoded(message).await
+    }
+
+    /// Sends a message that was already encoded for this connection.
+    async fn send_encoded(&self, message: Message) -> Result<(), String> {
+        self.send_message(message)
+            .await
+            .map_err(|e| format!("Error sending message: {}", e))?;
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Sends a shortcut update, giving up after `UPDATE_TIMEOUT` so one slow
+    /// client doesn't hold up the others. A client that missed an update is
+    /// marked to resync.
+    async fn send_update(&self, message: Message) {
+        let error = match tokio::time::timeout(UPDATE_TIMEOUT, self.send_encoded(message)).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e,
+            Err(_) => "The client is too slow to take shortcut updates".to_string(),
+        };
+        warn!("{}; it will get the whole list next time", error);
+        self.needs_resync.store(true, Ordering::SeqCst);
+    }
+
+    pub async fn close(&self) {
+        self.sink.lock().await.close().await.ok();
+    }
+}
+
+/// Serializes `value` for a connection with the given encoding.
+fn encode<T: Serialize>(value: &T, encoding: Encoding) -> Result<Message, String> {
+    match encoding {
+        Encoding::Json => serde_json::to_string(value)
+            .map(Message::text)
+            .map_err(|e| e.to_string()),
+        Encoding::MessagePack => rmp_serde::to_vec_named(value)
+            .map(Message::binary)
+            .map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("Error serializing message: {}", e))
+}
+
+/// Decodes a text (JSON) or binary (MessagePack) frame. Returns `None` for
+/// control frames.
+fn decode_message(message: &Message) -> Option<Result<Value, String>> {
+    if message.is_binary() {
+        Some(rmp_serde::from_slice(message.as_bytes()).map_err(|e| e.to_string()))
+    } else if let Ok(text) = message.to_str() {
+        Some(serde_json::from_str(text).map_err(|e| e.to_string()))
+    } else {
+        None
+    }
+}
+
+/// How often each connection is pinged.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// Connections silent for longer than this are considered dead and dropped.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a client may take to accept a shortcut update.
+const UPDATE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often `connection_stats` is emitted to the frontend.
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a disconnected device may resume its session without pairing again.
+const SESSION_TTL: Duration = Duration::from_secs(10 * 60);
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Device {
+    pub id: String,
     pub name: String,
     pub connected: bool,
+    /// False while the device waits for the user to approve pairing.
+    pub approved: bool,
+    #[serde(flatten)]
+    pub status: DeviceStatus,
+}
+
+/// Payload of the `device_pairing_requested` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingRequest {
+    pub device: Device,
+    pub pin: String,
+}
+
+/// State of a disconnected device, kept so it can resume without pairing again.
+pub struct Session {
+    pub device: Device,
+    pub pairing_pin: Option<String>,
+    /// Whether the connection had the token or the device secret; a device
+    /// still pairing without either doesn't get it by resuming.
+    pub authenticated: bool,
+    pub expires_at: Instant,
+}
+
+/// An open WebSocket; `device` is set once the client has sent `device_info`.
+pub struct Connection {
+    pub device: Option<Device>,
+    pub sender: WsSender,
+    pub protocol_version: u32,
+    /// Capabilities announced by the client in `hello`.
+    pub capabilities: Vec<String>,
+    /// Whether the client has presented the desktop's auth token.
+    pub authenticated: bool,
+    /// PIN shown on both screens while pairing approval is pending.
+    pub pairing_pin: Option<String>,
+    /// Limits how fast this connection may trigger shortcuts.
+    pub trigger_limiter: TokenBucket,
+    /// Issued with the device info reply; lets the client resume after a reconnect.
+    pub session_token: Option<String>,
+    pub connected_at: SystemTime,
+    pub messages_received: u64,
+    /// Round trip of the most recent heartbeat ping.
+    pub last_rtt: Option<Duration>,
+}
+
+/// Live metrics of one connection, for the diagnostics panel.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStats {
+    pub connection_id: String,
+    pub device: Option<Device>,
+    /// Milliseconds since the Unix epoch.
+    pub connected_at: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub last_rtt_ms: Option<f64>,
+}
+
+impl Connection {
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    pub fn is_approved(&self) -> bool {
+        self.device.as_ref().is_some_and(|d| d.approved)
+    }
+}
+
+/// The whole shortcut list, encoded, kept until the shortcuts change so that
+/// devices connecting or resyncing don't each serialize it again.
+#[derive(Default)]
+struct ShortcutListCache {
+    /// The store revision the messages were built from.
+    revision: u64,
+    /// By whether the client takes diffs, which get a `sync` message instead
+    /// of the bare array, and by encoding.
+    messages: HashMap<(bool, Encoding), Message>,
 }
 
 pub struct AppState {
-    pub device: Mutex<Option<Device>>,
+    pub connections: Mutex<HashMap<String, Connection>>,
+    /// Resumable sessions of recently disconnected devices, by session token.
+    pub sessions: Mutex<HashMap<String, Session>>,
+    /// One per trigger source besides connections, by name, e.g. all
+    /// requests to the HTTP API share one. See [`crate::triggers`].
+    pub trigger_limiters: Mutex<HashMap<&'static str, TokenBucket>>,
+    /// Devices kicked from the desktop, refused until the given time.
+    pub reconnect_bans: std::sync::Mutex<HashMap<String, Instant>>,
+    /// Refuses every trigger, remote or by global hotkey, while set.
+    pub triggering_paused: AtomicBool,
+    /// Sequences devices are running, for the execution limits.
+    pub running: Arc<RunningSequences>,
+    shortcut_lists: std::sync::Mutex<ShortcutListCache>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
-            device: Mutex::new(None),
+            connections: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            trigger_limiters: Mutex::new(HashMap::new()),
+            reconnect_bans: std::sync::Mutex::new(HashMap::new()),
+            triggering_paused: AtomicBool::new(false),
+            running: Arc::new(RunningSequences::new()),
+            shortcut_lists: std::sync::Mutex::new(ShortcutListCache::default()),
+        }
+    }
+
+    /// The whole shortcut list as a message for a client, encoded only once
+    /// per change to the shortcuts.
+    fn shortcut_list_message(
+        &self,
+        store: &ShortcutStore,
+        diffs: bool,
+        encoding: Encoding,
+    ) -> Result<Message, String> {
+        let revision = store.revision();
+        let mut cache = self.shortcut_lists.lock().unwrap();
+        if cache.revision != revision {
+            cache.messages.clear();
+            cache.revision = revision;
+        }
+        if let Some(message) = cache.messages.get(&(diffs, encoding)) {
+            return Ok(message.clone());
+        }
+        let value = shortcut_message(&ShortcutChange::Reset, &store.get_shortcuts(), diffs)
+            .map_err(|e| format!("Error serializing shortcuts: {}", e))?;
+        let message = encode(&value, encoding)?;
+        cache.messages.insert((diffs, encoding), message.clone());
+        Ok(message)
+    }
+
+    /// Whether a kicked device is still barred from reconnecting.
+    pub fn is_reconnect_banned(&self, device_id: &str) -> bool {
+        let mut bans = self.reconnect_bans.lock().unwrap();
+        bans.retain(|_, until| *until > Instant::now());
+        bans.contains_key(device_id)
+    }
+
+    pub async fn devices(&self) -> Vec<Device> {
+        let connections = self.connections.lock().await;
+        connections
+            .values()
+            .filter_map(|c| c.device.clone())
+            .collect()
+    }
+
+    pub async fn connection_stats(&self) -> Vec<ConnectionStats> {
+        let connections = self.connections.lock().await;
+        connections
+            .iter()
+            .map(|(id, c)| ConnectionStats {
+                connection_id: id.clone(),
+                device: c.device.clone(),
+                connected_at: c
+                    .connected_at
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as u64),
+                messages_sent: c.sender.messages_sent(),
+                messages_received: c.messages_received,
+                last_rtt_ms: c.last_rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+            })
+            .collect()
+    }
+
+    /// Changes the displayed name of a device on all its connections and sessions.
+    pub async fn rename_device(&self, device_id: &str, name: &str) {
+        let mut connections = self.connections.lock().await;
+        for device in connections.values_mut().filter_map(|c| c.device.as_mut()) {
+            if device.id == device_id {
+                device.name = name.to_string();
+            }
+        }
+        drop(connections);
+
+        let mut sessions = self.sessions.lock().await;
+        for session in sessions.values_mut() {
+            if session.device.id == device_id {
+                session.device.name = name.to_string();
+            }
+        }
+    }
+
+    pub async fn device_name(&self, device_id: &str) -> Option<String> {
+        let connections = self.connections.lock().await;
+        connections
+            .values()
+            .filter_map(|c| c.device.as_ref())
+            .find(|d| d.id == device_id)
+            .map(|d| d.name.clone())
+    }
+
+    /// Sends a shortcut change to every paired device, as a diff or as the
+    /// full list depending on what the client supports. Devices that missed
+    /// an earlier update get the full list instead of the diff.
+    pub async fn broadcast_shortcut_change(&self, change: &ShortcutChange, store: &ShortcutStore) {
+        let targets: Vec<(WsSender, bool)> = {
+            let connections = self.connections.lock().await;
+            connections
+                .values()
+                .filter(|c| c.is_approved())
+                .map(|c| (c.sender.clone(), c.supports(CAP_SHORTCUT_DIFFS)))
+                .collect()
+        };
+
+        // After a reset every client needs the whole list
+        let diff = match change {
+            ShortcutChange::Reset => None,
+            change => match shortcut_message(change, &store.get_shortcuts(), true) {
+                Ok(diff) => Some(diff),
+                Err(e) => {
+                    error!("Error serializing shortcuts: {}", e);
+                    return;
+                }
+            },
+        };
+
+        // The diff is encoded once per encoding, the whole list comes from the cache
+        let mut diff_messages: HashMap<Encoding, Message> = HashMap::new();
+        let mut sends = Vec::new();
+        for (sender, diffs) in targets {
+            let encoding = sender.encoding();
+            let resync = sender.needs_resync.swap(false, Ordering::SeqCst);
+            let message = match &diff {
+                Some(diff) if diffs && !resync => match diff_messages.get(&encoding) {
+                    Some(message) => Ok(message.clone()),
+                    None => encode(diff, encoding).inspect(|message| {
+                        diff_messages.insert(encoding, message.clone());
+                    }),
+                },
+                _ => self.shortcut_list_message(store, diffs, encoding),
+            };
+            match message {
+                Ok(message) => sends.push((sender, message)),
+                Err(e) => error!("{}", e),
+            }
+        }
+
+        futures_util::future::join_all(
+            sends
+                .into_iter()
+                .map(|(sender, message)| async move { sender.send_update(message).await }),
+        )
+        .await;
+    }
+
+    /// Sends a message to every connection whose device is paired.
+    pub async fn broadcast<T: Serialize>(&self, message: &T) {
+        let senders: Vec<WsSender> = {
+            let connections = self.connections.lock().await;
+            connections
+                .values()
+                .filter(|c| c.is_approved())
+                .map(|c| c.sender.clone())
+                .collect()
+        };
+
+        for sender in senders {
+            sender.send_value(message).await;
+        }
+    }
+
+    /// Whether any paired connection supports `capability`, so work done only
+    /// for them can be skipped otherwise.
+    pub async fn any_supports(&self, capability: &str) -> bool {
+        self.connections
+            .lock()
+            .await
+            .values()
+            .any(|c| c.is_approved() && c.supports(capability))
+    }
+
+    /// Sends a message to every paired connection that supports `capability`.
+    pub async fn broadcast_to<T: Serialize>(&self, capability: &str, message: &T) {
+        let senders: Vec<WsSender> = {
+            let connections = self.connections.lock().await;
+            connections
+                .values()
+                .filter(|c| c.is_approved() && c.supports(capability))
+                .map(|c| c.sender.clone())
+                .collect()
+        };
+
+        for sender in senders {
+            sender.send_value(message).await;
+        }
+    }
+
+    /// Sends a message to the paired connections of one device that support `capability`.
+    pub async fn send_to_device<T: Serialize>(
+        &self,
+        device_id: &str,
+        capability: &str,
+        message: &T,
+    ) {
+        let senders: Vec<WsSender> = {
+            let connections = self.connections.lock().await;
+            connections
+                .values()
+                .filter(|c| c.is_approved() && c.supports(capability))
+                .filter(|c| c.device.as_ref().is_some_and(|d| d.id == device_id))
+                .map(|c| c.sender.clone())
+                .collect()
+        };
+
+        for sender in senders {
+            sender.send_value(message).await;
+        }
+    }
+
+    /// Drops every authenticated connection along with all resumable
+    /// sessions, e.g. once the token they were granted under was replaced.
+    /// Each connection is told `reason` first.
+    pub async fn revoke_access(&self, reason: &str) {
+        let senders: Vec<WsSender> = {
+            let mut connections = self.connections.lock().await;
+            connections
+                .values_mut()
+                .filter(|c| c.authenticated)
+                .map(|c| {
+                    // Taking the token keeps the socket from saving a session on close
+                    c.session_token = None;
+                    c.sender.clone()
+                })
+                .collect()
+        };
+        self.sessions.lock().await.clear();
+
+        for sender in senders {
+            sender
+                .send_value(&serde_json::json!({ "type": reason }))
+                .await;
+            sender.close().await;
+        }
+    }
+
+    /// Tells every connection why it is being dropped, then closes them.
+    pub async fn close_all(&self, reason: &str) {
+        let senders: Vec<WsSender> = {
+            let connections = self.connections.lock().await;
+            connections.values().map(|c| c.sender.clone()).collect()
+        };
+
+        for sender in senders {
+            sender
+                .send_value(&serde_json::json!({ "type": reason }))
+                .await;
+            sender.close().await;
         }
     }
 }
 
-pub async fn start_websocket_server(
-    ip: &str,
-    port: u16,
-    store: Arc<ShortcutStore>,
-    app_state: Arc<AppState>,
-    app_handle: tauri::AppHandle,
-) {
-    let ws_route = warp::path::end()
+/// Shared services handed to every connection.
+#[derive(Clone)]
+pub struct ServerContext {
+    pub store: Arc<ShortcutStore>,
+    pub app_state: Arc<AppState>,
+    pub auth: Arc<AuthStore>,
+    pub devices: Arc<DeviceRegistry>,
+    pub activity: Arc<ActivityLog>,
+    pub settings: Arc<SettingsStore>,
+    pub events: Arc<EventBus>,
+    pub app_handle: tauri::AppHandle,
+}
+
+/// Emits `connection_stats` to the frontend every few seconds.
+pub fn spawn_stats_reporter(ctx: &ServerContext) {
+    let app_state = Arc::clone(&ctx.app_state);
+    let app_handle = ctx.app_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) =
+                app_handle.emit_all("connection_stats", app_state.connection_stats().await)
+            {
+                error!("Error emitting connection stats: {}", e);
+            }
+        }
+    });
+}
+
+/// The WebSocket endpoint clients connect to.
+pub fn routes(
+    ctx: ServerContext,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    warp::path::end()
         .and(warp::ws())
-        .and(warp::any().map(move || store.clone()))
-        .and(warp::any().map(move || app_state.clone()))
-        .and(warp::any().map(move || app_handle.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::any().map(move || ctx.clone()))
         .map(
-            |ws: warp::ws::Ws,
-             store: Arc<ShortcutStore>,
-             app_state: Arc<AppState>,
-             app_handle: tauri::AppHandle| {
+            |ws: warp::ws::Ws, query: HashMap<String, String>, ctx: ServerContext| {
+                // Blocked devices that identify themselves in the URL never get a socket
+                if let Some(device_id) = query.get("device_id") {
+                    if ctx.devices.is_blocked(device_id) {
+                        warn!("Refusing blocked device {}.", device_id);
+                        return warp::reply::with_status(
+                            "Device is blocked",
+                            warp::http::StatusCode::FORBIDDEN,
+                        )
+                        .into_response();
+                    }
+                    if ctx.app_state.is_reconnect_banned(device_id) {
+                        warn!("Refusing recently disconnected device {}.", device_id);
+                        return warp::reply::with_status(
+                            "Device was disconnected from the desktop; try again later",
+                            warp::http::StatusCode::FORBIDDEN,
+                        )
+                        .into_response();
+                    }
+                }
+
+                // Clients may authenticate up front with `?token=...` in the URL
+                let authenticated = query
+                    .get("token")
+                    .is_some_and(|token| ctx.auth.verify(token));
                 ws.on_upgrade(move |websocket| {
-                    handle_websocket_connection(websocket, store, app_state, app_handle)
+                    handle_websocket_connection(websocket, authenticated, ctx)
                 })
+                .into_response()
             },
-        );
-
-    let addr = format!("{}:{}", ip, port);
-    println!("WebSocket server listening on ws://{}", addr);
-
-    warp::serve(ws_route)
-        .run(addr.parse::<std::net::SocketAddr>().unwrap())
-        .await;
+        )
 }
 
 pub async fn handle_websocket_connection(
     websocket: WebSocket,
-    store: Arc<ShortcutStore>,
-    app_state: Arc<AppState>,
-    app_handle: tauri::AppHandle,
+    authenticated: bool,
+    ctx: ServerContext,
 ) {
-    let (ws_sender, mut ws_receiver) = websocket.split();
-    let send_ws_sender = Arc::new(Mutex::new(ws_sender));
+    let (ws_sender, ws_receiver) = websocket.split();
+    let incoming = ws_receiver.map(|result| result.map_err(|e| e.to_string()));
+    run_connection(incoming, WsSender::new(ws_sender), authenticated, ctx).await;
+}
 
-    {
-        let device_lock = app_state.device.lock().await;
-        if device_lock.is_some() {
-            println!("A device is already connected. Rejecting new connection.");
-            let rejection_message = Message::text("connection_rejected");
-            send_ws_sender
-                .lock()
-                .await
-                .send(rejection_message)
-                .await
-                .ok();
-            return;
-        } else {
-            println!("New connection attempt.");
-        }
-    }
-
-    let app_handle_clone = app_handle.clone();
-    let recv_store = Arc::clone(&store);
-    let recv_app_state = Arc::clone(&app_state);
-    let send_ws_sender_clone = Arc::clone(&send_ws_sender);
-
-    let recv_task = tokio::spawn(async move {
-        while let Some(result) = ws_receiver.next().await {
-            match result {
-                Ok(message) => {
-                    if let Ok(text) = message.to_str() {
-                        if let Ok(data) = serde_json::from_str::<Value>(text) {
-                            match data.get("type").and_then(|t| t.as_str()) {
-                                Some("device_info") => {
-                                    handle_device_info(
-                                        data,
-                                        recv_app_state.clone(),
-                                        app_handle_clone.clone(),
-                                        send_ws_sender_clone.clone(),
-                                        recv_store.clone(),
-                                    )
+/// Serves one client connection until it closes, whatever the transport.
+pub async fn run_connection<S>(
+    mut incoming: S,
+    sender: WsSender,
+    authenticated: bool,
+    ctx: ServerContext,
+) where
+    S: Stream<Item = Result<Message, String>> + Unpin,
+{
+    let connection_id = uuid::Uuid::new_v4().to_string();
+
+    ctx.app_state.connections.lock().await.insert(
+        connection_id.clone(),
+        Connection {
+            device: None,
+            sender: sender.clone(),
+            protocol_version: 1,
+            capabilities: Vec::new(),
+            authenticated,
+            pairing_pin: None,
+            trigger_limiter: TokenBucket::new(),
+            session_token: None,
+            connected_at: SystemTime::now(),
+            messages_received: 0,
+            last_rtt: None,
+        },
+    );
+    debug!("New connection {}.", connection_id);
+
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    let mut ping_sent_at = None;
+
+    loop {
+        tokio::select! {
+            frame = incoming.next() => {
+                let Some(result) = frame else { break };
+                // Any frame, including pongs, proves the client is still there
+                last_seen = Instant::now();
+
+                match result {
+                    Ok(message) if message.is_close() => break,
+                    Ok(message) if message.is_pong() => {
+                        if let Some(sent) = ping_sent_at.take() {
+                            let rtt = Instant::now().duration_since(sent);
+                            if let Some(connection) =
+                                ctx.app_state.connections.lock().await.get_mut(&connection_id)
+                            {
+                                connection.last_rtt = Some(rtt);
+                            }
+                        }
+                    }
+                    Ok(message) => match decode_message(&message) {
+                        Some(Ok(data)) => {
+                            let received = last_seen;
+                            if let Some(connection) =
+                                ctx.app_state.connections.lock().await.get_mut(&connection_id)
+                            {
+                                connection.messages_received += 1;
+                            }
+                            let keep_open =
+                                handle_message(data, received, &connection_id, &sender, &ctx)
                                     .await;
-                                }
-                                Some("execute_shortcut") => {
-                                    handle_execute_shortcut(data, recv_store.clone()).await;
-                                }
-                                _ => println!("Unknown message type or missing type field."),
+                            if !keep_open {
+                                break;
                             }
                         }
+                        Some(Err(e)) => warn!("Received undecodable message: {}", e),
+                        None => {}
+                    },
+                    Err(e) => {
+                        error!("Connection error: {}", e);
+                        break;
                     }
                 }
-                Err(e) => {
-                    eprintln!("WebSocket error: {}", e);
+            }
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > HEARTBEAT_TIMEOUT {
+                    info!("Connection {} timed out.", connection_id);
+                    sender.close().await;
                     break;
                 }
+                match sender.send_message(Message::ping(Vec::new())).await {
+                    Ok(()) => ping_sent_at = Some(Instant::now()),
+                    Err(e) => error!("Error sending ping: {}", e),
+                }
             }
         }
+    }
 
-        if recv_app_state.device.lock().await.is_some() {
-            println!("Device disconnected.");
-            let mut device_lock = recv_app_state.device.lock().await;
-            *device_lock = None;
+    let removed = ctx
+        .app_state
+        .connections
+        .lock()
+        .await
+        .remove(&connection_id);
+    if let Some(Connection {
+        device: Some(mut device),
+        pairing_pin,
+        session_token,
+        authenticated,
+        ..
+    }) = removed
+    {
+        info!("Device disconnected: {}", device.name);
+        if let Err(e) = ctx.devices.touch(&device.id) {
+            report(&ctx.app_handle, &e);
+        }
+        if device.approved {
+            ctx.activity.record(
+                Some(&device.id),
+                Some(&device.name),
+                ActivityEvent::Disconnected,
+            );
+        }
 
-            // Emit events on device disconnection
-            app_handle_clone
-                .emit_all("devices_updated", None::<&Device>)
-                .unwrap();
+        // Keep the session around so a brief network drop doesn't require pairing again
+        if let Some(token) = session_token {
+            if !ctx.devices.is_blocked(&device.id) {
+                let mut sessions = ctx.app_state.sessions.lock().await;
+                sessions.retain(|_, session| session.expires_at > Instant::now());
+                sessions.insert(
+                    token,
+                    Session {
+                        device: device.clone(),
+                        pairing_pin,
+                        authenticated,
+                        expires_at: Instant::now() + SESSION_TTL,
+                    },
+                );
+            }
         }
-    });
 
-    tokio::select! {
-        _ = recv_task => {},
+        device.connected = false;
+        ctx.events.publish(AppEvent::DeviceDisconnected(device));
     }
 }
 
-async fn handle_device_info(
+/// Dispatches one decoded message, replying if it carried an `id`.
+/// `received` is when its frame arrived. Returns `false` when the connection
+/// should be closed.
+async fn handle_message(
     data: Value,
-    app_state: Arc<AppState>,
-    app_handle: tauri::AppHandle,
-    send_ws_sender: Arc<Mutex<futures_util::stream::SplitSink<WebSocket, Message>>>,
-    store: Arc<ShortcutStore>,
+    received: Instant,
+    connection_id: &str,
+    sender: &WsSender,
+    ctx: &ServerContext,
+) -> bool {
+    let request_id = data.get("id").cloned();
+    let message = serde_json::from_value::<ClientMessage>(data);
+
+    let mut close_after_reply = false;
+    let mut switch_encoding = None;
+    let too_old = match &message {
+        Ok(ClientMessage::Hello {
+            protocol_version, ..
+        }) => Some(*protocol_version).filter(|version| *version < MIN_PROTOCOL_VERSION),
+        _ => None,
+    };
+    let result = if let Some(protocol_version) = too_old {
+        // Clients that are too old get a clear error whatever else they sent,
+        // then the socket is closed
+        close_after_reply = true;
+        Err(format!(
+            "Client protocol version {} is too old; this desktop requires version {} or newer. Please update the mobile app.",
+            protocol_version, MIN_PROTOCOL_VERSION
+        ))
+    } else if !authenticate(&message, connection_id, ctx).await {
+        // Unauthenticated sockets only get to hear why they are being dropped
+        warn!("Rejecting unauthenticated connection {}.", connection_id);
+        close_after_reply = true;
+        Err("Authentication required: missing or invalid token".to_string())
+    } else if let Err(e) = authorize(&message, connection_id, ctx).await {
+        Err(e)
+    } else {
+        match message {
+            Ok(ClientMessage::Hello {
+                protocol_version,
+                capabilities,
+                ..
+            }) => {
+                if capabilities.iter().any(|c| c == CAP_MSGPACK) {
+                    switch_encoding = Some(Encoding::MessagePack);
+                }
+                handle_hello(protocol_version, capabilities, connection_id, ctx).await
+            }
+            Ok(ClientMessage::Auth { .. }) => Ok(None),
+            Ok(ClientMessage::DeviceInfo {
+                device_name,
+                device_id,
+                device_secret,
+                status,
+            }) => {
+                if device_id
+                    .as_deref()
+                    .is_some_and(|id| ctx.devices.is_blocked(id))
+                {
+                    close_after_reply = true;
+                    Err("This device has been blocked on the desktop".to_string())
+                } else if device_id
+                    .as_deref()
+                    .is_some_and(|id| ctx.app_state.is_reconnect_banned(id))
+                {
+                    close_after_reply = true;
+                    Err("Device was disconnected from the desktop; try again later".to_string())
+                } else {
+                    handle_device_info(
+                        device_name,
+                        device_id,
+                        device_secret,
+                        status,
+                        connection_id,
+                        ctx,
+                    )
+                    .await
+                }
+            }
+            Ok(ClientMessage::DeviceStatus(status)) => {
+                handle_device_status(status, connection_id, ctx).await
+            }
+            Ok(ClientMessage::ExecuteShortcut {
+                shortcut_id,
+                interval_ms,
+                press_kind,
+            }) => {
+                handle_execute_shortcut(
+                    shortcut_id,
+                    interval_ms,
+                    press_kind,
+                    received,
+                    request_id.clone(),
+                    connection_id,
+                    ctx,
+                )
+                .await
+            }
+            Ok(ClientMessage::DialRotate { shortcut_id, delta }) => {
+                handle_control(shortcut_id, ControlInput::Rotate(delta), connection_id, ctx).await
+            }
+            Ok(ClientMessage::SliderSet { shortcut_id, value }) => {
+                handle_control(shortcut_id, ControlInput::Set(value), connection_id, ctx).await
+            }
+            Ok(ClientMessage::Resume { session_token }) => {
+                handle_resume(session_token, connection_id, ctx).await
+            }
+            Ok(ClientMessage::AddShortcut { shortcut }) => {
+                add_shortcut_to_store(shortcut, &ctx.store, &ctx.app_handle)
+                    .and_then(|added| serde_json::to_value(added).map_err(|e| e.to_string()))
+                    .map(Some)
+            }
+            Ok(ClientMessage::UpdateShortcut { shortcut }) => {
+                update_shortcut_in_store(shortcut, &ctx.store, &ctx.app_handle)
+                    .and_then(|updated| serde_json::to_value(updated).map_err(|e| e.to_string()))
+                    .map(Some)
+            }
+            Ok(ClientMessage::DeleteShortcut { shortcut_id }) => {
+                delete_shortcut_from_store(shortcut_id, &ctx.store).map(|()| None)
+            }
+            Ok(ClientMessage::GetShortcuts {
+                group,
+                tag,
+                page,
+                page_size,
+            }) => shortcut_page(ctx.store.get_shortcuts(), group, tag, page, page_size).map(Some),
+            Ok(ClientMessage::GetSettings) => serde_json::to_value(ctx.settings.get_settings())
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            Ok(ClientMessage::UploadAsset { name, data }) => {
+                let assets = ctx.app_handle.state::<Arc<AssetStore>>().inner().clone();
+                upload_asset(name, data, assets, &ctx.app_handle)
+                    .await
+                    .map(|()| None)
+            }
+            Ok(ClientMessage::ListAssets) => {
+                let assets = ctx.app_handle.state::<Arc<AssetStore>>();
+                Ok(Some(assets.list().into()))
+            }
+            Ok(ClientMessage::GetSystemLevels) => tokio::task::spawn_blocking(levels::current)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|levels| serde_json::to_value(levels).map_err(|e| e.to_string()))
+                .map(Some),
+            Ok(ClientMessage::GetNowPlaying) => tokio::task::spawn_blocking(media::now_playing)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|track| serde_json::to_value(track?).map_err(|e| e.to_string()))
+                .map(Some),
+            Ok(ClientMessage::VoiceCommand { text }) => {
+                handle_voice_command(text, received, request_id.clone(), connection_id, ctx).await
+            }
+            Err(e) => Err(format!("Invalid message: {}", e)),
+        }
+    };
+
+    let succeeded = result.is_ok();
+    match request_id {
+        Some(id) => sender.send_value(&Response::from_result(id, result)).await,
+        // Whoever is dropped hears why, even without asking
+        None if close_after_reply => {
+            if let Err(e) = result {
+                sender
+                    .send_value(&serde_json::json!({ "type": "error", "error": e }))
+                    .await;
+            }
+        }
+        None => {
+            if let Err(e) = result {
+                error!("{}", e);
+            }
+        }
+    }
+
+    if close_after_reply {
+        sender.close().await;
+        return false;
+    }
+    // The hello reply goes out in JSON; everything after it uses the new encoding
+    if let (true, Some(encoding)) = (succeeded, switch_encoding) {
+        sender.set_encoding(encoding);
+    }
+    true
+}
+
+/// Returns whether the connection may proceed, authenticating it if this
+/// message carries a valid token. When the desktop only pairs, a client
+/// without the token may still say hello and ask to pair; `handle_device_info`
+/// authenticates it once it is approved or presents its device secret.
+async fn authenticate(
+    message: &Result<ClientMessage, serde_json::Error>,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> bool {
+    let mut connections = ctx.app_state.connections.lock().await;
+    let Some(connection) = connections.get_mut(connection_id) else {
+        return false;
+    };
+    if connection.authenticated {
+        return true;
+    }
+    let token = match message {
+        Ok(ClientMessage::Auth { token }) => Some(token),
+        Ok(ClientMessage::Hello { token, .. }) => token.as_ref(),
+        // The session token is checked, and the connection authenticated, by `handle_resume`
+        Ok(ClientMessage::Resume { .. }) => return true,
+        _ => None,
+    };
+    if let Some(token) = token {
+        connection.authenticated = ctx.auth.verify(token);
+        return connection.authenticated;
+    }
+    ctx.settings.auth_mode() == AuthMode::PairingOnly
+        && matches!(
+            message,
+            Ok(ClientMessage::Hello { .. })
+                | Ok(ClientMessage::DeviceInfo { .. })
+                | Ok(ClientMessage::DeviceStatus(_))
+        )
+}
+
+async fn handle_hello(
+    protocol_version: u32,
+    capabilities: Vec<String>,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<Option<Value>, String> {
+    info!(
+        "Connection {} speaks protocol version {} with capabilities {:?}",
+        connection_id, protocol_version, capabilities
+    );
+
+    {
+        let mut connections = ctx.app_state.connections.lock().await;
+        let connection = connections
+            .get_mut(connection_id)
+            .ok_or("Connection is closed")?;
+        connection.protocol_version = protocol_version.min(PROTOCOL_VERSION);
+        connection.capabilities = capabilities;
+    }
+
+    Ok(Some(serde_json::json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "min_protocol_version": MIN_PROTOCOL_VERSION,
+        "capabilities": SERVER_CAPABILITIES,
+    })))
+}
+
+/// Checks that the connection's device is paired and its role permits the message.
+async fn authorize(
+    message: &Result<ClientMessage, serde_json::Error>,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<(), String> {
+    let Some(required) = message.as_ref().ok().and_then(ClientMessage::required_role) else {
+        return Ok(());
+    };
+    let device_id = {
+        let connections = ctx.app_state.connections.lock().await;
+        connections
+            .get(connection_id)
+            .filter(|c| c.is_approved())
+            .and_then(|c| c.device.as_ref())
+            .map(|d| d.id.clone())
+            .ok_or("Device is not paired; approve it on the desktop first")?
+    };
+    if required == DeviceRole::Admin && ctx.devices.role(&device_id) != DeviceRole::Admin {
+        return Err(
+            "This device may only trigger shortcuts; make it an admin on the desktop".to_string(),
+        );
+    }
+    Ok(())
+}
+
+fn generate_pin() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+async fn handle_device_info(
+    name: String,
+    device_id: Option<String>,
+    device_secret: Option<String>,
+    status: DeviceStatus,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<Option<Value>, String> {
+    // Only devices with a stable ID can be recognised again later
+    if let Some(id) = &device_id {
+        if let Err(e) = ctx.devices.record_seen(id, &name) {
+            report(&ctx.app_handle, &e);
+        }
+    }
+    let remembered = device_id.is_some();
+    let id = device_id.unwrap_or_else(|| connection_id.to_string());
+    // A nickname set on the desktop wins over the name the phone reports
+    let name = ctx.devices.nickname(&id).unwrap_or(name);
+    info!("Device connected: {}", name);
+    // Without the token, only the secret issued at pairing proves this is the
+    // trusted device and not another one claiming its ID
+    let authenticated = {
+        let mut connections = ctx.app_state.connections.lock().await;
+        let connection = connections
+            .get_mut(connection_id)
+            .ok_or("Connection is closed")?;
+        if !connection.authenticated && remembered {
+            connection.authenticated = device_secret
+                .as_deref()
+                .is_some_and(|secret| ctx.devices.verify_secret(&id, secret));
+        }
+        connection.authenticated
+    };
+    let approved = authenticated && ctx.devices.trust_state(&id) == Some(TrustState::Trusted);
+    let device = Device {
+        id,
+        name,
+        connected: true,
+        approved,
+        status,
+    };
+    let pin = if approved { None } else { Some(generate_pin()) };
+
+    let (sender, capabilities, session_token) = {
+        let mut connections = ctx.app_state.connections.lock().await;
+        let connection = connections
+            .get_mut(connection_id)
+            .ok_or("Connection is closed")?;
+        connection.device = Some(device.clone());
+        connection.pairing_pin = pin.clone();
+        let session_token = connection
+            .session_token
+            .get_or_insert_with(|| uuid::Uuid::new_v4().simple().to_string())
+            .clone();
+        (
+            connection.sender.clone(),
+            connection.capabilities.clone(),
+            session_token,
+        )
+    };
+
+    if let Some(pin) = pin {
+        // Unknown device: wait until the user compares the PIN and approves it
+        info!("Device {} is awaiting pairing approval.", device.name);
+        ctx.events
+            .publish(AppEvent::DevicePendingApproval(device.clone()));
+        ctx.events
+            .publish(AppEvent::DevicePairingRequested(PairingRequest {
+                device: device.clone(),
+                pin: pin.clone(),
+            }));
+        return Ok(Some(serde_json::json!({
+            "status": "pending_approval",
+            "pin": pin,
+            "device": device,
+            "session_token": session_token,
+        })));
+    }
+
+    accept_device(
+        &device,
+        &sender,
+        &capabilities,
+        &ctx.devices,
+        &ctx.store,
+        &ctx.app_handle,
+    )
+    .await;
+
+    let mut payload = serde_json::to_value(&device).map_err(|e| e.to_string())?;
+    payload["session_token"] = Value::String(session_token);
+    Ok(Some(payload))
+}
+
+/// Moves a session onto this connection: from a disconnected device, or from
+/// a connection that hasn't noticed yet that the network dropped.
+async fn handle_resume(
+    session_token: String,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<Option<Value>, String> {
+    let mut stale_sender = None;
+    let session = {
+        let mut connections = ctx.app_state.connections.lock().await;
+        let stale = connections
+            .iter_mut()
+            .filter(|(id, _)| id.as_str() != connection_id)
+            .map(|(_, connection)| connection)
+            .find(|c| c.session_token.as_deref() == Some(session_token.as_str()));
+        match stale {
+            Some(connection) => {
+                // Taking the token keeps the old socket from saving the session on close
+                connection.session_token = None;
+                stale_sender = Some(connection.sender.clone());
+                connection.device.take().map(|device| Session {
+                    device,
+                    pairing_pin: connection.pairing_pin.take(),
+                    authenticated: connection.authenticated,
+                    expires_at: Instant::now() + SESSION_TTL,
+                })
+            }
+            None => ctx
+                .app_state
+                .sessions
+                .lock()
+                .await
+                .remove(&session_token)
+                .filter(|session| session.expires_at > Instant::now()),
+        }
+    };
+    if let Some(sender) = stale_sender {
+        sender.close().await;
+    }
+
+    let Session {
+        mut device,
+        mut pairing_pin,
+        authenticated,
+        ..
+    } = session.ok_or("Session expired or unknown; send device_info to pair again")?;
+    if ctx.devices.is_blocked(&device.id) {
+        return Err("This device has been blocked on the desktop".to_string());
+    }
+    if ctx.app_state.is_reconnect_banned(&device.id) {
+        return Err("Device was disconnected from the desktop; try again later".to_string());
+    }
+
+    // The user may have trusted the device while it was away
+    device.connected = true;
+    if authenticated && ctx.devices.trust_state(&device.id) == Some(TrustState::Trusted) {
+        device.approved = true;
+        pairing_pin = None;
+    }
+    info!("Device {} resumed its session.", device.name);
+    if let Err(e) = ctx.devices.touch(&device.id) {
+        report(&ctx.app_handle, &e);
+    }
+
+    let (sender, capabilities) = {
+        let mut connections = ctx.app_state.connections.lock().await;
+        let connection = connections
+            .get_mut(connection_id)
+            .ok_or("Connection is closed")?;
+        connection.authenticated = authenticated;
+        connection.device = Some(device.clone());
+        connection.pairing_pin = pairing_pin.clone();
+        connection.session_token = Some(session_token.clone());
+        (connection.sender.clone(), connection.capabilities.clone())
+    };
+
+    if device.approved {
+        accept_device(
+            &device,
+            &sender,
+            &capabilities,
+            &ctx.devices,
+            &ctx.store,
+            &ctx.app_handle,
+        )
+        .await;
+        return Ok(Some(serde_json::json!({
+            "status": "resumed",
+            "device": device,
+            "session_token": session_token,
+        })));
+    }
+
+    ctx.events
+        .publish(AppEvent::DevicePendingApproval(device.clone()));
+    Ok(Some(serde_json::json!({
+        "status": "pending_approval",
+        "pin": pairing_pin,
+        "device": device,
+        "session_token": session_token,
+    })))
+}
+
+async fn handle_device_status(
+    status: DeviceStatus,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<Option<Value>, String> {
+    let device = {
+        let mut connections = ctx.app_state.connections.lock().await;
+        let device = connections
+            .get_mut(connection_id)
+            .ok_or("Connection is closed")?
+            .device
+            .as_mut()
+            .ok_or("Send device_info before device_status")?;
+        device.status.merge(status);
+        device.clone()
+    };
+
+    ctx.events.publish(AppEvent::DeviceUpdated(device));
+    Ok(None)
+}
+
+/// Completes the connection of a paired device.
+async fn accept_device(
+    device: &Device,
+    sender: &WsSender,
+    capabilities: &[String],
+    registry: &DeviceRegistry,
+    store: &ShortcutStore,
+    app_handle: &AppHandle,
 ) {
-    if let Some(name) = data.get("device_name").and_then(|n| n.as_str()) {
-        println!("Device connected: {}", name);
-        let mut device_lock = app_state.device.lock().await;
+    publish(app_handle, AppEvent::DeviceConnected(device.clone()));
+    notify(
+        app_handle,
+        NotificationKind::DeviceConnected,
+        format!("{} can now trigger shortcuts", device.name),
+    );
+    app_handle.state::<Arc<ActivityLog>>().record(
+        Some(&device.id),
+        Some(&device.name),
+        ActivityEvent::Connected,
+    );
+
+    // Send shortcuts to client
+    let diffs = capabilities.iter().any(|c| c == CAP_SHORTCUT_DIFFS);
+    let message =
+        app_handle
+            .state::<Arc<AppState>>()
+            .shortcut_list_message(store, diffs, sender.encoding());
+    if let Err(e) = match message {
+        Ok(message) => sender.send_encoded(message).await,
+        Err(e) => Err(e),
+    } {
+        error!("{}", e);
+    }
+
+    if capabilities.iter().any(|c| c == CAP_LAYOUTS) {
+        let layout = registry.layout(&device.id);
+        sender.send_value(&layout_message(layout.as_ref())).await;
+    }
+
+    if capabilities.iter().any(|c| c == CAP_HOTKEY_CONFLICTS) {
+        let conflicts = app_handle.state::<Arc<RegisteredHotkeys>>().conflicts();
+        if !conflicts.is_empty() {
+            sender.send_value(&conflicts_message(&conflicts)).await;
+        }
+    }
+
+    if capabilities.iter().any(|c| c == CAP_SHORTCUT_STATES) {
+        let states = app_handle.state::<Arc<ShortcutStates>>().all();
+        sender.send_value(&states_message(&states)).await;
+    }
+
+    if capabilities.iter().any(|c| c == CAP_CYCLE_POSITIONS) {
+        sender
+            .send_value(&cycle_positions_message(&store.cycle_positions()))
+            .await;
+    }
+
+    if app_handle
+        .state::<Arc<AppState>>()
+        .triggering_paused
+        .load(Ordering::SeqCst)
+    {
+        sender.send_value(&paused_message(true)).await;
+    }
+}
+
+fn paused_message(paused: bool) -> Value {
+    serde_json::json!({ "type": "server_paused", "paused": paused })
+}
+
+/// Pauses or resumes all triggering: WS, HTTP and global shortcuts. Paired
+/// clients get a `server_paused` message so they can grey out their buttons.
+pub async fn set_paused(paused: bool, app_state: &AppState, app_handle: &AppHandle) {
+    if app_state.triggering_paused.swap(paused, Ordering::SeqCst) == paused {
+        return;
+    }
+    info!("Triggering {}.", if paused { "paused" } else { "resumed" });
+
+    app_state.broadcast(&paused_message(paused)).await;
+    set_pause_item_title(app_handle, paused);
+    emit(app_handle, "triggering_paused_changed", paused);
+}
+
+/// Flips the pause state; used by the tray and the pause hotkey.
+pub fn toggle_paused(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let app_state = app_handle.state::<Arc<AppState>>().inner().clone();
+        let paused = !app_state.triggering_paused.load(Ordering::SeqCst);
+        set_paused(paused, &app_state, &app_handle).await;
+    });
+}
+
+async fn handle_execute_shortcut(
+    shortcut_id: u64,
+    interval_ms: Option<u64>,
+    press_kind: PressKind,
+    received: Instant,
+    request_id: Option<Value>,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<Option<Value>, String> {
+    if ctx.app_state.triggering_paused.load(Ordering::SeqCst) {
+        return Err("Triggering is paused on the desktop".to_string());
+    }
+
+    let rate = ctx.settings.max_triggers_per_second();
+    let (sender, wants_result, wants_latency, device) = {
+        let mut connections = ctx.app_state.connections.lock().await;
+        let connection = connections
+            .get_mut(connection_id)
+            .ok_or("Connection is closed")?;
+        if !connection.trigger_limiter.try_take(rate) {
+            warn!("Rate limit exceeded by connection {}.", connection_id);
+            return Err(format!(
+                "Rate limit exceeded: at most {} triggers per second",
+                rate
+            ));
+        }
+        (
+            connection.sender.clone(),
+            connection.supports(CAP_EXECUTION_RESULTS),
+            connection.supports(CAP_LATENCY),
+            connection.device.clone(),
+        )
+    };
+
+    info!(
+        "Executing shortcut with ID: {} ({:?})",
+        shortcut_id, press_kind
+    );
+
+    let all_shortcuts = ctx.store.get_shortcuts();
+
+    // Find the shortcut by ID
+    let shortcut = all_shortcuts
+        .iter()
+        .find(|s| s.id == shortcut_id)
+        .ok_or_else(|| format!("Shortcut with ID {} not found.", shortcut_id))?;
+    debug!("Found shortcut: {:?}", shortcut);
+
+    // Over the limits a trigger is refused here, or waits in the task below
+    let reservation = ctx.app_state.running.reserve(
+        device.as_ref().map(|d| d.id.as_str()),
+        ctx.settings.execution_limits(),
+    )?;
+    notify(
+        &ctx.app_handle,
+        NotificationKind::ShortcutTriggered,
+        format!(
+            "{} ran \"{}\"",
+            device.as_ref().map_or("A device", |d| d.name.as_str()),
+            shortcut.name
+        ),
+    );
+
+    // The shortcut's own timing wins; the client's `interval_ms` is only a fallback
+    let timing = shortcut
+        .timing()
+        .or_default_interval(interval_ms)
+        .or_default_interval(ctx.settings.default_interval_ms());
+
+    // Run the whole sequence, including text and secret steps, off the async runtime
+    let sequence = ctx.store.next_sequence(shortcut, press_kind);
+    let activity = Arc::clone(&ctx.activity);
+    let app_handle = ctx.app_handle.clone();
+    tokio::spawn(async move {
+        let monitor = app_handle
+            .state::<Arc<PerformanceMonitor>>()
+            .inner()
+            .clone();
+        let (result, latency) = match reservation.wait().await {
+            Ok(slot) => match tokio::task::spawn_blocking(move || {
+                // Given back once the sequence is done, even if it panics
+                let _slot = slot;
+                let started = Instant::now();
+                let result = run_sequence(&app_handle, shortcut_id, sequence, timing);
+                (
+                    result,
+                    TriggerLatency::new(received, started, Instant::now()),
+                )
+            })
+            .await
+            {
+                Ok((result, latency)) => {
+                    debug!(
+                        "Shortcut {} took {:.1} ms ({:.1} ms before it started)",
+                        shortcut_id, latency.total_ms, latency.queue_ms
+                    );
+                    monitor.record(latency);
+                    (result, Some(latency))
+                }
+                Err(e) => (Err(format!("Shortcut execution panicked: {}", e)), None),
+            },
+            // Waited in the queue for too long
+            Err(e) => (Err(e), None),
+        };
+        activity.record(
+            device.as_ref().map(|d| d.id.as_str()),
+            device.as_ref().map(|d| d.name.as_str()),
+            ActivityEvent::ShortcutExecuted {
+                shortcut_id,
+                ok: result.is_ok(),
+                error: result.as_ref().err().cloned(),
+            },
+        );
+        if !wants_result {
+            return;
+        }
 
-        *device_lock = Some(Device {
-            name: name.to_string(),
-            connected: true,
+        // Lets the button on the phone show whether it actually worked
+        let mut message = serde_json::json!({
+            "type": "execution_result",
+            "shortcut_id": shortcut_id,
+            "ok": result.is_ok(),
+            "duration_ms": latency.map_or(0, |l| l.execution_ms as u64),
         });
+        // Not as `id`, which clients match their one response by
+        if let Some(id) = request_id {
+            message["request_id"] = id;
+        }
+        if let Some(latency) = latency.filter(|_| wants_latency) {
+            message["latency"] = serde_json::json!(latency);
+        }
+        match result {
+            Ok(output) if !output.files.is_empty() => message["files"] = output.files.into(),
+            Ok(_) => {}
+            Err(e) => message["error"] = Value::String(e),
+        }
+        sender.send_value(&message).await;
+    });
+    Ok(None)
+}
 
-        // Emit events
-        app_handle
-            .emit_all("devices_updated", &*device_lock)
-            .unwrap();
-        app_handle
-            .emit_all("device_connected", &*device_lock)
-            .unwrap();
+/// How a phone moved a dial or slider.
+enum ControlInput {
+    Rotate(i32),
+    Set(f64),
+}
+
+/// Drives a shortcut's control. Dials send a message per detent, so these
+/// don't count towards the trigger rate limit.
+async fn handle_control(
+    shortcut_id: u64,
+    input: ControlInput,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<Option<Value>, String> {
+    // MessagePack can carry NaN and infinity, which no level maps to
+    if let ControlInput::Set(value) = input {
+        if !value.is_finite() {
+            return Err("Slider value must be a finite number".to_string());
+        }
+    }
+    if ctx.app_state.triggering_paused.load(Ordering::SeqCst) {
+        return Err("Triggering is paused on the desktop".to_string());
+    }
+    let shortcut = ctx
+        .store
+        .get_shortcuts()
+        .into_iter()
+        .find(|s| s.id == shortcut_id)
+        .ok_or_else(|| format!("Shortcut with ID {} not found.", shortcut_id))?;
+    let control = shortcut
+        .control
+        .ok_or_else(|| format!("\"{}\" isn't a dial or slider", shortcut.name))?;
+
+    // Counted against the execution limits like any other run
+    let device_id = {
+        let connections = ctx.app_state.connections.lock().await;
+        connections
+            .get(connection_id)
+            .ok_or("Connection is closed")?
+            .device
+            .as_ref()
+            .map(|d| d.id.clone())
+    };
+    let slot = ctx
+        .app_state
+        .running
+        .reserve(device_id.as_deref(), ctx.settings.execution_limits())?
+        .wait()
+        .await?;
+
+    let app_handle = ctx.app_handle.clone();
+    tokio::task::spawn_blocking(move || {
+        let _slot = slot;
+        match input {
+            ControlInput::Rotate(delta) => controls::rotate(&app_handle, control, delta),
+            ControlInput::Set(value) => controls::set(&app_handle, control, value),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map(|()| None)
+}
+
+async fn handle_voice_command(
+    text: String,
+    received: Instant,
+    request_id: Option<Value>,
+    connection_id: &str,
+    ctx: &ServerContext,
+) -> Result<Option<Value>, String> {
+    let shortcut = ctx.store.find_by_spoken_name(&text)?;
+    info!(
+        "Voice command \"{}\" matched shortcut {}",
+        text, shortcut.id
+    );
+    handle_execute_shortcut(
+        shortcut.id,
+        None,
+        PressKind::Tap,
+        received,
+        request_id,
+        connection_id,
+        ctx,
+    )
+    .await?;
+    Ok(Some(serde_json::json!({
+        "shortcut_id": shortcut.id,
+        "name": shortcut.name,
+    })))
+}
 
-        // Send shortcuts to client
-        let all_shortcuts = store.get_shortcuts();
-        let shortcuts_json = serde_json::to_string(&all_shortcuts).unwrap();
-        let mut sender_guard = send_ws_sender.lock().await;
+/// Approves a device that is waiting for pairing on one of the open connections.
+pub async fn approve_pending_device(
+    device_id: &str,
+    registry: &DeviceRegistry,
+    store: &ShortcutStore,
+    app_state: &AppState,
+    app_handle: &AppHandle,
+) -> Result<(), String> {
+    let (device, sender, capabilities, paired_without_token) = {
+        let mut connections = app_state.connections.lock().await;
+        let connection = connections
+            .values_mut()
+            .find(|c| {
+                c.pairing_pin.is_some() && c.device.as_ref().is_some_and(|d| d.id == device_id)
+            })
+            .ok_or_else(|| format!("No device {} is waiting for approval", device_id))?;
+        connection.pairing_pin = None;
+        let paired_without_token = !connection.authenticated;
+        connection.authenticated = true;
+        let device = connection
+            .device
+            .as_mut()
+            .ok_or("Device is not identified")?;
+        device.approved = true;
+        let device = device.clone();
+        (
+            device,
+            connection.sender.clone(),
+            connection.capabilities.clone(),
+            paired_without_token,
+        )
+    };
 
-        sender_guard.send(Message::text(shortcuts_json)).await.ok();
+    info!("Device {} approved.", device.name);
+    let mut approved = serde_json::json!({ "type": "pairing_approved" });
+    if paired_without_token {
+        // The device needs this instead of the token to be recognised later
+        if let Some(secret) = registry.issue_secret(&device.id)? {
+            approved["device_secret"] = Value::String(secret);
+        }
+    }
+    sender.send_value(&approved).await;
+    accept_device(&device, &sender, &capabilities, registry, store, app_handle).await;
+
+    Ok(())
+}
+
+/// Tells every connection of a device why it is being dropped, then closes
+/// them. Their sessions are discarded, so the device can't simply resume.
+/// Returns the number of connections closed.
+pub async fn disconnect_device_connections(
+    device_id: &str,
+    reason: &str,
+    app_state: &AppState,
+) -> usize {
+    let senders: Vec<WsSender> = {
+        let mut connections = app_state.connections.lock().await;
+        connections
+            .values_mut()
+            .filter(|c| c.device.as_ref().is_some_and(|d| d.id == device_id))
+            .map(|c| {
+                c.session_token = None;
+                c.sender.clone()
+            })
+            .collect()
+    };
+    app_state
+        .sessions
+        .lock()
+        .await
+        .retain(|_, session| session.device.id != device_id);
+
+    let count = senders.len();
+    for sender in senders {
+        sender
+            .send_value(&serde_json::json!({ "type": reason }))
+            .await;
+        sender.close().await;
+    }
+    count
+}
+
+/// Closes a device's connections from the desktop, e.g. to evict a stuck or
+/// unwanted client.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the device to disconnect.
+/// * `block_seconds` - If set, the device is refused for this long before it may reconnect.
+/// * `app_state` - Shared state containing the connections.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn disconnect_device(
+    device_id: String,
+    block_seconds: Option<u64>,
+    app_state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    // Banned before closing, so the device can't slip back in between, and
+    // taken back if there was nothing to close
+    let ban = block_seconds
+        .filter(|seconds| *seconds > 0)
+        .map(|seconds| -> Result<_, String> {
+            Ok(app_state
+                .reconnect_bans
+                .lock()
+                .map_err(|e| e.to_string())?
+                .insert(
+                    device_id.clone(),
+                    Instant::now() + Duration::from_secs(seconds),
+                ))
+        })
+        .transpose()?;
+
+    let closed =
+        disconnect_device_connections(&device_id, "disconnected_by_desktop", &app_state).await;
+    if closed == 0 {
+        if let Some(earlier) = ban {
+            let mut bans = app_state.reconnect_bans.lock().map_err(|e| e.to_string())?;
+            match earlier {
+                Some(until) => bans.insert(device_id.clone(), until),
+                None => bans.remove(&device_id),
+            };
+        }
+        return Err(format!("Device {} is not connected", device_id));
+    }
+    info!("Disconnected device {}.", device_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_triggering_paused(app_state: State<Arc<AppState>>) -> Result<bool, String> {
+    Ok(app_state.triggering_paused.load(Ordering::SeqCst))
+}
+
+/// Suspends or resumes every trigger, e.g. while typing a password or
+/// sharing the screen.
+///
+/// # Arguments
+///
+/// * `paused` - Whether to refuse triggers.
+/// * `app_state` - Shared state containing the connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_triggering_paused(
+    paused: bool,
+    app_state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    set_paused(paused, &app_state, &app_handle).await;
+    Ok(())
+}
+
+/// Returns the devices on open connections, including those awaiting approval.
+/// Changes after this call arrive as `device_connected`, `device_disconnected`,
+/// `device_pending_approval`, `device_rejected` and `device_updated` events.
+#[tauri::command]
+pub async fn get_connected_devices(
+    app_state: State<'_, Arc<AppState>>,
+) -> Result<Vec<Device>, String> {
+    Ok(app_state.devices().await)
+}
+
+/// Returns live metrics for every open connection.
+#[tauri::command]
+pub async fn get_connection_stats(
+    app_state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ConnectionStats>, String> {
+    Ok(app_state.connection_stats().await)
+}
+
+/// Returns the maximum number of triggers per second allowed per connection.
+#[tauri::command]
+pub async fn get_max_triggers_per_second(
+    settings: State<'_, Arc<SettingsStore>>,
+) -> Result<f64, String> {
+    Ok(settings.max_triggers_per_second())
+}
+
+/// Sets the maximum number of triggers per second allowed per connection and
+/// saves it in the settings.
+///
+/// # Arguments
+///
+/// * `limit` - Triggers per second; 0 disables rate limiting.
+/// * `settings` - Shared state containing the settings.
+/// * `app_handle` - Handle to emit events to the frontend.
+#[tauri::command]
+pub async fn set_max_triggers_per_second(
+    limit: f64,
+    settings: State<'_, Arc<SettingsStore>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    validate_trigger_rate(limit)?;
+    settings.update(&app_handle, |settings| {
+        settings.max_triggers_per_second = Some(limit)
+    })?;
+    Ok(())
+}
+
+// Pairing-related Tauri commands
+
+/// Approves a device that is waiting for pairing, after the user checked that
+/// the PIN shown on the desktop matches the one on the phone. The device is
+/// remembered as trusted.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the pending device.
+/// * `registry` - Shared state containing the known devices.
+/// * `store` - Shared state containing the shortcuts.
+/// * `app_state` - Shared state containing the connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn approve_device(
+    device_id: String,
+    registry: State<'_, Arc<DeviceRegistry>>,
+    store: State<'_, Arc<ShortcutStore>>,
+    app_state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    approve_pending_device(&device_id, &registry, &store, &app_state, &app_handle).await?;
+
+    let name = app_state.device_name(&device_id).await;
+    registry.set_trust(&device_id, name.as_deref(), TrustState::Trusted)?;
+    publish(&app_handle, AppEvent::KnownDevicesChanged);
+    Ok(())
+}
+/// Rejects a device that is waiting for pairing and closes its connection.
+///
+/// # Arguments
+///
+/// * `device_id` - The ID of the pending device.
+/// * `app_state` - Shared state containing the connections.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn deny_device(
+    device_id: String,
+    app_state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let (device, sender) = {
+        let mut connections = app_state.connections.lock().await;
+        let connection = connections
+            .values_mut()
+            .find(|c| {
+                c.pairing_pin.is_some() && c.device.as_ref().is_some_and(|d| d.id == device_id)
+            })
+            .ok_or_else(|| format!("No device {} is waiting for approval", device_id))?;
+        connection.pairing_pin = None;
+        (connection.device.clone(), connection.sender.clone())
+    };
+
+    info!("Device {} denied.", device_id);
+    if let Some(device) = &device {
+        publish(&app_handle, AppEvent::DeviceRejected(device.clone()));
+        notify(
+            &app_handle,
+            NotificationKind::DeviceRejected,
+            format!("Pairing with {} was denied", device.name),
+        );
     }
+    sender
+        .send_value(&serde_json::json!({ "type": "pairing_denied" }))
+        .await;
+    sender.close().await;
+
+    Ok(())
 }
 
-async fn handle_execute_shortcut(data: Value, store: Arc<ShortcutStore>) {
-    if let Some(shortcut_id) = data.get("shortcut_id").and_then(|id| id.as_i64()) {
-        println!("Executing shortcut with ID: {}", shortcut_id);
+#[cfg(all(test, any(target_os = "windows", target_os = "linux")))]
+mod tests {
+    use super::*;
+    use crate::devices::set_device_role;
+    use crate::shortcuts::{Shortcut, StateSource};
+    use crate::testing::{
+        approve, change_settings, paired_client, server, shared_settings, TestClient,
+    };
+    use serde_json::json;
+
+    fn shortcut(name: &str) -> Shortcut {
+        serde_json::from_value(json!({ "id": 0, "name": name, "sequence": [] })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn wrong_token_is_refused() {
+        let _settings = shared_settings().await;
+        let mut client = TestClient::connect().await;
+        let response = client
+            .request(
+                json!({ "type": "hello", "protocol_version": PROTOCOL_VERSION, "token": "wrong" }),
+            )
+            .await;
+        assert_eq!(response["ok"], false);
+        client.closed().await;
+    }
+
+    #[tokio::test]
+    async fn outdated_clients_hear_why_before_anything_else() {
+        let _settings = shared_settings().await;
+        let mut client = TestClient::connect().await;
+        // Neither authenticated nor asking for a reply
+        client
+            .send(json!({ "type": "hello", "protocol_version": MIN_PROTOCOL_VERSION - 1 }))
+            .await;
+        let error = client.expect("error", |_| true).await;
+        assert!(error["error"].as_str().unwrap().contains("too old"));
+        client.closed().await;
+    }
+
+    #[tokio::test]
+    async fn devices_paired_without_the_token_need_their_secret() {
+        let ctx = &server().ctx;
+        let _settings =
+            change_settings(|settings| settings.auth_mode = AuthMode::PairingOnly).await;
+        let hello = json!({ "type": "hello", "protocol_version": PROTOCOL_VERSION });
+
+        let mut client = TestClient::connect().await;
+        assert_eq!(client.request(hello.clone()).await["ok"], true);
+        let pending = client.identify("tokenless").await;
+        assert_eq!(pending["payload"]["status"], "pending_approval");
+        approve("tokenless").await.unwrap();
+        let approved = client.expect("pairing_approved", |_| true).await;
+        let secret = approved["device_secret"].as_str().unwrap().to_string();
+        ctx.devices
+            .set_trust("tokenless", None, TrustState::Trusted)
+            .unwrap();
+
+        // Claiming a trusted device's ID isn't enough
+        let mut impostor = TestClient::connect().await;
+        impostor.request(hello.clone()).await;
+        let pending = impostor.identify("tokenless").await;
+        assert_eq!(pending["payload"]["status"], "pending_approval");
+
+        let mut client = TestClient::connect().await;
+        client.request(hello).await;
+        let response = client
+            .request(json!({
+                "type": "device_info",
+                "device_name": "Test tokenless",
+                "device_id": "tokenless",
+                "device_secret": secret,
+            }))
+            .await;
+        assert_eq!(response["payload"]["approved"], true);
+    }
+
+    #[tokio::test]
+    async fn approved_device_gets_the_shortcut_list() {
+        let _settings = shared_settings().await;
+        let mut client = paired_client("approved", &[CAP_SHORTCUT_DIFFS]).await;
+        let sync = client.expect("sync", |_| true).await;
+        assert!(sync["shortcuts"].is_array());
+
+        // Triggering is allowed from now on
+        let response = client.request(json!({ "type": "get_shortcuts" })).await;
+        assert_eq!(response["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn only_known_devices_can_be_changed() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let error = ctx
+            .devices
+            .set_nickname("never-connected", Some("Ghost".into()))
+            .unwrap_err();
+        assert!(error.to_string().contains("Unknown device"));
+        assert!(ctx
+            .devices
+            .set_role("never-connected", DeviceRole::Admin)
+            .is_err());
+        assert!(!ctx
+            .devices
+            .get_devices()
+            .iter()
+            .any(|d| d.id == "never-connected"));
+    }
+
+    #[tokio::test]
+    async fn pending_device_may_not_trigger() {
+        let _settings = shared_settings().await;
+        let mut client = TestClient::connect().await;
+        client.hello(&[]).await;
+        let pending = client.identify("pending").await;
+        assert_eq!(pending["payload"]["pin"].as_str().map(str::len), Some(6));
+
+        let response = client
+            .request(json!({ "type": "execute_shortcut", "shortcut_id": 1 }))
+            .await;
+        assert_eq!(response["ok"], false);
+    }
+
+    #[tokio::test]
+    async fn denied_device_is_disconnected() {
+        let _settings = shared_settings().await;
+        let mut client = TestClient::connect().await;
+        client.hello(&[]).await;
+        client.identify("denied").await;
+
+        let ctx = &server().ctx;
+        deny_device(
+            "denied".to_string(),
+            ctx.app_handle.state(),
+            ctx.app_handle.clone(),
+        )
+        .await
+        .unwrap();
+        client.expect("pairing_denied", |_| true).await;
+        client.closed().await;
+    }
+
+    #[tokio::test]
+    async fn executed_shortcut_reports_its_result() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let added =
+            add_shortcut_to_store(shortcut("Executed"), &ctx.store, &ctx.app_handle).unwrap();
+        let mut client = paired_client("executing", &[CAP_EXECUTION_RESULTS, CAP_LATENCY]).await;
+
+        let response = client
+            .request(json!({ "type": "execute_shortcut", "shortcut_id": added.id }))
+            .await;
+        assert_eq!(response["ok"], true);
+        let result = client
+            .expect("execution_result", |m| m["shortcut_id"] == added.id)
+            .await;
+        assert_eq!(result["ok"], true);
+        assert!(result["latency"]["total_ms"].is_number());
+        assert!(result.get("id").is_none());
+        assert!(result["request_id"].is_number());
+
+        let response = client
+            .request(json!({ "type": "execute_shortcut", "shortcut_id": 404 }))
+            .await;
+        assert_eq!(response["ok"], false);
+    }
+
+    #[tokio::test]
+    async fn only_controls_can_be_rotated() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let added =
+            add_shortcut_to_store(shortcut("Not a dial"), &ctx.store, &ctx.app_handle).unwrap();
+        let mut client = paired_client("rotating", &[]).await;
+
+        let response = client
+            .request(json!({ "type": "dial_rotate", "shortcut_id": added.id, "delta": 1 }))
+            .await;
+        assert_eq!(response["ok"], false);
+    }
+
+    #[tokio::test]
+    async fn manual_toggles_flip_when_run() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let mut toggle = shortcut("Toggle");
+        toggle.state_source = Some(StateSource::Manual);
+        let added = add_shortcut_to_store(toggle, &ctx.store, &ctx.app_handle).unwrap();
+        let mut client = paired_client("toggling", &[CAP_SHORTCUT_STATES]).await;
+        client.expect("shortcut_states", |_| true).await;
+
+        for active in [true, false] {
+            client
+                .request(json!({ "type": "execute_shortcut", "shortcut_id": added.id }))
+                .await;
+            let changed = client
+                .expect("shortcut_state_changed", |m| m["shortcut_id"] == added.id)
+                .await;
+            assert_eq!(changed["active"], active);
+        }
+    }
+
+    #[tokio::test]
+    async fn admins_can_upload_assets() {
+        let _settings = shared_settings().await;
+        // A 1x1 PNG
+        const PIXEL: &str =
+            "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGM4ISLyHwAEiAHwkp1qPAAAAABJRU5ErkJggg==";
+        let mut client = paired_client("uploading", &[]).await;
+        let upload = json!({ "type": "upload_asset", "name": "pixel", "data": PIXEL });
+        let response = client.request(upload.clone()).await;
+        assert_eq!(response["ok"], false);
+
+        let ctx = &server().ctx;
+        set_device_role(
+            "uploading".to_string(),
+            DeviceRole::Admin,
+            ctx.app_handle.state(),
+            ctx.app_handle.clone(),
+        )
+        .unwrap();
+        let response = client.request(upload).await;
+        assert_eq!(response["ok"], true);
+        let response = client.request(json!({ "type": "list_assets" })).await;
+        assert!(response["payload"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("pixel")));
+
+        let response = client
+            .request(json!({ "type": "upload_asset", "name": "../pixel", "data": PIXEL }))
+            .await;
+        assert_eq!(response["ok"], false);
+    }
 
-        let all_shortcuts = store.get_shortcuts();
+    #[tokio::test]
+    async fn devices_are_held_to_their_limit() {
+        let ctx = &server().ctx;
+        let _settings =
+            change_settings(|settings| settings.execution_limits.max_running_per_device = Some(1))
+                .await;
+        let slow: Shortcut = serde_json::from_value(json!({
+            "id": 0,
+            "name": "Slow",
+            "sequence": [{ "type": "script", "script": "sleep(2000)" }],
+        }))
+        .unwrap();
+        let added = add_shortcut_to_store(slow, &ctx.store, &ctx.app_handle).unwrap();
+        let mut client = paired_client("busy", &[]).await;
 
-        // Find the shortcut by ID
-        if let Some(shortcut) = all_shortcuts.iter().find(|s| s.id == shortcut_id as u64) {
-            println!("Found shortcut: {:?}", shortcut);
+        let run = json!({ "type": "execute_shortcut", "shortcut_id": added.id });
+        assert_eq!(client.request(run.clone()).await["ok"], true);
+        let response = client.request(run).await;
+        assert_eq!(response["ok"], false);
+        assert!(response["error"].as_str().unwrap().contains("per device"));
+    }
+
+    #[tokio::test]
+    async fn random_choices_are_checked_when_saved() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let add = |choices: Value| {
+            let random: Shortcut = serde_json::from_value(json!({
+                "id": 0,
+                "name": "Random",
+                "sequence": [{ "type": "random_choice", "choices": choices }],
+            }))
+            .unwrap();
+            add_shortcut_to_store(random, &ctx.store, &ctx.app_handle)
+        };
+
+        assert!(add(json!([])).is_err());
+        assert!(add(json!([{ "sequence": ["F1"], "weight": 0 }])).is_err());
+        assert!(
+            add(json!([{ "sequence": ["F1"], "weight": -1 }, { "sequence": ["F2"] }])).is_err()
+        );
+        assert!(add(json!([{ "sequence": [{ "type": "no_such_step" }] }])).is_err());
+
+        let added = add(json!([
+            { "sequence": ["control+a"], "weight": 2 },
+            { "sequence": ["F2"] },
+        ]))
+        .unwrap();
+        let choices = &json!(added.sequence)[0]["choices"];
+        assert_eq!(choices[0]["sequence"][0], "Ctrl+a");
+    }
+
+    fn runs(id: u64) -> Value {
+        json!({ "type": "run_shortcut", "shortcut_id": id })
+    }
 
-            // Here we assume there's a field `interval_ms` in the incoming data
-            let interval_ms = data.get("interval_ms").and_then(|i| i.as_u64());
+    #[tokio::test]
+    async fn shortcuts_may_not_be_saved_running_themselves() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let first = add_shortcut_to_store(shortcut("First"), &ctx.store, &ctx.app_handle).unwrap();
+        let mut second = shortcut("Second");
+        second.sequence = vec![serde_json::from_value(runs(first.id)).unwrap()];
+        let second = add_shortcut_to_store(second, &ctx.store, &ctx.app_handle).unwrap();
 
-            // Use the simulate_shortcut function to simulate the key presses
-            if let Err(e) = simulate_shortcut(shortcut.sequence.clone(), interval_ms) {
-                eprintln!("Failed to simulate shortcut: {}", e);
+        // Hidden in a random choice, it still closes the loop
+        let looped: Shortcut = serde_json::from_value(json!({
+            "id": first.id,
+            "name": "First",
+            "sequence": [{ "type": "random_choice", "choices": [{ "sequence": [runs(second.id)] }] }],
+        }))
+        .unwrap();
+        let error = update_shortcut_in_store(looped, &ctx.store, &ctx.app_handle).unwrap_err();
+        assert!(error.contains("running itself"));
+    }
+
+    #[tokio::test]
+    async fn running_shortcuts_stop_at_loops_and_depth() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        // Stored as a sync from an older version could have, past the checks
+        let stored = |id: u64, sequence: Value| -> Shortcut {
+            serde_json::from_value(
+                json!({ "id": id, "name": id.to_string(), "sequence": sequence }),
+            )
+            .unwrap()
+        };
+        {
+            let mut shortcuts = ctx.store.shortcuts.write();
+            shortcuts.push(stored(7_000_001, json!([runs(7_000_002)])));
+            shortcuts.push(stored(7_000_002, json!([runs(7_000_001)])));
+            for id in 7_100_000..7_100_020 {
+                shortcuts.push(stored(id, json!([runs(id + 1)])));
             }
-        } else {
-            eprintln!("Shortcut with ID {} not found.", shortcut_id);
+            shortcuts.push(stored(7_100_020, json!([])));
+        }
+        let mut client = paired_client("nesting", &[CAP_EXECUTION_RESULTS]).await;
+
+        for (id, error) in [(7_000_001, "runs itself"), (7_100_000, "deep")] {
+            client
+                .request(json!({ "type": "execute_shortcut", "shortcut_id": id }))
+                .await;
+            let result = client
+                .expect("execution_result", |m| m["shortcut_id"] == id)
+                .await;
+            assert_eq!(result["ok"], false);
+            assert!(result["error"].as_str().unwrap().contains(error));
+        }
+    }
+
+    #[tokio::test]
+    async fn taps_report_where_the_cycle_is() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let mut cycling = shortcut("Cycling");
+        cycling.cycle = vec![vec![], vec![]];
+        let added = add_shortcut_to_store(cycling, &ctx.store, &ctx.app_handle).unwrap();
+        let mut client = paired_client("cycling", &[CAP_CYCLE_POSITIONS]).await;
+        client.expect("cycle_positions", |_| true).await;
+
+        for position in [1, 2, 0] {
+            client
+                .request(json!({ "type": "execute_shortcut", "shortcut_id": added.id }))
+                .await;
+            let moved = client
+                .expect("cycle_position", |m| m["shortcut_id"] == added.id)
+                .await;
+            assert_eq!(moved["position"], position);
         }
     }
+
+    #[tokio::test]
+    #[ignore = "needs a system clipboard; run with --ignored in a desktop session"]
+    async fn clipboard_steps_keep_what_they_set() {
+        let _settings = shared_settings().await;
+        let ctx = &server().ctx;
+        let copying: Shortcut = serde_json::from_value(json!({
+            "id": 0,
+            "name": "Copying",
+            "sequence": [
+                { "type": "set_clipboard", "text": "beam" },
+                { "type": "save_clipboard", "variable": "kept" },
+                { "type": "set_clipboard", "text": "{{var:kept}} twice" },
+            ],
+        }))
+        .unwrap();
+        let added = add_shortcut_to_store(copying, &ctx.store, &ctx.app_handle).unwrap();
+        let mut client = paired_client("copying", &[CAP_EXECUTION_RESULTS]).await;
+
+        client
+            .request(json!({ "type": "execute_shortcut", "shortcut_id": added.id }))
+            .await;
+        let result = client
+            .expect("execution_result", |m| m["shortcut_id"] == added.id)
+            .await;
+        assert_eq!(result["ok"], true);
+        // Still there after the steps are done
+        let text = crate::system::clipboard::with_clipboard(|clipboard| {
+            clipboard.get_text().map_err(|e| e.to_string())
+        })
+        .unwrap();
+        assert_eq!(text, "beam twice");
+    }
+
+    #[tokio::test]
+    async fn shortcut_changes_arrive_as_diffs() {
+        let _settings = shared_settings().await;
+        let mut client = paired_client("diffs", &[CAP_SHORTCUT_DIFFS]).await;
+        client.expect("sync", |_| true).await;
+
+        let ctx = &server().ctx;
+        let added = add_shortcut_to_store(shortcut("Diffed"), &ctx.store, &ctx.app_handle).unwrap();
+        client
+            .expect("shortcut_added", |m| m["shortcut"]["name"] == "Diffed")
+            .await;
+
+        delete_shortcut_from_store(added.id, &ctx.store).unwrap();
+        client
+            .expect("shortcut_deleted", |m| m["id"] == added.id)
+            .await;
+    }
 }
@@ -0,0 +1,414 @@
+use s3::creds::Credentials;
+use s3::error::S3Error;
+use s3::{Bucket, Region};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tracing::info;
+
+use crate::actions::ActionRegistry;
+use crate::error::{read_json_or_default, write_json, Error};
+use crate::secrets::check_secret_ids;
+use crate::shortcuts::{
+    check_for_cycles, prepare_shortcut, Shortcut, ShortcutChange, ShortcutStore,
+};
+
+/// Remote location the shortcut store is mirrored to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SyncBackend {
+    WebDav {
+        /// Full URL of the remote file, e.g. `https://dav.example.com/button-beam/shortcuts.json`.
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        key: String,
+        access_key: String,
+        secret_key: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SyncConfig {
+    pub backend: Option<SyncBackend>,
+    /// ETag of the remote file as of the last successful push or pull.
+    #[serde(default)]
+    pub last_remote_etag: Option<String>,
+    /// Hash of the local shortcuts as of the last successful push or pull.
+    #[serde(default)]
+    pub last_local_hash: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SyncResult {
+    pub etag: Option<String>,
+    pub shortcuts: usize,
+}
+
+struct RemoteFile {
+    body: Vec<u8>,
+    etag: Option<String>,
+}
+
+/// What the remote file has to be for an upload to replace it, so two
+/// desktops pushing at once can't overwrite each other's shortcuts.
+enum Precondition {
+    /// Anything; the upload is forced.
+    Any,
+    /// The file as of the last sync, by ETag.
+    Matches(String),
+    /// No file yet, for the first push.
+    Absent,
+}
+
+const CONFLICT: &str = "Sync conflict: the remote shortcuts changed since the last sync";
+
+pub struct SyncStore {
+    pub config: Mutex<SyncConfig>,
+    pub file_path: PathBuf,
+}
+
+impl SyncStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        let config = read_json_or_default(&file_path);
+
+        Self {
+            config: Mutex::new(config),
+            file_path,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let config = self.config.lock().unwrap();
+        write_json(&self.file_path, &*config)
+    }
+
+    fn get_config(&self) -> SyncConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    fn record_sync(&self, etag: Option<String>, local_hash: String) -> Result<(), Error> {
+        {
+            let mut config = self.config.lock().unwrap();
+            config.last_remote_etag = etag;
+            config.last_local_hash = Some(local_hash);
+        }
+        self.save()
+    }
+}
+
+impl SyncBackend {
+    fn s3_bucket(
+        endpoint: &str,
+        region: &str,
+        bucket: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> Result<Box<Bucket>, String> {
+        let region = Region::Custom {
+            region: region.to_string(),
+            endpoint: endpoint.to_string(),
+        };
+        let credentials = Credentials::new(Some(access_key), Some(secret_key), None, None, None)
+            .map_err(|e| format!("Invalid S3 credentials: {}", e))?;
+        let bucket = Bucket::new(bucket, region, credentials)
+            .map_err(|e| format!("Invalid S3 bucket: {}", e))?;
+        Ok(bucket.with_path_style())
+    }
+
+    async fn fetch(&self) -> Result<Option<RemoteFile>, String> {
+        match self {
+            SyncBackend::WebDav {
+                url,
+                username,
+                password,
+            } => {
+                let mut request = reqwest::Client::new().get(url);
+                if let Some(username) = username {
+                    request = request.basic_auth(username, password.as_ref());
+                }
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to reach WebDAV server: {}", e))?;
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                if !response.status().is_success() {
+                    return Err(format!("WebDAV server returned {}", response.status()));
+                }
+                let etag = response
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                let body = response
+                    .bytes()
+                    .await
+                    .map_err(|e| format!("Failed to read WebDAV response: {}", e))?;
+                Ok(Some(RemoteFile {
+                    body: body.to_vec(),
+                    etag,
+                }))
+            }
+            SyncBackend::S3 {
+                endpoint,
+                region,
+                bucket,
+                key,
+                access_key,
+                secret_key,
+            } => {
+                let bucket = Self::s3_bucket(endpoint, region, bucket, access_key, secret_key)?;
+                match bucket.get_object(key).await {
+                    Ok(response) => {
+                        let etag = response.headers().get("etag").cloned();
+                        Ok(Some(RemoteFile {
+                            body: response.bytes().to_vec(),
+                            etag,
+                        }))
+                    }
+                    Err(S3Error::HttpFailWithBody(404, _)) => Ok(None),
+                    Err(e) => Err(format!("Failed to download from S3: {}", e)),
+                }
+            }
+        }
+    }
+
+    async fn upload(
+        &self,
+        body: Vec<u8>,
+        precondition: Precondition,
+    ) -> Result<Option<String>, String> {
+        match self {
+            SyncBackend::WebDav {
+                url,
+                username,
+                password,
+            } => {
+                let mut request = reqwest::Client::new()
+                    .put(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body);
+                request = match precondition {
+                    Precondition::Any => request,
+                    Precondition::Matches(etag) => request.header(reqwest::header::IF_MATCH, etag),
+                    Precondition::Absent => request.header(reqwest::header::IF_NONE_MATCH, "*"),
+                };
+                if let Some(username) = username {
+                    request = request.basic_auth(username, password.as_ref());
+                }
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to reach WebDAV server: {}", e))?;
+                if response.status() == reqwest::StatusCode::PRECONDITION_FAILED {
+                    return Err(CONFLICT.into());
+                }
+                if !response.status().is_success() {
+                    return Err(format!("WebDAV server returned {}", response.status()));
+                }
+                Ok(response
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string))
+            }
+            SyncBackend::S3 {
+                endpoint,
+                region,
+                bucket,
+                key,
+                access_key,
+                secret_key,
+            } => {
+                let mut bucket = Self::s3_bucket(endpoint, region, bucket, access_key, secret_key)?;
+                match precondition {
+                    Precondition::Any => {}
+                    Precondition::Matches(etag) => bucket.add_header("If-Match", &etag),
+                    Precondition::Absent => bucket.add_header("If-None-Match", "*"),
+                }
+                match bucket
+                    .put_object_with_content_type(key, &body, "application/json")
+                    .await
+                {
+                    Ok(response) => Ok(response.headers().get("etag").cloned()),
+                    Err(S3Error::HttpFailWithBody(412, _)) => Err(CONFLICT.into()),
+                    Err(e) => Err(format!("Failed to upload to S3: {}", e)),
+                }
+            }
+        }
+    }
+}
+
+fn hash_shortcuts(shortcuts: &[Shortcut]) -> Result<String, String> {
+    let json = serde_json::to_vec(shortcuts).map_err(|e| e.to_string())?;
+    let digest = Sha256::digest(&json);
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// Sync-related Tauri commands
+
+/// Returns the current sync configuration.
+#[tauri::command]
+pub fn get_sync_config(sync_store: State<Arc<SyncStore>>) -> Result<SyncConfig, String> {
+    Ok(sync_store.get_config())
+}
+
+/// Sets (or clears) the remote endpoint used for syncing.
+///
+/// Changing the backend forgets the previous sync state, so the next push or
+/// pull against the new endpoint is checked for conflicts from scratch.
+///
+/// # Arguments
+///
+/// * `backend` - The remote endpoint, or `None` to disable syncing.
+/// * `sync_store` - Shared state containing the sync configuration.
+#[tauri::command]
+pub fn set_sync_config(
+    backend: Option<SyncBackend>,
+    sync_store: State<Arc<SyncStore>>,
+) -> Result<(), String> {
+    {
+        let mut config = sync_store.config.lock().map_err(|e| e.to_string())?;
+        *config = SyncConfig {
+            backend,
+            last_remote_etag: None,
+            last_local_hash: None,
+        };
+    }
+    sync_store.save()?;
+    Ok(())
+}
+
+/// Uploads the local shortcuts to the configured remote.
+///
+/// Fails with a conflict error if the remote file changed since the last sync,
+/// unless `force` is set. The first push fails if the remote already has
+/// shortcuts, which should be pulled or deliberately replaced with `force`.
+///
+/// # Arguments
+///
+/// * `force` - Overwrite the remote even if it changed since the last sync.
+/// * `store` - Shared state containing the shortcuts.
+/// * `sync_store` - Shared state containing the sync configuration.
+///
+/// # Returns
+///
+/// * `Result<SyncResult, String>` - The new remote ETag, or an error message.
+#[tauri::command]
+pub async fn sync_push(
+    force: Option<bool>,
+    store: State<'_, Arc<ShortcutStore>>,
+    sync_store: State<'_, Arc<SyncStore>>,
+) -> Result<SyncResult, String> {
+    let config = sync_store.get_config();
+    let backend = config.backend.ok_or("Sync is not configured")?;
+
+    let precondition = if force.unwrap_or(false) {
+        Precondition::Any
+    } else {
+        match backend.fetch().await? {
+            None => Precondition::Absent,
+            // Every sync records the local hash, so nothing was synced yet
+            Some(_) if config.last_local_hash.is_none() => {
+                return Err("The remote already has shortcuts: pull them first, or push with force to replace them".into());
+            }
+            Some(remote) => match (remote.etag, config.last_remote_etag) {
+                (Some(etag), Some(last)) if etag == last => Precondition::Matches(last),
+                // Servers without ETags can't be checked
+                (None, _) => Precondition::Any,
+                _ => return Err(CONFLICT.into()),
+            },
+        }
+    };
+
+    let shortcuts = store.get_shortcuts();
+    let body = serde_json::to_vec_pretty(&shortcuts).map_err(|e| e.to_string())?;
+    let etag = backend.upload(body, precondition).await?;
+    sync_store.record_sync(etag.clone(), hash_shortcuts(&shortcuts)?)?;
+
+    info!("Pushed {} shortcuts to remote", shortcuts.len());
+    Ok(SyncResult {
+        etag,
+        shortcuts: shortcuts.len(),
+    })
+}
+
+/// Replaces the local shortcuts with the ones stored on the configured remote.
+///
+/// Fails with a conflict error if both the local shortcuts and the remote file
+/// changed since the last sync, unless `force` is set.
+///
+/// # Arguments
+///
+/// * `force` - Overwrite local changes even if they were never pushed.
+/// * `store` - Shared state containing the shortcuts.
+/// * `sync_store` - Shared state containing the sync configuration.
+/// * `registry` - The step types shortcuts may use.
+///
+/// # Returns
+///
+/// * `Result<SyncResult, String>` - The remote ETag, or an error message.
+#[tauri::command]
+pub async fn sync_pull(
+    force: Option<bool>,
+    store: State<'_, Arc<ShortcutStore>>,
+    sync_store: State<'_, Arc<SyncStore>>,
+    registry: State<'_, Arc<ActionRegistry>>,
+) -> Result<SyncResult, String> {
+    let config = sync_store.get_config();
+    let backend = config.backend.ok_or("Sync is not configured")?;
+
+    let remote = backend
+        .fetch()
+        .await?
+        .ok_or("No shortcuts found on the remote")?;
+
+    let local_changed =
+        config.last_local_hash.as_deref() != Some(hash_shortcuts(&store.get_shortcuts())?.as_str());
+    let remote_changed = remote.etag.is_none() || remote.etag != config.last_remote_etag;
+    if !force.unwrap_or(false) && local_changed && remote_changed {
+        return Err(
+            "Sync conflict: both local and remote shortcuts changed since the last sync".into(),
+        );
+    }
+
+    let mut shortcuts: Vec<Shortcut> = serde_json::from_slice(&remote.body)
+        .map_err(|e| format!("Remote shortcuts are invalid: {}", e))?;
+    // Checked like any other edit; shortcuts pushed from another OS may also
+    // spell their modifiers differently. Nothing is replaced if one is invalid.
+    for shortcut in &mut shortcuts {
+        prepare_shortcut(shortcut, &registry)
+            .map_err(|e| format!("Remote shortcut \"{}\" is invalid: {}", shortcut.name, e))?;
+    }
+    for shortcut in &shortcuts {
+        check_for_cycles(shortcut, &shortcuts)?;
+        check_secret_ids(shortcut, &shortcuts)?;
+    }
+    let count = shortcuts.len();
+    let local_hash = hash_shortcuts(&shortcuts)?;
+
+    {
+        let mut current = store.shortcuts.write();
+        *current = shortcuts;
+    }
+    store.save();
+    sync_store.record_sync(remote.etag.clone(), local_hash)?;
+
+    // Devices have to resync the whole list
+    store.broadcast_change(ShortcutChange::Reset);
+
+    info!("Pulled {} shortcuts from remote", count);
+    Ok(SyncResult {
+        etag: remote.etag,
+        shortcuts: count,
+    })
+}
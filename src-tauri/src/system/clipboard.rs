@@ -0,0 +1,68 @@
+use arboard::Clipboard;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+
+// Clipboard steps. Together with variables they let a macro work on the
+// selection and clean up after itself, e.g. keep the clipboard, copy the
+// selection, type `**{{var:selection}}**` and put the old clipboard back.
+
+/// Kept for the life of the app: on Linux the clipboard belongs to whoever
+/// set it, and what was set is gone once its `Clipboard` is dropped.
+static CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+/// Runs `f` with the app's clipboard, connecting to it on first use.
+pub fn with_clipboard<T>(f: impl FnOnce(&mut Clipboard) -> Result<T, String>) -> Result<T, String> {
+    let mut clipboard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new().map_err(|e| format!("Clipboard unavailable: {}", e))?);
+    }
+    f(clipboard.as_mut().expect("connected above"))
+}
+
+/// Puts text on the clipboard. `{{var:NAME}}` in it is replaced with what
+/// earlier steps kept.
+#[derive(Deserialize)]
+struct SetClipboard {
+    text: String,
+}
+
+impl Action for SetClipboard {
+    const TYPE: &'static str = "set_clipboard";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        let text = ctx.output.substitute(&self.text);
+        with_clipboard(|clipboard| {
+            clipboard
+                .set_text(text)
+                .map_err(|e| format!("Failed to write the clipboard: {}", e))
+        })
+    }
+}
+
+/// Keeps the text on the clipboard in `variable`, for later steps of the
+/// same run.
+#[derive(Deserialize)]
+struct SaveClipboard {
+    variable: String,
+}
+
+impl Action for SaveClipboard {
+    const TYPE: &'static str = "save_clipboard";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        let text = with_clipboard(|clipboard| {
+            clipboard
+                .get_text()
+                .map_err(|e| format!("Failed to read the clipboard: {}", e))
+        })?;
+        ctx.output.variables.insert(self.variable, text);
+        Ok(())
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<SetClipboard>();
+    registry.register::<SaveClipboard>();
+}
@@ -0,0 +1,123 @@
+use button_beam_core::mouse::{self, MouseButton};
+use button_beam_core::template;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use super::screenshot::{capture_image, primary_region, scale_factor_at, Region};
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::assets::AssetStore;
+
+// Steps that look for a reference image on screen, for apps without hotkeys
+// for what a macro needs: wait until a dialog shows up, or click a button
+// wherever it is. The images are assets, see `assets`; they have to be cut
+// from a screenshot at the scale the screen is shown at, as matching doesn't
+// scale or rotate them.
+
+/// How long to look when the step sets no timeout.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Per color channel, enough for compression artifacts and subtle theme
+/// differences.
+const DEFAULT_TOLERANCE: u8 = 16;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What to look for and where, shared by the image steps.
+#[derive(Deserialize)]
+struct ImageSearch {
+    /// The asset's name.
+    image: String,
+    /// Where to look; the primary monitor when unset. A smaller region is
+    /// searched faster.
+    region: Option<Region>,
+    tolerance: Option<u8>,
+    timeout_ms: Option<u64>,
+}
+
+impl ImageSearch {
+    /// Looks until the image shows up and returns its center in desktop
+    /// coordinates. Fails once the timeout passed without it.
+    fn wait(&self, app_handle: &AppHandle) -> Result<(i32, i32), String> {
+        let wanted = app_handle.state::<Arc<AssetStore>>().load(&self.image)?;
+        let wanted = template::RgbaImage {
+            width: wanted.width(),
+            height: wanted.height(),
+            pixels: wanted.as_raw(),
+        };
+        let region = match self.region {
+            Some(region) => region,
+            None => primary_region()?,
+        };
+        // Captures are in the monitor's pixels, the result in desktop ones
+        let scale = f64::from(scale_factor_at(region.x, region.y)?);
+        let tolerance = self.tolerance.unwrap_or(DEFAULT_TOLERANCE);
+        let timeout = self
+            .timeout_ms
+            .map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+        let deadline = Instant::now() + timeout;
+        loop {
+            let screen = capture_image(Some(region))?;
+            let screen = template::RgbaImage {
+                width: screen.width(),
+                height: screen.height(),
+                pixels: screen.as_raw(),
+            };
+            if let Some((x, y)) = template::find(screen, wanted, tolerance) {
+                let center = |offset: u32, size: u32| (f64::from(offset + size / 2) / scale) as i32;
+                return Ok((
+                    region.x + center(x, wanted.width),
+                    region.y + center(y, wanted.height),
+                ));
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "\"{}\" didn't show up on screen within {} ms",
+                    self.image,
+                    timeout.as_millis()
+                ));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Waits until an image shows up on screen.
+#[derive(Deserialize)]
+struct WaitForImage {
+    #[serde(flatten)]
+    search: ImageSearch,
+}
+
+impl Action for WaitForImage {
+    const TYPE: &'static str = "wait_for_image";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        self.search.wait(ctx.app_handle).map(|_| ())
+    }
+}
+
+/// Waits for an image like `wait_for_image`, then clicks its center.
+#[derive(Deserialize)]
+struct ClickImage {
+    #[serde(flatten)]
+    search: ImageSearch,
+    #[serde(default)]
+    button: MouseButton,
+    count: Option<u32>,
+}
+
+impl Action for ClickImage {
+    const TYPE: &'static str = "click_image";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        let (x, y) = self.search.wait(ctx.app_handle)?;
+        mouse::click_at(x, y, self.button, self.count.unwrap_or(1))
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<WaitForImage>();
+    registry.register::<ClickImage>();
+}
@@ -0,0 +1,135 @@
+use serde::Deserialize;
+use tracing::info;
+
+use super::run_command;
+use crate::actions::{Action, ActionContext, ActionRegistry};
+
+// Turns notification banners off and on for "deep work" buttons. None of the
+// OSes has a public API for this, so each uses what is scriptable:
+//
+//   Windows - the "Get notifications from apps" switch in the registry
+//   macOS   - the user's Shortcuts named "Do Not Disturb On" and "Do Not
+//             Disturb Off", each with a single "Set Focus" action
+//   Linux   - GNOME's notification banners, or dunst when GNOME isn't running
+
+#[cfg(target_os = "windows")]
+const TOASTS_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Notifications\Settings";
+
+#[cfg(target_os = "windows")]
+fn is_enabled() -> Result<bool, String> {
+    // The value only exists once the switch has been turned off
+    Ok(run_command(
+        "reg",
+        &[
+            "query",
+            TOASTS_KEY,
+            "/v",
+            "NOC_GLOBAL_SETTING_TOASTS_ENABLED",
+        ],
+    )
+    .is_ok_and(|output| output.ends_with("0x0")))
+}
+
+#[cfg(target_os = "windows")]
+fn set_enabled(enabled: bool) -> Result<(), String> {
+    let value = if enabled { "0" } else { "1" };
+    run_command(
+        "reg",
+        &[
+            "add",
+            TOASTS_KEY,
+            "/v",
+            "NOC_GLOBAL_SETTING_TOASTS_ENABLED",
+            "/t",
+            "REG_DWORD",
+            "/d",
+            value,
+            "/f",
+        ],
+    )
+    .map(|_| ())
+}
+
+#[cfg(target_os = "macos")]
+fn is_enabled() -> Result<bool, String> {
+    // Lists the active Focus while one is on; unreadable without Full Disk Access
+    let path = tauri::api::path::home_dir()
+        .ok_or("No home folder")?
+        .join("Library/DoNotDisturb/DB/Assertions.json");
+    let assertions = std::fs::read_to_string(&path).map_err(|_| {
+        "Can't tell whether Do Not Disturb is on; turn it on or off explicitly".to_string()
+    })?;
+    Ok(assertions.contains("storeAssertionRecords"))
+}
+
+#[cfg(target_os = "macos")]
+fn set_enabled(enabled: bool) -> Result<(), String> {
+    let shortcut = if enabled {
+        "Do Not Disturb On"
+    } else {
+        "Do Not Disturb Off"
+    };
+    run_command("shortcuts", &["run", shortcut])
+        .map(|_| ())
+        .map_err(|e| format!("{} (is the \"{}\" shortcut set up?)", e, shortcut))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn is_enabled() -> Result<bool, String> {
+    match run_command(
+        "gsettings",
+        &["get", "org.gnome.desktop.notifications", "show-banners"],
+    ) {
+        Ok(banners) => Ok(banners == "false"),
+        Err(_) => run_command("dunstctl", &["is-paused"]).map(|paused| paused == "true"),
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn set_enabled(enabled: bool) -> Result<(), String> {
+    let banners = if enabled { "false" } else { "true" };
+    run_command(
+        "gsettings",
+        &[
+            "set",
+            "org.gnome.desktop.notifications",
+            "show-banners",
+            banners,
+        ],
+    )
+    .or_else(|_| run_command("dunstctl", &["set-paused", &enabled.to_string()]))
+    .map(|_| ())
+}
+
+/// Turns do-not-disturb on or off, or flips it when `enabled` is `None`.
+pub fn set(enabled: Option<bool>) -> Result<(), String> {
+    let enabled = match enabled {
+        Some(enabled) => enabled,
+        None => !is_enabled()?,
+    };
+    set_enabled(enabled)?;
+    info!(
+        "Turned do-not-disturb {}",
+        if enabled { "on" } else { "off" }
+    );
+    Ok(())
+}
+
+/// Silences notification banners; flips the current state when `enabled` is
+/// unset.
+#[derive(Deserialize)]
+struct DoNotDisturb {
+    enabled: Option<bool>,
+}
+
+impl Action for DoNotDisturb {
+    const TYPE: &'static str = "do_not_disturb";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        set(self.enabled)
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<DoNotDisturb>();
+}
@@ -0,0 +1,406 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tracing::debug;
+
+#[cfg(not(target_os = "windows"))]
+use super::run_command;
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::error::emit;
+use crate::sockets::AppState;
+
+// Output volume and display brightness as percentages, set through the OS
+// rather than media keys so a slider can land on an exact level.
+
+/// Current levels; `None` where the OS or hardware doesn't expose one.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SystemLevels {
+    pub volume: Option<u8>,
+    pub brightness: Option<u8>,
+}
+
+pub fn current() -> SystemLevels {
+    SystemLevels {
+        volume: volume().map_err(|e| debug!("{}", e)).ok(),
+        brightness: brightness().map_err(|e| debug!("{}", e)).ok(),
+    }
+}
+
+/// Parses the first percentage in a tool's output, e.g. `60%`.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn parse_percent(output: &str) -> Result<u8, String> {
+    output
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '/')
+        .find_map(|word| word.strip_suffix('%')?.parse::<u8>().ok())
+        .ok_or_else(|| format!("Unexpected output: {}", output))
+}
+
+fn adjusted(level: u8, delta: i32) -> u8 {
+    (level as i32 + delta).clamp(0, 100) as u8
+}
+
+#[cfg(target_os = "windows")]
+mod endpoint {
+    use windows::core::Interface;
+    use windows::Win32::Media::Audio::Endpoints::IAudioEndpointVolume;
+    use windows::Win32::Media::Audio::{
+        eConsole, eRender, IMMDeviceEnumerator, MMDeviceEnumerator,
+    };
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED,
+    };
+
+    /// The master volume of the default output device.
+    fn endpoint_volume() -> Result<IAudioEndpointVolume, String> {
+        unsafe {
+            // Fails harmlessly when COM is already set up on this thread
+            CoInitializeEx(std::ptr::null(), COINIT_MULTITHREADED).ok();
+            let enumerator: IMMDeviceEnumerator =
+                CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+                    .map_err(|e| e.to_string())?;
+            let device = enumerator
+                .GetDefaultAudioEndpoint(eRender, eConsole)
+                .map_err(|e| e.to_string())?;
+            let mut volume: Option<IAudioEndpointVolume> = None;
+            device
+                .Activate(
+                    &IAudioEndpointVolume::IID,
+                    CLSCTX_ALL,
+                    std::ptr::null(),
+                    &mut volume as *mut _ as *mut *mut std::ffi::c_void,
+                )
+                .map_err(|e| e.to_string())?;
+            volume.ok_or_else(|| "No audio output device".to_string())
+        }
+    }
+
+    pub fn volume() -> Result<u8, String> {
+        let level = unsafe { endpoint_volume()?.GetMasterVolumeLevelScalar() }
+            .map_err(|e| e.to_string())?;
+        Ok((level * 100.0).round() as u8)
+    }
+
+    pub fn set_volume(level: u8) -> Result<(), String> {
+        unsafe {
+            endpoint_volume()?.SetMasterVolumeLevelScalar(level as f32 / 100.0, std::ptr::null())
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+use endpoint::{set_volume as set_os_volume, volume};
+
+#[cfg(target_os = "macos")]
+fn volume() -> Result<u8, String> {
+    run_command(
+        "osascript",
+        &["-e", "output volume of (get volume settings)"],
+    )?
+    .parse()
+    .map_err(|_| "Output volume is unavailable".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn set_os_volume(level: u8) -> Result<(), String> {
+    let script = format!("set volume output volume {}", level);
+    run_command("osascript", &["-e", &script]).map(|_| ())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn volume() -> Result<u8, String> {
+    parse_percent(&run_command(
+        "pactl",
+        &["get-sink-volume", "@DEFAULT_SINK@"],
+    )?)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn set_os_volume(level: u8) -> Result<(), String> {
+    let level = format!("{}%", level);
+    run_command("pactl", &["set-sink-volume", "@DEFAULT_SINK@", &level]).map(|_| ())
+}
+
+/// One PowerShell kept running for brightness, which Windows only exposes
+/// over WMI. Starting a new one per step would take longer than a dial
+/// takes to send the next detent.
+#[cfg(target_os = "windows")]
+mod powershell {
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+    use std::sync::Mutex;
+
+    /// Printed after each script, so its output can be told apart.
+    const END_MARKER: &str = "--button-beam-end--";
+
+    struct Helper {
+        child: Child,
+        stdin: ChildStdin,
+        stdout: BufReader<ChildStdout>,
+    }
+
+    static HELPER: Mutex<Option<Helper>> = Mutex::new(None);
+
+    fn spawn() -> io::Result<Helper> {
+        let mut child = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("PowerShell has no pipes"));
+        };
+        Ok(Helper {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    fn exchange(helper: &mut Helper, script: &str) -> io::Result<String> {
+        writeln!(
+            helper.stdin,
+            "try {{ {} }} catch {{ Write-Output \"ERROR: $_\" }}; Write-Output '{}'",
+            script, END_MARKER
+        )?;
+        helper.stdin.flush()?;
+        let mut output = Vec::new();
+        loop {
+            let mut line = String::new();
+            if helper.stdout.read_line(&mut line)? == 0 {
+                return Err(io::Error::other("PowerShell exited"));
+            }
+            match line.trim() {
+                END_MARKER => return Ok(output.join("\n")),
+                line => output.push(line.to_string()),
+            }
+        }
+    }
+
+    /// Runs a one-line `script` and returns what it printed. The helper is
+    /// started on first use and again after it failed.
+    pub fn run(script: &str) -> Result<String, String> {
+        let mut helper = HELPER.lock().unwrap();
+        if helper.is_none() {
+            *helper = Some(spawn().map_err(|e| format!("Failed to run powershell: {}", e))?);
+        }
+        let output = match exchange(helper.as_mut().expect("started above"), script) {
+            Ok(output) => output,
+            Err(e) => {
+                if let Some(mut failed) = helper.take() {
+                    failed.child.kill().ok();
+                }
+                return Err(format!("powershell failed: {}", e));
+            }
+        };
+        match output.strip_prefix("ERROR: ") {
+            Some(error) => Err(format!("powershell failed: {}", error)),
+            None => Ok(output),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn brightness() -> Result<u8, String> {
+    // Only internal displays expose brightness over WMI
+    powershell::run(
+        "(Get-CimInstance -Namespace root/WMI -ClassName WmiMonitorBrightness).CurrentBrightness",
+    )?
+    .parse()
+    .map_err(|_| "Display brightness is unavailable".to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn set_brightness_level(level: u8) -> Result<(), String> {
+    let script = format!(
+        "Get-CimInstance -Namespace root/WMI -ClassName WmiMonitorBrightnessMethods | \
+         Invoke-CimMethod -MethodName WmiSetBrightness -Arguments @{{Timeout=1; Brightness={}}} | \
+         Out-Null",
+        level
+    );
+    powershell::run(&script).map(|_| ())
+}
+
+/// The brightness of the main display through DisplayServices, the private
+/// framework behind the Control Center slider. It isn't in the SDK, so it is
+/// looked up at runtime.
+#[cfg(target_os = "macos")]
+mod display_services {
+    use std::ffi::{c_char, c_int, c_void};
+    use std::sync::OnceLock;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGMainDisplayID() -> u32;
+    }
+
+    extern "C" {
+        fn dlopen(path: *const c_char, mode: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+
+    const RTLD_LAZY: c_int = 1;
+
+    type GetBrightness = unsafe extern "C" fn(display: u32, brightness: *mut f32) -> c_int;
+    type SetBrightness = unsafe extern "C" fn(display: u32, brightness: f32) -> c_int;
+
+    struct Functions {
+        get: GetBrightness,
+        set: SetBrightness,
+    }
+
+    fn functions() -> Result<&'static Functions, String> {
+        static FUNCTIONS: OnceLock<Option<Functions>> = OnceLock::new();
+        FUNCTIONS
+            .get_or_init(|| unsafe {
+                let handle = dlopen(
+                    c"/System/Library/PrivateFrameworks/DisplayServices.framework/DisplayServices"
+                        .as_ptr(),
+                    RTLD_LAZY,
+                );
+                if handle.is_null() {
+                    return None;
+                }
+                let get = dlsym(handle, c"DisplayServicesGetBrightness".as_ptr());
+                let set = dlsym(handle, c"DisplayServicesSetBrightness".as_ptr());
+                if get.is_null() || set.is_null() {
+                    return None;
+                }
+                Some(Functions {
+                    get: std::mem::transmute::<*mut c_void, GetBrightness>(get),
+                    set: std::mem::transmute::<*mut c_void, SetBrightness>(set),
+                })
+            })
+            .as_ref()
+            .ok_or_else(|| "Display brightness is unavailable".to_string())
+    }
+
+    pub fn brightness() -> Result<u8, String> {
+        let functions = functions()?;
+        let mut level = 0.0;
+        // External displays usually don't report one
+        if unsafe { (functions.get)(CGMainDisplayID(), &mut level) } != 0 {
+            return Err("Display brightness is unavailable".into());
+        }
+        Ok((level * 100.0).round() as u8)
+    }
+
+    pub fn set_brightness(level: u8) -> Result<(), String> {
+        let functions = functions()?;
+        if unsafe { (functions.set)(CGMainDisplayID(), f32::from(level) / 100.0) } != 0 {
+            return Err("Failed to set the display brightness".into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+use display_services::{brightness, set_brightness as set_brightness_level};
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn brightness() -> Result<u8, String> {
+    // Machine-readable output: device,class,current,percent,max
+    parse_percent(&run_command("brightnessctl", &["-m"])?)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn set_brightness_level(level: u8) -> Result<(), String> {
+    let level = format!("{}%", level);
+    run_command("brightnessctl", &["set", &level]).map(|_| ())
+}
+
+pub fn set_volume(level: u8) -> Result<(), String> {
+    set_os_volume(level.min(100))
+}
+
+pub fn adjust_volume(delta: i32) -> Result<(), String> {
+    set_os_volume(adjusted(volume()?, delta))
+}
+
+pub fn set_brightness(level: u8) -> Result<(), String> {
+    set_brightness_level(level.min(100))
+}
+
+pub fn adjust_brightness(delta: i32) -> Result<(), String> {
+    set_brightness_level(adjusted(brightness()?, delta))
+}
+
+/// Sends the new levels to paired devices as `system_levels` and to the
+/// frontend as `system_levels_changed`.
+pub fn report(app_handle: &AppHandle) {
+    let levels = current();
+    emit(app_handle, "system_levels_changed", &levels);
+    let app_state = app_handle.state::<Arc<AppState>>().inner().clone();
+    tauri::async_runtime::spawn(async move {
+        let message = json!({
+            "type": "system_levels",
+            "volume": levels.volume,
+            "brightness": levels.brightness,
+        });
+        app_state.broadcast(&message).await;
+    });
+}
+
+/// Sets the output volume, in percent.
+#[derive(Deserialize)]
+struct SetVolume {
+    level: u8,
+}
+
+impl Action for SetVolume {
+    const TYPE: &'static str = "set_volume";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        set_volume(self.level).map(|_| report(ctx.app_handle))
+    }
+}
+
+/// Raises or lowers the output volume by `delta` percentage points.
+#[derive(Deserialize)]
+struct AdjustVolume {
+    delta: i32,
+}
+
+impl Action for AdjustVolume {
+    const TYPE: &'static str = "adjust_volume";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        adjust_volume(self.delta).map(|_| report(ctx.app_handle))
+    }
+}
+
+/// Sets the display brightness, in percent.
+#[derive(Deserialize)]
+struct SetBrightness {
+    level: u8,
+}
+
+impl Action for SetBrightness {
+    const TYPE: &'static str = "set_brightness";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        set_brightness(self.level).map(|_| report(ctx.app_handle))
+    }
+}
+
+#[derive(Deserialize)]
+struct AdjustBrightness {
+    delta: i32,
+}
+
+impl Action for AdjustBrightness {
+    const TYPE: &'static str = "adjust_brightness";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        adjust_brightness(self.delta).map(|_| report(ctx.app_handle))
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<SetVolume>();
+    registry.register::<AdjustVolume>();
+    registry.register::<SetBrightness>();
+    registry.register::<AdjustBrightness>();
+}
@@ -0,0 +1,40 @@
+//! Action steps that control the operating system itself, through the tools
+//! each OS ships with.
+
+use std::process::Command;
+
+pub mod clipboard;
+pub mod find_image;
+pub mod focus;
+pub mod levels;
+pub mod pixel;
+pub mod power;
+pub mod screenshot;
+
+use crate::actions::ActionRegistry;
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    clipboard::register_actions(registry);
+    find_image::register_actions(registry);
+    focus::register_actions(registry);
+    levels::register_actions(registry);
+    pixel::register_actions(registry);
+    power::register_actions(registry);
+    screenshot::register_actions(registry);
+}
+
+/// Runs a system tool and fails with its error output if it doesn't succeed.
+pub fn run_command(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
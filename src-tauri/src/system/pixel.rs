@@ -0,0 +1,133 @@
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use super::screenshot::{capture_image, Region};
+use crate::actions::{Action, ActionContext, ActionRegistry};
+
+// Waits for a pixel to turn a given color, so a macro can go on as soon as
+// an app has loaded, e.g. once a button turns green, instead of sleeping for
+// however long it might take.
+
+/// How long to wait when the step sets no timeout.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest wait a step may ask for, so a typo can't tie up a sequence slot
+/// for hours.
+const MAX_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Parses `#rrggbb`.
+fn parse_color(color: &str) -> Result<[u8; 3], String> {
+    let invalid = || format!("Invalid color {}, expected #rrggbb", color);
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(invalid());
+    }
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(invalid)
+    };
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Whether every channel of `seen` is within `tolerance` of `wanted`.
+fn within_tolerance(seen: [u8; 3], wanted: [u8; 3], tolerance: u8) -> bool {
+    seen.iter()
+        .zip(wanted)
+        .all(|(seen, wanted)| seen.abs_diff(wanted) <= tolerance)
+}
+
+/// The color of the pixel at `x`, `y` in desktop coordinates.
+fn pixel_at(x: i32, y: i32) -> Result<[u8; 3], String> {
+    let image = capture_image(Some(Region {
+        x,
+        y,
+        width: 1,
+        height: 1,
+    }))?;
+    let [r, g, b, _] = image.get_pixel(0, 0).0;
+    Ok([r, g, b])
+}
+
+/// Waits until the pixel at `x`, `y` has `color`, give or take `tolerance`
+/// per channel, and fails once `timeout_ms`, at most five minutes, passed
+/// without it.
+#[derive(Deserialize)]
+struct WaitForPixel {
+    x: i32,
+    y: i32,
+    color: String,
+    #[serde(default)]
+    tolerance: u8,
+    timeout_ms: Option<u64>,
+}
+
+impl Action for WaitForPixel {
+    const TYPE: &'static str = "wait_for_pixel";
+
+    fn run(self, _ctx: &mut ActionContext) -> Result<(), String> {
+        let wanted = parse_color(&self.color)?;
+        let timeout = self
+            .timeout_ms
+            .map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+        if timeout > MAX_TIMEOUT {
+            return Err(format!(
+                "A pixel wait can last at most {} ms",
+                MAX_TIMEOUT.as_millis()
+            ));
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            let seen = pixel_at(self.x, self.y)?;
+            if within_tolerance(seen, wanted, self.tolerance) {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "Pixel at {}, {} was still #{:02x}{:02x}{:02x}, not {}, after {} ms",
+                    self.x,
+                    self.y,
+                    seen[0],
+                    seen[1],
+                    seen[2],
+                    self.color,
+                    timeout.as_millis()
+                ));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<WaitForPixel>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors_with_or_without_a_hash() {
+        assert_eq!(parse_color("#00ff7f").unwrap(), [0, 255, 127]);
+        assert_eq!(parse_color("FF8000").unwrap(), [255, 128, 0]);
+    }
+
+    #[test]
+    fn rejects_malformed_colors() {
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("#gg0000").is_err());
+        assert!(parse_color("#00ff7f00").is_err());
+        // Six bytes, but not six hex digits
+        assert!(parse_color("#éff00").is_err());
+    }
+
+    #[test]
+    fn tolerance_applies_to_each_channel() {
+        assert!(within_tolerance([10, 20, 30], [10, 20, 30], 0));
+        assert!(within_tolerance([15, 15, 35], [10, 20, 30], 5));
+        assert!(!within_tolerance([16, 20, 30], [10, 20, 30], 5));
+        assert!(within_tolerance([0, 255, 0], [255, 0, 255], 255));
+    }
+}
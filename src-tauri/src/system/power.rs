@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::api::dialog::blocking::confirm;
+use tauri::{AppHandle, Manager};
+use tracing::info;
+
+use super::run_command;
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::settings::SettingsStore;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    Lock,
+    Sleep,
+    Shutdown,
+    Restart,
+}
+
+impl PowerAction {
+    fn label(self) -> &'static str {
+        match self {
+            PowerAction::Lock => "lock the screen",
+            PowerAction::Sleep => "put this computer to sleep",
+            PowerAction::Shutdown => "shut down this computer",
+            PowerAction::Restart => "restart this computer",
+        }
+    }
+
+    /// The OS command for the action, program first.
+    fn command(self) -> &'static [&'static str] {
+        if cfg!(target_os = "windows") {
+            match self {
+                PowerAction::Lock => &["rundll32.exe", "user32.dll,LockWorkStation"],
+                PowerAction::Sleep => &["rundll32.exe", "powrprof.dll,SetSuspendState", "0,1,0"],
+                PowerAction::Shutdown => &["shutdown", "/s", "/t", "0"],
+                PowerAction::Restart => &["shutdown", "/r", "/t", "0"],
+            }
+        } else if cfg!(target_os = "macos") {
+            match self {
+                // Locks as long as a password is required after the display sleeps
+                PowerAction::Lock => &["pmset", "displaysleepnow"],
+                PowerAction::Sleep => &["pmset", "sleepnow"],
+                PowerAction::Shutdown => {
+                    &["osascript", "-e", "tell app \"System Events\" to shut down"]
+                }
+                PowerAction::Restart => {
+                    &["osascript", "-e", "tell app \"System Events\" to restart"]
+                }
+            }
+        } else {
+            match self {
+                PowerAction::Lock => &["loginctl", "lock-session"],
+                PowerAction::Sleep => &["systemctl", "suspend"],
+                PowerAction::Shutdown => &["systemctl", "poweroff"],
+                PowerAction::Restart => &["systemctl", "reboot"],
+            }
+        }
+    }
+}
+
+/// Runs a power action. Unless the `unconfirmed_power_actions` setting is on,
+/// everything but locking first asks on the desktop, so a stray button press
+/// can't switch the computer off.
+pub fn run(app_handle: &AppHandle, action: PowerAction) -> Result<(), String> {
+    let unconfirmed = app_handle
+        .state::<Arc<SettingsStore>>()
+        .get_settings()
+        .unconfirmed_power_actions;
+    if action != PowerAction::Lock && !unconfirmed {
+        let window = app_handle.get_window("main");
+        let message = format!("A button wants to {}. Continue?", action.label());
+        if !confirm(window.as_ref(), "Button Beam", message) {
+            return Err(format!("Declined to {}", action.label()));
+        }
+    }
+
+    info!("Running power action: {}", action.label());
+    let (program, args) = action
+        .command()
+        .split_first()
+        .ok_or("No command for this power action")?;
+    run_command(program, args).map(|_| ())
+}
+
+/// Locks, sleeps, shuts down or restarts the computer.
+#[derive(Deserialize)]
+struct Power {
+    action: PowerAction,
+}
+
+impl Action for Power {
+    const TYPE: &'static str = "power";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        run(ctx.app_handle, self.action)
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<Power>();
+}
@@ -0,0 +1,207 @@
+use arboard::ImageData;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tauri::Manager;
+use tracing::info;
+use xcap::Monitor;
+
+use crate::actions::{Action, ActionContext, ActionRegistry};
+use crate::devices::now_millis;
+use crate::system::clipboard::with_clipboard;
+
+/// Part of the screen, in desktop coordinates. On scaled displays the
+/// capture of it is larger by the scale factor of the monitor containing its
+/// top-left corner.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The whole primary monitor, in desktop coordinates.
+pub fn primary_region() -> Result<Region, String> {
+    let monitors = Monitor::all().map_err(|e| format!("Failed to list monitors: {}", e))?;
+    let monitor = monitors
+        .iter()
+        .find(|monitor| monitor.is_primary())
+        .or(monitors.first())
+        .ok_or("No monitor found")?;
+    Ok(Region {
+        x: monitor.x(),
+        y: monitor.y(),
+        width: monitor.width(),
+        height: monitor.height(),
+    })
+}
+
+fn monitor_at(monitors: &[Monitor], x: i32, y: i32) -> Result<&Monitor, String> {
+    monitors
+        .iter()
+        .find(|monitor| {
+            (monitor.x()..monitor.x() + monitor.width() as i32).contains(&x)
+                && (monitor.y()..monitor.y() + monitor.height() as i32).contains(&y)
+        })
+        .ok_or_else(|| "The region is outside every monitor".to_string())
+}
+
+/// How many pixels of a capture make one desktop pixel at `x`, `y`, e.g. 2
+/// on a Retina display.
+pub fn scale_factor_at(x: i32, y: i32) -> Result<f32, String> {
+    let monitors = Monitor::all().map_err(|e| format!("Failed to list monitors: {}", e))?;
+    Ok(monitor_at(&monitors, x, y)?.scale_factor())
+}
+
+/// Captures `region`, or the whole primary monitor, in the monitor's pixels.
+pub fn capture_image(region: Option<Region>) -> Result<RgbaImage, String> {
+    let monitors = Monitor::all().map_err(|e| format!("Failed to list monitors: {}", e))?;
+    let Some(region) = region else {
+        let monitor = monitors
+            .iter()
+            .find(|monitor| monitor.is_primary())
+            .or(monitors.first())
+            .ok_or("No monitor found")?;
+        return monitor.capture_image().map_err(|e| e.to_string());
+    };
+
+    if region.width == 0 || region.height == 0 {
+        return Err("The region is empty".to_string());
+    }
+    let monitor = monitor_at(&monitors, region.x, region.y)?;
+    let image = monitor.capture_image().map_err(|e| e.to_string())?;
+    // The monitor's geometry is in desktop coordinates, the capture in its pixels
+    let scale = monitor.scale_factor();
+    let to_pixels = |value: i64| (value as f32 * scale).round() as u32;
+    let x = to_pixels(i64::from(region.x - monitor.x())).min(image.width() - 1);
+    let y = to_pixels(i64::from(region.y - monitor.y())).min(image.height() - 1);
+    // Regions reaching past the monitor's edge are cut off there
+    let width = to_pixels(i64::from(region.width)).clamp(1, image.width() - x);
+    let height = to_pixels(i64::from(region.height)).clamp(1, image.height() - y);
+    Ok(image::imageops::crop_imm(&image, x, y, width, height).to_image())
+}
+
+/// Where screenshots may be saved besides the Pictures folder: the app's data
+/// folder, so a device can't write anywhere else on disk.
+pub struct ScreenshotFolders {
+    pub data_dir: PathBuf,
+}
+
+impl ScreenshotFolders {
+    /// `save_path` if it lies in the Pictures or data folder, with relative
+    /// paths taken from the Pictures folder.
+    fn resolve(&self, save_path: &str) -> Result<PathBuf, String> {
+        let refused = || {
+            format!(
+                "Screenshots can only be saved in the Pictures folder or in {}",
+                self.data_dir.display()
+            )
+        };
+        let path = Path::new(save_path);
+        if path
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return Err(refused());
+        }
+        let pictures = tauri::api::path::picture_dir();
+        let path = match &pictures {
+            Some(pictures) if path.is_relative() => pictures.join(path),
+            _ => path.to_path_buf(),
+        };
+        let roots: Vec<&PathBuf> = pictures.iter().chain([&self.data_dir]).collect();
+        if !roots.iter().any(|root| path.starts_with(root)) {
+            return Err(refused());
+        }
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        // Links inside the allowed folders mustn't lead out of them either
+        let canonical = |path: &Path| std::fs::canonicalize(path).ok();
+        let real = canonical(&path).ok_or_else(refused)?;
+        if !roots
+            .iter()
+            .filter_map(|root| canonical(root))
+            .any(|root| real.starts_with(root))
+        {
+            return Err(refused());
+        }
+        Ok(path)
+    }
+}
+
+/// Captures `region`, or the whole primary monitor, then copies it to the
+/// clipboard and/or saves it as a PNG. It is saved to `save_dir`, or to the
+/// Pictures folder when neither a folder nor the clipboard is given.
+/// Returns the path of the saved file.
+pub fn capture(
+    region: Option<Region>,
+    save_dir: Option<PathBuf>,
+    clipboard: bool,
+) -> Result<Option<String>, String> {
+    let image = capture_image(region)?;
+
+    if clipboard {
+        let data = ImageData {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            bytes: Cow::Borrowed(image.as_raw()),
+        };
+        with_clipboard(|clipboard| {
+            clipboard
+                .set_image(data)
+                .map_err(|e| format!("Failed to copy the screenshot: {}", e))
+        })?;
+    }
+    if clipboard && save_dir.is_none() {
+        return Ok(None);
+    }
+
+    let dir = save_dir
+        .or_else(tauri::api::path::picture_dir)
+        .ok_or("No folder to save the screenshot to")?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("Screenshot {}.png", now_millis()));
+    image
+        .save(&path)
+        .map_err(|e| format!("Failed to save the screenshot: {}", e))?;
+
+    info!("Saved screenshot to {}", path.display());
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+/// Captures `region`, or the primary monitor, to the clipboard and/or a PNG
+/// in `save_path`, which must be in the Pictures or data folder. Without
+/// either it is saved to the Pictures folder.
+#[derive(Deserialize)]
+struct Screenshot {
+    region: Option<Region>,
+    save_path: Option<String>,
+    #[serde(default)]
+    clipboard: bool,
+}
+
+impl Action for Screenshot {
+    const TYPE: &'static str = "screenshot";
+
+    fn run(self, ctx: &mut ActionContext) -> Result<(), String> {
+        let save_dir = match &self.save_path {
+            Some(save_path) => Some(
+                ctx.app_handle
+                    .state::<Arc<ScreenshotFolders>>()
+                    .resolve(save_path)?,
+            ),
+            None => None,
+        };
+        let path = capture(self.region, save_dir, self.clipboard)?;
+        ctx.output.files.extend(path);
+        Ok(())
+    }
+}
+
+pub fn register_actions(registry: &mut ActionRegistry) {
+    registry.register::<Screenshot>();
+}
@@ -0,0 +1,283 @@
+use button_beam_core::protocol::PROTOCOL_VERSION;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{mpsc, Arc, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::actions::ActionRegistry;
+use crate::activity::ActivityLog;
+use crate::assets::AssetStore;
+use crate::auth::AuthStore;
+use crate::devices::DeviceRegistry;
+use crate::events::{spawn_device_subscribers, EventBus};
+use crate::hotkeys::{HotkeyStore, RegisteredHotkeys};
+use crate::performance::PerformanceMonitor;
+use crate::server::ServerHandle;
+use crate::settings::{Settings, SettingsStore};
+use crate::shortcut_states::{spawn_state_reporter, ShortcutStates};
+use crate::shortcuts::{ShortcutChange, ShortcutStore};
+use crate::snippets::SnippetStore;
+use crate::sockets::{approve_pending_device, AppState, ServerContext};
+use crate::system::screenshot::ScreenshotFolders;
+
+// The WebSocket server without the desktop around it, so the device protocol
+// can be tested with `cargo test`, plus a client that speaks it. Tauri allows
+// one event loop per process, so a single server is started for the whole
+// test binary: no window, tray or global hotkeys, its data in a temporary
+// folder and listening on a free port on localhost. Tests share it, so each
+// one uses its own device IDs and shortcut names. Settings are shared too: a
+// test that changes them runs alone and puts them back when it ends.
+//
+// Tauri still initializes the windowing toolkit, so on Linux the tests need a
+// display, e.g. `xvfb-run cargo test`.
+
+/// How long a test waits for the server before failing.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct TestServer {
+    pub ctx: ServerContext,
+    pub addr: SocketAddr,
+}
+
+/// The state the server reaches through the app handle, as `main` manages it.
+fn manage_state(app_handle: &AppHandle, dir: &Path) -> ServerContext {
+    let (sender, _) = broadcast::channel::<ShortcutChange>(64);
+    let events = Arc::new(EventBus::new());
+    let ctx = ServerContext {
+        store: Arc::new(ShortcutStore::new(dir.join("shortcuts.json"), sender)),
+        app_state: Arc::new(AppState::new()),
+        auth: Arc::new(AuthStore::new(dir.join("auth.json"))),
+        devices: Arc::new(DeviceRegistry::new(dir.join("devices.json"))),
+        activity: Arc::new(ActivityLog::new(
+            dir.join("activity.jsonl"),
+            Arc::clone(&events),
+        )),
+        settings: Arc::new(SettingsStore::new(dir.join("settings.json"))),
+        events,
+        app_handle: app_handle.clone(),
+    };
+
+    app_handle.manage(Arc::clone(&ctx.store));
+    app_handle.manage(Arc::clone(&ctx.app_state));
+    app_handle.manage(Arc::clone(&ctx.auth));
+    app_handle.manage(Arc::clone(&ctx.devices));
+    app_handle.manage(Arc::clone(&ctx.activity));
+    app_handle.manage(Arc::clone(&ctx.settings));
+    app_handle.manage(Arc::clone(&ctx.events));
+    app_handle.manage(Arc::new(HotkeyStore::new(dir.join("hotkeys.json"), &[])));
+    app_handle.manage(Arc::new(RegisteredHotkeys::default()));
+    app_handle.manage(Arc::new(PerformanceMonitor::new()));
+    app_handle.manage(Arc::new(ShortcutStates::new()));
+    app_handle.manage(Arc::new(SnippetStore::new(dir.join("snippets.json"))));
+    app_handle.manage(Arc::new(AssetStore::new(dir.join("assets"))));
+    app_handle.manage(Arc::new(ScreenshotFolders {
+        data_dir: dir.to_path_buf(),
+    }));
+    app_handle.manage(Arc::new(ActionRegistry::builtin()));
+    app_handle.manage(ctx.clone());
+    ctx
+}
+
+fn start() -> TestServer {
+    let dir = std::env::temp_dir().join(format!("button-beam-test-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).expect("Cannot create the test data folder");
+
+    let (started, server) = mpsc::channel();
+    std::thread::spawn(move || {
+        let app = tauri::Builder::default()
+            .any_thread()
+            .build(tauri::generate_context!())
+            .expect("Cannot build the test app");
+        let ctx = manage_state(&app.handle(), &dir);
+        let handle = Arc::new(ServerHandle::new(ctx.clone()));
+        app.manage(Arc::clone(&handle));
+        let addr = tauri::async_runtime::block_on(async {
+            spawn_device_subscribers(&ctx);
+            spawn_state_reporter(&ctx);
+            handle.start("127.0.0.1", 0, None).await
+        })
+        .expect("Cannot start the test server");
+        started.send(TestServer { ctx, addr }).ok();
+        app.run(|_, _| {});
+    });
+    server
+        .recv()
+        .expect("The test app exited before the server started")
+}
+
+/// The server shared by all tests, started by the first one to ask.
+pub fn server() -> &'static TestServer {
+    static SERVER: OnceLock<TestServer> = OnceLock::new();
+    SERVER.get_or_init(start)
+}
+
+/// Read by every test that uses the server, written by one that changes its
+/// settings for the others.
+static SETTINGS: RwLock<()> = RwLock::const_new(());
+
+/// Keeps the server's settings as they are while the guard lives. Other
+/// tests holding one run alongside.
+pub async fn shared_settings() -> RwLockReadGuard<'static, ()> {
+    SETTINGS.read().await
+}
+
+/// Settings a test changed for the whole server, put back when dropped, even
+/// if the test failed.
+pub struct SettingsGuard {
+    previous: Settings,
+    _exclusive: RwLockWriteGuard<'static, ()>,
+}
+
+impl Drop for SettingsGuard {
+    fn drop(&mut self) {
+        let ctx = &server().ctx;
+        let previous = self.previous.clone();
+        if let Err(e) = ctx
+            .settings
+            .update(&ctx.app_handle, |settings| *settings = previous)
+        {
+            eprintln!("Cannot restore the test settings: {}", e);
+        }
+    }
+}
+
+/// Applies `change` to the server's settings once no other test uses them,
+/// and keeps them to this test until the guard is dropped.
+pub async fn change_settings(change: impl FnOnce(&mut Settings)) -> SettingsGuard {
+    let exclusive = SETTINGS.write().await;
+    let ctx = &server().ctx;
+    let previous = ctx.settings.get_settings();
+    ctx.settings
+        .update(&ctx.app_handle, change)
+        .expect("Cannot change the test settings");
+    SettingsGuard {
+        previous,
+        _exclusive: exclusive,
+    }
+}
+
+/// A phone's side of a connection, speaking JSON.
+pub struct TestClient {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl TestClient {
+    pub async fn connect() -> Self {
+        let url = format!("ws://{}/", server().addr);
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .expect("Cannot connect to the test server");
+        Self { socket, next_id: 1 }
+    }
+
+    pub async fn send(&mut self, message: Value) {
+        self.socket
+            .send(Message::text(message.to_string()))
+            .await
+            .expect("Cannot send to the test server");
+    }
+
+    /// The next message from the server, or `None` once it closed the
+    /// connection. Fails the test if nothing arrives in time.
+    pub async fn next(&mut self) -> Option<Value> {
+        loop {
+            let frame = tokio::time::timeout(TIMEOUT, self.socket.next())
+                .await
+                .expect("The server sent nothing in time");
+            match frame {
+                Some(Ok(Message::Text(text))) => {
+                    return Some(serde_json::from_str(&text).expect("The server sent invalid JSON"))
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+                // Heartbeats are answered by tungstenite itself
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+
+    /// Waits for a message of type `kind` that `matches` accepts, skipping
+    /// anything else, e.g. changes made by other tests.
+    pub async fn expect(&mut self, kind: &str, matches: impl Fn(&Value) -> bool) -> Value {
+        loop {
+            let message = self
+                .next()
+                .await
+                .unwrap_or_else(|| panic!("Connection closed while waiting for {}", kind));
+            if message["type"] == kind && matches(&message) {
+                return message;
+            }
+        }
+    }
+
+    /// Sends `message` with a fresh `id` and returns the server's response.
+    pub async fn request(&mut self, mut message: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        message["id"] = json!(id);
+        self.send(message).await;
+        self.expect("response", |response| response["id"] == id)
+            .await
+    }
+
+    /// Says hello with the desktop's token, announcing `capabilities`.
+    pub async fn hello(&mut self, capabilities: &[&str]) -> Value {
+        self.request(json!({
+            "type": "hello",
+            "protocol_version": PROTOCOL_VERSION,
+            "capabilities": capabilities,
+            "token": server().ctx.auth.get_token(),
+        }))
+        .await
+    }
+
+    /// Identifies the connection as `device_id` and returns the response.
+    pub async fn identify(&mut self, device_id: &str) -> Value {
+        self.request(json!({
+            "type": "device_info",
+            "device_name": format!("Test {}", device_id),
+            "device_id": device_id,
+        }))
+        .await
+    }
+
+    /// Waits for the server to close the connection, skipping what it sends
+    /// before.
+    pub async fn closed(&mut self) {
+        while self.next().await.is_some() {}
+    }
+}
+
+/// Approves `device_id` on the desktop, as the user would after comparing
+/// the PIN.
+pub async fn approve(device_id: &str) -> Result<(), String> {
+    let ctx = &server().ctx;
+    approve_pending_device(
+        device_id,
+        &ctx.devices,
+        &ctx.store,
+        &ctx.app_state,
+        &ctx.app_handle,
+    )
+    .await
+}
+
+/// A client whose device was paired, announcing `capabilities`. The
+/// `pairing_approved` message has been read; what follows it hasn't.
+pub async fn paired_client(device_id: &str, capabilities: &[&str]) -> TestClient {
+    let mut client = TestClient::connect().await;
+    assert_eq!(client.hello(capabilities).await["ok"], true);
+    let pending = client.identify(device_id).await;
+    assert_eq!(pending["payload"]["status"], "pending_approval");
+    approve(device_id).await.expect("Cannot approve the device");
+    client.expect("pairing_approved", |_| true).await;
+    client
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use tauri::{
+    AppHandle, ClipboardManager, CustomMenuItem, Manager, SystemTray, SystemTrayEvent,
+    SystemTrayMenu, SystemTrayMenuItem,
+};
+use tracing::error;
+
+use crate::auth::AuthStore;
+use crate::server::ServerHandle;
+use crate::sockets::toggle_paused;
+
+// Tray icon that keeps the receiver reachable while its window is closed.
+
+const SHOW: &str = "show";
+const PAUSE: &str = "pause";
+const COPY_ADDRESS: &str = "copy_address";
+const QUIT: &str = "quit";
+
+pub fn system_tray() -> SystemTray {
+    let menu = SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new(SHOW, "Show Button Beam"))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new(PAUSE, "Pause triggering"))
+        .add_item(CustomMenuItem::new(COPY_ADDRESS, "Copy connection address"))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new(QUIT, "Quit"));
+    SystemTray::new().with_menu(menu)
+}
+
+fn show_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_window("main") {
+        window.show().ok();
+        window.unminimize().ok();
+        window.set_focus().ok();
+    }
+}
+
+/// The address encoded in the pairing QR code, for typing into a phone by hand.
+async fn connection_address(app_handle: &AppHandle) -> Result<String, String> {
+    let server = app_handle.state::<Arc<ServerHandle>>();
+    let (ip, port) = server
+        .advertised_addr()
+        .await
+        .ok_or("Server is not running")?;
+    let host = if ip.contains(':') {
+        format!("[{}]", ip)
+    } else {
+        ip
+    };
+    let token = app_handle.state::<Arc<AuthStore>>().get_token();
+    Ok(format!("{}:{}?token={}", host, port, token))
+}
+
+/// Keeps the pause item's label in step with the pause state.
+pub fn set_pause_item_title(app_handle: &AppHandle, paused: bool) {
+    let title = if paused {
+        "Resume triggering"
+    } else {
+        "Pause triggering"
+    };
+    app_handle
+        .tray_handle()
+        .get_item(PAUSE)
+        .set_title(title)
+        .ok();
+}
+
+pub fn handle_tray_event(app_handle: &AppHandle, event: SystemTrayEvent) {
+    match event {
+        SystemTrayEvent::LeftClick { .. } => show_main_window(app_handle),
+        SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
+            SHOW => show_main_window(app_handle),
+            PAUSE => toggle_paused(app_handle),
+            COPY_ADDRESS => {
+                let app_handle = app_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    match connection_address(&app_handle).await {
+                        Ok(address) => {
+                            if let Err(e) = app_handle.clipboard_manager().write_text(address) {
+                                error!("Failed to copy the connection address: {}", e);
+                            }
+                        }
+                        Err(e) => error!("{}", e),
+                    }
+                });
+            }
+            QUIT => {
+                // Exiting this way skips `RunEvent::Exit`
+                let server = app_handle.state::<Arc<ServerHandle>>().inner().clone();
+                tauri::async_runtime::block_on(async move { server.shut_down().await });
+                app_handle.exit(0);
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+}
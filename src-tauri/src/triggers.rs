@@ -0,0 +1,160 @@
+use std::sync::atomic::Ordering;
+use tracing::info;
+
+use crate::activity::ActivityEvent;
+use crate::notifications::{notify, NotificationKind};
+use crate::shortcuts::{run_sequence, PressKind};
+use crate::sockets::ServerContext;
+
+// Runs shortcuts triggered from outside the WebSocket protocol: the HTTP API,
+// MQTT, the serial port, Twitch and YouTube chat, schedules and global hotkeys. They are
+// held to the same rules as devices: nothing runs while triggering is paused,
+// each source is rate limited like a connection, and the sequences count
+// against the execution limits.
+
+/// Where a trigger came from.
+pub enum TriggerSource {
+    Http,
+    Mqtt,
+    Serial,
+    Twitch { viewer: String },
+    YouTube { viewer: String },
+    Schedule { id: u64 },
+    Hotkey,
+}
+
+impl TriggerSource {
+    /// Names the source in the activity log; sources are rate limited by it.
+    fn name(&self) -> &'static str {
+        match self {
+            TriggerSource::Http => "HTTP",
+            TriggerSource::Mqtt => "MQTT",
+            TriggerSource::Serial => "Serial",
+            TriggerSource::Twitch { .. } => "Twitch",
+            TriggerSource::YouTube { .. } => "YouTube",
+            TriggerSource::Schedule { .. } => "Scheduler",
+            TriggerSource::Hotkey => "Hotkey",
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            TriggerSource::Http => "over HTTP".to_string(),
+            TriggerSource::Mqtt => "over MQTT".to_string(),
+            TriggerSource::Serial => "from the serial port".to_string(),
+            TriggerSource::Twitch { viewer } => format!("for {} on Twitch", viewer),
+            TriggerSource::YouTube { viewer } => format!("for {} on YouTube", viewer),
+            TriggerSource::Schedule { id } => format!("on schedule {}", id),
+            TriggerSource::Hotkey => "by hotkey".to_string(),
+        }
+    }
+
+    /// What the notification of a run says. Schedules and hotkeys are set
+    /// off by the user at the desktop, so they go unannounced.
+    fn notice(&self, shortcut: &str) -> Option<String> {
+        match self {
+            TriggerSource::Http => Some(format!("\"{}\" was triggered over HTTP", shortcut)),
+            TriggerSource::Mqtt => Some(format!("\"{}\" was triggered over MQTT", shortcut)),
+            TriggerSource::Serial => Some(format!("\"{}\" was triggered over serial", shortcut)),
+            TriggerSource::Twitch { viewer } => {
+                Some(format!("{} triggered \"{}\" on Twitch", viewer, shortcut))
+            }
+            TriggerSource::YouTube { viewer } => {
+                Some(format!("{} triggered \"{}\" on YouTube", viewer, shortcut))
+            }
+            TriggerSource::Schedule { .. } | TriggerSource::Hotkey => None,
+        }
+    }
+}
+
+/// Why a trigger was refused.
+#[derive(Debug, thiserror::Error)]
+pub enum TriggerError {
+    #[error("Triggering is paused on the desktop")]
+    Paused,
+    #[error("Rate limit exceeded: at most {0} triggers per second")]
+    RateLimited(f64),
+    #[error("Shortcut with ID {0} not found.")]
+    NotFound(u64),
+    /// Over the execution limits.
+    #[error("{0}")]
+    Busy(String),
+}
+
+impl From<TriggerError> for String {
+    fn from(error: TriggerError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Runs a tap of shortcut `id` in the background, or says why it may not
+/// run. How the run went ends up in the activity log.
+pub async fn trigger_shortcut(
+    id: u64,
+    source: TriggerSource,
+    ctx: &ServerContext,
+) -> Result<(), TriggerError> {
+    if ctx.app_state.triggering_paused.load(Ordering::SeqCst) {
+        return Err(TriggerError::Paused);
+    }
+    let rate = ctx.settings.max_triggers_per_second();
+    if !ctx
+        .app_state
+        .trigger_limiters
+        .lock()
+        .await
+        .entry(source.name())
+        .or_default()
+        .try_take(rate)
+    {
+        return Err(TriggerError::RateLimited(rate));
+    }
+    let shortcut = ctx
+        .store
+        .get_shortcuts()
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or(TriggerError::NotFound(id))?;
+    // Over the limits a trigger is refused here, or waits in the task below
+    let reservation = ctx
+        .app_state
+        .running
+        .reserve(None, ctx.settings.execution_limits())
+        .map_err(TriggerError::Busy)?;
+
+    info!("Executing shortcut with ID {} {}", id, source.describe());
+    if let Some(notice) = source.notice(&shortcut.name) {
+        notify(&ctx.app_handle, NotificationKind::ShortcutTriggered, notice);
+    }
+    let timing = shortcut
+        .timing()
+        .or_default_interval(ctx.settings.default_interval_ms());
+    let sequence = ctx.store.next_sequence(&shortcut, PressKind::Tap);
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let result = match reservation.wait().await {
+            Ok(slot) => {
+                let app_handle = ctx.app_handle.clone();
+                tokio::task::spawn_blocking(move || {
+                    // Given back once the sequence is done, even if it panics
+                    let _slot = slot;
+                    run_sequence(&app_handle, id, sequence, timing).map(|_| ())
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("Shortcut execution panicked: {}", e)))
+            }
+            // Waited in the queue for too long
+            Err(e) => Err(e),
+        };
+        ctx.activity.record(
+            None,
+            Some(source.name()),
+            ActivityEvent::ShortcutExecuted {
+                shortcut_id: id,
+                ok: result.is_ok(),
+                error: result.err(),
+            },
+        );
+    });
+    Ok(())
+}
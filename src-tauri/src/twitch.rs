@@ -0,0 +1,361 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use crate::rate_limit::TokenBucket;
+use crate::secrets::{delete_secret, read_secret, store_secret};
+use crate::settings::SettingsStore;
+use crate::sockets::ServerContext;
+use crate::triggers::{self, TriggerSource};
+
+// Turns viewer interactions in a Twitch channel into shortcuts: chat
+// commands such as `!confetti`, and channel-point redemptions. Only
+// redemptions that ask the viewer for text show up in chat, so other rewards
+// can't be mapped. Reward IDs are logged when an unmapped one is redeemed.
+//
+// Without a token the chat is read anonymously, which is all that's needed.
+
+/// Keychain entry holding the bot account's OAuth token.
+const TOKEN_SECRET_ID: &str = "twitch-token";
+
+const CHAT_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TwitchTriggerSource {
+    /// A chat message starting with this word, e.g. `!confetti`.
+    ChatCommand { command: String },
+    /// A channel-point reward, by its ID.
+    Redemption { reward_id: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TwitchTrigger {
+    #[serde(flatten)]
+    pub source: TwitchTriggerSource,
+    pub shortcut_id: u64,
+    /// Ignores repeats within this many seconds of the last run.
+    #[serde(default)]
+    pub cooldown_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TwitchSettings {
+    pub enabled: bool,
+    /// Channel to watch, without the `#`.
+    pub channel: String,
+    /// Account the token belongs to; the token is kept in the OS keychain.
+    pub username: Option<String>,
+    #[serde(default)]
+    pub triggers: Vec<TwitchTrigger>,
+    /// Cap on runs across all triggers, so a busy chat can't take over the
+    /// desktop; zero disables it.
+    #[serde(default = "default_max_triggers_per_minute")]
+    pub max_triggers_per_minute: u32,
+}
+
+fn default_max_triggers_per_minute() -> u32 {
+    20
+}
+
+impl Default for TwitchSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: String::new(),
+            username: None,
+            triggers: Vec::new(),
+            max_triggers_per_minute: default_max_triggers_per_minute(),
+        }
+    }
+}
+
+/// A line of Twitch's IRC dialect, e.g.
+/// `@custom-reward-id=...;display-name=Ann :ann!ann@ann.tmi.twitch.tv PRIVMSG #chan :hi`.
+struct IrcMessage<'a> {
+    tags: &'a str,
+    command: &'a str,
+    trailing: &'a str,
+}
+
+impl<'a> IrcMessage<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let (tags, rest) = match line.strip_prefix('@') {
+            Some(rest) => rest.split_once(' ')?,
+            None => ("", line),
+        };
+        let rest = match rest.strip_prefix(':') {
+            Some(prefixed) => prefixed.split_once(' ')?.1,
+            None => rest,
+        };
+        let (params, trailing) = rest.split_once(" :").unwrap_or((rest, ""));
+        Some(Self {
+            tags,
+            command: params.split(' ').next()?,
+            trailing,
+        })
+    }
+
+    fn tag(&self, name: &str) -> Option<&'a str> {
+        self.tags
+            .split(';')
+            .filter_map(|tag| tag.split_once('='))
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| value)
+    }
+}
+
+/// Rate limiting state, kept across reconnects. Shared with the YouTube
+/// bridge, whose triggers work the same way.
+pub(crate) struct Limits {
+    overall: TokenBucket,
+    /// When each trigger, by index, last ran.
+    last_run: HashMap<usize, Instant>,
+}
+
+impl Limits {
+    pub(crate) fn new() -> Self {
+        Self {
+            overall: TokenBucket::new(),
+            last_run: HashMap::new(),
+        }
+    }
+
+    /// Whether trigger `index` may run now, counting the run if so. It may
+    /// not within `cooldown_seconds` of its last run, nor once all triggers
+    /// together ran `max_per_minute` times in the last minute.
+    pub(crate) fn allow(
+        &mut self,
+        index: usize,
+        cooldown_seconds: u64,
+        max_per_minute: u32,
+    ) -> bool {
+        let cooldown = Duration::from_secs(cooldown_seconds);
+        if self
+            .last_run
+            .get(&index)
+            .is_some_and(|last_run| last_run.elapsed() < cooldown)
+        {
+            return false;
+        }
+        if !self.overall.try_take(max_per_minute as f64 / 60.0) {
+            return false;
+        }
+        self.last_run.insert(index, Instant::now());
+        true
+    }
+}
+
+/// Keeps the chat connection running while the bridge is enabled.
+pub struct TwitchBridge {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TwitchBridge {
+    pub fn new() -> Self {
+        Self {
+            task: Mutex::new(None),
+        }
+    }
+
+    pub async fn start(&self, settings: TwitchSettings, ctx: ServerContext) -> Result<(), String> {
+        let channel = settings
+            .channel
+            .trim()
+            .trim_start_matches('#')
+            .to_lowercase();
+        if channel.is_empty() {
+            return Err("No Twitch channel is set".into());
+        }
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            return Ok(());
+        }
+        info!("Watching Twitch chat of #{}", channel);
+        *task = Some(tokio::spawn(run(channel, settings, ctx)));
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+            info!("Stopped watching Twitch chat.");
+        }
+    }
+}
+
+impl Default for TwitchBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn run(channel: String, settings: TwitchSettings, ctx: ServerContext) {
+    let mut limits = Limits::new();
+    loop {
+        match watch_chat(&channel, &settings, &ctx, &mut limits).await {
+            Ok(()) => info!("Twitch chat asked us to reconnect"),
+            Err(e) => warn!("Twitch chat connection error: {}", e),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Reads the channel's chat until the connection drops.
+async fn watch_chat(
+    channel: &str,
+    settings: &TwitchSettings,
+    ctx: &ServerContext,
+    limits: &mut Limits,
+) -> Result<(), String> {
+    let (mut socket, _) = connect_async(CHAT_URL)
+        .await
+        .map_err(|e| format!("Failed to connect to Twitch chat: {}", e))?;
+
+    let token = read_secret(TOKEN_SECRET_ID).ok();
+    let mut login = vec!["CAP REQ :twitch.tv/tags twitch.tv/commands".to_string()];
+    match (&settings.username, token) {
+        (Some(username), Some(token)) => {
+            let token = token.trim_start_matches("oauth:");
+            login.push(format!("PASS oauth:{}", token));
+            login.push(format!("NICK {}", username.to_lowercase()));
+        }
+        // Twitch lets any `justinfan` nick read chat without logging in
+        _ => login.push(format!("NICK justinfan{}", rand::random::<u32>() % 100_000)),
+    }
+    login.push(format!("JOIN #{}", channel));
+    for line in login {
+        socket
+            .send(Message::Text(line))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    while let Some(message) = socket.next().await {
+        let Message::Text(text) = message.map_err(|e| e.to_string())? else {
+            continue;
+        };
+        for line in text.lines() {
+            let Some(message) = IrcMessage::parse(line) else {
+                continue;
+            };
+            match message.command {
+                "PING" => {
+                    let pong = format!("PONG :{}", message.trailing);
+                    socket
+                        .send(Message::Text(pong))
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                "PRIVMSG" => handle_chat_message(&message, settings, ctx, limits),
+                "RECONNECT" => return Ok(()),
+                "NOTICE" if message.trailing.contains("authentication failed") => {
+                    return Err(format!("Twitch login failed: {}", message.trailing));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_chat_message(
+    message: &IrcMessage,
+    settings: &TwitchSettings,
+    ctx: &ServerContext,
+    limits: &mut Limits,
+) {
+    let reward_id = message.tag("custom-reward-id");
+    let command = message.trailing.split_whitespace().next().unwrap_or("");
+    let matched = settings.triggers.iter().enumerate().find(|(_, trigger)| {
+        match (&trigger.source, reward_id) {
+            (TwitchTriggerSource::Redemption { reward_id: wanted }, Some(reward_id)) => {
+                wanted == reward_id
+            }
+            (TwitchTriggerSource::ChatCommand { command: wanted }, None) => {
+                wanted.eq_ignore_ascii_case(command)
+            }
+            _ => false,
+        }
+    });
+    let viewer = message
+        .tag("display-name")
+        .unwrap_or("A viewer")
+        .to_string();
+    let Some((index, trigger)) = matched else {
+        if let Some(reward_id) = reward_id {
+            info!("{} redeemed unmapped reward {}", viewer, reward_id);
+        }
+        return;
+    };
+
+    if !limits.allow(
+        index,
+        trigger.cooldown_seconds,
+        settings.max_triggers_per_minute,
+    ) {
+        debug!(
+            "Ignoring Twitch trigger from {}: cooldown or rate limit",
+            viewer
+        );
+        return;
+    }
+    tokio::spawn(trigger_shortcut(trigger.shortcut_id, viewer, ctx.clone()));
+}
+
+async fn trigger_shortcut(id: u64, viewer: String, ctx: ServerContext) {
+    if let Err(e) = triggers::trigger_shortcut(id, TriggerSource::Twitch { viewer }, &ctx).await {
+        warn!("Ignoring Twitch trigger for {}: {}", id, e);
+    }
+}
+
+// Twitch-related Tauri commands
+
+/// Saves the chat settings and starts, restarts or stops the bridge to match.
+///
+/// # Arguments
+///
+/// * `twitch` - The channel, triggers and whether the bridge is enabled.
+/// * `token` - A new OAuth token; empty to remove it, `None` to keep it.
+/// * `settings` - Shared state containing the settings.
+/// * `bridge` - The Twitch bridge.
+/// * `ctx` - The server context, used to run triggered shortcuts.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_twitch_bridge(
+    twitch: TwitchSettings,
+    token: Option<String>,
+    settings: State<'_, Arc<SettingsStore>>,
+    bridge: State<'_, Arc<TwitchBridge>>,
+    ctx: State<'_, ServerContext>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    match token.as_deref() {
+        Some("") => delete_secret(TOKEN_SECRET_ID),
+        Some(token) => store_secret(TOKEN_SECRET_ID, token)?,
+        None => {}
+    }
+
+    // Saved first, so the bridge never runs with settings that aren't kept
+    settings.update(&app_handle, |current| current.twitch = twitch.clone())?;
+
+    bridge.stop().await;
+    if twitch.enabled {
+        bridge.start(twitch, ctx.inner().clone()).await?;
+    }
+    Ok(())
+}
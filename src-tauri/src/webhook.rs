@@ -0,0 +1,191 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::activity::ActivityEntry;
+use crate::devices::now_millis;
+use crate::events::{next, AppEvent};
+use crate::secrets::{delete_secret, read_secret, store_secret};
+use crate::settings::SettingsStore;
+use crate::sockets::ServerContext;
+
+// Posts activity entries, such as `shortcut_executed` and `connected`, to a
+// URL of the user's choosing so IFTTT, Zapier and the like can react to
+// them. The body is the entry as JSON, the same as in the activity log.
+//
+// With a secret set, each request is signed so the receiver can tell it came
+// from this desktop:
+//
+//   X-Button-Beam-Timestamp: <milliseconds since the Unix epoch>
+//   X-Button-Beam-Signature: sha256=<hex HMAC-SHA256 of "<timestamp>.<body>">
+
+/// Keychain entry holding the signing secret.
+const SECRET_ID: &str = "webhook-secret";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    pub url: String,
+    /// Activity events to send, e.g. `shortcut_executed`; all when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// Keeps the activity forwarder running while the webhook is enabled.
+pub struct WebhookForwarder {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl WebhookForwarder {
+    pub fn new() -> Self {
+        Self {
+            task: Mutex::new(None),
+        }
+    }
+
+    pub async fn start(&self, settings: WebhookSettings, ctx: ServerContext) -> Result<(), String> {
+        reqwest::Url::parse(&settings.url)
+            .map_err(|e| format!("Invalid webhook URL {}: {}", settings.url, e))?;
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            return Ok(());
+        }
+        info!("Sending activity to webhook {}", settings.url);
+        *task = Some(tokio::spawn(forward_activity(settings, ctx)));
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+            info!("Stopped sending activity to the webhook.");
+        }
+    }
+}
+
+impl Default for WebhookForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn signature(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+async fn forward_activity(settings: WebhookSettings, ctx: ServerContext) {
+    let client = match reqwest::Client::builder().timeout(TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to create the webhook client: {}", e);
+            return;
+        }
+    };
+    let mut events = ctx.events.subscribe();
+    while let Some(event) = next(&mut events, "Webhook").await {
+        let AppEvent::Activity(entry) = event else {
+            continue;
+        };
+        if let Err(e) = post(&client, &settings, &entry).await {
+            warn!("{}", e);
+        }
+    }
+}
+
+async fn post(
+    client: &reqwest::Client,
+    settings: &WebhookSettings,
+    entry: &ActivityEntry,
+) -> Result<(), String> {
+    let payload = serde_json::to_value(entry).map_err(|e| e.to_string())?;
+    let event = payload["event"].as_str().unwrap_or_default();
+    if !settings.events.is_empty() && !settings.events.iter().any(|e| e == event) {
+        return Ok(());
+    }
+
+    let body = payload.to_string();
+    let mut request = client
+        .post(&settings.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    // Read each time, so a new secret applies without restarting
+    if let Ok(secret) = read_secret(SECRET_ID) {
+        let timestamp = now_millis();
+        request = request.header("X-Button-Beam-Timestamp", timestamp).header(
+            "X-Button-Beam-Signature",
+            signature(&secret, timestamp, &body),
+        );
+    }
+    let response = request
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send {} to the webhook: {}", event, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "The webhook answered {} with {}",
+            event,
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+// Webhook-related Tauri commands
+
+/// Saves the webhook settings and starts, restarts or stops forwarding to
+/// match.
+///
+/// # Arguments
+///
+/// * `webhook` - The URL, events to send and whether forwarding is enabled.
+/// * `secret` - A new signing secret; empty to stop signing, `None` to keep it.
+/// * `settings` - Shared state containing the settings.
+/// * `forwarder` - The webhook forwarder.
+/// * `ctx` - The server context, whose activity is forwarded.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_webhook(
+    webhook: WebhookSettings,
+    secret: Option<String>,
+    settings: State<'_, Arc<SettingsStore>>,
+    forwarder: State<'_, Arc<WebhookForwarder>>,
+    ctx: State<'_, ServerContext>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    match secret.as_deref() {
+        Some("") => delete_secret(SECRET_ID),
+        Some(secret) => store_secret(SECRET_ID, secret)?,
+        None => {}
+    }
+
+    forwarder.stop().await;
+    if webhook.enabled {
+        forwarder
+            .start(webhook.clone(), ctx.inner().clone())
+            .await?;
+    }
+
+    settings.update(&app_handle, |current| current.webhook = webhook)?;
+    Ok(())
+}
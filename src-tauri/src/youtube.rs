@@ -0,0 +1,343 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::secrets::{delete_secret, read_secret, store_secret};
+use crate::settings::SettingsStore;
+use crate::sockets::ServerContext;
+use crate::triggers::{self, TriggerSource};
+use crate::twitch::Limits;
+
+// Turns chat commands such as `!confetti` in the live chat of a YouTube
+// stream into shortcuts, like the Twitch bridge does for Twitch chat.
+//
+// The chat is polled through the YouTube Data API with an API key, as often
+// as YouTube asks. Every poll counts against the key's daily quota, which
+// the default quota covers for a few hours of streaming.
+
+/// Keychain entry holding the YouTube Data API key.
+const API_KEY_SECRET_ID: &str = "youtube-api-key";
+
+const API_URL: &str = "https://www.googleapis.com/youtube/v3";
+
+/// Polls no faster than this, whatever YouTube suggests.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Long enough not to burn through the quota while the stream is offline.
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct YouTubeTrigger {
+    /// A chat message starting with this word, e.g. `!confetti`.
+    pub command: String,
+    pub shortcut_id: u64,
+    /// Ignores repeats within this many seconds of the last run.
+    #[serde(default)]
+    pub cooldown_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct YouTubeSettings {
+    pub enabled: bool,
+    /// The live stream, as its URL or video ID. The API key is kept in the
+    /// OS keychain.
+    pub stream: String,
+    #[serde(default)]
+    pub triggers: Vec<YouTubeTrigger>,
+    /// Cap on runs across all triggers, so a busy chat can't take over the
+    /// desktop; zero disables it.
+    #[serde(default = "default_max_triggers_per_minute")]
+    pub max_triggers_per_minute: u32,
+}
+
+fn default_max_triggers_per_minute() -> u32 {
+    20
+}
+
+impl Default for YouTubeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stream: String::new(),
+            triggers: Vec::new(),
+            max_triggers_per_minute: default_max_triggers_per_minute(),
+        }
+    }
+}
+
+/// The video ID in a stream URL like `https://www.youtube.com/watch?v=ID`,
+/// `https://youtu.be/ID` or `https://www.youtube.com/live/ID`, or the ID
+/// itself.
+fn video_id(stream: &str) -> Option<String> {
+    let stream = stream.trim();
+    let id = match stream.split_once("v=") {
+        Some((_, rest)) => rest.split('&').next()?,
+        None => stream
+            .split(['?', '#'])
+            .next()?
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()?,
+    };
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| id.to_string())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatPage {
+    next_page_token: Option<String>,
+    #[serde(default)]
+    polling_interval_millis: u64,
+    /// Set once the stream is over.
+    offline_at: Option<String>,
+    #[serde(default)]
+    items: Vec<ChatItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatItem {
+    snippet: ChatSnippet,
+    author_details: ChatAuthor,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatSnippet {
+    /// Missing for messages that were deleted.
+    display_message: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatAuthor {
+    display_name: String,
+}
+
+/// Keeps polling the live chat while the bridge is enabled.
+pub struct YouTubeBridge {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl YouTubeBridge {
+    pub fn new() -> Self {
+        Self {
+            task: Mutex::new(None),
+        }
+    }
+
+    pub async fn start(&self, settings: YouTubeSettings, ctx: ServerContext) -> Result<(), String> {
+        let video_id =
+            video_id(&settings.stream).ok_or("The YouTube stream is not a video URL or ID")?;
+        let api_key =
+            read_secret(API_KEY_SECRET_ID).map_err(|_| "No YouTube API key is set".to_string())?;
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            return Ok(());
+        }
+        info!("Watching the YouTube live chat of {}", video_id);
+        *task = Some(tokio::spawn(run(video_id, api_key, settings, ctx)));
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+            info!("Stopped watching YouTube live chat.");
+        }
+    }
+}
+
+impl Default for YouTubeBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn run(video_id: String, api_key: String, settings: YouTubeSettings, ctx: ServerContext) {
+    let client = reqwest::Client::new();
+    let mut limits = Limits::new();
+    loop {
+        if let Err(e) = watch_chat(&client, &video_id, &api_key, &settings, &ctx, &mut limits).await
+        {
+            warn!("YouTube live chat error: {}", e);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Calls the Data API, failing with the message of any error it returns.
+async fn get<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    path: &str,
+    query: &[(&str, &str)],
+) -> Result<T, String> {
+    let response = client
+        .get(format!("{}/{}", API_URL, path))
+        .query(query)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach YouTube: {}", e))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read the YouTube response: {}", e))?;
+    if !status.is_success() {
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|body| body["error"]["message"].as_str().map(str::to_string))
+            .unwrap_or_else(|| status.to_string());
+        return Err(format!("YouTube returned {}", message));
+    }
+    serde_json::from_str(&body).map_err(|e| format!("Unexpected YouTube response: {}", e))
+}
+
+/// The ID of the stream's live chat, while it is live.
+async fn live_chat_id(
+    client: &reqwest::Client,
+    video_id: &str,
+    api_key: &str,
+) -> Result<String, String> {
+    let videos: Value = get(
+        client,
+        "videos",
+        &[
+            ("part", "liveStreamingDetails"),
+            ("id", video_id),
+            ("key", api_key),
+        ],
+    )
+    .await?;
+    videos["items"][0]["liveStreamingDetails"]["activeLiveChatId"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("{} is not live", video_id))
+}
+
+/// Polls the stream's chat until it ends or a request fails.
+async fn watch_chat(
+    client: &reqwest::Client,
+    video_id: &str,
+    api_key: &str,
+    settings: &YouTubeSettings,
+    ctx: &ServerContext,
+    limits: &mut Limits,
+) -> Result<(), String> {
+    let chat_id = live_chat_id(client, video_id, api_key).await?;
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut query = vec![
+            ("liveChatId", chat_id.as_str()),
+            ("part", "snippet,authorDetails"),
+            ("key", api_key),
+        ];
+        if let Some(page_token) = &page_token {
+            query.push(("pageToken", page_token.as_str()));
+        }
+        let page: ChatPage = get(client, "liveChat/messages", &query).await?;
+        if page.offline_at.is_some() {
+            return Err(format!("The stream {} has ended", video_id));
+        }
+        // The first page holds what was said before the bridge started
+        if page_token.is_some() {
+            for item in &page.items {
+                handle_chat_message(item, settings, ctx, limits);
+            }
+        }
+        page_token = Some(page.next_page_token.ok_or("YouTube sent no page token")?);
+        let interval = Duration::from_millis(page.polling_interval_millis);
+        tokio::time::sleep(interval.max(MIN_POLL_INTERVAL)).await;
+    }
+}
+
+fn handle_chat_message(
+    item: &ChatItem,
+    settings: &YouTubeSettings,
+    ctx: &ServerContext,
+    limits: &mut Limits,
+) {
+    let Some(message) = &item.snippet.display_message else {
+        return;
+    };
+    let command = message.split_whitespace().next().unwrap_or("");
+    let Some((index, trigger)) = settings
+        .triggers
+        .iter()
+        .enumerate()
+        .find(|(_, trigger)| trigger.command.eq_ignore_ascii_case(command))
+    else {
+        return;
+    };
+    let viewer = item.author_details.display_name.clone();
+    if !limits.allow(
+        index,
+        trigger.cooldown_seconds,
+        settings.max_triggers_per_minute,
+    ) {
+        debug!(
+            "Ignoring YouTube trigger from {}: cooldown or rate limit",
+            viewer
+        );
+        return;
+    }
+    tokio::spawn(trigger_shortcut(trigger.shortcut_id, viewer, ctx.clone()));
+}
+
+async fn trigger_shortcut(id: u64, viewer: String, ctx: ServerContext) {
+    if let Err(e) = triggers::trigger_shortcut(id, TriggerSource::YouTube { viewer }, &ctx).await {
+        warn!("Ignoring YouTube trigger for {}: {}", id, e);
+    }
+}
+
+// YouTube-related Tauri commands
+
+/// Saves the live chat settings and starts, restarts or stops the bridge to
+/// match.
+///
+/// # Arguments
+///
+/// * `youtube` - The stream, triggers and whether the bridge is enabled.
+/// * `api_key` - A new Data API key; empty to remove it, `None` to keep it.
+/// * `settings` - Shared state containing the settings.
+/// * `bridge` - The YouTube bridge.
+/// * `ctx` - The server context, used to run triggered shortcuts.
+/// * `app_handle` - Handle to emit events to the frontend.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - Ok if successful, Err with an error message otherwise.
+#[tauri::command]
+pub async fn set_youtube_bridge(
+    youtube: YouTubeSettings,
+    api_key: Option<String>,
+    settings: State<'_, Arc<SettingsStore>>,
+    bridge: State<'_, Arc<YouTubeBridge>>,
+    ctx: State<'_, ServerContext>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    match api_key.as_deref() {
+        Some("") => delete_secret(API_KEY_SECRET_ID),
+        Some(api_key) => store_secret(API_KEY_SECRET_ID, api_key)?,
+        None => {}
+    }
+
+    // Saved first, so the bridge never runs with settings that aren't kept
+    settings.update(&app_handle, |current| current.youtube = youtube.clone())?;
+
+    bridge.stop().await;
+    if youtube.enabled {
+        bridge.start(youtube, ctx.inner().clone()).await?;
+    }
+    Ok(())
+}
@@ -5,30 +5,41 @@ import AddShortcutForm, { Shortcut } from "./components/AddShortcutForm";
 import { buttonVariants } from "./components/ui/button";
 import clsx from "clsx";
 import ConnectWithQR from "./components/ConnectWithQR";
+import PairingDialog from "./components/PairingDialog";
 import { listen } from "@tauri-apps/api/event";
 import { LucideSettings, Plus } from "lucide-react";
 
 interface Device {
+  id: string;
   name: string;
   connected: boolean;
+  battery_level?: number;
+  platform?: string;
+  app_version?: string;
 }
 
-function getHotkeyLabel(index: number): string {
-  if (index < 10) {
-    return `Ctrl+${(index + 1) % 10 || 10}`;
-  } else if (index < 20) {
-    return `Ctrl+Shift+${(index - 9) % 10 || 10}`;
-  } else {
-    return "";
+// Battery percentage below which a device is flagged in the status bar
+const LOW_BATTERY_LEVEL = 15;
+
+function deviceLabel(device: Device): string {
+  if (device.battery_level !== undefined && device.battery_level < LOW_BATTERY_LEVEL) {
+    return `${device.name} (battery ${device.battery_level}%)`;
   }
+  return device.name;
+}
+
+interface HotkeyBinding {
+  hotkey: string;
+  shortcut_id: number;
 }
 
 function App() {
   const [shortcuts, setShortcuts] = useState<Shortcut[]>([]);
   const [editingShortcut, setEditingShortcut] = useState<Shortcut | null>(null);
   const [isAddingShortcut, setIsAddingShortcut] = useState(false);
-  const [connectedDevice, setConnectedDevice] = useState<Device | null>(null);
+  const [connectedDevices, setConnectedDevices] = useState<Device[]>([]);
   const [isQRDialogOpen, setIsQRDialogOpen] = useState(false);
+  const [hotkeyBindings, setHotkeyBindings] = useState<HotkeyBinding[]>([]);
 
   useEffect(() => {
     fetchShortcuts();
@@ -41,21 +52,53 @@ function App() {
       }
     );
 
-    const unlistenDevices = listen<Device | null>(
-      "devices_updated",
-      (event) => {
-        setConnectedDevice(event.payload);
-      }
+    invoke<HotkeyBinding[]>("get_hotkey_bindings")
+      .then(setHotkeyBindings)
+      .catch((error) => console.error("Error fetching hotkeys:", error));
+    const unlistenHotkeys = listen<HotkeyBinding[]>(
+      "hotkey_bindings_updated",
+      (event) => setHotkeyBindings(event.payload)
     );
 
-    const unlistenDeviceConnected = listen<Device>("device_connected", () => {
-      setIsQRDialogOpen(false);
-    });
+    invoke<Device[]>("get_connected_devices")
+      .then(setConnectedDevices)
+      .catch((error) => console.error("Error fetching devices:", error));
+
+    const upsertDevice = (device: Device) =>
+      setConnectedDevices((devices) => [
+        ...devices.filter((d) => d.id !== device.id),
+        device,
+      ]);
+    const removeDevice = (device: Device | null) => {
+      if (!device) return;
+      setConnectedDevices((devices) =>
+        devices.filter((d) => d.id !== device.id)
+      );
+    };
+
+    const unlistenDeviceEvents = [
+      listen<Device>("device_connected", (event) => {
+        upsertDevice(event.payload);
+        setIsQRDialogOpen(false);
+      }),
+      listen<Device>("device_pending_approval", (event) =>
+        upsertDevice(event.payload)
+      ),
+      listen<Device>("device_updated", (event) => upsertDevice(event.payload)),
+      listen<Device>("device_disconnected", (event) =>
+        removeDevice(event.payload)
+      ),
+      listen<Device | null>("device_rejected", (event) =>
+        removeDevice(event.payload)
+      ),
+    ];
 
     return () => {
       unlistenShortcuts.then((unlisten) => unlisten());
-      unlistenDevices.then((unlisten) => unlisten());
-      unlistenDeviceConnected.then((unlisten) => unlisten());
+      unlistenHotkeys.then((unlisten) => unlisten());
+      unlistenDeviceEvents.forEach((promise) =>
+        promise.then((unlisten) => unlisten())
+      );
     };
   }, []);
 
@@ -144,9 +187,12 @@ function App() {
             />
           </svg>
         </div>
-        {connectedDevice ? (
+        {connectedDevices.length > 0 ? (
           <div className="flex items-center gap-2">
-            <span>{connectedDevice.name} Connected</span>
+            <span>
+              {connectedDevices.map(deviceLabel).join(", ")}{" "}
+              Connected
+            </span>
           </div>
         ) : (
           <ConnectWithQR
@@ -194,7 +240,8 @@ function App() {
               </div>
               {/* Hotkey at the bottom left */}
               <div className="absolute bottom-2 left-2 text-sm text-gray-600">
-                {getHotkeyLabel(index)}
+                {hotkeyBindings.find((b) => b.shortcut_id === shortcut.id)
+                  ?.hotkey ?? ""}
               </div>
             </Card>
 
@@ -221,6 +268,7 @@ function App() {
           />
         )}
       </div>
+      <PairingDialog />
     </div>
   );
 }
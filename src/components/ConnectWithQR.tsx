@@ -22,8 +22,12 @@ const ConnectWithQR: React.FC<ConnectWithQRProps> = ({ isOpen, onOpenChange }) =
 
   const fetchQRData = async () => {
     try {
-      const config = await invoke<{ ip: string; port: number }>("get_server_config");
-      setQrData(`${config.ip}:${config.port}`);
+      const config = await invoke<{ ip: string; port: number; token: string }>(
+        "get_server_config"
+      );
+      // IPv6 addresses need brackets to be followed by a port
+      const host = config.ip.includes(":") ? `[${config.ip}]` : config.ip;
+      setQrData(`${host}:${config.port}?token=${config.token}`);
     } catch (error) {
       console.error("Failed to fetch QR data:", error);
     }
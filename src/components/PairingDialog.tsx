@@ -0,0 +1,67 @@
+import React, { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import { Button } from "./ui/button";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogFooter,
+  DialogHeader,
+  DialogTitle,
+} from "./ui/dialog";
+
+interface PairingRequest {
+  device: { id: string; name: string };
+  pin: string;
+}
+
+const PairingDialog: React.FC = () => {
+  const [request, setRequest] = useState<PairingRequest | null>(null);
+
+  useEffect(() => {
+    const unlisten = listen<PairingRequest>("device_pairing_requested", (event) => {
+      setRequest(event.payload);
+    });
+
+    return () => {
+      unlisten.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const respond = async (approve: boolean) => {
+    if (!request) return;
+    try {
+      await invoke(approve ? "approve_device" : "deny_device", {
+        deviceId: request.device.id,
+      });
+    } catch (error) {
+      console.error("Error responding to pairing request:", error);
+    }
+    setRequest(null);
+  };
+
+  return (
+    <Dialog open={!!request} onOpenChange={(open) => !open && respond(false)}>
+      <DialogContent>
+        <DialogHeader>
+          <DialogTitle>Pair {request?.device.name}?</DialogTitle>
+          <DialogDescription>
+            Only approve if the phone shows the same PIN.
+          </DialogDescription>
+        </DialogHeader>
+        <p className="text-center font-mono text-3xl tracking-widest">
+          {request?.pin}
+        </p>
+        <DialogFooter>
+          <Button variant="outline" onClick={() => respond(false)}>
+            Deny
+          </Button>
+          <Button onClick={() => respond(true)}>Approve</Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+};
+
+export default PairingDialog;